@@ -0,0 +1,717 @@
+//! Local document attachments
+//!
+//! Files attached to a message (PDF, DOCX, Markdown, source code, CSV and plain
+//! text) are extracted once into pages of text and stored under the app data
+//! directory by id. When a message is sent, small documents are inlined whole;
+//! larger ones are split into chunks and only the chunks that best match the
+//! question are given to the model. Every excerpt is numbered as a citation
+//! naming the file and, for PDFs, the page.
+
+use crate::tools::citations::{CitationList, ToolSource};
+use crate::tools::readability::decode_entities;
+use base64::{engine::general_purpose, Engine as _};
+use regex::Regex;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use tauri::{AppHandle, Manager};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+const DOCUMENT_DIRNAME: &str = "documents";
+/// Largest file that can be attached
+const MAX_DOCUMENT_BYTES: usize = 25 * 1024 * 1024;
+/// Documents up to this many characters in total are inlined instead of retrieved from
+const MAX_INLINE_CHARS: usize = 30000;
+/// Characters of retrieved chunks given to the model per message
+const MAX_RETRIEVED_CHARS: usize = 16000;
+pub const CHUNK_CHARS: usize = 1500;
+
+/// Words too common to say anything about which chunk is relevant
+const STOPWORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "by", "can", "did", "do", "does", "for", "from",
+    "has", "have", "how", "in", "is", "it", "its", "me", "of", "on", "or", "that", "the", "this",
+    "to", "was", "what", "when", "where", "which", "who", "why", "with", "you", "your",
+];
+
+const CODE_EXTENSIONS: &[&str] = &[
+    "rs", "py", "js", "jsx", "ts", "tsx", "go", "java", "kt", "swift", "c", "h", "cc", "cpp",
+    "hpp", "cs", "rb", "php", "scala", "lua", "r", "sh", "bash", "zsh", "sql", "html", "css",
+    "scss", "vue", "svelte", "json", "toml", "yaml", "yml", "xml", "zig", "hs", "ex", "exs",
+    "dart",
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DocumentKind {
+    Pdf,
+    Docx,
+    Markdown,
+    Code,
+    Csv,
+    Text,
+}
+
+impl DocumentKind {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "pdf" => Some(DocumentKind::Pdf),
+            "docx" => Some(DocumentKind::Docx),
+            "md" | "markdown" | "mdx" => Some(DocumentKind::Markdown),
+            "csv" | "tsv" => Some(DocumentKind::Csv),
+            "txt" | "text" | "log" | "rst" | "org" | "vtt" | "srt" => Some(DocumentKind::Text),
+            ext if CODE_EXTENSIONS.contains(&ext) => Some(DocumentKind::Code),
+            _ => None,
+        }
+    }
+}
+
+/// One page of a PDF, or the whole text of any other document
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DocumentPage {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub number: Option<usize>, // 1-based, PDFs only
+    pub text: String,
+}
+
+/// Extracted text of an attached file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Document {
+    pub id: String,
+    pub filename: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>, // Where the file was attached from, if it came from disk
+    pub kind: DocumentKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>, // File extension of source code, used for fencing
+    pub pages: Vec<DocumentPage>,
+    pub added_at: String, // RFC 3339, UTC
+}
+
+/// Summary of an attached document returned to the frontend
+#[derive(Serialize, Debug, Clone)]
+pub struct DocumentInfo {
+    pub id: String,
+    pub filename: String,
+    pub kind: DocumentKind,
+    pub pages: usize,
+    pub characters: usize,
+    /// Whether the document is small enough to be inlined whole
+    pub inline: bool,
+}
+
+/// A piece of a document small enough to be retrieved on its own
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentChunk {
+    pub page: Option<usize>,
+    pub text: String,
+}
+
+impl Document {
+    pub fn characters(&self) -> usize {
+        self.pages.iter().map(|p| p.text.chars().count()).sum()
+    }
+
+    pub fn info(&self) -> DocumentInfo {
+        let characters = self.characters();
+        DocumentInfo {
+            id: self.id.clone(),
+            filename: self.filename.clone(),
+            kind: self.kind,
+            pages: self.pages.len(),
+            characters,
+            inline: characters <= MAX_INLINE_CHARS,
+        }
+    }
+
+    /// Citation source for an excerpt, pointing at the page where there is one
+    fn source(&self, page: Option<usize>, text: &str) -> ToolSource {
+        let title = match page {
+            Some(number) => format!("{}, page {}", self.filename, number),
+            None => self.filename.clone(),
+        };
+        let mut url = self
+            .path
+            .as_deref()
+            .and_then(|path| Url::from_file_path(path).ok())
+            .map(|url| url.to_string())
+            .unwrap_or_else(|| format!("document://{}", self.id));
+        if let Some(number) = page {
+            url.push_str(&format!("#page={}", number));
+        }
+        ToolSource::new(&title, &url, Some(text))
+    }
+
+    /// Excerpt text as shown to the model, fencing code and tables
+    fn render(&self, text: &str) -> String {
+        match self.kind {
+            DocumentKind::Code => format!(
+                "```{}\n{}\n```",
+                self.language.as_deref().unwrap_or_default(),
+                text.trim_end()
+            ),
+            DocumentKind::Csv => format!("```csv\n{}\n```", text.trim_end()),
+            _ => text.trim().to_string(),
+        }
+    }
+}
+
+fn extension_of(filename: &str) -> String {
+    Path::new(filename)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+/// Extract the text of a DOCX file from its `word/document.xml` part
+fn extract_docx(bytes: &[u8]) -> Result<String, String> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes))
+        .map_err(|e| format!("Failed to open DOCX file: {}", e))?;
+    let mut xml = String::new();
+    archive
+        .by_name("word/document.xml")
+        .map_err(|e| format!("DOCX file has no document body: {}", e))?
+        .read_to_string(&mut xml)
+        .map_err(|e| format!("Failed to read DOCX document body: {}", e))?;
+    Ok(docx_xml_to_text(&xml))
+}
+
+/// Paragraph text of a WordprocessingML body, one paragraph per line
+pub fn docx_xml_to_text(xml: &str) -> String {
+    static PARAGRAPH: OnceLock<Regex> = OnceLock::new();
+    static RUN: OnceLock<Regex> = OnceLock::new();
+    let paragraph = PARAGRAPH.get_or_init(|| {
+        Regex::new(r"(?s)<w:p[\s>].*?</w:p>").expect("DOCX paragraph regex is valid")
+    });
+    let run = RUN.get_or_init(|| {
+        Regex::new(r"(?s)<w:t(?:\s[^>]*)?>(.*?)</w:t>|<w:tab\s*/>|<w:br\s*/>")
+            .expect("DOCX run regex is valid")
+    });
+
+    let mut paragraphs: Vec<String> = Vec::new();
+    for found in paragraph.find_iter(xml) {
+        let mut text = String::new();
+        for captures in run.captures_iter(found.as_str()) {
+            match captures.get(1) {
+                Some(run_text) => text.push_str(&decode_entities(run_text.as_str())),
+                None if captures[0].starts_with("<w:tab") => text.push('\t'),
+                None => text.push('\n'),
+            }
+        }
+        paragraphs.push(text.trim_end().to_string());
+    }
+    paragraphs.join("\n").trim().to_string()
+}
+
+/// Extract a file's text by its extension
+///
+/// PDF extraction can panic on malformed files, so callers run this on a blocking thread.
+pub fn extract_document(
+    filename: &str,
+    path: Option<String>,
+    bytes: &[u8],
+) -> Result<Document, String> {
+    if bytes.len() > MAX_DOCUMENT_BYTES {
+        return Err(format!(
+            "{} is larger than the {} MB attachment limit",
+            filename,
+            MAX_DOCUMENT_BYTES / (1024 * 1024)
+        ));
+    }
+    let extension = extension_of(filename);
+    let kind = match DocumentKind::from_extension(&extension) {
+        Some(kind) => kind,
+        // Unknown extensions are accepted as long as they are text
+        None if std::str::from_utf8(bytes).is_ok() => DocumentKind::Text,
+        None => return Err(format!("Unsupported document type: {}", filename)),
+    };
+
+    let pages = match kind {
+        DocumentKind::Pdf => pdf_extract::extract_text_from_mem_by_pages(bytes)
+            .map_err(|e| format!("Failed to extract PDF text: {}", e))?
+            .into_iter()
+            .enumerate()
+            .map(|(i, text)| DocumentPage {
+                number: Some(i + 1),
+                text: text.trim().to_string(),
+            })
+            .collect(),
+        DocumentKind::Docx => vec![DocumentPage {
+            number: None,
+            text: extract_docx(bytes)?,
+        }],
+        _ => vec![DocumentPage {
+            number: None,
+            text: String::from_utf8_lossy(bytes).replace("\r\n", "\n"),
+        }],
+    };
+    if pages.iter().all(|p| p.text.trim().is_empty()) {
+        return Err(format!("No text could be extracted from {}", filename));
+    }
+
+    Ok(Document {
+        id: Uuid::new_v4().to_string(),
+        filename: filename.to_string(),
+        path,
+        kind,
+        language: (kind == DocumentKind::Code).then_some(extension),
+        pages,
+        added_at: OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .unwrap_or_default(),
+    })
+}
+
+/// Read an attachment from a path on disk or from base64 data sent by the frontend
+pub fn read_attachment(path: Option<&str>, data_base64: Option<&str>) -> Result<Vec<u8>, String> {
+    match (path, data_base64) {
+        (Some(path), _) => {
+            let size = fs::metadata(path)
+                .map_err(|e| format!("Failed to read {}: {}", path, e))?
+                .len();
+            if size as usize > MAX_DOCUMENT_BYTES {
+                return Err(format!(
+                    "{} is larger than the {} MB attachment limit",
+                    path,
+                    MAX_DOCUMENT_BYTES / (1024 * 1024)
+                ));
+            }
+            fs::read(path).map_err(|e| format!("Failed to read {}: {}", path, e))
+        }
+        (None, Some(data)) => general_purpose::STANDARD
+            .decode(data)
+            .map_err(|e| format!("Failed to decode attachment: {}", e)),
+        (None, None) => Err("Attachment has neither a path nor data".to_string()),
+    }
+}
+
+/// Split text into pieces of at most `max_chars`, preferring paragraph, then line breaks
+pub fn split_text(text: &str, max_chars: usize) -> Vec<String> {
+    let mut pieces: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut push_unit = |unit: &str, separator: &str, pieces: &mut Vec<String>| {
+        if !current.is_empty()
+            && current.chars().count() + separator.len() + unit.chars().count() > max_chars
+        {
+            pieces.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push_str(separator);
+        }
+        current.push_str(unit);
+    };
+
+    for paragraph in text.split("\n\n").filter(|p| !p.trim().is_empty()) {
+        if paragraph.chars().count() <= max_chars {
+            push_unit(paragraph.trim_end(), "\n\n", &mut pieces);
+            continue;
+        }
+        for line in paragraph.lines() {
+            let chars: Vec<char> = line.chars().collect();
+            for part in chars.chunks(max_chars) {
+                push_unit(&part.iter().collect::<String>(), "\n", &mut pieces);
+            }
+        }
+    }
+    if !current.trim().is_empty() {
+        pieces.push(current);
+    }
+    pieces
+}
+
+/// Split CSV rows into chunks that each repeat the header row
+fn split_table(text: &str, max_chars: usize) -> Vec<String> {
+    let mut lines = text.lines().filter(|l| !l.trim().is_empty());
+    let header = match lines.next() {
+        Some(header) => header,
+        None => return Vec::new(),
+    };
+    let mut chunks: Vec<String> = Vec::new();
+    let mut current = header.to_string();
+    let mut has_rows = false;
+    for row in lines {
+        if has_rows && current.chars().count() + 1 + row.chars().count() > max_chars {
+            chunks.push(std::mem::replace(&mut current, header.to_string()));
+        }
+        current.push('\n');
+        current.push_str(row);
+        has_rows = true;
+    }
+    chunks.push(current);
+    chunks
+}
+
+/// Split a document into chunks that never span pages
+pub fn chunk_document(document: &Document, max_chars: usize) -> Vec<DocumentChunk> {
+    document
+        .pages
+        .iter()
+        .flat_map(|page| {
+            let pieces = if document.kind == DocumentKind::Csv {
+                split_table(&page.text, max_chars)
+            } else {
+                split_text(&page.text, max_chars)
+            };
+            pieces.into_iter().map(|text| DocumentChunk {
+                page: page.number,
+                text,
+            })
+        })
+        .collect()
+}
+
+fn terms(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() > 1)
+        .map(str::to_lowercase)
+        .filter(|word| !STOPWORDS.contains(&word.as_str()))
+        .collect()
+}
+
+/// How often each term occurs in a text, and how many terms it has, for BM25
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TermCounts {
+    counts: HashMap<String, u32>,
+    length: usize,
+}
+
+impl TermCounts {
+    pub fn of(text: &str) -> TermCounts {
+        let words = terms(text);
+        let mut counts: HashMap<String, u32> = HashMap::new();
+        for word in &words {
+            *counts.entry(word.clone()).or_insert(0) += 1;
+        }
+        TermCounts {
+            counts,
+            length: words.len(),
+        }
+    }
+}
+
+/// BM25 score of each text against the question, in input order
+pub fn score_texts(texts: &[&str], question: &str) -> Vec<f64> {
+    let counts: Vec<TermCounts> = texts.iter().map(|text| TermCounts::of(text)).collect();
+    score_term_counts(&counts.iter().collect::<Vec<_>>(), question)
+}
+
+/// BM25 score of each text, given as its term counts, against the question
+pub fn score_term_counts(texts: &[&TermCounts], question: &str) -> Vec<f64> {
+    const K1: f64 = 1.2;
+    const B: f64 = 0.75;
+    let query_terms: HashSet<String> = terms(question).into_iter().collect();
+    if query_terms.is_empty() || texts.is_empty() {
+        return vec![0.0; texts.len()];
+    }
+
+    let total = texts.len() as f64;
+    let average_length = (texts.iter().map(|t| t.length).sum::<usize>() as f64 / total).max(1.0);
+    let document_frequency: HashMap<&String, f64> = query_terms
+        .iter()
+        .map(|term| {
+            let count = texts.iter().filter(|t| t.counts.contains_key(term)).count();
+            (term, count as f64)
+        })
+        .collect();
+
+    texts
+        .iter()
+        .map(|text| {
+            let length = text.length as f64;
+            query_terms
+                .iter()
+                .map(|term| {
+                    let frequency = text.counts.get(term).copied().unwrap_or(0) as f64;
+                    if frequency == 0.0 {
+                        return 0.0;
+                    }
+                    let df = document_frequency[term];
+                    let idf = ((total - df + 0.5) / (df + 0.5) + 1.0).ln();
+                    idf * frequency * (K1 + 1.0)
+                        / (frequency + K1 * (1.0 - B + B * length / average_length))
+                })
+                .sum()
+        })
+        .collect()
+}
+
+/// Indices of the chunks to give the model, in reading order, within `max_chars`
+///
+/// Chunks are picked by relevance to the question; when nothing matches (e.g.
+/// "summarize this"), chunks spread evenly over the documents are used instead.
+pub fn select_chunks(texts: &[&str], question: &str, max_chars: usize) -> Vec<usize> {
+    let scores = score_texts(texts, question);
+    let mut order: Vec<usize> = (0..texts.len()).collect();
+    if scores.iter().any(|score| *score > 0.0) {
+        order.retain(|&i| scores[i] > 0.0);
+        order.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+    } else if !texts.is_empty() {
+        let average = texts.iter().map(|t| t.chars().count()).sum::<usize>() / texts.len();
+        let wanted = (max_chars / average.max(1)).clamp(1, texts.len());
+        order = (0..wanted).map(|i| i * texts.len() / wanted).collect();
+    }
+
+    let mut selected: Vec<usize> = Vec::new();
+    let mut used = 0;
+    for i in order {
+        let length = texts[i].chars().count();
+        if used + length > max_chars && !selected.is_empty() {
+            continue;
+        }
+        used += length;
+        selected.push(i);
+    }
+    selected.sort_unstable();
+    selected
+}
+
+/// Research context for the attached documents, numbering each excerpt as a citation
+pub fn build_context(
+    documents: &[Document],
+    question: &str,
+    citations: &mut CitationList,
+) -> Option<String> {
+    if documents.is_empty() {
+        return None;
+    }
+    let total_chars: usize = documents.iter().map(Document::characters).sum();
+
+    // (document, page, text) of each excerpt in reading order
+    let excerpts: Vec<(&Document, Option<usize>, String)> = if total_chars <= MAX_INLINE_CHARS {
+        documents
+            .iter()
+            .flat_map(|document| {
+                document
+                    .pages
+                    .iter()
+                    .filter(|page| !page.text.trim().is_empty())
+                    .map(move |page| (document, page.number, page.text.clone()))
+            })
+            .collect()
+    } else {
+        let chunks: Vec<(&Document, DocumentChunk)> = documents
+            .iter()
+            .flat_map(|document| {
+                chunk_document(document, CHUNK_CHARS)
+                    .into_iter()
+                    .map(move |chunk| (document, chunk))
+            })
+            .collect();
+        let texts: Vec<&str> = chunks
+            .iter()
+            .map(|(_, chunk)| chunk.text.as_str())
+            .collect();
+        select_chunks(&texts, question, MAX_RETRIEVED_CHARS)
+            .into_iter()
+            .map(|i| (chunks[i].0, chunks[i].1.page, chunks[i].1.text.clone()))
+            .collect()
+    };
+    if excerpts.is_empty() {
+        return None;
+    }
+
+    let names: Vec<&str> = documents.iter().map(|d| d.filename.as_str()).collect();
+    let mut context = if total_chars <= MAX_INLINE_CHARS {
+        format!("Attached Documents ({}):\n\n", names.join(", "))
+    } else {
+        format!(
+            "Excerpts of the Attached Documents ({}) most relevant to the question:\n\n",
+            names.join(", ")
+        )
+    };
+    for (document, page, text) in excerpts {
+        let source = document.source(page, &text);
+        let number = citations.add(std::slice::from_ref(&source))[0];
+        context.push_str(&format!(
+            "[{}] {}:\n{}\n\n",
+            number,
+            source.title,
+            document.render(&text)
+        ));
+    }
+    Some(context.trim_end().to_string())
+}
+
+// --- Storage ---
+
+fn loaded_documents() -> &'static Mutex<HashMap<String, Document>> {
+    static LOADED: OnceLock<Mutex<HashMap<String, Document>>> = OnceLock::new();
+    LOADED.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Document ids are UUIDs; anything else is rejected before touching the filesystem
+fn document_path(app_handle: &AppHandle, id: &str) -> Result<PathBuf, String> {
+    if Uuid::parse_str(id).is_err() {
+        return Err(format!("Invalid document id: {}", id));
+    }
+    match app_handle.path().app_data_dir() {
+        Ok(dir) => Ok(dir.join(DOCUMENT_DIRNAME).join(format!("{}.json", id))),
+        Err(e) => Err(format!("Failed to get app data directory: {}", e)),
+    }
+}
+
+pub fn save_document(app_handle: &AppHandle, document: &Document) -> Result<(), String> {
+    let path = document_path(app_handle, &document.id)?;
+    if let Some(parent_dir) = path.parent() {
+        if !parent_dir.exists() {
+            fs::create_dir_all(parent_dir)
+                .map_err(|e| format!("Failed to create document directory: {}", e))?;
+        }
+    }
+    let json = serde_json::to_string(document)
+        .map_err(|e| format!("Failed to serialize document: {}", e))?;
+    fs::write(&path, json).map_err(|e| format!("Failed to write document: {}", e))?;
+    if let Ok(mut loaded) = loaded_documents().lock() {
+        loaded.insert(document.id.clone(), document.clone());
+    }
+    Ok(())
+}
+
+pub fn load_document(app_handle: &AppHandle, id: &str) -> Result<Document, String> {
+    if let Some(document) = loaded_documents()
+        .lock()
+        .ok()
+        .and_then(|loaded| loaded.get(id).cloned())
+    {
+        return Ok(document);
+    }
+    let path = document_path(app_handle, id)?;
+    let content =
+        fs::read_to_string(&path).map_err(|e| format!("Failed to read document {}: {}", id, e))?;
+    let document: Document = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse document {}: {}", id, e))?;
+    if let Ok(mut loaded) = loaded_documents().lock() {
+        loaded.insert(id.to_string(), document.clone());
+    }
+    Ok(document)
+}
+
+pub fn delete_document(app_handle: &AppHandle, id: &str) -> Result<(), String> {
+    let path = document_path(app_handle, id)?;
+    if let Ok(mut loaded) = loaded_documents().lock() {
+        loaded.remove(id);
+    }
+    if path.exists() {
+        fs::remove_file(&path).map_err(|e| format!("Failed to delete document: {}", e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(kind: DocumentKind, pages: Vec<(Option<usize>, &str)>) -> Document {
+        Document {
+            id: "5b0c1c38-5a50-4c8e-9d5c-0e6f4f0f2a11".to_string(),
+            filename: "notes.txt".to_string(),
+            path: None,
+            kind,
+            language: None,
+            pages: pages
+                .into_iter()
+                .map(|(number, text)| DocumentPage {
+                    number,
+                    text: text.to_string(),
+                })
+                .collect(),
+            added_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_docx_paragraphs_and_runs() {
+        let xml = r#"<w:document><w:body>
+            <w:p><w:pPr><w:pStyle w:val="Title"/></w:pPr><w:r><w:t>Quarterly</w:t></w:r><w:r><w:t xml:space="preserve"> report</w:t></w:r></w:p>
+            <w:p><w:r><w:t>Revenue</w:t><w:tab/><w:t>R&amp;D</w:t></w:r></w:p>
+            <w:p/>
+        </w:body></w:document>"#;
+        assert_eq!(docx_xml_to_text(xml), "Quarterly report\nRevenue\tR&D");
+    }
+
+    #[test]
+    fn test_extraction_by_extension() {
+        let code = extract_document("main.rs", None, b"fn main() {}\r\n").unwrap();
+        assert_eq!(code.kind, DocumentKind::Code);
+        assert_eq!(code.language.as_deref(), Some("rs"));
+        assert_eq!(code.pages[0].text, "fn main() {}\n");
+        assert_eq!(
+            extract_document("notes.unknown", None, b"plain")
+                .unwrap()
+                .kind,
+            DocumentKind::Text
+        );
+        assert!(extract_document("image.bin", None, &[0xff, 0xfe, 0x00]).is_err());
+        assert!(extract_document("empty.md", None, b"  \n").is_err());
+    }
+
+    #[test]
+    fn test_chunks_stay_within_pages_and_tables_repeat_header() {
+        let pdf = document(
+            DocumentKind::Pdf,
+            vec![
+                (Some(1), "First paragraph.\n\nSecond paragraph."),
+                (Some(2), "Third paragraph."),
+            ],
+        );
+        let chunks = chunk_document(&pdf, 20);
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[1].page, Some(1));
+        assert_eq!(chunks[2].page, Some(2));
+        assert!(chunks.iter().all(|c| c.text.chars().count() <= 20));
+
+        let csv = document(
+            DocumentKind::Csv,
+            vec![(None, "city,temp\nTokyo,21\nParis,14\nLima,19\n")],
+        );
+        let chunks = chunk_document(&csv, 30);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].text, "city,temp\nTokyo,21\nParis,14");
+        assert_eq!(chunks[1].text, "city,temp\nLima,19");
+    }
+
+    #[test]
+    fn test_relevant_chunks_are_selected_in_reading_order() {
+        let texts = [
+            "The company was founded in 1998 in Menlo Park.",
+            "Revenue grew 20% driven by advertising revenue.",
+            "The board has nine members.",
+            "Operating costs rose, but revenue outpaced them.",
+        ];
+        assert_eq!(
+            select_chunks(&texts, "How did revenue change?", 1000),
+            vec![1, 3]
+        );
+        assert_eq!(
+            select_chunks(&texts, "How did revenue change?", 50),
+            vec![1]
+        );
+        // Nothing matches a generic request, so the excerpts are spread out
+        assert_eq!(select_chunks(&texts, "Summarize it", 100), vec![0, 2]);
+    }
+
+    #[test]
+    fn test_context_cites_pages() {
+        let mut pdf = document(
+            DocumentKind::Pdf,
+            vec![(Some(1), "Intro text."), (Some(2), "Results text.")],
+        );
+        pdf.filename = "paper.pdf".to_string();
+        let mut citations = CitationList::default();
+        let context = build_context(&[pdf], "results", &mut citations).unwrap();
+        assert!(context.starts_with("Attached Documents (paper.pdf):"));
+        assert!(context.contains("[1] paper.pdf, page 1:\nIntro text."));
+        assert!(context.contains("[2] paper.pdf, page 2:\nResults text."));
+        let cited = citations.cited_in("The results are good [2].");
+        assert_eq!(
+            cited[0].source.url,
+            "document://5b0c1c38-5a50-4c8e-9d5c-0e6f4f0f2a11#page=2"
+        );
+    }
+}
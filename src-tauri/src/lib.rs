@@ -1,10 +1,8 @@
 #![allow(unexpected_cfgs)] // Added to suppress unexpected_cfgs warnings from dependencies
 
-use arxiv_tools::Paper as ArXivPaper; // SortOrder as ArXivSortOrder};
 use base64::{engine::general_purpose, Engine as _}; // Added base64 import
 use image::{DynamicImage, ImageFormat};
 use leptess::{LepTess, Variable};
-use quick_xml::de::from_str;
 use reqwest;
 use serde::{Deserialize, Serialize};
 use serde_json;
@@ -22,113 +20,81 @@ use tauri_nspanel::{panel_delegate, Panel}; // Added for panel delegate
 use tauri_plugin_global_shortcut::{
     self as tauri_gs, GlobalShortcutExt, Shortcut, ShortcutEvent, ShortcutState,
 };
-use time::OffsetDateTime;
-use urlencoding; // Added urlencoding crate for URL encoding
 use uuid::Uuid; // For unique filenames // Added for base64 encoding // Plugin imports
-use yahoo_finance_api as yfa; // Using an alias for brevity // For timestamp conversion
 
 // MCP (Model Context Protocol) simplified module
 pub mod mcp_simple;
+// User-editable system prompt presets
+pub mod prompts;
+// Slash-command prompt templates
+pub mod templates;
+// Attached PDF, DOCX, Markdown, code and CSV files
+mod documents;
+// Per-tool enable/confirm/limit settings
+pub mod tool_policy;
+// Background deep research jobs producing cited reports
+mod research;
+// Tool trait, registry and the built-in research tools
+mod tools;
+
+pub use tools::wikipedia::{perform_iterative_wikipedia_research, IterativeSearchResult};
 
 // Per-stream cancellation system
 static CURRENT_STREAM_ID: AtomicU64 = AtomicU64::new(0);
 static CANCELLED_STREAM_ID: AtomicU64 = AtomicU64::new(u64::MAX); // Use MAX as "no cancellation"
 
-// --- ADDED: Structs for parsing ArXiv Atom XML response ---
-
-// NEW Enum to represent children of the <feed> tag
-#[derive(Debug, Deserialize)]
-enum FeedChild {
-    #[serde(rename = "entry")]
-    Entry(ArxivEntry), // For <entry> tags
-    #[serde(other)] // Catches any other tags like <link>, <title>, <id>, <updated> under <feed>
-    Other,
-}
-
-#[derive(Debug, Deserialize, Default)]
-struct ArxivFeed {
-    // This field will collect all direct children of the <feed> element.
-    // Each child will be deserialized into the appropriate FeedChild variant.
-    #[serde(rename = "$value", default)]
-    children: Vec<FeedChild>,
-}
-
-#[derive(Debug, Deserialize, Default)]
-struct ArxivEntry {
-    id: Option<String>,
-    updated: Option<String>,
-    published: Option<String>,
-    title: Option<String>,
-    summary: Option<String>, // This is the abstract
-    #[serde(rename = "author", default)]
-    authors: Vec<ArxivAuthor>,
-    #[serde(rename = "link", default)] // XML tag is still "link"
-    entry_links: Vec<ArxivLink>, // Rust field name changed from 'links' to 'entry_links'
-    #[serde(rename = "primary_category", default)]
-    primary_category: Option<ArxivCategory>,
-    #[serde(rename = "category", default)]
-    categories: Vec<ArxivCategory>,
-    comment: Option<String>, // arxiv:comment
-    doi: Option<String>,     // arxiv:doi
-}
-
-#[derive(Debug, Deserialize, Default)]
-struct ArxivAuthor {
-    name: Option<String>,
-    // Can add affiliation if needed: #[serde(rename = "arxiv:affiliation")] affiliation: Option<String>
-}
-
-#[derive(Debug, Deserialize, Default)]
-struct ArxivLink {
-    #[serde(rename = "@href")]
-    href: Option<String>,
-    #[serde(rename = "@rel")]
-    rel: Option<String>,
-    #[serde(rename = "@title")]
-    title: Option<String>,
-    #[serde(rename = "@type")]
-    link_type: Option<String>,
-}
-
-#[derive(Debug, Deserialize, Default)]
-struct ArxivCategory {
-    #[serde(rename = "@term")]
-    term: Option<String>,
-}
-// --- End of ArXiv Atom XML structs ---
-
-// --- ADDED: Struct for LLM to output ArXiv search parameters ---
-#[derive(Serialize, Deserialize, Debug, Default, Clone)]
-struct ArxivSearchParameters {
-    title: Option<String>,
-    author: Option<String>,
-    abstract_text: Option<String>,
-}
-
 // Default model if none is selected
 const DEFAULT_MODEL: &str = "gemini-2.5-flash-preview-05-20#thinking-enabled";
 
+// Models that can be selected in settings or pinned by a prompt template
+const ALLOWED_MODELS: [&str; 5] = [
+    "deepseek/deepseek-chat-v3-0324:free",
+    // "deepseek/deepseek-r1:free",
+    "deepseek/deepseek-r1-0528:free",
+    "gemini-2.0-flash", // Keep this for potential direct use or alias
+    "gemini-2.5-flash-preview-05-20", // This is the "Gemini 2.5 Flash (non-thinking)"
+    "gemini-2.5-flash-preview-05-20#thinking-enabled",
+];
+
 // --- System Instruction ---
 const SYSTEM_INSTRUCTION: &str = "You are a helpful assistant that provides accurate, factual answers. If you do not know the answer, make your best guess. You are casual in tone and prefer concise responses. Avoid starting responses with \"**\". You prefer bulleted lists when needed but never use nested lists/sub-bullets. Use markdown for code blocks and links. For math: use $$....$$ for display equations (full-line) and \\(...\\) for inline math. Never mix $ and $$ syntax.
 
 IMPORTANT: You have access to research tools that can help answer questions requiring current information or specialized knowledge:
 - Wikipedia Research: For factual information and background context
-- Weather Lookup: For current weather conditions
-- Stock Price Data: For real-time stock prices and market data (ticker symbols only - NOT for valuations, GDP, economic indicators, investor sentiment, or other financial analysis)
+- Weather Lookup: For current weather, daily forecasts up to 16 days, hourly breakdowns and air quality (add the period, e.g. "Paris weekend forecast", "Lyon hourly"; add the region or country for ambiguous names, e.g. "Paris, Texas"; saved places like "home" work too, and "default location" when the user names no place)
+- Stock Price Data: For real-time prices, price history and comparisons of stocks, market indices, exchange rates, crypto and commodity futures (ticker symbols or market names, optionally with a range such as 6mo, 1y or ytd - NOT for valuations, GDP, economic indicators, investor sentiment, or other financial analysis)
 - ArXiv Research: For academic papers and scientific research
+- ArXiv Paper Reader: For questions about the full text of one arXiv paper
+- Crossref: For published journal and conference papers and DOI metadata
+- Semantic Scholar: For citation counts, papers citing or cited by a paper, and influential work
+- PubMed: For biomedical and life-science literature
+- Web Search: For recent events, niche topics and pages outside Wikipedia
+- URL Reader: For reading a specific web page, PDF or text document by its URL
+- Video Transcript: For summarizing YouTube videos from their captions, with timestamp links
+- Local Knowledge: For the user's own notes and code in their registered knowledge folders
 
 When you need external information to properly answer a question, you can request tool usage by responding with a JSON object in this format:
 {\"tools\": [{\"tool_type\": \"WIKIPEDIA_LOOKUP\", \"query\": \"search term\", \"reasoning\": \"why needed\", \"priority\": 1}], \"reasoning\": \"explanation\"}
 
-Available tool types: WIKIPEDIA_LOOKUP, WEATHER_LOOKUP, FINANCIAL_DATA, ARXIV_LOOKUP";
+Available tool types: WIKIPEDIA_LOOKUP, WEATHER_LOOKUP, FINANCIAL_DATA, ARXIV_LOOKUP, ARXIV_PAPER, CROSSREF_LOOKUP, SEMANTIC_SCHOLAR_LOOKUP, PUBMED_LOOKUP, WEB_SEARCH, FETCH_URL, VIDEO_TRANSCRIPT, LOCAL_KNOWLEDGE";
 
 // --- Config Structures ---
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 struct AppConfig {
     api_key: Option<String>,
     selected_model: Option<String>,
-    gemini_api_key: Option<String>,  // Added for Gemini
-    enable_web_search: Option<bool>, // ADDED for web search toggle
+    gemini_api_key: Option<String>,               // Added for Gemini
+    enable_web_search: Option<bool>,              // ADDED for web search toggle
+    system_prompt_id: Option<String>, // Default system prompt preset for new conversations
+    tool_policy: Option<tool_policy::ToolPolicy>, // Per-tool settings within web search
+    max_parallel_tools: Option<usize>, // How many tools may run at the same time
+    persist_tool_traces: Option<bool>, // Store tool traces with their conversation on disk
+    web_search: Option<tools::web_search::WebSearchSettings>, // Search provider for WEB_SEARCH
+    url_fetch: Option<tools::fetch_url::UrlFetchSettings>, // Size limit, robots and domain rules for page fetches
+    knowledge_base: Option<tools::knowledge::KnowledgeBaseSettings>, // Folders indexed for LOCAL_KNOWLEDGE
+    wiki: Option<tools::wikipedia::WikiSettings>, // Wikipedia language and extra MediaWiki endpoints
+    deep_research: Option<research::ResearchBudget>, // Default limits of deep research jobs
+    weather: Option<tools::weather::WeatherSettings>, // Units, saved places and default place for weather lookups
 }
 
 const CONFIG_FILENAME: &str = "config.toml";
@@ -193,6 +159,10 @@ struct ChatMessage {
     // Internal field for backend use after uploading, not directly set by frontend for sending
     #[serde(skip_serializing_if = "Option::is_none")]
     image_file_api_uri: Option<String>, // URI from Gemini File API
+
+    // Documents attached with `attach_document`; their text is added as context, not sent as-is
+    #[serde(default, skip_serializing)]
+    document_ids: Option<Vec<String>>,
 }
 
 #[derive(Serialize, Debug)]
@@ -314,46 +284,16 @@ struct StreamEndPayload {
     reasoning: Option<String>, // Or whatever final data you want to send
 }
 
+#[derive(Serialize, Clone)] // Payload for STREAM_SOURCES event, sent after STREAM_END
+struct StreamSourcesPayload {
+    sources: Vec<tools::citations::CitedSource>, // Only the sources the answer cites
+}
+
 #[derive(Serialize, Clone)] // ADDED - Payload for STREAM_ERROR event
 struct StreamErrorPayload {
     error: String,
 }
 
-// --- Web Search Event Payloads ---
-#[derive(Serialize, Clone, Debug)]
-struct ArticleLookupStartedPayload {
-    query: String,
-}
-
-#[derive(Serialize, Clone, Debug)]
-struct ArticleLookupCompletedPayload {
-    query: String,
-    success: bool,
-    summary: Option<String>,
-    source_name: Option<Vec<String>>,
-    source_url: Option<Vec<String>>,
-    error: Option<String>,
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct IterativeSearchResult {
-    pub title: String,
-    pub summary: String,
-    pub url: String,
-    pub path_taken: Vec<String>,
-}
-
-#[derive(Deserialize, Debug)]
-#[serde(tag = "decision_type")]
-enum AnalysisLLMDecision {
-    #[serde(rename = "FOUND_ANSWER")]
-    FoundAnswer { summary: String, title: String },
-    #[serde(rename = "NEXT_TERM")]
-    NextTerm { term: String, reason: String },
-    #[serde(rename = "STOP")]
-    Stop { reason: String },
-}
-
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq)]
 enum ToolType {
     #[serde(rename = "WIKIPEDIA_LOOKUP")]
@@ -364,6 +304,47 @@ enum ToolType {
     FinancialData,
     #[serde(rename = "ARXIV_LOOKUP")]
     ArxivLookup,
+    #[serde(rename = "ARXIV_PAPER")]
+    ArxivPaper,
+    #[serde(rename = "CROSSREF_LOOKUP")]
+    CrossrefLookup,
+    #[serde(rename = "SEMANTIC_SCHOLAR_LOOKUP")]
+    SemanticScholarLookup,
+    #[serde(rename = "PUBMED_LOOKUP")]
+    PubmedLookup,
+    #[serde(rename = "WEB_SEARCH")]
+    WebSearch,
+    #[serde(rename = "FETCH_URL")]
+    FetchUrl,
+    #[serde(rename = "VIDEO_TRANSCRIPT")]
+    VideoTranscript,
+    #[serde(rename = "LOCAL_KNOWLEDGE")]
+    LocalKnowledge,
+}
+
+impl ToolType {
+    /// Wire name of the tool type, as used by the decider and in settings
+    fn as_str(&self) -> &'static str {
+        match self {
+            ToolType::WikipediaLookup => "WIKIPEDIA_LOOKUP",
+            ToolType::WeatherLookup => "WEATHER_LOOKUP",
+            ToolType::FinancialData => "FINANCIAL_DATA",
+            ToolType::ArxivLookup => "ARXIV_LOOKUP",
+            ToolType::ArxivPaper => "ARXIV_PAPER",
+            ToolType::CrossrefLookup => "CROSSREF_LOOKUP",
+            ToolType::SemanticScholarLookup => "SEMANTIC_SCHOLAR_LOOKUP",
+            ToolType::PubmedLookup => "PUBMED_LOOKUP",
+            ToolType::WebSearch => "WEB_SEARCH",
+            ToolType::FetchUrl => "FETCH_URL",
+            ToolType::VideoTranscript => "VIDEO_TRANSCRIPT",
+            ToolType::LocalKnowledge => "LOCAL_KNOWLEDGE",
+        }
+    }
+
+    /// Parse a tool type from its wire name (e.g. "WIKIPEDIA_LOOKUP")
+    fn from_name(name: &str) -> Option<ToolType> {
+        serde_json::from_value(serde_json::Value::String(name.trim().to_uppercase())).ok()
+    }
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -372,6 +353,8 @@ struct ToolDecision {
     query: String,
     reasoning: String,
     priority: u8, // 1-5, where 1 is highest priority
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    depends_on: Vec<ToolType>, // Tool types in the same round that must finish first
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -390,123 +373,17 @@ struct ToolExecutionResult {
     error: Option<String>,
 }
 
-// --- ADDED: Weather Lookup Event Payloads ---
-#[derive(Serialize, Clone, Debug)]
-struct WeatherLookupStartedPayload {
-    location: String,
-}
-
-#[derive(Serialize, Clone, Debug)]
-struct WeatherLookupCompletedPayload {
-    location: String,
-    success: bool,
-    temperature: Option<f32>,
-    unit: Option<String>,
-    description: Option<String>,
-    error: Option<String>,
-}
-
-// --- Financial Data Event Payloads ---
-#[derive(Serialize, Clone, Debug)]
-struct FinancialDataStartedPayload {
-    query: String,
-    symbol: String,
-}
-
-#[derive(Serialize, Clone, Debug)]
-struct FinancialDataCompletedPayload {
-    query: String,
-    symbol: String,
-    success: bool,
-    data: Option<String>, // Formatted financial data string
-    error: Option<String>,
-}
-
-// --- ADDED: Wikipedia API Structures ---
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct WikipediaQueryPage {
-    pageid: Option<i64>,
-    title: Option<String>,
-    extract: Option<String>,
-    missing: Option<String>,
-}
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct WikipediaQuery {
-    pages: Vec<WikipediaQueryPage>, // Changed from HashMap<String, WikipediaQueryPage>
-}
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct WikipediaResponse {
-    batchcomplete: Option<bool>, // Changed from Option<String> to Option<bool>
-    query: Option<WikipediaQuery>,
-}
-
-// --- ADDED: Open-Meteo Geocoding API Structures ---
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct GeocodingResult {
-    id: Option<f64>,
-    name: Option<String>,
-    latitude: Option<f32>,
-    longitude: Option<f32>,
-    country: Option<String>,
-    admin1: Option<String>,
-}
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct GeocodingResponse {
-    results: Option<Vec<GeocodingResult>>,
-    generationtime_ms: Option<f32>,
-}
-
-// --- ADDED: Open-Meteo Weather API Structures ---
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct WeatherCurrentUnits {
-    temperature_2m: Option<String>,
-}
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct WeatherCurrentData {
-    time: Option<String>,
-    interval: Option<i32>,
-    temperature_2m: Option<f32>,
-}
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct WeatherResponse {
-    latitude: Option<f32>,
-    longitude: Option<f32>,
-    generationtime_ms: Option<f32>,
-    utc_offset_seconds: Option<i32>,
-    timezone: Option<String>,
-    timezone_abbreviation: Option<String>,
-    elevation: Option<f32>,
-    current_units: Option<WeatherCurrentUnits>,
-    current: Option<WeatherCurrentData>,
-}
-
-// --- ADDED: ArXiv Lookup Event Payloads ---
 #[derive(Serialize, Clone, Debug)]
-struct ArxivLookupStartedPayload {
-    query: String,
+struct ToolTraceRecordedPayload {
+    trace_id: String,
+    conversation_id: Option<String>,
 }
 
 #[derive(Serialize, Clone, Debug)]
-struct ArxivLookupCompletedPayload {
+struct ToolSkippedPayload {
+    tool_type: String,
     query: String,
-    success: bool,
-    results: Option<Vec<ArxivPaperSummary>>, // Using a summarized version for the event
-    error: Option<String>,
-}
-
-#[derive(Serialize, Deserialize, Debug, Clone)] // Added Deserialize for ArXivPaper
-struct ArxivPaperSummary {
-    title: String,
-    summary: String,
-    authors: Vec<String>,
-    id: String,
-    published_date: Option<String>, // Assuming ArXivPaper has a field we can format to string
-    pdf_url: String,
+    reason: String,
 }
 
 fn separate_reasoning_from_content(text: &str) -> (String, String) {
@@ -613,108 +490,6 @@ fn separate_reasoning_from_content(text: &str) -> (String, String) {
     (content, reasoning)
 }
 
-// --- ADDED: Wikipedia Lookup Function ---
-async fn perform_wikipedia_lookup(
-    client: &reqwest::Client,
-    search_term: &str,
-) -> Result<Option<(String, String, String)>, String> {
-    // (summary, source_name, source_url)
-    let base_url = "https://en.wikipedia.org/w/api.php";
-    let params = [
-        ("action", "query"),
-        ("format", "json"),
-        ("titles", search_term),
-        ("prop", "extracts"),
-        ("exintro", "true"),
-        ("explaintext", "true"),
-        ("redirects", "1"),
-        ("formatversion", "2"),
-    ];
-    let request_url = client
-        .get(base_url)
-        .query(&params)
-        .build()
-        .expect("Failed to build Wikipedia URL")
-        .url()
-        .to_string();
-    log::info!("Performing Wikipedia lookup. Request URL: {}", request_url);
-    match client
-        .get(base_url)
-        .query(&params)
-        .header("User-Agent", "Shard/1.0 (https://github.com/shard-app/shard)")
-        .send()
-        .await
-    {
-        Ok(response) => {
-            let status = response.status();
-            let response_text = response
-                .text()
-                .await
-                .map_err(|e| format!("Wikipedia: Failed to read response text: {}", e))?;
-            if status.is_success() {
-                match serde_json::from_str::<WikipediaResponse>(&response_text) {
-                    Ok(wiki_response) => {
-                        log::info!("Wikipedia: Successfully parsed JSON: {:#?}", wiki_response);
-                        if let Some(query_data) = wiki_response.query {
-                            if let Some(page) = query_data.pages.first() {
-                                // Changed from .values().next() to .first()
-                                if page.missing.is_some() {
-                                    log::info!("Wikipedia: Page '{}' does not exist.", search_term);
-                                    return Ok(None);
-                                }
-                                if let Some(extract) = &page.extract {
-                                    if !extract.trim().is_empty() {
-                                        let title = page
-                                            .title
-                                            .clone()
-                                            .unwrap_or_else(|| search_term.to_string());
-                                        let source_url = format!(
-                                            "https://en.wikipedia.org/wiki/{}",
-                                            title.replace(" ", "_")
-                                        );
-                                        log::info!(
-                                            "Wikipedia: Found extract for title '{}'",
-                                            title
-                                        );
-                                        return Ok(Some((
-                                            title,
-                                            extract.trim().to_string(),
-                                            source_url,
-                                        )));
-                                    }
-                                }
-                            }
-                        }
-                        log::info!("Wikipedia: No suitable extract for '{}'.", search_term);
-                        Ok(None)
-                    }
-                    Err(e) => {
-                        log::error!(
-                            "Wikipedia: Failed to parse JSON: {}. Raw: {}",
-                            e,
-                            response_text
-                        );
-                        Err(format!(
-                            "Wikipedia JSON parse error: {}. Ensure response is valid JSON.",
-                            e
-                        ))
-                    }
-                }
-            } else {
-                log::error!("Wikipedia: API error status {}: {}", status, response_text);
-                Err(format!(
-                    "Wikipedia API error: {} - {}",
-                    status, response_text
-                ))
-            }
-        }
-        Err(e) => {
-            log::error!("Wikipedia: Network error: {}", e);
-            Err(format!("Wikipedia network error: {}", e))
-        }
-    }
-}
-
 // --- Screen Capture & OCR Helper Functions ---
 fn ocr_image_buffer(_app_handle: &AppHandle, img_buffer: &DynamicImage) -> Result<String, String> {
     log::info!("Starting OCR process with leptess for an image buffer");
@@ -804,161 +579,6 @@ fn process_image_with_ocr(
     }
 }
 
-// --- ADDED: Geocoding Function ---
-async fn geocode_location(
-    client: &reqwest::Client,
-    location_name: &str,
-) -> Result<Option<(f32, f32, String)>, String> {
-    // (latitude, longitude, resolved_name)
-    let base_url = "https://geocoding-api.open-meteo.com/v1/search";
-    let params = [
-        ("name", location_name),
-        ("count", "1"),
-        ("language", "en"),
-        ("format", "json"),
-    ];
-    let request_url = client
-        .get(base_url)
-        .query(&params)
-        .build()
-        .expect("Failed to build geocoding URL")
-        .url()
-        .to_string();
-    log::info!("Geocoding for '{}'. URL: {}", location_name, request_url);
-    match client.get(base_url).query(&params).send().await {
-        Ok(response) => {
-            let status = response.status();
-            let response_text = response
-                .text()
-                .await
-                .map_err(|e| format!("Geocoding: Failed to read response text: {}", e))?;
-            if status.is_success() {
-                match serde_json::from_str::<GeocodingResponse>(&response_text) {
-                    Ok(geo_response) => {
-                        log::info!("Geocoding: Parsed JSON: {:#?}", geo_response);
-                        if let Some(results) = geo_response.results {
-                            if let Some(top) = results.first() {
-                                if let (Some(lat_val), Some(lon_val), Some(name_val)) =
-                                    (top.latitude, top.longitude, &top.name)
-                                {
-                                    let resolved = format!(
-                                        "{}{}{}",
-                                        name_val,
-                                        top.admin1
-                                            .as_ref()
-                                            .map_or_else(|| "".to_string(), |a| format!(", {}", a)),
-                                        top.country
-                                            .as_ref()
-                                            .map_or_else(|| "".to_string(), |c| format!(", {}", c))
-                                    );
-                                    log::info!(
-                                        "Geocoding: Found for '{}': ({}, {}). Resolved: {}",
-                                        location_name,
-                                        lat_val,
-                                        lon_val,
-                                        resolved
-                                    );
-                                    return Ok(Some((lat_val, lon_val, resolved)));
-                                    // No deref needed for f32
-                                }
-                            }
-                        }
-                        log::info!("Geocoding: No coords for '{}'.", location_name);
-                        Ok(None)
-                    }
-                    Err(e) => {
-                        log::error!("Geocoding: JSON parse error: {}. Raw: {}", e, response_text);
-                        Err(format!(
-                            "Geocoding JSON error: {}. Ensure response is valid JSON.",
-                            e
-                        ))
-                    }
-                }
-            } else {
-                log::error!("Geocoding: API error status {}: {}", status, response_text);
-                Err(format!(
-                    "Geocoding API error: {} - {}",
-                    status, response_text
-                ))
-            }
-        }
-        Err(e) => {
-            log::error!("Geocoding: Network error: {}", e);
-            Err(format!("Geocoding network error: {}", e))
-        }
-    }
-}
-
-// --- ADDED: Stock Price Data Lookup Function ---
-async fn perform_financial_data_lookup(
-    _client: &reqwest::Client, // Not directly used by yfa, but kept for consistency if other libs need it
-    symbol: &str,
-) -> Result<String, String> {
-    log::info!(
-        "Performing stock price data lookup for symbol: '{}' using yahoo_finance_api",
-        symbol
-    );
-
-    let provider = match yfa::YahooConnector::new() {
-        Ok(p) => p,
-        Err(e) => {
-            let err_msg = format!("Failed to create YahooConnector: {}", e.to_string());
-            log::error!("{}", err_msg);
-            return Err(err_msg);
-        }
-    };
-
-    match provider.get_latest_quotes(symbol, "1d").await {
-        // Get latest daily quote
-        Ok(response) => {
-            if let Some(quote) = response.last_quote().ok() {
-                // last_quote returns Result<Quote, Error>
-                // Convert Unix timestamp to readable date
-                // The timestamp from yahoo_finance_api::Quote is u64
-                let dt = OffsetDateTime::from_unix_timestamp(quote.timestamp as i64)
-                    .map_err(|e| format!("Failed to convert timestamp: {}", e))?;
-
-                let date_str = dt
-                    .format(
-                        &time::format_description::parse("[year]-[month]-[day]")
-                            .map_err(|e| format!("Failed to parse date format: {}", e))?,
-                    )
-                    .map_err(|e| format!("Failed to format date: {}", e))?;
-
-                let formatted_data = format!(
-                    "Latest data for {}: Date: {}, Open: {:.2}, High: {:.2}, Low: {:.2}, Close: {:.2}, Volume: {}",
-                    symbol.to_uppercase(),
-                    date_str,
-                    quote.open,
-                    quote.high,
-                    quote.low,
-                    quote.close,
-                    quote.volume
-                );
-                log::info!(
-                    "Stock price data lookup successful for symbol: '{}'. Data: {}",
-                    symbol,
-                    formatted_data
-                );
-                Ok(formatted_data)
-            } else {
-                let msg = format!("No stock price data found for symbol {}.", symbol);
-                log::warn!("Stock price data lookup for symbol '{}': {}", symbol, msg);
-                Err(msg)
-            }
-        }
-        Err(e) => {
-            let err_msg = format!(
-                "Failed to retrieve stock price data for {} from yahoo_finance_api: {}",
-                symbol,
-                e.to_string()
-            );
-            log::error!("{}", err_msg);
-            Err(err_msg)
-        }
-    }
-}
-
 // --- Tauri Commands ---
 
 #[tauri::command]
@@ -1269,9 +889,13 @@ async fn process_clipboard_image(app_handle: AppHandle) -> Result<CaptureResult,
 // --- Other Tauri Commands (send_text_to_model, get_api_key, etc.) should remain the same ---
 #[tauri::command]
 async fn send_text_to_model(
-    messages: Vec<ChatMessage>,
+    mut messages: Vec<ChatMessage>,
     app_handle: AppHandle,
     window: Window,
+    system_prompt_id: Option<String>, // Per-conversation preset, falls back to the configured default
+    selected_text: Option<String>,    // Fills the {selected_text} prompt variable
+    tool_policy_override: Option<tool_policy::ToolPolicy>, // Per-conversation tool settings
+    conversation_id: Option<String>,  // Groups persisted tool traces by conversation
 ) -> Result<(), String> {
     // Generate unique stream ID for this request
     let stream_id = CURRENT_STREAM_ID.fetch_add(1, Ordering::Relaxed) + 1;
@@ -1281,20 +905,65 @@ async fn send_text_to_model(
     // Original user messages will be added after potential web search or financial data context
 
     let config = load_config(&app_handle)?;
+    let tool_policy_override = tool_policy_override
+        .map(tool_policy::ToolPolicy::normalized)
+        .transpose()?;
+
+    // Expand a leading `/template` command in the latest user message
+    let mut template_model: Option<String> = None;
+    let mut template_tools: Option<(Vec<ToolType>, String)> = None; // (tools, tool query)
+    if let Some(last_user_message) = messages.iter_mut().rev().find(|msg| msg.role == "user") {
+        if let Some(invocation) = templates::parse_invocation(&last_user_message.content) {
+            let template_library =
+                templates::load_template_library(&app_handle).unwrap_or_else(|e| {
+                    log::warn!("Failed to load prompt templates: {}. Using built-ins.", e);
+                    templates::TemplateLibrary::default()
+                });
+            match template_library.find(&invocation.name) {
+                Some(template) => {
+                    let expanded = template.expand(&invocation.args)?;
+                    log::info!(
+                        "Expanded template '/{}' ({} chars)",
+                        template.name,
+                        expanded.content.len()
+                    );
+                    last_user_message.content = expanded.content;
+                    template_model = template.model.clone();
+                    template_tools = template.tools.as_ref().map(|names| {
+                        (
+                            names
+                                .iter()
+                                .filter_map(|n| ToolType::from_name(n))
+                                .collect(),
+                            expanded.tool_query,
+                        )
+                    });
+                }
+                None => log::info!(
+                    "No template named '/{}', sending message as-is",
+                    invocation.name
+                ),
+            }
+        }
+    }
 
-    let model_name = config.selected_model.clone().unwrap_or_else(|| {
-        log::warn!(
-            "No model selected in config, using default: {}",
-            DEFAULT_MODEL
-        );
-        DEFAULT_MODEL.to_string()
-    });
+    let model_name = template_model
+        .or_else(|| config.selected_model.clone())
+        .unwrap_or_else(|| {
+            log::warn!(
+                "No model selected in config, using default: {}",
+                DEFAULT_MODEL
+            );
+            DEFAULT_MODEL.to_string()
+        });
 
     log::info!("Processing request for model: {}", model_name);
 
     // Tool execution state
     let mut tool_context_available = false;
     let mut comprehensive_tool_context: Option<String> = None;
+    // Sources consulted by tools, numbered for [n] citations in the answer
+    let mut citations = tools::citations::CitationList::default();
 
     // Create reqwest client once
     let client = reqwest::Client::new();
@@ -1311,18 +980,33 @@ async fn send_text_to_model(
                     user_query
                 );
 
+                // Per-tool settings, with the conversation's overrides layered on top
+                let effective_policy = config
+                    .tool_policy
+                    .clone()
+                    .unwrap_or_default()
+                    .merged_with(&tool_policy_override.unwrap_or_default());
+                let mut call_budget = tool_policy::ToolCallBudget::default();
+                let mut trace = tools::trace::ToolTrace::new(user_query, conversation_id.clone());
+                let tool_registry = tools::registry();
+                // LOCAL_KNOWLEDGE is only offered once a knowledge folder is registered
+                let has_knowledge_folders =
+                    tools::knowledge::knowledge_base(&app_handle).has_folders();
+                let is_tool_enabled = |name: &str| {
+                    effective_policy.is_enabled(name)
+                        && (name != ToolType::LocalKnowledge.as_str() || has_knowledge_folders)
+                };
+
                 let decider_prompt = format!(
                         "You are an expert MCP (Model Context Protocol) tool reasoning assistant. Your job is to analyze user queries and determine ALL research tools needed to provide a comprehensive answer.\n\n\
                         AVAILABLE TOOLS:\n\
-                        1. WIKIPEDIA_LOOKUP: Iterative Wikipedia research for factual information, background context, and general knowledge\n\
-                        2. WEATHER_LOOKUP: Current weather conditions for specific locations (use city names or zip codes)\n\
-                        3. FINANCIAL_DATA: Real-time stock prices and trading data (use stock ticker symbols like AAPL, GOOGL, TSLA) - NOT for valuations, GDP, economic indicators, investor sentiment, etc.\n\
-                        4. ARXIV_LOOKUP: Academic papers and research from arXiv repository\n\n\
+                        {}\n\
                         MULTI-TOOL STRATEGY GUIDELINES (REQUIRED FOR COMPLEX QUERIES):\n\
                         - Business/investment queries: Wikipedia (context) + Financial data (current metrics)\n\
                         - Technology + market queries: Wikipedia (background) + ArXiv (research) + Financial (companies)\n\
                         - Travel queries: Weather (conditions) + Wikipedia (location info)\n\
                         - Research queries: Wikipedia (overview) + ArXiv (latest papers)\n\
+                        - Recent events, releases, niche topics: Web search (current pages) + Wikipedia (background)\n\
                         - Priority 1 = most important, 5 = least important\n\n\
                         WIKIPEDIA QUERY GUIDELINES:\n\
                         - Use GENERIC, foundational terms (e.g., \"quantum computing\", \"artificial intelligence\", \"renewable energy\")\n\
@@ -1356,12 +1040,15 @@ async fn send_text_to_model(
                         - \"tools\": Array of tool decisions (empty if no tools needed)\n\
                         - \"reasoning\": Brief explanation of your tool selection strategy\n\n\
                         Each tool decision should have:\n\
-                        - \"tool_type\": One of WIKIPEDIA_LOOKUP, WEATHER_LOOKUP, FINANCIAL_DATA, ARXIV_LOOKUP\n\
+                        - \"tool_type\": One of {}\n\
                         - \"query\": Specific search query for that tool\n\
                         - \"reasoning\": Why this tool is needed\n\
-                        - \"priority\": Number 1-5 (1 = highest priority)\n\n\
+                        - \"priority\": Number 1-5 (1 = highest priority)\n\
+                        - \"depends_on\": Optional array of tool types from this response that must finish first (omit for independent tools, which run in parallel)\n\n\
                         Respond only with valid JSON:",
-                        user_query
+                        tool_registry.prompt_tool_list(false, is_tool_enabled),
+                        user_query,
+                        tool_registry.tool_names(is_tool_enabled)
                 );
 
                 let decider_messages = vec![ChatMessage {
@@ -1370,6 +1057,7 @@ async fn send_text_to_model(
                     image_base64_data: None,
                     image_mime_type: None,
                     image_file_api_uri: None,
+                    document_ids: None,
                 }];
                 let decider_model_name = "gemini-2.0-flash".to_string();
 
@@ -1381,8 +1069,28 @@ async fn send_text_to_model(
                     }
                 };
 
-                let tool_decisions: Vec<ToolDecision>; // Initialize tool decisions
-                if !decider_gemini_api_key_string.is_empty() {
+                let mut tool_decisions: Vec<ToolDecision>; // Initialize tool decisions
+                if let Some((tools, tool_query)) = &template_tools {
+                    // The prompt template chose the tools, so skip the decider
+                    log::info!(
+                        "Using {} tool(s) declared by the prompt template",
+                        tools.len()
+                    );
+                    tool_decisions = tools
+                        .iter()
+                        .map(|tool_type| ToolDecision {
+                            tool_type: tool_type.clone(),
+                            query: tool_query.clone(),
+                            reasoning: "Requested by prompt template".to_string(),
+                            priority: 1,
+                            depends_on: Vec::new(),
+                        })
+                        .collect();
+                } else if !decider_gemini_api_key_string.is_empty() {
+                    let mut decider_trace = tools::trace::DeciderTrace::new(
+                        &decider_model_name,
+                        &decider_messages[0].content,
+                    );
                     match call_gemini_api_non_streaming(
                         &client,
                         decider_messages,
@@ -1397,6 +1105,7 @@ async fn send_text_to_model(
                                 user_query,
                                 decider_response_text
                             );
+                            decider_trace.responded(&decider_response_text);
 
                             // Clean the response to extract JSON
                             let cleaned_response = decider_response_text
@@ -1416,6 +1125,7 @@ async fn send_text_to_model(
                                         decision_response.tools.len(),
                                         decision_response.reasoning
                                     );
+                                    decider_trace.decided(&decision_response);
                                     tool_decisions = decision_response.tools;
                                 }
                                 Err(e) => {
@@ -1425,20 +1135,71 @@ async fn send_text_to_model(
                                         e,
                                         decider_response_text
                                     );
+                                    decider_trace.failed(&format!(
+                                        "Failed to parse decider response: {}",
+                                        e
+                                    ));
                                     tool_decisions = Vec::new();
                                 }
                             }
                         }
                         Err(e) => {
                             log::error!("Error calling multi-tool decider for query '{}': {}. Defaulting to no tools.", user_query, e);
+                            decider_trace.failed(&e);
                             tool_decisions = Vec::new();
                         }
                     }
+                    trace.decider = Some(decider_trace);
                 } else {
                     log::warn!("Decider Gemini API key is empty. No tools will be executed for query '{}'.", user_query);
+                    let mut decider_trace = tools::trace::DeciderTrace::new(
+                        &decider_model_name,
+                        &decider_messages[0].content,
+                    );
+                    decider_trace.failed("Gemini API key is not configured");
+                    trace.decider = Some(decider_trace);
                     tool_decisions = Vec::new();
                 }
 
+                // Links in the message are read even if the decider did not ask for them
+                let url_fetch_settings = config.url_fetch.clone().unwrap_or_default();
+                if url_fetch_settings.auto_fetch.unwrap_or(true) {
+                    let url_decisions: Vec<ToolDecision> =
+                        tools::fetch_url::auto_fetch_decisions(user_query, &tool_decisions)
+                            .into_iter()
+                            .filter(|d| is_tool_enabled(d.tool_type.as_str()))
+                            .collect();
+                    if !url_decisions.is_empty() {
+                        log::info!(
+                            "Reading {} link(s) from the message automatically",
+                            url_decisions.len()
+                        );
+                        tool_decisions.extend(url_decisions);
+                    }
+                }
+
+                let max_parallel_tools = config
+                    .max_parallel_tools
+                    .unwrap_or(tools::executor::DEFAULT_MAX_PARALLEL_TOOLS);
+                // Besides paths typed in the message, tools may only read attached files
+                let attached_paths: Vec<String> = messages
+                    .iter()
+                    .filter(|msg| msg.role == "user")
+                    .flat_map(|msg| msg.document_ids.iter().flatten())
+                    .filter_map(|id| documents::load_document(&app_handle, id).ok())
+                    .filter_map(|document| document.path)
+                    .collect();
+                let tool_context = tools::ToolContext {
+                    client: &client,
+                    window: &window,
+                    cache: tools::cache::tool_cache(&app_handle),
+                    config: &config,
+                    gemini_api_key: &decider_gemini_api_key_string,
+                    model_name: &decider_model_name,
+                    user_query,
+                    attached_paths: &attached_paths,
+                };
+
                 // Execute tools iteratively - allow for multiple rounds of tool calling
                 let mut tool_results: Vec<ToolExecutionResult> = Vec::new();
                 let mut all_tool_context = String::new();
@@ -1460,502 +1221,115 @@ async fn send_text_to_model(
 
                     let mut iteration_context = String::new();
                     let mut new_tool_requests: Vec<ToolDecision> = Vec::new();
+                    let mut approved_tools: Vec<ToolDecision> = Vec::new();
 
                     for tool_decision in sorted_tools {
-                        log::info!(
-                            "Executing tool {:?} with query: '{}' (priority: {})",
-                            tool_decision.tool_type,
-                            tool_decision.query,
-                            tool_decision.priority
-                        );
+                        let tool_name = tool_decision.tool_type.as_str();
+
+                        // Enforce the tool policy before running anything
+                        let mut skip_reason =
+                            call_budget.try_acquire(&effective_policy, tool_name).err();
+                        if skip_reason.is_none()
+                            && effective_policy.requires_confirmation(tool_name)
+                            && !tool_policy::request_confirmation(
+                                &window,
+                                tool_name,
+                                &tool_decision.query,
+                                &tool_decision.reasoning,
+                            )
+                            .await
+                        {
+                            call_budget.release(tool_name);
+                            skip_reason =
+                                Some(format!("{} was not approved by the user", tool_name));
+                        }
+                        if let Some(reason) = skip_reason {
+                            log::info!(
+                                "Skipping tool {:?} with query '{}': {}",
+                                tool_decision.tool_type,
+                                tool_decision.query,
+                                reason
+                            );
+                            trace.iteration(iteration_count).tool_calls.push(
+                                tools::trace::ToolCallTrace::skipped(&tool_decision, &reason),
+                            );
+                            tool_results.push(ToolExecutionResult {
+                                tool_type: tool_decision.tool_type.clone(),
+                                query: tool_decision.query.clone(),
+                                success: false,
+                                content: None,
+                                error: Some(reason.clone()),
+                            });
+                            if let Err(e) = window.emit(
+                                "TOOL_SKIPPED",
+                                ToolSkippedPayload {
+                                    tool_type: tool_name.to_string(),
+                                    query: tool_decision.query.clone(),
+                                    reason,
+                                },
+                            ) {
+                                log::warn!("Failed to emit TOOL_SKIPPED event: {}", e);
+                            }
+                            continue;
+                        }
 
-                        match tool_decision.tool_type {
-                            ToolType::WikipediaLookup => {
-                                let max_iterations = 4;
+                        approved_tools.push(tool_decision);
+                    }
 
-                                if let Err(e) = window.emit(
-                                    "ARTICLE_LOOKUP_STARTED",
-                                    ArticleLookupStartedPayload {
-                                        query: tool_decision.query.clone(),
-                                    },
-                                ) {
-                                    log::warn!(
-                                        "Failed to emit ARTICLE_LOOKUP_STARTED event: {}",
-                                        e
-                                    );
-                                }
+                    // Independent tools run concurrently; results come back in priority order
+                    for run in tools::executor::run_tools(
+                        &tool_context,
+                        approved_tools,
+                        max_parallel_tools,
+                    )
+                    .await
+                    {
+                        trace
+                            .iteration(iteration_count)
+                            .tool_calls
+                            .push(tools::trace::ToolCallTrace::from_run(&run));
+                        if let Some(context_text) = run.context {
+                            let context_text = citations.annotate(&context_text, &run.sources);
+                            iteration_context.push_str(&format!("{}\n\n", context_text));
+                        }
+                        tool_results.push(run.result);
+                    }
 
-                                match perform_iterative_wikipedia_research(
-                                    &client,
-                                    &tool_decision.query,
-                                    &decider_gemini_api_key_string,
-                                    &decider_model_name,
-                                    max_iterations,
-                                )
-                                .await
-                                {
-                                    Ok(results) => {
-                                        if results.is_empty() {
-                                            log::info!("Wikipedia lookup for '{}' completed, but no information found.", tool_decision.query);
-
-                                            tool_results.push(ToolExecutionResult {
-                                                tool_type: ToolType::WikipediaLookup,
-                                                query: tool_decision.query.clone(),
-                                                success: true,
-                                                content: Some("No specific information found after iterative search.".to_string()),
-                                                error: None,
-                                            });
-
-                                            if let Err(e) = window.emit(
-                                                "ARTICLE_LOOKUP_COMPLETED",
-                                                ArticleLookupCompletedPayload {
-                                                    query: tool_decision.query.clone(),
-                                                    success: true,
-                                                    summary: Some("No specific information found after iterative search.".to_string()),
-                                                    source_name: None,
-                                                    source_url: None,
-                                                    error: None,
-                                                },
-                                            ) {
-                                                log::warn!("Failed to emit ARTICLE_LOOKUP_COMPLETED event: {}", e);
-                                            }
-                                        } else {
-                                            log::info!("Wikipedia lookup successful for '{}'. Found {} results.", tool_decision.query, results.len());
-                                            let mut combined_summary = String::new();
-                                            let mut combined_source_names = Vec::<String>::new();
-                                            let mut combined_source_urls = Vec::<String>::new();
-
-                                            for res in results.iter() {
-                                                combined_summary.push_str(&format!(
-                                                    "Title: {}\nSummary: {}\n\n",
-                                                    res.title, res.summary,
-                                                ));
-                                                combined_source_names.push(res.title.clone());
-                                                combined_source_urls.push(res.url.clone());
-                                            }
+                    // Add iteration context to overall context
+                    trace.iteration(iteration_count).context_chars = iteration_context.len();
+                    all_tool_context.push_str(&iteration_context);
 
-                                            let context_text = format!(
-                                                "Wikipedia Research Results for '{}':\n\n{}",
-                                                tool_decision.query,
-                                                combined_summary.trim_end()
-                                            );
-
-                                            tool_results.push(ToolExecutionResult {
-                                                tool_type: ToolType::WikipediaLookup,
-                                                query: tool_decision.query.clone(),
-                                                success: true,
-                                                content: Some(context_text.clone()),
-                                                error: None,
-                                            });
-
-                                            iteration_context
-                                                .push_str(&format!("{}\n\n", context_text));
-
-                                            if let Err(e) = window.emit(
-                                                "ARTICLE_LOOKUP_COMPLETED",
-                                                ArticleLookupCompletedPayload {
-                                                    query: tool_decision.query.clone(),
-                                                    success: true,
-                                                    summary: Some(
-                                                        combined_summary.trim_end().to_string(),
-                                                    ),
-                                                    source_name: Some(combined_source_names),
-                                                    source_url: Some(combined_source_urls),
-                                                    error: None,
-                                                },
-                                            ) {
-                                                log::warn!(
-                                                    "Failed to emit ARTICLE_LOOKUP_COMPLETED event: {}",
-                                                    e
-                                                );
-                                            }
-                                        }
-                                    }
-                                    Err(e) => {
-                                        log::error!(
-                                            "Wikipedia lookup failed for '{}': {}",
-                                            tool_decision.query,
-                                            e
-                                        );
-
-                                        tool_results.push(ToolExecutionResult {
-                                            tool_type: ToolType::WikipediaLookup,
-                                            query: tool_decision.query.clone(),
-                                            success: false,
-                                            content: None,
-                                            error: Some(e.clone()),
-                                        });
-
-                                        if let Err(emit_error) = window.emit(
-                                            "ARTICLE_LOOKUP_COMPLETED",
-                                            ArticleLookupCompletedPayload {
-                                                query: tool_decision.query.clone(),
-                                                success: false,
-                                                summary: None,
-                                                source_name: None,
-                                                source_url: None,
-                                                error: Some(e),
-                                            },
-                                        ) {
-                                            log::warn!("Failed to emit ARTICLE_LOOKUP_COMPLETED error event: {}", emit_error);
-                                        }
-                                    }
-                                }
-                            }
-                            ToolType::WeatherLookup => {
-                                if let Err(e) = window.emit(
-                                    "WEATHER_LOOKUP_STARTED",
-                                    WeatherLookupStartedPayload {
-                                        location: tool_decision.query.clone(),
-                                    },
-                                ) {
-                                    log::warn!(
-                                        "Failed to emit WEATHER_LOOKUP_STARTED event: {}",
-                                        e
-                                    );
-                                }
-
-                                match perform_weather_lookup(
-                                    &client,
-                                    &tool_decision.query,
-                                    &decider_gemini_api_key_string,
-                                    decider_model_name.clone(),
-                                )
-                                .await
-                                {
-                                    Ok(Some((temperature, unit, description, location))) => {
-                                        let weather_text = format!(
-                                            "Weather in {}: {}°{} - {}",
-                                            location, temperature, unit, description
-                                        );
-
-                                        tool_results.push(ToolExecutionResult {
-                                            tool_type: ToolType::WeatherLookup,
-                                            query: tool_decision.query.clone(),
-                                            success: true,
-                                            content: Some(weather_text.clone()),
-                                            error: None,
-                                        });
-
-                                        iteration_context.push_str(&format!(
-                                            "Weather Information for '{}':\n{}\n\n",
-                                            tool_decision.query, weather_text
-                                        ));
-
-                                        if let Err(e) = window.emit(
-                                            "WEATHER_LOOKUP_COMPLETED",
-                                            WeatherLookupCompletedPayload {
-                                                location: tool_decision.query.clone(),
-                                                success: true,
-                                                temperature: Some(temperature),
-                                                unit: Some(unit),
-                                                description: Some(description),
-                                                error: None,
-                                            },
-                                        ) {
-                                            log::warn!(
-                                                "Failed to emit WEATHER_LOOKUP_COMPLETED event: {}",
-                                                e
-                                            );
-                                        }
-                                    }
-                                    Ok(None) => {
-                                        let no_weather_text = format!(
-                                            "Unable to find weather information for '{}'",
-                                            tool_decision.query
-                                        );
-
-                                        tool_results.push(ToolExecutionResult {
-                                            tool_type: ToolType::WeatherLookup,
-                                            query: tool_decision.query.clone(),
-                                            success: false,
-                                            content: Some(no_weather_text.clone()),
-                                            error: Some("Location not found".to_string()),
-                                        });
-
-                                        if let Err(e) = window.emit(
-                                            "WEATHER_LOOKUP_COMPLETED",
-                                            WeatherLookupCompletedPayload {
-                                                location: tool_decision.query.clone(),
-                                                success: false,
-                                                temperature: None,
-                                                unit: None,
-                                                description: None,
-                                                error: Some("Location not found".to_string()),
-                                            },
-                                        ) {
-                                            log::warn!(
-                                                "Failed to emit WEATHER_LOOKUP_COMPLETED event: {}",
-                                                e
-                                            );
-                                        }
-                                    }
-                                    Err(e) => {
-                                        log::error!(
-                                            "Weather lookup failed for '{}': {}",
-                                            tool_decision.query,
-                                            e
-                                        );
-
-                                        tool_results.push(ToolExecutionResult {
-                                            tool_type: ToolType::WeatherLookup,
-                                            query: tool_decision.query.clone(),
-                                            success: false,
-                                            content: None,
-                                            error: Some(e.clone()),
-                                        });
-
-                                        if let Err(emit_error) = window.emit(
-                                            "WEATHER_LOOKUP_COMPLETED",
-                                            WeatherLookupCompletedPayload {
-                                                location: tool_decision.query.clone(),
-                                                success: false,
-                                                temperature: None,
-                                                unit: None,
-                                                description: None,
-                                                error: Some(e),
-                                            },
-                                        ) {
-                                            log::warn!("Failed to emit WEATHER_LOOKUP_COMPLETED error event: {}", emit_error);
-                                        }
-                                    }
-                                }
-                            }
-                            ToolType::FinancialData => {
-                                if let Err(e) = window.emit(
-                                    "FINANCIAL_DATA_STARTED",
-                                    FinancialDataStartedPayload {
-                                        query: tool_decision.query.clone(),
-                                        symbol: tool_decision.query.clone(),
-                                    },
-                                ) {
-                                    log::warn!(
-                                        "Failed to emit FINANCIAL_DATA_STARTED event: {}",
-                                        e
-                                    );
-                                }
-
-                                match perform_financial_data_lookup(&client, &tool_decision.query)
-                                    .await
-                                {
-                                    Ok(financial_data) => {
-                                        tool_results.push(ToolExecutionResult {
-                                            tool_type: ToolType::FinancialData,
-                                            query: tool_decision.query.clone(),
-                                            success: true,
-                                            content: Some(financial_data.clone()),
-                                            error: None,
-                                        });
-
-                                        iteration_context.push_str(&format!(
-                                            "Financial Data for '{}':\n{}\n\n",
-                                            tool_decision.query, financial_data
-                                        ));
-
-                                        if let Err(e) = window.emit(
-                                            "FINANCIAL_DATA_COMPLETED",
-                                            FinancialDataCompletedPayload {
-                                                query: tool_decision.query.clone(),
-                                                symbol: tool_decision.query.clone(),
-                                                success: true,
-                                                data: Some(financial_data),
-                                                error: None,
-                                            },
-                                        ) {
-                                            log::warn!(
-                                                "Failed to emit FINANCIAL_DATA_COMPLETED event: {}",
-                                                e
-                                            );
-                                        }
-                                    }
-                                    Err(e) => {
-                                        log::error!(
-                                            "Financial data lookup failed for '{}': {}",
-                                            tool_decision.query,
-                                            e
-                                        );
-
-                                        tool_results.push(ToolExecutionResult {
-                                            tool_type: ToolType::FinancialData,
-                                            query: tool_decision.query.clone(),
-                                            success: false,
-                                            content: None,
-                                            error: Some(e.clone()),
-                                        });
-
-                                        if let Err(emit_error) = window.emit(
-                                            "FINANCIAL_DATA_COMPLETED",
-                                            FinancialDataCompletedPayload {
-                                                query: tool_decision.query.clone(),
-                                                symbol: tool_decision.query.clone(),
-                                                success: false,
-                                                data: None,
-                                                error: Some(e),
-                                            },
-                                        ) {
-                                            log::warn!("Failed to emit FINANCIAL_DATA_COMPLETED error event: {}", emit_error);
-                                        }
-                                    }
-                                }
-                            }
-                            ToolType::ArxivLookup => {
-                                if let Err(e) = window.emit(
-                                    "ARXIV_LOOKUP_STARTED",
-                                    ArxivLookupStartedPayload {
-                                        query: tool_decision.query.clone(),
-                                    },
-                                ) {
-                                    log::warn!("Failed to emit ARXIV_LOOKUP_STARTED event: {}", e);
-                                }
-
-                                match perform_arxiv_lookup(&client, tool_decision.query.clone())
-                                    .await
-                                {
-                                    Ok(papers) => {
-                                        if papers.is_empty() {
-                                            tool_results.push(ToolExecutionResult {
-                                                tool_type: ToolType::ArxivLookup,
-                                                query: tool_decision.query.clone(),
-                                                success: true,
-                                                content: Some("No papers found.".to_string()),
-                                                error: None,
-                                            });
-
-                                            if let Err(e) = window.emit(
-                                                "ARXIV_LOOKUP_COMPLETED",
-                                                ArxivLookupCompletedPayload {
-                                                    query: tool_decision.query.clone(),
-                                                    success: true,
-                                                    results: Some(vec![]),
-                                                    error: None,
-                                                },
-                                            ) {
-                                                log::warn!(
-                                                    "Failed to emit ARXIV_LOOKUP_COMPLETED event: {}",
-                                                    e
-                                                );
-                                            }
-                                        } else {
-                                            let mut arxiv_context = String::new();
-                                            for paper in &papers {
-                                                arxiv_context.push_str(&format!(
-                                                    "Title: {}\nAuthors: {}\nSummary: {}\n\n",
-                                                    paper.title,
-                                                    paper.authors.join(", "),
-                                                    paper.abstract_text
-                                                ));
-                                            }
-
-                                            tool_results.push(ToolExecutionResult {
-                                                tool_type: ToolType::ArxivLookup,
-                                                query: tool_decision.query.clone(),
-                                                success: true,
-                                                content: Some(arxiv_context.clone()),
-                                                error: None,
-                                            });
-
-                                            iteration_context.push_str(&format!(
-                                                "ArXiv Research for '{}':\n{}\n\n",
-                                                tool_decision.query, arxiv_context
-                                            ));
-
-                                            if let Err(e) = window.emit(
-                                                "ARXIV_LOOKUP_COMPLETED",
-                                                ArxivLookupCompletedPayload {
-                                                    query: tool_decision.query.clone(),
-                                                    success: true,
-                                                    results: Some(
-                                                        papers
-                                                            .iter()
-                                                            .map(|p| ArxivPaperSummary {
-                                                                title: p.title.clone(),
-                                                                summary: p.abstract_text.clone(),
-                                                                authors: p.authors.clone(),
-                                                                id: p.id.clone(),
-                                                                published_date: Some(
-                                                                    p.published.clone(),
-                                                                ),
-                                                                pdf_url: p.pdf_url.clone(),
-                                                            })
-                                                            .collect(),
-                                                    ),
-                                                    error: None,
-                                                },
-                                            ) {
-                                                log::warn!(
-                                                    "Failed to emit ARXIV_LOOKUP_COMPLETED event: {}",
-                                                    e
-                                                );
-                                            }
-                                        }
-                                    }
-                                    Err(e) => {
-                                        log::error!(
-                                            "ArXiv lookup failed for '{}': {}",
-                                            tool_decision.query,
-                                            e
-                                        );
-
-                                        tool_results.push(ToolExecutionResult {
-                                            tool_type: ToolType::ArxivLookup,
-                                            query: tool_decision.query.clone(),
-                                            success: false,
-                                            content: None,
-                                            error: Some(e.clone()),
-                                        });
-
-                                        if let Err(emit_error) = window.emit(
-                                            "ARXIV_LOOKUP_COMPLETED",
-                                            ArxivLookupCompletedPayload {
-                                                query: tool_decision.query.clone(),
-                                                success: false,
-                                                results: Some(vec![]),
-                                                error: Some(e),
-                                            },
-                                        ) {
-                                            log::warn!(
-                                                "Failed to emit ARXIV_LOOKUP_COMPLETED error event: {}",
-                                                emit_error
-                                            );
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-
-                    // Add iteration context to overall context
-                    all_tool_context.push_str(&iteration_context);
-
-                    // After each iteration, check if we need more tools based on results
-                    if iteration_count < MAX_ITERATIONS && !iteration_context.is_empty() {
-                        let follow_up_prompt = format!(
-                        "Based on the following research results, determine if additional tools are needed to fully answer the user's query: '{}'\n\n\
-                        Research Results So Far:\n{}\n\n\
-                        AVAILABLE TOOLS for follow-up:\n\
-                        1. WIKIPEDIA_LOOKUP: Use GENERIC terms only (e.g., \"artificial intelligence\", not \"AI companies\")\n\
-                        2. WEATHER_LOOKUP: Weather for specific cities (use city names)\n\
-                        3. FINANCIAL_DATA: Real-time stock prices (use ticker symbols like AAPL, GOOGL, TSLA) - NOT for valuations, GDP, or economic data\n\
-                        4. ARXIV_LOOKUP: Academic papers\n\n\
-                        IMPORTANT GUIDELINES:\n\
-                        - For Wikipedia: Use broad, foundational terms, not specific subtopics\n\
-                        - For Financial: Extract exact ticker symbols from companies mentioned in research\n\
-                        - Example: If research mentions 'IBM Corporation', use ticker 'IBM' for financial lookup\n\
-                        - For broader financial topics (valuations, GDP, economic indicators), use Wikipedia Research instead\n\n\
-                        Respond with JSON:\n\
-                        - If MORE tools needed: {{\"tools\": [{{\"tool_type\": \"...\", \"query\": \"...\", \"reasoning\": \"...\", \"priority\": 1}}], \"reasoning\": \"why more tools needed\"}}\n\
-                        - If NO more tools needed: {{\"tools\": [], \"reasoning\": \"sufficient information gathered\"}}\n\n\
-                        Be specific with queries - use exact ticker symbols for stocks, city names for weather.",
-                        user_query,
-                        all_tool_context.trim_end()
-                    );
+                    // After each iteration, check if we need more tools based on results
+                    if iteration_count < MAX_ITERATIONS && !iteration_context.is_empty() {
+                        let follow_up_prompt = format!(
+                        "Based on the following research results, determine if additional tools are needed to fully answer the user's query: '{}'\n\n\
+                        Research Results So Far:\n{}\n\n\
+                        AVAILABLE TOOLS for follow-up:\n\
+                        {}\n\
+                        IMPORTANT GUIDELINES:\n\
+                        - For Wikipedia: Use broad, foundational terms, not specific subtopics\n\
+                        - For Financial: Extract exact ticker symbols from companies mentioned in research\n\
+                        - Example: If research mentions 'IBM Corporation', use ticker 'IBM' for financial lookup\n\
+                        - For broader financial topics (valuations, GDP, economic indicators), use Wikipedia Research instead\n\
+                        - For Web search: Use short keyword queries for details Wikipedia lacks (recent news, versions, products)\n\n\
+                        Respond with JSON:\n\
+                        - If MORE tools needed: {{\"tools\": [{{\"tool_type\": \"...\", \"query\": \"...\", \"reasoning\": \"...\", \"priority\": 1}}], \"reasoning\": \"why more tools needed\"}}\n\
+                        - If NO more tools needed: {{\"tools\": [], \"reasoning\": \"sufficient information gathered\"}}\n\n\
+                        Be specific with queries - use exact ticker symbols for stocks, city names (or saved places like 'home') for weather.",
+                        user_query,
+                        all_tool_context.trim_end(),
+                        tool_registry.prompt_tool_list(true, is_tool_enabled)
+                    );
 
+                        let mut follow_up_trace =
+                            tools::trace::DeciderTrace::new(&decider_model_name, &follow_up_prompt);
                         let follow_up_messages = vec![ChatMessage {
                             role: "user".to_string(),
                             content: follow_up_prompt,
                             image_base64_data: None,
                             image_mime_type: None,
                             image_file_api_uri: None,
+                            document_ids: None,
                         }];
 
                         match call_gemini_api_non_streaming(
@@ -1967,6 +1341,7 @@ async fn send_text_to_model(
                         .await
                         {
                             Ok(follow_up_response) => {
+                                follow_up_trace.responded(&follow_up_response);
                                 let cleaned_response = follow_up_response
                                     .trim()
                                     .trim_start_matches("```json")
@@ -1983,71 +1358,30 @@ async fn send_text_to_model(
                                             iteration_count,
                                             follow_up_decision.tools.len()
                                         );
+                                        follow_up_trace.decided(&follow_up_decision);
+                                        trace.iteration(iteration_count).follow_up =
+                                            Some(follow_up_trace);
 
                                         if follow_up_decision.tools.is_empty() {
                                             log::info!("No additional tools requested, stopping iterations");
                                             break;
                                         } else {
-                                            // For financial queries, extract ticker symbols from previous context
-                                            for tool in follow_up_decision.tools {
-                                                if tool.tool_type == ToolType::FinancialData {
-                                                    // First try to use the query directly if it looks like a ticker symbol
-                                                    let query_upper =
-                                                        tool.query.trim().to_uppercase();
-                                                    if query_upper.len() <= 5
-                                                        && query_upper
-                                                            .chars()
-                                                            .all(|c| c.is_alphabetic())
-                                                    {
-                                                        // Looks like a ticker symbol already
-                                                        log::info!("Using query as ticker symbol directly: {}", query_upper);
-                                                        new_tool_requests.push(ToolDecision {
-                                                            tool_type: ToolType::FinancialData,
-                                                            query: query_upper.clone(),
-                                                            reasoning: format!(
-                                                                "Stock data for {} (direct symbol)",
-                                                                query_upper
-                                                            ),
-                                                            priority: tool.priority,
-                                                        });
-                                                    } else {
-                                                        // Try to extract ticker symbols from the query or context
-                                                        if let Ok(symbols) =
-                                                            extract_ticker_symbols_from_companies(
-                                                                &client,
-                                                                &format!(
-                                                                    "{} {}",
-                                                                    tool.query, all_tool_context
-                                                                ),
-                                                                &decider_gemini_api_key_string,
-                                                                decider_model_name.clone(),
-                                                            )
-                                                            .await
-                                                        {
-                                                            if !symbols.is_empty() {
-                                                                // Create separate tool calls for each ticker symbol
-                                                                for symbol in symbols {
-                                                                    new_tool_requests.push(ToolDecision {
-                                                                        tool_type: ToolType::FinancialData,
-                                                                        query: symbol.clone(),
-                                                                        reasoning: format!("Stock data for {} (extracted from: {})", symbol, tool.reasoning),
-                                                                        priority: tool.priority,
-                                                                    });
-                                                                }
-                                                            } else {
-                                                                // No valid symbols found, try the original query as fallback
-                                                                log::warn!("No valid ticker symbols extracted from: {}, trying original query", tool.query);
-                                                                new_tool_requests.push(tool);
-                                                            }
-                                                        } else {
-                                                            // Fallback: use the original query if extraction fails
-                                                            new_tool_requests.push(tool);
-                                                        }
-                                                    }
-                                                } else {
-                                                    new_tool_requests.push(tool);
+                                            // Let each tool refine its follow-up requests (e.g. resolve tickers)
+                                            for decision in follow_up_decision.tools {
+                                                match tool_registry.get(&decision.tool_type) {
+                                                    Some(tool) => new_tool_requests.extend(
+                                                        tool.expand_follow_up(
+                                                            &tool_context,
+                                                            decision,
+                                                            &all_tool_context,
+                                                        )
+                                                        .await,
+                                                    ),
+                                                    None => new_tool_requests.push(decision),
                                                 }
                                             }
+                                            trace.iteration(iteration_count).expanded_follow_ups =
+                                                new_tool_requests.clone();
                                         }
                                     }
                                     Err(e) => {
@@ -2055,12 +1389,20 @@ async fn send_text_to_model(
                                             "Failed to parse follow-up tool decision: {}",
                                             e
                                         );
+                                        follow_up_trace.failed(&format!(
+                                            "Failed to parse follow-up decision: {}",
+                                            e
+                                        ));
+                                        trace.iteration(iteration_count).follow_up =
+                                            Some(follow_up_trace);
                                         break;
                                     }
                                 }
                             }
                             Err(e) => {
                                 log::error!("Failed to get follow-up tool decision: {}", e);
+                                follow_up_trace.failed(&e);
+                                trace.iteration(iteration_count).follow_up = Some(follow_up_trace);
                                 break;
                             }
                         }
@@ -2087,6 +1429,28 @@ async fn send_text_to_model(
                     tool_results.len(),
                     all_tool_context.len()
                 );
+
+                // Keep the trace for the inspector and, if enabled, with the conversation;
+                // the papers it found are always stored for export
+                trace.finish(comprehensive_tool_context.as_ref().map_or(0, |c| c.len()));
+                if let Err(e) = tools::trace::persist_papers(&app_handle, &trace) {
+                    log::warn!("Failed to store the papers found: {}", e);
+                }
+                if config.persist_tool_traces.unwrap_or(false) {
+                    if let Err(e) = tools::trace::persist(&app_handle, &trace) {
+                        log::warn!("Failed to persist tool trace: {}", e);
+                    }
+                }
+                if let Err(e) = window.emit(
+                    "TOOL_TRACE_RECORDED",
+                    ToolTraceRecordedPayload {
+                        trace_id: trace.id.clone(),
+                        conversation_id: trace.conversation_id.clone(),
+                    },
+                ) {
+                    log::warn!("Failed to emit TOOL_TRACE_RECORDED event: {}", e);
+                }
+                tools::trace::remember(trace);
             } else {
                 // No tools selected - continue with normal processing
                 log::info!("No tools selected for query: '{}'", user_query);
@@ -2094,14 +1458,72 @@ async fn send_text_to_model(
         }
     }
 
+    // Documents attached anywhere in the conversation: small ones are inlined whole,
+    // large ones contribute the chunks most relevant to the latest question
+    let mut document_ids: Vec<String> = Vec::new();
+    for id in messages
+        .iter()
+        .filter(|msg| msg.role == "user")
+        .flat_map(|msg| msg.document_ids.iter().flatten())
+    {
+        if !document_ids.contains(id) {
+            document_ids.push(id.clone());
+        }
+    }
+    let document_context = if document_ids.is_empty() {
+        None
+    } else {
+        let attached_documents: Vec<documents::Document> = document_ids
+            .iter()
+            .filter_map(|id| match documents::load_document(&app_handle, id) {
+                Ok(document) => Some(document),
+                Err(e) => {
+                    log::warn!("Skipping attached document {}: {}", id, e);
+                    None
+                }
+            })
+            .collect();
+        let question = messages
+            .iter()
+            .rev()
+            .find(|msg| msg.role == "user")
+            .map(|msg| msg.content.as_str())
+            .unwrap_or_default();
+        let context = documents::build_context(&attached_documents, question, &mut citations);
+        log::info!(
+            "Added {} chars of context from {} attached document(s)",
+            context.as_ref().map_or(0, |c| c.len()),
+            attached_documents.len()
+        );
+        context
+    };
+
+    // Resolve the system prompt preset for this conversation and fill in its variables
+    let prompt_library = prompts::load_prompt_library(&app_handle).unwrap_or_else(|e| {
+        log::warn!(
+            "Failed to load prompt presets: {}. Using default prompt.",
+            e
+        );
+        prompts::PromptLibrary::default()
+    });
+    let preset = prompt_library.resolve(
+        system_prompt_id
+            .as_deref()
+            .or(config.system_prompt_id.as_deref()),
+        SYSTEM_INSTRUCTION,
+    );
+    log::info!("Using system prompt preset: '{}'", preset.id);
+    let base_system_prompt =
+        prompts::render_prompt(&preset.content, &prompts::PromptVariables { selected_text });
+
     // Add system instruction - include enhanced MCP guidance only if tools were used
     let system_content = if tool_context_available {
         // Tools were used, add detailed MCP guidance
         use crate::mcp_simple::create_reasoning_enhanced_prompt;
-        create_reasoning_enhanced_prompt(SYSTEM_INSTRUCTION)
+        create_reasoning_enhanced_prompt(&base_system_prompt)
     } else {
-        // No tools used, use standard system instruction
-        SYSTEM_INSTRUCTION.to_string()
+        // No tools used, use the preset as-is
+        base_system_prompt
     };
 
     final_messages.push(ChatMessage {
@@ -2110,21 +1532,43 @@ async fn send_text_to_model(
         image_base64_data: None,
         image_mime_type: None,
         image_file_api_uri: None,
+        document_ids: None,
     });
 
+    if let Some(document_context) = document_context {
+        final_messages.push(ChatMessage {
+            role: "user".to_string(),
+            content: format!(
+                "{}\n\nUse these documents to answer the following user query. Cite the numbered excerpts inline with markers like [1] or [2, 3] wherever you use them.",
+                document_context
+            ),
+            image_base64_data: None,
+            image_mime_type: None,
+            image_file_api_uri: None,
+            document_ids: None,
+        });
+    }
+
     // Add comprehensive tool context if available
     if tool_context_available && comprehensive_tool_context.is_some() {
+        let citation_instruction = if citations.is_empty() {
+            ""
+        } else {
+            " Cite the numbered sources inline with markers like [1] or [2, 3] wherever you use them."
+        };
         final_messages.push(ChatMessage {
             role: "user".to_string(),
             content: format!(
-                "{}\n\nGiven this research context, please answer the following user query:",
+                "{}\n\nGiven this research context, please answer the following user query:{}",
                 comprehensive_tool_context
                     .as_deref()
-                    .unwrap_or("No context available")
+                    .unwrap_or("No context available"),
+                citation_instruction
             ),
             image_base64_data: None,
             image_mime_type: None,
             image_file_api_uri: None,
+            document_ids: None,
         });
     }
 
@@ -2200,7 +1644,10 @@ async fn send_text_to_model(
         )
         .await
         {
-            Ok(_) => Ok(()),
+            Ok(answer) => {
+                emit_cited_sources(&window, &citations, &answer);
+                Ok(())
+            }
             Err(e) => {
                 let _ = window.emit("STREAM_ERROR", StreamErrorPayload { error: e.clone() });
                 Err(e)
@@ -2236,7 +1683,10 @@ async fn send_text_to_model(
         .await
         {
             // Pass client
-            Ok(_) => Ok(()),
+            Ok(answer) => {
+                emit_cited_sources(&window, &citations, &answer);
+                Ok(())
+            }
             Err(e) => {
                 let _ = window.emit("STREAM_ERROR", StreamErrorPayload { error: e.clone() });
                 Err(e)
@@ -2245,6 +1695,18 @@ async fn send_text_to_model(
     }
 }
 
+/// Tell the frontend which numbered sources the finished answer cites
+fn emit_cited_sources(window: &Window, citations: &tools::citations::CitationList, answer: &str) {
+    let sources = citations.cited_in(answer);
+    if sources.is_empty() {
+        return;
+    }
+    log::info!("Answer cites {} source(s)", sources.len());
+    if let Err(e) = window.emit("STREAM_SOURCES", StreamSourcesPayload { sources }) {
+        log::warn!("Failed to emit STREAM_SOURCES event: {}", e);
+    }
+}
+
 #[tauri::command]
 async fn get_api_key(app_handle: AppHandle) -> Result<String, String> {
     load_config(&app_handle).map(|config| config.api_key.unwrap_or_default())
@@ -2274,20 +1736,12 @@ async fn get_selected_model(app_handle: AppHandle) -> Result<String, String> {
 
 #[tauri::command]
 async fn set_selected_model(model_name: String, app_handle: AppHandle) -> Result<(), String> {
-    let allowed_models = vec![
-        "deepseek/deepseek-chat-v3-0324:free",
-        // "deepseek/deepseek-r1:free",
-        "deepseek/deepseek-r1-0528:free",
-        "gemini-2.0-flash", // Keep this for potential direct use or alias
-        "gemini-2.5-flash-preview-05-20", // This is the "Gemini 2.5 Flash (non-thinking)"
-        "gemini-2.5-flash-preview-05-20#thinking-enabled",
-    ];
     // Updated check to be more specific
-    if !allowed_models.contains(&model_name.as_str()) {
+    if !ALLOWED_MODELS.contains(&model_name.as_str()) {
         log::error!("Attempted to set invalid model: {}", model_name);
         return Err(format!(
             "Invalid model selection: {}. Allowed models are: {:?}",
-            model_name, allowed_models
+            model_name, ALLOWED_MODELS
         ));
     }
     let mut config = load_config(&app_handle).unwrap_or_else(|e| {
@@ -2341,65 +1795,764 @@ async fn get_enable_web_search(app_handle: AppHandle) -> Result<bool, String> {
     load_config(&app_handle).map(|config| config.enable_web_search.unwrap_or(true))
 }
 
-// --- ADDED: Command to cancel current stream ---
+// --- Commands for parallel tool execution ---
 #[tauri::command]
-async fn cancel_current_stream() -> Result<(), String> {
-    log::info!("Stream cancellation requested");
-    let current_stream = CURRENT_STREAM_ID.load(Ordering::Relaxed);
-    CANCELLED_STREAM_ID.store(current_stream, Ordering::Relaxed);
-    log::info!("Cancelled stream ID: {}", current_stream);
-    Ok(())
+async fn get_max_parallel_tools(app_handle: AppHandle) -> Result<usize, String> {
+    load_config(&app_handle).map(|config| {
+        config
+            .max_parallel_tools
+            .unwrap_or(tools::executor::DEFAULT_MAX_PARALLEL_TOOLS)
+    })
 }
 
-// --- API Call Logic ---
-async fn call_gemini_api(
-    client: &reqwest::Client, // MODIFIED: Accept client
-    messages: Vec<ChatMessage>,
-    api_key: String,
-    model_identifier_from_config: String, // RENAMED for clarity
+#[tauri::command]
+async fn set_max_parallel_tools(limit: usize, app_handle: AppHandle) -> Result<(), String> {
+    if limit == 0 {
+        return Err("At least one tool must be allowed to run at a time".to_string());
+    }
+    let mut config = load_config(&app_handle).unwrap_or_else(|e| {
+        log::warn!(
+            "Failed to load config when setting parallel tool limit: {}. Using default.",
+            e
+        );
+        AppConfig::default()
+    });
+    config.max_parallel_tools = Some(limit);
+    save_config(&app_handle, &config)
+}
+
+// --- Command to clear the tool result cache ---
+/// Clear cached tool results, optionally only those of one tool type; returns the number removed
+#[tauri::command]
+async fn clear_tool_cache(
+    tool_type: Option<String>,
+    app_handle: AppHandle,
+) -> Result<usize, String> {
+    let tool_name = match tool_type.as_deref() {
+        Some(name) => Some(
+            ToolType::from_name(name)
+                .ok_or_else(|| format!("Unknown tool type: {}", name))?
+                .as_str(),
+        ),
+        None => None,
+    };
+    let removed = tools::cache::tool_cache(&app_handle).clear(tool_name);
+    log::info!("Cleared {} cached tool result(s)", removed);
+    Ok(removed)
+}
+
+// --- Commands for deep research ---
+/// Start a deep research job in the background and return its id
+///
+/// Progress arrives as DEEP_RESEARCH_PROGRESS events and the report as
+/// DEEP_RESEARCH_COMPLETED; `get_deep_research_job` returns the saved state.
+#[tauri::command]
+async fn start_deep_research(
+    question: String,
+    conversation_id: Option<String>,
+    budget: Option<research::ResearchBudget>,
+    app_handle: AppHandle,
+    window: Window,
+) -> Result<String, String> {
+    if question.trim().is_empty() {
+        return Err("Deep research needs a question".to_string());
+    }
+    let config = load_config(&app_handle)?;
+    if !config.enable_web_search.unwrap_or(true) {
+        return Err("Deep research needs research tools; enable web search in settings.".to_string());
+    }
+    if config.gemini_api_key.as_deref().unwrap_or_default().is_empty() {
+        return Err("Deep research needs a Gemini API key. Please set it in settings.".to_string());
+    }
+    let budget = budget
+        .or(config.deep_research)
+        .unwrap_or_default();
+    budget.validate()?;
+    let job = research::ResearchJob::new(
+        &question,
+        conversation_id,
+        &research::report_model(config.selected_model.as_deref()),
+        budget,
+    );
+    research::save_job(&app_handle, &job)?;
+    let job_id = job.id.clone();
+    tauri::async_runtime::spawn(research::run_job(app_handle, window, job));
+    Ok(job_id)
+}
+
+/// Continue an interrupted job from its last saved step
+#[tauri::command]
+async fn resume_deep_research(
+    job_id: String,
+    app_handle: AppHandle,
     window: Window,
-    stream_id: u64,
 ) -> Result<(), String> {
-    let mut actual_model_name_for_api = model_identifier_from_config.clone();
-    let mut gen_config: Option<GenerationConfigForGemini> = None;
+    let job = research::load_job(&app_handle, &job_id)?;
+    if job.status != research::ResearchStatus::Interrupted {
+        return Err(format!("Research job is {:?}, not interrupted", job.status));
+    }
+    tauri::async_runtime::spawn(research::run_job(app_handle, window, job));
+    Ok(())
+}
 
-    if model_identifier_from_config == "gemini-2.5-flash-preview-05-20" {
-        // This is the "Gemini 2.5 Flash" (non-thinking explicit budget 0)
-        gen_config = Some(GenerationConfigForGemini {
-            thinking_config: Some(ThinkingConfig {
-                include_thoughts: None, // Let API decide default or if it's implied by budget
-                thinking_budget: Some(0),
-            }),
-            // ..Default::default() // for other potential future fields in GenerationConfigForGemini
-        });
-        // actual_model_name_for_api is already correct
-    } else if model_identifier_from_config == "gemini-2.5-flash-preview-05-20#thinking-enabled" {
-        // This is "Gemini 2.5 Flash (Thinking)" (default thinking, no specific budget)
-        actual_model_name_for_api = "gemini-2.5-flash-preview-05-20".to_string(); // Use base model name for API
-        gen_config = Some(GenerationConfigForGemini {
-            thinking_config: Some(ThinkingConfig {
-                include_thoughts: Some(true),
-                thinking_budget: None,
-            }),
-            // This means include_thoughts is true and thinking_budget is non-zero.
-        });
+#[tauri::command]
+async fn cancel_deep_research(job_id: String, app_handle: AppHandle) -> Result<(), String> {
+    if research::request_cancel(&job_id) {
+        return Ok(());
     }
-    // For other gemini models, gen_config remains None (no other thinking models), and no specific generation_config will be sent.
+    // Not running in this process: an interrupted job is cancelled on disk
+    let mut job = research::load_job(&app_handle, &job_id)?;
+    if job.status == research::ResearchStatus::Interrupted {
+        job.status = research::ResearchStatus::Cancelled;
+        research::save_job(&app_handle, &job)?;
+    }
+    Ok(())
+}
 
-    let api_url = format!(
-        "https://generativelanguage.googleapis.com/v1beta/models/{}:streamGenerateContent?key={}&alt=sse",
-        actual_model_name_for_api, // Use the potentially modified model name
-        api_key
-    );
+#[tauri::command]
+async fn get_deep_research_job(
+    job_id: String,
+    app_handle: AppHandle,
+) -> Result<research::ResearchJob, String> {
+    research::load_job(&app_handle, &job_id)
+}
 
-    let request_payload = GeminiChatCompletionRequest {
-        contents: messages
-            .into_iter()
-            .map(|chat_msg| {
-                let role_for_gemini = if chat_msg.role == "assistant" {
-                    "model".to_string()
-                } else if chat_msg.role == "system" {
-                    // Our prepended system instruction
+/// Saved research jobs, newest first
+#[tauri::command]
+async fn list_deep_research_jobs(
+    conversation_id: Option<String>,
+    app_handle: AppHandle,
+) -> Result<Vec<research::ResearchJob>, String> {
+    research::list_jobs(&app_handle, conversation_id.as_deref())
+}
+
+#[tauri::command]
+async fn delete_deep_research_job(job_id: String, app_handle: AppHandle) -> Result<(), String> {
+    research::delete_job(&app_handle, &job_id)
+}
+
+#[tauri::command]
+async fn get_deep_research_budget(
+    app_handle: AppHandle,
+) -> Result<research::ResearchBudget, String> {
+    load_config(&app_handle).map(|config| config.deep_research.unwrap_or_default())
+}
+
+#[tauri::command]
+async fn set_deep_research_budget(
+    budget: research::ResearchBudget,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    budget.validate()?;
+    let mut config = load_config(&app_handle).unwrap_or_else(|e| {
+        log::warn!(
+            "Failed to load config when setting deep research budget: {}. Using default.",
+            e
+        );
+        AppConfig::default()
+    });
+    config.deep_research = Some(budget);
+    save_config(&app_handle, &config)
+}
+
+// --- Command for exporting papers as a bibliography ---
+/// Export papers found during research as BibTeX, RIS or CSL-JSON: those found
+/// in the conversation `conversation_id`, then `papers`, then `references`
+/// (arXiv ids, arXiv URLs or DOIs), which are looked up
+#[tauri::command]
+async fn export_papers(
+    conversation_id: Option<String>,
+    papers: Option<Vec<tools::scholarly::PaperSummary>>,
+    references: Option<Vec<String>>,
+    format: String,
+    app_handle: AppHandle,
+) -> Result<String, String> {
+    let format = tools::bibliography::BibliographyFormat::from_name(&format)
+        .ok_or_else(|| format!("Unknown bibliography format: {}", format))?;
+    let mut exported = match &conversation_id {
+        Some(id) => tools::trace::conversation_papers(&app_handle, id)?,
+        None => Vec::new(),
+    };
+    exported.extend(papers.unwrap_or_default());
+    let client = reqwest::Client::new();
+    for reference in references.unwrap_or_default() {
+        exported.push(tools::bibliography::resolve_reference(&client, &reference).await?);
+    }
+    if exported.is_empty() {
+        return Err(match conversation_id {
+            Some(id) => format!("No papers were found in conversation {}", id),
+            None => "No papers to export".to_string(),
+        });
+    }
+    Ok(tools::bibliography::export(&exported, format))
+}
+
+// --- Commands for the ticker table ---
+/// Replace the user's company-to-ticker table (tab-separated: symbol, name,
+/// `|`-separated aliases), which adds to and overrides the bundled one
+#[tauri::command]
+async fn import_ticker_table(tsv: String, app_handle: AppHandle) -> Result<usize, String> {
+    let count = tools::tickers::import_table(&app_handle, &tsv)?;
+    log::info!("Imported {} ticker(s)", count);
+    Ok(count)
+}
+
+/// Remove the user's ticker table, going back to the bundled one
+#[tauri::command]
+async fn reset_ticker_table(app_handle: AppHandle) -> Result<(), String> {
+    tools::tickers::reset_table(&app_handle)
+}
+
+// --- Commands for the tool trace inspector ---
+/// The trace with the given id, or the most recent one
+#[tauri::command]
+async fn get_tool_trace(
+    trace_id: Option<String>,
+) -> Result<Option<tools::trace::ToolTrace>, String> {
+    Ok(tools::trace::find(trace_id.as_deref()))
+}
+
+#[tauri::command]
+async fn get_conversation_tool_traces(
+    conversation_id: String,
+    app_handle: AppHandle,
+) -> Result<Vec<tools::trace::ToolTrace>, String> {
+    tools::trace::load_conversation_traces(&app_handle, &conversation_id)
+}
+
+#[tauri::command]
+async fn get_persist_tool_traces(app_handle: AppHandle) -> Result<bool, String> {
+    load_config(&app_handle).map(|config| config.persist_tool_traces.unwrap_or(false))
+}
+
+#[tauri::command]
+async fn set_persist_tool_traces(enabled: bool, app_handle: AppHandle) -> Result<(), String> {
+    let mut config = load_config(&app_handle).unwrap_or_else(|e| {
+        log::warn!(
+            "Failed to load config when setting tool trace persistence: {}. Using default.",
+            e
+        );
+        AppConfig::default()
+    });
+    config.persist_tool_traces = Some(enabled);
+    save_config(&app_handle, &config)
+}
+
+// --- Commands for web search settings ---
+#[tauri::command]
+async fn get_web_search_settings(
+    app_handle: AppHandle,
+) -> Result<tools::web_search::WebSearchSettings, String> {
+    load_config(&app_handle).map(|config| config.web_search.unwrap_or_default())
+}
+
+#[tauri::command]
+async fn set_web_search_settings(
+    settings: tools::web_search::WebSearchSettings,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    // Reject settings that would make every web search fail
+    tools::web_search::provider_for(&settings)?;
+    let mut config = load_config(&app_handle).unwrap_or_else(|e| {
+        log::warn!(
+            "Failed to load config when setting web search settings: {}. Using default.",
+            e
+        );
+        AppConfig::default()
+    });
+    config.web_search = Some(settings);
+    save_config(&app_handle, &config)
+}
+
+// --- Commands for wiki settings ---
+#[tauri::command]
+async fn get_wiki_settings(
+    app_handle: AppHandle,
+) -> Result<tools::wikipedia::WikiSettings, String> {
+    load_config(&app_handle).map(|config| config.wiki.unwrap_or_default())
+}
+
+#[tauri::command]
+async fn set_wiki_settings(
+    settings: tools::wikipedia::WikiSettings,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    settings.validate()?;
+    let mut config = load_config(&app_handle).unwrap_or_else(|e| {
+        log::warn!(
+            "Failed to load config when setting wiki settings: {}. Using default.",
+            e
+        );
+        AppConfig::default()
+    });
+    config.wiki = Some(settings);
+    save_config(&app_handle, &config)
+}
+
+// --- Commands for weather settings ---
+#[tauri::command]
+async fn get_weather_settings(
+    app_handle: AppHandle,
+) -> Result<tools::weather::WeatherSettings, String> {
+    load_config(&app_handle).map(|config| config.weather.unwrap_or_default())
+}
+
+#[tauri::command]
+async fn set_weather_settings(
+    settings: tools::weather::WeatherSettings,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    settings.validate()?;
+    let mut config = load_config(&app_handle).unwrap_or_else(|e| {
+        log::warn!(
+            "Failed to load config when setting weather settings: {}. Using default.",
+            e
+        );
+        AppConfig::default()
+    });
+    config.weather = Some(settings);
+    save_config(&app_handle, &config)
+}
+
+/// Places matching a name, the likeliest first, for saving as "home", "office", etc.
+#[tauri::command]
+async fn search_weather_locations(
+    query: String,
+) -> Result<Vec<tools::weather::LocationCandidate>, String> {
+    let client = reqwest::Client::new();
+    tools::weather::find_locations(&client, &query)
+        .await
+        .map(|(candidates, _)| candidates)
+}
+
+// --- Commands for URL fetch settings ---
+#[tauri::command]
+async fn get_url_fetch_settings(
+    app_handle: AppHandle,
+) -> Result<tools::fetch_url::UrlFetchSettings, String> {
+    load_config(&app_handle).map(|config| config.url_fetch.unwrap_or_default())
+}
+
+#[tauri::command]
+async fn set_url_fetch_settings(
+    settings: tools::fetch_url::UrlFetchSettings,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    if settings.max_bytes == Some(0) {
+        return Err("The download size limit must be greater than zero".to_string());
+    }
+    let mut config = load_config(&app_handle).unwrap_or_else(|e| {
+        log::warn!(
+            "Failed to load config when setting URL fetch settings: {}. Using default.",
+            e
+        );
+        AppConfig::default()
+    });
+    config.url_fetch = Some(settings);
+    save_config(&app_handle, &config)
+}
+
+// --- Commands for the local knowledge base ---
+/// Watch `folders` and bring the index up to date with them
+async fn apply_knowledge_folders(
+    app_handle: &AppHandle,
+    folders: Vec<String>,
+) -> Result<tools::knowledge::IndexStats, String> {
+    let knowledge = tools::knowledge::knowledge_base(app_handle);
+    knowledge.set_folders(folders);
+    tokio::task::spawn_blocking(move || knowledge.reindex())
+        .await
+        .map_err(|e| format!("Knowledge indexing failed: {}", e))
+}
+
+#[tauri::command]
+async fn get_knowledge_folders(app_handle: AppHandle) -> Result<Vec<String>, String> {
+    load_config(&app_handle).map(|config| config.knowledge_base.unwrap_or_default().folders)
+}
+
+#[tauri::command]
+async fn add_knowledge_folder(
+    path: String,
+    app_handle: AppHandle,
+) -> Result<tools::knowledge::IndexStats, String> {
+    let folder = fs::canonicalize(path.trim())
+        .map_err(|e| format!("Failed to open folder {}: {}", path, e))?;
+    if !folder.is_dir() {
+        return Err(format!("{} is not a folder", folder.display()));
+    }
+    let folder = folder.to_string_lossy().into_owned();
+    let mut config = load_config(&app_handle).unwrap_or_else(|e| {
+        log::warn!(
+            "Failed to load config when adding a knowledge folder: {}. Using default.",
+            e
+        );
+        AppConfig::default()
+    });
+    let mut settings = config.knowledge_base.clone().unwrap_or_default();
+    if !settings.folders.contains(&folder) {
+        settings.folders.push(folder);
+    }
+    config.knowledge_base = Some(settings.clone());
+    save_config(&app_handle, &config)?;
+    apply_knowledge_folders(&app_handle, settings.folders).await
+}
+
+#[tauri::command]
+async fn remove_knowledge_folder(
+    path: String,
+    app_handle: AppHandle,
+) -> Result<tools::knowledge::IndexStats, String> {
+    let mut config = load_config(&app_handle).unwrap_or_else(|e| {
+        log::warn!(
+            "Failed to load config when removing a knowledge folder: {}. Using default.",
+            e
+        );
+        AppConfig::default()
+    });
+    // Folders are stored canonicalized; a folder that no longer exists is matched as given
+    let folder = fs::canonicalize(path.trim())
+        .map(|folder| folder.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| {
+            path.trim()
+                .trim_end_matches(std::path::MAIN_SEPARATOR)
+                .to_string()
+        });
+    let mut settings = config.knowledge_base.clone().unwrap_or_default();
+    let before = settings.folders.len();
+    settings.folders.retain(|existing| *existing != folder);
+    if settings.folders.len() == before {
+        return Err(format!("{} is not a knowledge folder", path.trim()));
+    }
+    config.knowledge_base = Some(settings.clone());
+    save_config(&app_handle, &config)?;
+    apply_knowledge_folders(&app_handle, settings.folders).await
+}
+
+#[tauri::command]
+async fn reindex_knowledge_base(
+    app_handle: AppHandle,
+) -> Result<tools::knowledge::IndexStats, String> {
+    let folders = load_config(&app_handle)?
+        .knowledge_base
+        .unwrap_or_default()
+        .folders;
+    apply_knowledge_folders(&app_handle, folders).await
+}
+
+#[tauri::command]
+async fn get_knowledge_base_status(
+    app_handle: AppHandle,
+) -> Result<tools::knowledge::IndexStats, String> {
+    Ok(tools::knowledge::knowledge_base(&app_handle).stats())
+}
+
+// --- Commands for document attachments ---
+/// Extract a file attached from disk (`path`) or uploaded by the frontend (`data_base64`)
+#[tauri::command]
+async fn attach_document(
+    filename: String,
+    path: Option<String>,
+    data_base64: Option<String>,
+    app_handle: AppHandle,
+) -> Result<documents::DocumentInfo, String> {
+    let bytes = documents::read_attachment(path.as_deref(), data_base64.as_deref())?;
+    // PDF extraction can panic on malformed files; the blocking task contains it
+    let document =
+        tokio::task::spawn_blocking(move || documents::extract_document(&filename, path, &bytes))
+            .await
+            .map_err(|e| format!("Document extraction failed: {}", e))??;
+    documents::save_document(&app_handle, &document)?;
+    let info = document.info();
+    log::info!(
+        "Attached document '{}' ({:?}, {} page(s), {} chars)",
+        info.filename,
+        info.kind,
+        info.pages,
+        info.characters
+    );
+    Ok(info)
+}
+
+#[tauri::command]
+async fn delete_document(id: String, app_handle: AppHandle) -> Result<(), String> {
+    documents::delete_document(&app_handle, &id)
+}
+
+// --- Commands for System Prompt Presets ---
+#[tauri::command]
+async fn list_system_prompts(app_handle: AppHandle) -> Result<Vec<prompts::PromptPreset>, String> {
+    prompts::load_prompt_library(&app_handle)
+        .map(|library| library.with_built_ins(SYSTEM_INSTRUCTION))
+}
+
+#[tauri::command]
+async fn save_system_prompt(
+    preset: prompts::PromptPreset,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    let mut library = prompts::load_prompt_library(&app_handle)?;
+    log::info!("Saving system prompt preset: '{}'", preset.id);
+    library.upsert(preset)?;
+    prompts::save_prompt_library(&app_handle, &library)
+}
+
+#[tauri::command]
+async fn delete_system_prompt(id: String, app_handle: AppHandle) -> Result<(), String> {
+    let mut library = prompts::load_prompt_library(&app_handle)?;
+    if !library.remove(&id)? {
+        return Err(format!("System prompt preset '{}' not found", id));
+    }
+    prompts::save_prompt_library(&app_handle, &library)?;
+
+    // Don't leave the config pointing at a preset that no longer exists
+    let mut config = load_config(&app_handle)?;
+    if config.system_prompt_id.as_deref() == Some(id.as_str()) {
+        config.system_prompt_id = None;
+        save_config(&app_handle, &config)?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_default_system_prompt(app_handle: AppHandle) -> Result<String, String> {
+    load_config(&app_handle).map(|config| {
+        config
+            .system_prompt_id
+            .unwrap_or_else(|| prompts::DEFAULT_PROMPT_ID.to_string())
+    })
+}
+
+#[tauri::command]
+async fn set_default_system_prompt(id: String, app_handle: AppHandle) -> Result<(), String> {
+    let library = prompts::load_prompt_library(&app_handle)?;
+    if id != prompts::DEFAULT_PROMPT_ID && !library.presets.iter().any(|p| p.id == id) {
+        return Err(format!("System prompt preset '{}' not found", id));
+    }
+    let mut config = load_config(&app_handle).unwrap_or_else(|e| {
+        log::warn!(
+            "Failed to load config when setting system prompt: {}. Using default.",
+            e
+        );
+        AppConfig::default()
+    });
+    config.system_prompt_id = Some(id);
+    save_config(&app_handle, &config)
+}
+
+/// Render a preset with its variables filled in, as it would be sent to the model
+#[tauri::command]
+async fn preview_system_prompt(
+    id: Option<String>,
+    selected_text: Option<String>,
+    app_handle: AppHandle,
+) -> Result<String, String> {
+    let library = prompts::load_prompt_library(&app_handle)?;
+    let preset = library.resolve(id.as_deref(), SYSTEM_INSTRUCTION);
+    Ok(prompts::render_prompt(
+        &preset.content,
+        &prompts::PromptVariables { selected_text },
+    ))
+}
+
+// --- Commands for Slash-Command Prompt Templates ---
+fn validate_template(template: &templates::PromptTemplate) -> Result<(), String> {
+    if let Some(tools) = &template.tools {
+        if let Some(unknown) = tools.iter().find(|t| ToolType::from_name(t).is_none()) {
+            return Err(format!(
+                "Template '/{}' uses unknown tool type '{}'",
+                template.name, unknown
+            ));
+        }
+    }
+    if let Some(model) = &template.model {
+        if !ALLOWED_MODELS.contains(&model.as_str()) {
+            return Err(format!(
+                "Template '/{}' uses invalid model '{}'. Allowed models are: {:?}",
+                template.name, model, ALLOWED_MODELS
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn list_prompt_templates(
+    app_handle: AppHandle,
+) -> Result<Vec<templates::PromptTemplate>, String> {
+    templates::load_template_library(&app_handle).map(|library| library.all())
+}
+
+#[tauri::command]
+async fn save_prompt_template(
+    template: templates::PromptTemplate,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    validate_template(&template)?;
+    let mut library = templates::load_template_library(&app_handle)?;
+    log::info!("Saving prompt template: '/{}'", template.name);
+    library.upsert(template)?;
+    templates::save_template_library(&app_handle, &library)
+}
+
+#[tauri::command]
+async fn delete_prompt_template(name: String, app_handle: AppHandle) -> Result<(), String> {
+    let mut library = templates::load_template_library(&app_handle)?;
+    if !library.remove(&name) {
+        return Err(format!("No user template named '/{}'", name));
+    }
+    templates::save_template_library(&app_handle, &library)
+}
+
+/// Import templates from TOML, replacing user templates with the same name
+#[tauri::command]
+async fn import_prompt_templates(toml: String, app_handle: AppHandle) -> Result<usize, String> {
+    let imported = templates::templates_from_toml(&toml)?;
+    for template in &imported {
+        validate_template(template)?;
+    }
+    let mut library = templates::load_template_library(&app_handle)?;
+    let count = imported.len();
+    for template in imported {
+        library.upsert(template)?;
+    }
+    templates::save_template_library(&app_handle, &library)?;
+    log::info!("Imported {} prompt template(s)", count);
+    Ok(count)
+}
+
+/// Export templates as TOML; exports all user templates when no names are given
+#[tauri::command]
+async fn export_prompt_templates(
+    names: Option<Vec<String>>,
+    app_handle: AppHandle,
+) -> Result<String, String> {
+    let library = templates::load_template_library(&app_handle)?;
+    let selected = match names {
+        Some(names) => library
+            .all()
+            .into_iter()
+            .filter(|t| names.contains(&t.name))
+            .collect(),
+        None => library.templates,
+    };
+    templates::templates_to_toml(selected)
+}
+
+/// Preview what a message starting with `/name` expands to
+#[tauri::command]
+async fn expand_prompt_template(message: String, app_handle: AppHandle) -> Result<String, String> {
+    let invocation = match templates::parse_invocation(&message) {
+        Some(invocation) => invocation,
+        None => return Ok(message),
+    };
+    let library = templates::load_template_library(&app_handle)?;
+    match library.find(&invocation.name) {
+        Some(template) => template.expand(&invocation.args).map(|e| e.content),
+        None => Ok(message),
+    }
+}
+
+// --- Commands for Per-Tool Policy ---
+#[tauri::command]
+async fn get_tool_policy(app_handle: AppHandle) -> Result<tool_policy::ToolPolicy, String> {
+    load_config(&app_handle).map(|config| config.tool_policy.unwrap_or_default())
+}
+
+#[tauri::command]
+async fn set_tool_policy(
+    policy: tool_policy::ToolPolicy,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    let policy = policy.normalized()?;
+    let mut config = load_config(&app_handle).unwrap_or_else(|e| {
+        log::warn!(
+            "Failed to load config when setting tool policy: {}. Using default.",
+            e
+        );
+        AppConfig::default()
+    });
+    config.tool_policy = Some(policy);
+    save_config(&app_handle, &config)
+}
+
+/// Answer a TOOL_CONFIRMATION_REQUESTED event
+#[tauri::command]
+async fn respond_tool_confirmation(request_id: String, approved: bool) -> Result<(), String> {
+    log::info!(
+        "Tool confirmation {} answered: {}",
+        request_id,
+        if approved { "approved" } else { "denied" }
+    );
+    if tool_policy::resolve_confirmation(&request_id, approved) {
+        Ok(())
+    } else {
+        Err(format!(
+            "No pending tool confirmation with ID {} (it may have timed out)",
+            request_id
+        ))
+    }
+}
+
+// --- ADDED: Command to cancel current stream ---
+#[tauri::command]
+async fn cancel_current_stream() -> Result<(), String> {
+    log::info!("Stream cancellation requested");
+    let current_stream = CURRENT_STREAM_ID.load(Ordering::Relaxed);
+    CANCELLED_STREAM_ID.store(current_stream, Ordering::Relaxed);
+    log::info!("Cancelled stream ID: {}", current_stream);
+    Ok(())
+}
+
+// --- API Call Logic ---
+async fn call_gemini_api(
+    client: &reqwest::Client, // MODIFIED: Accept client
+    messages: Vec<ChatMessage>,
+    api_key: String,
+    model_identifier_from_config: String, // RENAMED for clarity
+    window: Window,
+    stream_id: u64,
+) -> Result<String, String> {
+    // Returns the final answer content (without reasoning) once the stream ends
+    let mut actual_model_name_for_api = model_identifier_from_config.clone();
+    let mut gen_config: Option<GenerationConfigForGemini> = None;
+
+    if model_identifier_from_config == "gemini-2.5-flash-preview-05-20" {
+        // This is the "Gemini 2.5 Flash" (non-thinking explicit budget 0)
+        gen_config = Some(GenerationConfigForGemini {
+            thinking_config: Some(ThinkingConfig {
+                include_thoughts: None, // Let API decide default or if it's implied by budget
+                thinking_budget: Some(0),
+            }),
+            // ..Default::default() // for other potential future fields in GenerationConfigForGemini
+        });
+        // actual_model_name_for_api is already correct
+    } else if model_identifier_from_config == "gemini-2.5-flash-preview-05-20#thinking-enabled" {
+        // This is "Gemini 2.5 Flash (Thinking)" (default thinking, no specific budget)
+        actual_model_name_for_api = "gemini-2.5-flash-preview-05-20".to_string(); // Use base model name for API
+        gen_config = Some(GenerationConfigForGemini {
+            thinking_config: Some(ThinkingConfig {
+                include_thoughts: Some(true),
+                thinking_budget: None,
+            }),
+            // This means include_thoughts is true and thinking_budget is non-zero.
+        });
+    }
+    // For other gemini models, gen_config remains None (no other thinking models), and no specific generation_config will be sent.
+
+    let api_url = format!(
+        "https://generativelanguage.googleapis.com/v1beta/models/{}:streamGenerateContent?key={}&alt=sse",
+        actual_model_name_for_api, // Use the potentially modified model name
+        api_key
+    );
+
+    let request_payload = GeminiChatCompletionRequest {
+        contents: messages
+            .into_iter()
+            .map(|chat_msg| {
+                let role_for_gemini = if chat_msg.role == "assistant" {
+                    "model".to_string()
+                } else if chat_msg.role == "system" {
+                    // Our prepended system instruction
                     "user".to_string() // Gemini handles system prompts as initial "user" messages
                 } else {
                     // "user" (from human actual input)
@@ -2605,7 +2758,7 @@ async fn call_gemini_api(
                     }
                 }
                 // Stream ended - check if cancelled or completed normally
-                if stream_id == CANCELLED_STREAM_ID.load(Ordering::Relaxed) {
+                let final_answer = if stream_id == CANCELLED_STREAM_ID.load(Ordering::Relaxed) {
                     // Stream was cancelled intentionally
                     log::info!("Gemini stream ended due to cancellation");
 
@@ -2620,7 +2773,7 @@ async fn call_gemini_api(
                     let _ = window.emit(
                         "STREAM_END",
                         StreamEndPayload {
-                            full_content: final_content,
+                            full_content: final_content.clone(),
                             reasoning: if final_reasoning.is_empty() {
                                 None
                             } else {
@@ -2628,6 +2781,7 @@ async fn call_gemini_api(
                             },
                         },
                     );
+                    final_content
                 } else {
                     // Stream completed normally
                     log::info!(
@@ -2646,7 +2800,7 @@ async fn call_gemini_api(
                     let _ = window.emit(
                         "STREAM_END",
                         StreamEndPayload {
-                            full_content: final_content,
+                            full_content: final_content.clone(),
                             reasoning: if final_reasoning.is_empty() {
                                 None
                             } else {
@@ -2654,8 +2808,9 @@ async fn call_gemini_api(
                             },
                         },
                     );
-                }
-                Ok(())
+                    final_content
+                };
+                Ok(final_answer)
             } else {
                 let status = response.status();
                 let error_text = response
@@ -2701,7 +2856,8 @@ async fn call_openrouter_api(
     model_name: String,
     window: Window,
     stream_id: u64,
-) -> Result<(), String> {
+) -> Result<String, String> {
+    // Returns the final answer content once the stream ends
     let api_url = "https://openrouter.ai/api/v1/chat/completions";
     let mut request_payload = ChatCompletionRequest {
         model: model_name.clone(),
@@ -2781,7 +2937,7 @@ async fn call_openrouter_api(
                                                         reasoning: final_reasoning,
                                                     },
                                                 );
-                                                return Ok(()); // Successfully finished streaming
+                                                return Ok(accumulated_content); // Successfully finished streaming
                                             }
                                             match serde_json::from_str::<
                                                 StreamingChatCompletionResponse,
@@ -2906,11 +3062,11 @@ async fn call_openrouter_api(
                     let _ = window.emit(
                         "STREAM_END",
                         StreamEndPayload {
-                            full_content: accumulated_content,
+                            full_content: accumulated_content.clone(),
                             reasoning: final_reasoning_cancelled,
                         },
                     );
-                    Ok(()) // Return Ok since cancellation is not an error
+                    Ok(accumulated_content) // Return Ok since cancellation is not an error
                 } else {
                     // Stream ended unexpectedly without cancellation
                     log::warn!("OpenRouter stream ended without [DONE] marker.");
@@ -2956,813 +3112,146 @@ async fn call_openrouter_api(
                     format!("API request failed: {} - {}", status, error_text)
                 };
 
-                log::error!(
-                    "OpenRouter API request failed with status {}: {}",
-                    status,
-                    error_text
-                );
-
-                // Emit the error only once
-                let _ = window.emit(
-                    "STREAM_ERROR",
-                    StreamErrorPayload {
-                        error: error_msg.clone(),
-                    },
-                );
-
-                // Return the detailed error message
-                Err(error_msg)
-            }
-        }
-        Err(e) => {
-            log::error!("Network request to OpenRouter failed: {}", e);
-            let err_msg = format!("Network request failed: {}", e);
-            let _ = window.emit(
-                "STREAM_ERROR",
-                StreamErrorPayload {
-                    error: err_msg.clone(),
-                },
-            );
-            Err(err_msg)
-        }
-    }
-}
-
-// --- ADDED: Non-streaming Gemini API call function ---
-async fn call_gemini_api_non_streaming(
-    client: &reqwest::Client,
-    messages: Vec<ChatMessage>,
-    api_key_slice: &str, // Changed parameter name for clarity
-    model_name: String,
-) -> Result<String, String> {
-    if api_key_slice.is_empty() {
-        return Err("API key is empty for non-streaming Gemini call".to_string());
-    }
-    let api_url = format!(
-        "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent?key={}",
-        model_name, api_key_slice
-    );
-
-    // For a simple YES/NO decider, complex generation_config is not needed.
-    // We can omit it or send a minimal one if required by the API.
-    // For now, omitting `generation_config` for simplicity for the decider call.
-    let request_payload = GeminiChatCompletionRequest {
-        contents: messages
-            .into_iter()
-            .map(|msg| GeminiContent {
-                parts: vec![GeminiPart::Text { text: msg.content }], // Corrected: Use enum variant
-                role: Some(msg.role), // Directly use the role, assuming "user" for decider prompt
-            })
-            .collect(),
-        generation_config: None, // No special generation config for the simple decider
-    };
-
-    // log::info!(
-    //     "Sending NON-STREAMING request to Gemini API for model: {}. Payload: {:?}",
-    //     model_name,
-    //     request_payload
-    // );
-
-    match client
-        .post(&api_url)
-        .header("Content-Type", "application/json")
-        .json(&request_payload)
-        .send()
-        .await
-    {
-        Ok(response) => {
-            if response.status().is_success() {
-                match response.json::<GeminiChatCompletionResponse>().await {
-                    Ok(gemini_response) => {
-                        if let Some(candidate) = gemini_response.candidates.get(0) {
-                            if let Some(part) = candidate.content.parts.get(0) {
-                                match part {
-                                    // Corrected: Destructure GeminiPart
-                                    GeminiPart::Text { text } => {
-                                        log::debug!("Non-streaming Gemini response text: {}", text);
-                                        Ok(text.clone())
-                                    }
-                                    GeminiPart::FileData { .. } => Err(
-                                        "Non-streaming Gemini response: Unexpected FileData part"
-                                            .to_string(),
-                                    ),
-                                }
-                            } else {
-                                Err("Non-streaming Gemini response: No content parts found"
-                                    .to_string())
-                            }
-                        } else {
-                            Err("Non-streaming Gemini response: No candidates found".to_string())
-                        }
-                    }
-                    Err(e) => Err(format!(
-                        "Failed to parse non-streaming Gemini JSON response: {}",
-                        e
-                    )),
-                }
-            } else {
-                let status = response.status();
-                let error_text = response.text().await.unwrap_or_else(|_| {
-                    "Could not read error body from Gemini (non-streaming)".to_string()
-                });
-                log::error!(
-                    "Gemini API (non-streaming) request failed with status {}: {}",
-                    status,
-                    error_text
-                );
-                Err(format!(
-                    "Gemini API (non-streaming) request failed: {} - {}",
-                    status, error_text
-                ))
-            }
-        }
-        Err(e) => {
-            log::error!(
-                "Network request to Gemini API (non-streaming) failed: {}",
-                e
-            );
-            Err(format!(
-                "Gemini API (non-streaming) network request failed: {}",
-                e
-            ))
-        }
-    }
-}
-
-#[cfg(target_os = "macos")]
-#[allow(dead_code)]
-fn window_should_become_key(_panel: Panel) -> bool {
-    log::info!("NSPanelDelegate: windowShouldBecomeKey called, returning false to prevent focus.");
-    false
-}
-
-// --- ADDED: Location Extractor Function for Geocoding ---
-async fn extract_ticker_symbols_from_companies(
-    client: &reqwest::Client,
-    company_text: &str,
-    api_key: &str,
-    model_name: String,
-) -> Result<Vec<String>, String> {
-    // First check if the input is already a ticker symbol or comma-separated list of symbols
-    let potential_symbols: Vec<String> = company_text
-        .split(&[',', ' ', '\n', ';'])
-        .map(|s| s.trim().to_uppercase())
-        .filter(|s| !s.is_empty() && s.len() <= 5 && s.chars().all(|c| c.is_alphabetic()))
-        .collect();
-
-    // If we have potential symbols, verify they're valid ticker symbols
-    if !potential_symbols.is_empty() && potential_symbols.len() <= 10 {
-        if potential_symbols
-            .iter()
-            .all(|s| s.len() >= 1 && s.len() <= 5)
-        {
-            log::info!(
-                "Input appears to be ticker symbols: {:?}",
-                potential_symbols
-            );
-            return Ok(potential_symbols);
-        }
-    }
-
-    let prompt = format!(
-        "Extract stock ticker symbols from the following text about companies. Return ONLY a JSON array of ticker symbols (e.g., [\"AAPL\", \"GOOGL\", \"TSLA\"]). \
-        Only include publicly traded companies with valid stock symbols. If no public companies are found, return an empty array [].\n\n\
-        IMPORTANT: If the input is already ticker symbols (like 'GOOGL', 'IBM', 'TSLA'), just return them as-is in the array.\n\n\
-        Examples:\n\
-        - Apple Inc. → [\"AAPL\"]\n\
-        - Google and Microsoft → [\"GOOGL\", \"MSFT\"]\n\
-        - IBM, Tesla, and Amazon → [\"IBM\", \"TSLA\", \"AMZN\"]\n\
-        - GOOGL → [\"GOOGL\"]\n\
-        - IBM IONQ → [\"IBM\", \"IONQ\"]\n\
-        - Private company XYZ → []\n\n\
-        Text to analyze:\n{}",
-        company_text
-    );
-
-    let messages = vec![ChatMessage {
-        role: "user".to_string(),
-        content: prompt,
-        image_base64_data: None,
-        image_mime_type: None,
-        image_file_api_uri: None,
-    }];
-
-    match call_gemini_api_non_streaming(client, messages, api_key, model_name).await {
-        Ok(response_text) => {
-            let cleaned_response = response_text
-                .trim()
-                .trim_start_matches("```json")
-                .trim_start_matches("```")
-                .trim_end_matches("```")
-                .trim();
-
-            match serde_json::from_str::<Vec<String>>(cleaned_response) {
-                Ok(symbols) => {
-                    log::info!("Extracted ticker symbols: {:?}", symbols);
-                    Ok(symbols)
-                }
-                Err(e) => {
-                    log::warn!(
-                        "Failed to parse ticker symbols from response '{}': {}",
-                        cleaned_response,
-                        e
-                    );
-                    Ok(vec![]) // Return empty vec on parse failure
-                }
-            }
-        }
-        Err(e) => {
-            log::error!("Failed to extract ticker symbols: {}", e);
-            Err(e)
-        }
-    }
-}
-
-async fn extract_location_for_geocoding(
-    client: &reqwest::Client,
-    user_query: &str, // The full user query, e.g., "what is the weather in Paris, France?"
-    gemini_api_key: &str, // API key as a slice
-    model_name: String, // Model name for Gemini
-) -> Result<String, String> {
-    // Returns the extracted location string or an error
-    let extractor_prompt = format!(
-        "{}{}{}{}{}{}{}{}{}{}",
-        "You are an expert at identifying the geographical location mentioned in a user\'s query about weather.\n",
-        "Given the user query, extract only the location (city, state, country, etc.). Do not include phrases like \"weather in\", \"what is the temperature in\", etc.\n",
-        "For example:\n",
-        "- User Query: \"weather in San Francisco, CA\" -> Location: \"San Francisco, CA\"\n",
-        "- User Query: \"what is the temperature in London today?\" -> Location: \"London\"\n",
-        "- User Query: \"Is it raining in Tokyo, Japan? Show me the forecast.\" -> Location: \"Tokyo, Japan\"\n",
-        "- User Query: \"Paris forecast\" -> Location: \"Paris\"\n",
-        "Output only the location itself.\n\n",
-        format!("User Query: '{}'\n", user_query),
-        "Location:"
-    );
-
-    let extractor_messages = vec![ChatMessage {
-        role: "user".to_string(),
-        content: extractor_prompt,
-        image_base64_data: None,
-        image_mime_type: None,
-        image_file_api_uri: None,
-    }];
-
-    log::info!(
-        "Requesting location extraction for geocoding from query: '{}'",
-        user_query
-    );
-
-    match call_gemini_api_non_streaming(client, extractor_messages, gemini_api_key, model_name)
-        .await
-    {
-        Ok(extracted_location_raw) => {
-            let extracted_location = extracted_location_raw.trim().trim_matches('"').to_string();
-            log::info!(
-                "Extracted location for geocoding: '{}' from original query: '{}'",
-                extracted_location,
-                user_query
-            );
-            if extracted_location.is_empty() {
-                log::warn!("Location extractor for geocoding returned empty. Falling back to original query (trimmed).");
-                Ok(user_query.trim().to_string()) // Fallback, though less ideal
-            } else {
-                Ok(extracted_location)
-            }
-        }
-        Err(e) => {
-            log::error!("Error calling location extractor for geocoding (query: '{}'): {}. Falling back to original query (trimmed).", user_query, e);
-            Ok(user_query.trim().to_string()) // Fallback on error
-        }
-    }
-}
-
-// --- ADDED: Wikipedia Search Term Extractor Function ---
-async fn extract_wikipedia_search_term(
-    client: &reqwest::Client,
-    user_query: &str,
-    gemini_api_key_string: String,
-    model_name: String,
-) -> Result<Vec<String>, String> {
-    let extractor_prompt = format!(
-        "You are an expert at identifying core subjects or named entities in a user's query that are suitable for Wikipedia searches.\n\
-        Given the user query, extract a list of concise and accurate search terms for Wikipedia.\n\
-        If the query is simple, provide a single search term in the list.\n\
-        If the query is complex or multifaceted, break it down into multiple relevant search terms.\n\
-        Focus on the main topics, persons, places, or concepts.\n\
-        Do not include conversational phrases like 'tell me about', 'what is', 'who was'.\n\
-        Output the search terms as a JSON array of strings. For example: [\"Term 1\", \"Term 2\"]. If only one term, output as [\"Term\"].\n\n\
-        Examples:\n\
-        - User Query: \"Tell me more about the history of the Eiffel Tower in Paris.\"\n\
-          Search Terms (JSON): [\"Eiffel Tower\"]\n\
-        - User Query: \"Who was the first president of the United States?\"\n\
-          Search Terms (JSON): [\"George Washington\"]\n\
-        - User Query: \"What are the symptoms of influenza?\"\n\
-          Search Terms (JSON): [\"Influenza\"]\n\
-        - User Query: \"Explain quantum entanglement for me.\"\n\
-          Search Terms (JSON): [\"Quantum entanglement\"]\n\
-        - User Query: \"Compare the economies of Germany and France.\"\n\
-          Search Terms (JSON): [\"Economy of Germany\", \"Economy of France\"]\n\
-        - User Query: \"Compare Federer and Nadal.\"\n\
-          Search Terms (JSON): [\"Roger Federer\", \"Rafael Nadal\"]\n\
-        - User Query: \"Impact of Renaissance art on Baroque architecture.\"\n\
-          Search Terms (JSON): [\"Renaissance art\", \"Baroque architecture\"]\n\
-        User Query: '{}'\n\
-        Search Terms (JSON):",
-        user_query
-    );
-
-    let extractor_messages = vec![ChatMessage {
-        role: "user".to_string(),
-        content: extractor_prompt,
-        image_base64_data: None,
-        image_mime_type: None,
-        image_file_api_uri: None,
-    }];
-
-    log::info!(
-        "Requesting Wikipedia search term extraction for query: '{}'",
-        user_query
-    );
-
-    match call_gemini_api_non_streaming(
-        client,
-        extractor_messages,
-        &gemini_api_key_string,
-        model_name,
-    )
-    .await
-    {
-        Ok(response_str) => match serde_json::from_str::<Vec<String>>(&response_str) {
-            Ok(terms) => {
-                if terms.is_empty() {
-                    log::warn!("Wikipedia search term extractor returned an empty list for query: '{}'. Falling back to original query.", user_query);
-                    Ok(vec![user_query.to_string()])
-                } else {
-                    log::info!(
-                        "Extracted Wikipedia search terms: {:?} for original query: '{}'",
-                        terms,
-                        user_query
-                    );
-                    Ok(terms)
-                }
-            }
-            Err(e) => {
-                log::error!("Failed to parse Wikipedia search terms from LLM response for query '{}'. Error: {}. Response: \"{}\". Falling back to original query.", user_query, e, response_str);
-                Ok(vec![user_query.to_string()])
-            }
-        },
-        Err(e) => {
-            log::error!("Error calling Wikipedia search term extractor for query '{}': {}. Falling back to original query.", user_query, e);
-            Ok(vec![user_query.to_string()])
-        }
-    }
-}
-
-async fn analyze_wikipedia_page_for_iteration(
-    client: &reqwest::Client,
-    original_user_query: &str,
-    searched_term: &str,
-    page_title: &str,
-    page_content: &str,
-    visited_page_titles: &[String],
-    gemini_api_key: &str,
-    model_name: &str,
-) -> Result<AnalysisLLMDecision, String> {
-    const MAX_CONTENT_CHARS: usize = 100000;
-    let truncated_content = if page_content.chars().count() > MAX_CONTENT_CHARS {
-        page_content
-            .chars()
-            .take(MAX_CONTENT_CHARS)
-            .collect::<String>()
-            + "\n[Content truncated]"
-    } else {
-        page_content.to_string()
-    };
-
-    let visited_titles_str = visited_page_titles.join(", ");
-
-    let prompt = format!(
-        "You are an AI assistant helping a user research a topic using Wikipedia. Your goal is to navigate Wikipedia pages iteratively to find the answer or relevant information for the user's original query.\n\n\
-        Original User Query: \"{}\"\n\n\
-        You have just read the Wikipedia page titled: \"{}\" (found by searching for \"{}\").\n\
-        Here is the (potentially truncated) content of this page:\n---\n{}\n---\n\n\
-        You have already visited or processed the following Wikipedia page titles in this research chain: [{}]. Do not suggest revisiting these.\n\n\
-        Based on the original user query and the content of the current page, decide the next step:\n\
-        1. If the current page's content directly and substantially answers the user's original query, or provides key information directly relevant to it: \
-           Respond with a JSON object: {{\"decision_type\": \"FOUND_ANSWER\", \"summary\": \"<brief summary of the answer/info found on this page>\", \"title\": \"<current page title>\"}}\n\
-        2. If the current page provides clues or mentions a more specific entity (person, place, event, concept, document, case name, etc.) that seems like a promising next step for a Wikipedia search to get closer to answering the original query: \
-           Respond with a JSON object: {{\"decision_type\": \"NEXT_TERM\", \"term\": \"<concise Wikipedia search term for the next step>\", \"reason\": \"<briefly explain why this term is a good next step>\"}}. The term should be a precise Wikipedia article title if possible. Ensure the term is not in the list of already visited pages.\n\
-        3. If the current page is not relevant, or doesn't offer a clear next step towards answering the query, or if you think the research path is a dead end: \
-           Respond with a JSON object: {{\"decision_type\": \"STOP\", \"reason\": \"<briefly explain why you are stopping this path>\"}}\n\n\
-        Focus on finding the most direct path to the answer. Be specific with \"NEXT_TERM\" suggestions. Ensure the JSON is valid.",
-        original_user_query, page_title, searched_term, truncated_content, visited_titles_str
-    );
-
-    let messages = vec![ChatMessage {
-        role: "user".to_string(),
-        content: prompt,
-        image_base64_data: None,
-        image_mime_type: None,
-        image_file_api_uri: None,
-    }];
-
-    log::info!(
-        "Requesting Wikipedia content analysis for page: '{}', original query: '{}'",
-        page_title,
-        original_user_query
-    );
+                log::error!(
+                    "OpenRouter API request failed with status {}: {}",
+                    status,
+                    error_text
+                );
 
-    match call_gemini_api_non_streaming(client, messages, gemini_api_key, model_name.to_string())
-        .await
-    {
-        Ok(response_str) => {
-            log::debug!(
-                "Raw analysis response for page '{}': {}",
-                page_title,
-                response_str
-            );
-            let cleaned_response = response_str
-                .trim()
-                .trim_start_matches("```json")
-                .trim_start_matches("```")
-                .trim_end_matches("```")
-                .trim();
-            match serde_json::from_str::<AnalysisLLMDecision>(cleaned_response) {
-                Ok(decision) => Ok(decision),
-                Err(e) => {
-                    log::error!("Failed to parse analysis LLM response for page '{}'. Error: {}. Response: '{}', Cleaned: '{}'", page_title, e, response_str, cleaned_response);
-                    Err(format!(
-                        "Failed to parse analysis response: {}. Raw: {}",
-                        e, response_str
-                    ))
-                }
+                // Emit the error only once
+                let _ = window.emit(
+                    "STREAM_ERROR",
+                    StreamErrorPayload {
+                        error: error_msg.clone(),
+                    },
+                );
+
+                // Return the detailed error message
+                Err(error_msg)
             }
         }
         Err(e) => {
-            log::error!(
-                "Error calling analysis LLM for page '{}': {}",
-                page_title,
-                e
+            log::error!("Network request to OpenRouter failed: {}", e);
+            let err_msg = format!("Network request failed: {}", e);
+            let _ = window.emit(
+                "STREAM_ERROR",
+                StreamErrorPayload {
+                    error: err_msg.clone(),
+                },
             );
-            Err(format!("LLM call failed for analysis: {}", e))
+            Err(err_msg)
         }
     }
 }
 
-pub async fn perform_iterative_wikipedia_research(
+// --- ADDED: Non-streaming Gemini API call function ---
+async fn call_gemini_api_non_streaming(
     client: &reqwest::Client,
-    initial_user_query: &str,
-    gemini_api_key: &str,
-    model_name: &str,
-    max_iterations: usize,
-) -> Result<Vec<IterativeSearchResult>, String> {
-    use std::collections::{HashSet, VecDeque};
-
-    let mut all_found_info: Vec<IterativeSearchResult> = Vec::new();
-    let mut visited_page_titles: HashSet<String> = HashSet::new();
-    let mut search_queue: VecDeque<(String, Vec<String>)> = VecDeque::new();
-
-    log::info!(
-        "Starting iterative Wikipedia research for query: '{}'",
-        initial_user_query
+    messages: Vec<ChatMessage>,
+    api_key_slice: &str, // Changed parameter name for clarity
+    model_name: String,
+) -> Result<String, String> {
+    if api_key_slice.is_empty() {
+        return Err("API key is empty for non-streaming Gemini call".to_string());
+    }
+    let api_url = format!(
+        "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent?key={}",
+        model_name, api_key_slice
     );
 
-    let initial_terms = match extract_wikipedia_search_term(
-        client,
-        initial_user_query,
-        gemini_api_key.to_string(),
-        model_name.to_string(),
-    )
-    .await
-    {
-        Ok(terms) => terms,
-        Err(e) => {
-            log::error!(
-                "Failed initial term extraction for query '{}': {}",
-                initial_user_query,
-                e
-            );
-            // Fallback to using the original query if extraction fails
-            vec![initial_user_query.to_string()]
-        }
+    // For a simple YES/NO decider, complex generation_config is not needed.
+    // We can omit it or send a minimal one if required by the API.
+    // For now, omitting `generation_config` for simplicity for the decider call.
+    let request_payload = GeminiChatCompletionRequest {
+        contents: messages
+            .into_iter()
+            .map(|msg| GeminiContent {
+                parts: vec![GeminiPart::Text { text: msg.content }], // Corrected: Use enum variant
+                role: Some(msg.role), // Directly use the role, assuming "user" for decider prompt
+            })
+            .collect(),
+        generation_config: None, // No special generation config for the simple decider
     };
 
-    for term in initial_terms {
-        if !term.trim().is_empty() {
-            search_queue.push_back((term.clone(), vec![term]));
-        }
-    }
-
-    if search_queue.is_empty() && !initial_user_query.trim().is_empty() {
-        log::warn!("Initial term extraction yielded empty results for query: '{}'. Falling back to original query.", initial_user_query);
-        search_queue.push_back((
-            initial_user_query.to_string(),
-            vec![initial_user_query.to_string()],
-        ));
-    }
-
-    let mut current_iteration = 0;
-
-    while let Some((current_term, current_path)) = search_queue.pop_front() {
-        if current_iteration >= max_iterations {
-            log::warn!(
-                "Max iterations ({}) reached for query: {}",
-                max_iterations,
-                initial_user_query
-            );
-            break;
-        }
-        // Check based on the term we intend to search. Actual page titles are checked after lookup.
-        if visited_page_titles.contains(&current_term) && current_path.len() > 1 {
-            // Allow initial terms to be re-processed if they lead to different actual titles
-            log::debug!(
-                "Skipping already processed search term in path: {}",
-                current_term
-            );
-            continue;
-        }
-
-        current_iteration += 1;
-        log::info!(
-            "Iterative search (iter {}/{}, path depth {}): Looking up '{}'. Path: {:?}",
-            current_iteration,
-            max_iterations,
-            current_path.len(),
-            current_term,
-            current_path
-        );
-
-        match perform_wikipedia_lookup(client, &current_term).await {
-            Ok(pages) => {
-                let mut page_content_opt: Option<String> = None;
-                let mut actual_page_title_opt: Option<String> = None;
-                let mut page_url_opt: Option<String> = None;
-
-                // The Wikipedia lookup returns a single tuple (title, extract, url)
-                if let Some((title, extract, url)) = pages {
-                    if !extract.is_empty() {
-                        page_content_opt = Some(extract.clone());
-                        actual_page_title_opt = Some(title.clone());
-                        page_url_opt = Some(url.clone());
-                    }
-                }
-
-                if let (Some(content), Some(title), Some(url)) =
-                    (page_content_opt, actual_page_title_opt, page_url_opt)
-                {
-                    if visited_page_titles.contains(&title) {
-                        log::debug!("Skipping already visited Wikipedia page title: {}", title);
-                        continue;
-                    }
-
-                    log::info!("Adding page to results: '{}'", title);
-                    all_found_info.push(IterativeSearchResult {
-                        title: title.clone(),
-                        summary: content.clone(), // Using the full extract as the summary
-                        url: url.clone(),
-                        path_taken: current_path.clone(),
-                    });
+    // log::info!(
+    //     "Sending NON-STREAMING request to Gemini API for model: {}. Payload: {:?}",
+    //     model_name,
+    //     request_payload
+    // );
 
-                    visited_page_titles.insert(title.clone());
-
-                    // Only analyze if we haven't hit max_iterations for the *next* step
-                    if current_iteration < max_iterations {
-                        let visited_titles_vec: Vec<String> =
-                            visited_page_titles.iter().cloned().collect();
-                        match analyze_wikipedia_page_for_iteration(
-                            client,
-                            initial_user_query,
-                            &current_term,
-                            &title,
-                            &content,
-                            &visited_titles_vec,
-                            gemini_api_key,
-                            model_name,
-                        )
-                        .await
-                        {
-                            Ok(decision) => match decision {
-                                AnalysisLLMDecision::FoundAnswer {
-                                    summary: llm_summary,
-                                    title: found_title,
-                                } => {
-                                    log::info!(
-                                        "LLM indicated page '{}' (summary: '{}') as directly answering query '{}'. Information already captured.",
-                                        found_title,
-                                        llm_summary,
-                                        initial_user_query
-                                    );
-                                    // Optionally, one could update the summary in all_found_info if llm_summary is preferred,
-                                    // or simply stop this particular search path by not queueing further terms from it.
-                                }
-                                AnalysisLLMDecision::NextTerm {
-                                    term: next_term,
-                                    reason,
-                                } => {
-                                    log::info!(
-                                        "Next term for '{}' is '{}'. Reason: {}",
-                                        initial_user_query,
-                                        next_term,
-                                        reason
-                                    );
-                                    // Check conditions for adding to queue
-                                    if !visited_page_titles.contains(&next_term)
-                                        && !search_queue.iter().any(|(t, _)| t == &next_term)
-                                        && current_path.len() < max_iterations
-                                    // Path depth check
-                                    {
-                                        let mut next_path = current_path.clone();
-                                        next_path.push(next_term.clone());
-                                        search_queue.push_back((next_term, next_path));
-                                    } else {
-                                        log::debug!("Skipping next term suggestion '{}': already visited, in queue, or path too deep.", next_term);
+    match client
+        .post(&api_url)
+        .header("Content-Type", "application/json")
+        .json(&request_payload)
+        .send()
+        .await
+    {
+        Ok(response) => {
+            if response.status().is_success() {
+                match response.json::<GeminiChatCompletionResponse>().await {
+                    Ok(gemini_response) => {
+                        if let Some(candidate) = gemini_response.candidates.get(0) {
+                            if let Some(part) = candidate.content.parts.get(0) {
+                                match part {
+                                    // Corrected: Destructure GeminiPart
+                                    GeminiPart::Text { text } => {
+                                        log::debug!("Non-streaming Gemini response text: {}", text);
+                                        Ok(text.clone())
                                     }
+                                    GeminiPart::FileData { .. } => Err(
+                                        "Non-streaming Gemini response: Unexpected FileData part"
+                                            .to_string(),
+                                    ),
                                 }
-                                AnalysisLLMDecision::Stop { reason } => {
-                                    log::info!(
-                                        "Stopping search on path {:?} for query '{}'. Reason: {}",
-                                        current_path,
-                                        initial_user_query,
-                                        reason
-                                    );
-                                }
-                            },
-                            Err(e) => {
-                                log::error!("Error analyzing Wikipedia content for term '{}', page title '{}': {}", current_term, title, e);
+                            } else {
+                                Err("Non-streaming Gemini response: No content parts found"
+                                    .to_string())
                             }
+                        } else {
+                            Err("Non-streaming Gemini response: No candidates found".to_string())
                         }
-                    } else {
-                        log::info!("Max iterations reached after processing page '{}'. Not analyzing for next steps.", title);
                     }
-                } else {
-                    log::warn!(
-                                            "No usable content found for Wikipedia term '{}' after processing API results.",
-                                            current_term
-                                        );
-                    visited_page_titles.insert(current_term.clone()); // Mark term as processed to avoid retrying if it yields nothing
+                    Err(e) => Err(format!(
+                        "Failed to parse non-streaming Gemini JSON response: {}",
+                        e
+                    )),
                 }
-            }
-            Err(e) => {
+            } else {
+                let status = response.status();
+                let error_text = response.text().await.unwrap_or_else(|_| {
+                    "Could not read error body from Gemini (non-streaming)".to_string()
+                });
                 log::error!(
-                    "Error performing Wikipedia lookup for term '{}': {}",
-                    current_term,
-                    e
+                    "Gemini API (non-streaming) request failed with status {}: {}",
+                    status,
+                    error_text
                 );
-                visited_page_titles.insert(current_term.clone());
-            }
-        }
-    }
-    log::info!(
-        "Finished iterative Wikipedia research for query: '{}'. Found {} results.",
-        initial_user_query,
-        all_found_info.len()
-    );
-    Ok(all_found_info)
-}
-
-// --- UPDATED: Weather Lookup Function (uses location extractor) ---
-async fn perform_weather_lookup(
-    client: &reqwest::Client,
-    original_user_query: &str, // This is the full query like "weather in Paris"
-    gemini_api_key_for_extractor: &str, // API key for the extractor LLM call
-    extractor_model_name: String, // Model for the extractor LLM call
-) -> Result<Option<(f32, String, String, String)>, String> {
-    // (temp, unit, description, resolved_location)
-
-    // 1. Extract location using the LLM extractor
-    let location_to_geocode = match extract_location_for_geocoding(
-        client,
-        original_user_query,
-        gemini_api_key_for_extractor,
-        extractor_model_name,
-    )
-    .await
-    {
-        Ok(loc) => loc,
-        Err(e) => {
-            log::error!("Weather: Location extraction step failed for query '{}': {}. No geocoding will be attempted.", original_user_query, e);
-            return Err(format!("Location extraction failed: {}", e)); // Propagate error if extraction itself fails badly
-        }
-    };
-
-    // 2. Geocode the extracted location
-    match geocode_location(client, &location_to_geocode).await {
-        Ok(Some((lat, lon, resolved_geocoded_name))) => {
-            log::info!(
-                "Geocoded extracted location '{}' to ({}, {}), name: {}",
-                location_to_geocode,
-                lat,
-                lon,
-                resolved_geocoded_name
-            );
-            let base_url = "https://api.open-meteo.com/v1/forecast";
-            let params = [
-                ("latitude", lat.to_string()),
-                ("longitude", lon.to_string()),
-                ("current", "temperature_2m".to_string()),
-                ("temperature_unit", "celsius".to_string()),
-                ("wind_speed_unit", "kmh".to_string()),
-                ("precipitation_unit", "mm".to_string()),
-                ("timezone", "auto".to_string()),
-            ];
-            let request_url = client
-                .get(base_url)
-                .query(&params)
-                .build()
-                .expect("Failed to build financial data URL")
-                .url()
-                .to_string();
-            log::info!(
-                "Weather lookup for ({}, {}). URL: {}",
-                lat,
-                lon,
-                request_url
-            );
-            match client.get(base_url).query(&params).send().await {
-                Ok(response) => {
-                    let status = response.status();
-                    let response_text = response
-                        .text()
-                        .await
-                        .map_err(|e| format!("Weather: Failed to read response text: {}", e))?;
-                    if status.is_success() {
-                        match serde_json::from_str::<WeatherResponse>(&response_text) {
-                            Ok(weather_data) => {
-                                log::info!("Weather: Parsed JSON: {:#?}", weather_data);
-                                if let Some(curr) = weather_data.current {
-                                    if let (Some(temp_val), Some(units)) =
-                                        (curr.temperature_2m, weather_data.current_units)
-                                    {
-                                        let unit = units
-                                            .temperature_2m
-                                            .unwrap_or_else(|| "°C".to_string());
-                                        let desc = format!(
-                                            "Current temperature in {}",
-                                            resolved_geocoded_name
-                                        );
-                                        log::info!(
-                                            "Weather: Found {} {} for {}",
-                                            temp_val,
-                                            unit,
-                                            resolved_geocoded_name
-                                        );
-                                        return Ok(Some((
-                                            temp_val,
-                                            unit,
-                                            desc,
-                                            resolved_geocoded_name.clone(),
-                                        ))); // No deref needed for f32
-                                    }
-                                }
-                                log::info!("Weather: No current data for ({}, {}).", lat, lon);
-                                Ok(None)
-                            }
-                            Err(e) => {
-                                log::error!(
-                                    "Weather: JSON parse error for ({}, {}): {}. Raw: {}",
-                                    lat,
-                                    lon,
-                                    e,
-                                    response_text
-                                );
-                                Err(format!(
-                                    "Weather JSON error: {}. Ensure response is valid JSON.",
-                                    e
-                                ))
-                            }
-                        }
-                    } else {
-                        log::error!(
-                            "Weather: API error for ({}, {}) status {}: {}",
-                            lat,
-                            lon,
-                            status,
-                            response_text
-                        );
-                        Err(format!("Weather API error: {} - {}", status, response_text))
-                    }
-                }
-                Err(e) => {
-                    log::error!("Weather: Network error for ({}, {}): {}", lat, lon, e);
-                    Err(format!("Weather network error: {}", e))
-                }
+                Err(format!(
+                    "Gemini API (non-streaming) request failed: {} - {}",
+                    status, error_text
+                ))
             }
         }
-        Ok(None) => {
-            log::warn!("Weather: Geocoding failed for '{}'.", location_to_geocode);
-            Ok(None)
-        }
         Err(e) => {
             log::error!(
-                "Weather: Geocoding step failed for '{}': {}",
-                location_to_geocode,
+                "Network request to Gemini API (non-streaming) failed: {}",
                 e
             );
-            Err(e)
+            Err(format!(
+                "Gemini API (non-streaming) network request failed: {}",
+                e
+            ))
         }
     }
 }
 
+#[cfg(target_os = "macos")]
+#[allow(dead_code)]
+fn window_should_become_key(_panel: Panel) -> bool {
+    log::info!("NSPanelDelegate: windowShouldBecomeKey called, returning false to prevent focus.");
+    false
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     // Create shortcut for Control+Space (Alt+Space)
@@ -3889,6 +3378,18 @@ pub fn run() {
                 }
             }
 
+            // Watch the knowledge folders and catch up on changes made while Shard was closed
+            let knowledge_folders = load_config(&config_handle)
+                .ok()
+                .and_then(|config| config.knowledge_base)
+                .map(|settings| settings.folders)
+                .unwrap_or_default();
+            if !knowledge_folders.is_empty() {
+                let knowledge = tools::knowledge::knowledge_base(&config_handle);
+                knowledge.set_folders(knowledge_folders);
+                std::thread::spawn(move || knowledge.reindex());
+            }
+
             // Convert the main window to a panel (for macOS only)
             #[cfg(target_os = "macos")]
             {
@@ -3950,190 +3451,61 @@ pub fn run() {
             cancel_current_stream,
             get_tool_reasoning_guidance,
             get_enhanced_system_prompt,
-            export_tool_capabilities
+            export_tool_capabilities,
+            list_system_prompts,
+            save_system_prompt,
+            delete_system_prompt,
+            get_default_system_prompt,
+            set_default_system_prompt,
+            preview_system_prompt,
+            list_prompt_templates,
+            save_prompt_template,
+            delete_prompt_template,
+            import_prompt_templates,
+            export_prompt_templates,
+            expand_prompt_template,
+            get_tool_policy,
+            set_tool_policy,
+            respond_tool_confirmation,
+            get_max_parallel_tools,
+            set_max_parallel_tools,
+            clear_tool_cache,
+            get_tool_trace,
+            get_conversation_tool_traces,
+            export_papers,
+            import_ticker_table,
+            reset_ticker_table,
+            start_deep_research,
+            resume_deep_research,
+            cancel_deep_research,
+            get_deep_research_job,
+            list_deep_research_jobs,
+            delete_deep_research_job,
+            get_deep_research_budget,
+            set_deep_research_budget,
+            get_persist_tool_traces,
+            set_persist_tool_traces,
+            get_web_search_settings,
+            set_web_search_settings,
+            get_url_fetch_settings,
+            set_url_fetch_settings,
+            attach_document,
+            delete_document,
+            get_knowledge_folders,
+            add_knowledge_folder,
+            remove_knowledge_folder,
+            reindex_knowledge_base,
+            get_knowledge_base_status,
+            get_wiki_settings,
+            set_wiki_settings,
+            get_weather_settings,
+            set_weather_settings,
+            search_weather_locations
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
 
-// Helper function to clean titles for ArXiv search
-fn clean_title(title_str: &str) -> String {
-    title_str
-        .replace('-', " ")
-        .replace('–', " ")
-        .replace('—', " ")
-        .replace('_', " ")
-        .to_string()
-}
-
-// --- ADDED: ArXiv Lookup Function ---
-async fn perform_arxiv_lookup(
-    client: &reqwest::Client,    // ADDED: client parameter
-    search_query_string: String, // MODIFIED: Now accepts the raw query string
-) -> Result<Vec<ArXivPaper>, String> {
-    log::info!(
-        "Performing ArXiv lookup with raw query string: '{}'",
-        search_query_string
-    );
-
-    let max_results = 2;
-    let base_url = "http://export.arxiv.org/api/query";
-
-    // URL encode the search_query_string. The `urlencoding` crate might be useful if not already a direct dependency,
-    // but reqwest might handle this sufficiently with .query(). For direct construction, it's safer.
-    let encoded_query = urlencoding::encode(&search_query_string);
-
-    let request_url = format!(
-        "{}?search_query={}&start=0&max_results={}",
-        base_url, encoded_query, max_results
-    );
-
-    log::info!("Constructed ArXiv API request URL: {}", request_url);
-
-    match client.get(&request_url).send().await {
-        Ok(response) => {
-            let status = response.status();
-            if status.is_success() {
-                match response.text().await {
-                    Ok(xml_text) => {
-                        log::info!(
-                            "Successfully fetched ArXiv XML response. Length: {}",
-                            xml_text.len()
-                        );
-                        log::debug!("ArXiv XML Response:\n{}", xml_text); // Keep this commented for now unless debugging specific XML issues
-
-                        match from_str::<ArxivFeed>(&xml_text) {
-                            Ok(parsed_feed) => {
-                                let mut papers: Vec<ArXivPaper> = Vec::new();
-                                let mut actual_entries: Vec<ArxivEntry> = Vec::new();
-
-                                // Iterate through children of <feed> and collect only Entry variants
-                                for child in parsed_feed.children {
-                                    if let FeedChild::Entry(entry) = child {
-                                        actual_entries.push(entry);
-                                    }
-                                }
-
-                                // Now process actual_entries like before
-                                for entry in actual_entries {
-                                    // MODIFIED: Iterate over actual_entries
-                                    let paper_id = entry.id.unwrap_or_default();
-                                    let mut title = entry.title.unwrap_or_default();
-                                    title = clean_title(&title);
-                                    let abstract_text = entry.summary.unwrap_or_default(); // 'summary' in Atom is the abstract
-                                    let published = entry.published.unwrap_or_default();
-                                    let updated = entry.updated.unwrap_or_default();
-                                    let comments = entry.comment;
-                                    let doi = entry.doi;
-
-                                    let authors: Vec<String> = entry
-                                        .authors
-                                        .into_iter()
-                                        .filter_map(|auth| auth.name)
-                                        .collect();
-
-                                    let mut pdf_url_option: Option<String> = None;
-                                    for link in entry.entry_links {
-                                        // MODIFIED: was entry.links
-                                        // MODIFIED: Clone link.href for the first check to avoid move issues
-                                        if let (Some(href), Some(title_attr)) =
-                                            (link.href.clone(), link.title)
-                                        {
-                                            if title_attr == "pdf" {
-                                                pdf_url_option = Some(href);
-                                                break;
-                                            }
-                                        }
-                                        // Fallback if title attribute is not present but rel="alternate" and type="application/pdf"
-                                        else if let (
-                                            Some(href),
-                                            Some(rel_attr),
-                                            Some(type_attr),
-                                        ) = (link.href.clone(), link.rel, link.link_type)
-                                        {
-                                            if rel_attr == "alternate"
-                                                && type_attr == "application/pdf"
-                                            {
-                                                pdf_url_option = Some(href);
-                                                break;
-                                            }
-                                        }
-                                    }
-                                    let pdf_url = pdf_url_option.unwrap_or_else(|| {
-                                        format!(
-                                            "http://arxiv.org/pdf/{}",
-                                            paper_id.split('/').last().unwrap_or_default()
-                                        )
-                                    });
-
-                                    let categories: Vec<String> = entry
-                                        .categories
-                                        .into_iter()
-                                        .filter_map(|cat| cat.term)
-                                        .collect();
-
-                                    let primary_category =
-                                        entry.primary_category.and_then(|pc| pc.term);
-
-                                    // Note: arxiv_tools::Paper has more fields like `journal_ref`, `links` (which is a specific struct in arxiv_tools not just a string list).
-                                    // We are populating the core ones. `links` in ArXivPaper is more for related links, not just the PDF.
-                                    // `journal_ref` is not directly available in the standard Atom entry without specific arxiv: namespace parsing for it.
-                                    papers.push(ArXivPaper {
-                                        id: paper_id,
-                                        title: clean_title(&title),
-                                        authors,
-                                        abstract_text,
-                                        categories,
-                                        comment: comments.map_or_else(Vec::new, |c| vec![c]), // MODIFIED: Convert Option<String> to Vec<String>
-                                        doi: doi.unwrap_or_default(),
-                                        journal_ref: String::new(),
-                                        pdf_url,
-                                        published,
-                                        updated,
-                                        primary_category: primary_category.unwrap_or_default(), // ArxivPaper expects String, not Option<String>
-                                    });
-                                }
-                                log::info!("Parsed {} papers from ArXiv XML feed.", papers.len());
-                                Ok(papers)
-                            }
-                            Err(e) => {
-                                log::error!(
-                                    "Failed to parse ArXiv XML: {}. XML was: {:.500}",
-                                    e,
-                                    xml_text
-                                );
-                                Err(format!("Failed to parse ArXiv XML: {}", e))
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        log::error!("Failed to read ArXiv response text: {}", e);
-                        Err(format!("Failed to read ArXiv response text: {}", e))
-                    }
-                }
-            } else {
-                let error_text = response
-                    .text()
-                    .await
-                    .unwrap_or_else(|_| "Could not read error body from ArXiv".to_string());
-                log::error!(
-                    "ArXiv API request failed with status {}: {}",
-                    status,
-                    error_text
-                );
-                Err(format!(
-                    "ArXiv API request failed: {} - {}",
-                    status, error_text
-                ))
-            }
-        }
-        Err(e) => {
-            log::error!("Network request to ArXiv API failed: {}", e);
-            Err(format!("ArXiv API network request failed: {}", e))
-        }
-    }
-}
-
 async fn upload_image_to_gemini_file_api(
     client: &reqwest::Client,
     image_base64_data: &str,
@@ -8,6 +8,8 @@ use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::collections::HashMap;
 
+use crate::tools::registry;
+
 /// Tool usage guidance for AI models
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ToolGuidance {
@@ -41,20 +43,30 @@ pub struct McpToolReasoning;
 impl McpToolReasoning {
     /// Generate comprehensive tool usage guidance for AI models
     pub fn generate_tool_guidance() -> Vec<ToolGuidance> {
-        vec![
-            Self::wikipedia_guidance(),
-            Self::weather_guidance(),
-            Self::financial_guidance(),
-            Self::arxiv_guidance(),
+        let mut guidance: Vec<ToolGuidance> =
+            registry().iter().map(|tool| tool.guidance()).collect();
+        guidance.extend([
             Self::ocr_guidance(),
             Self::multi_tool_research_guidance(),
             Self::iterative_research_guidance(),
-        ]
+        ]);
+        guidance
     }
 
     /// Get reasoning instructions for AI models
+    ///
+    /// The "When to use" sections come from each tool's guidance, so they
+    /// stay in step with the registry.
     pub fn get_reasoning_instructions() -> String {
-        r#"# Shard Tool Usage Reasoning Guide
+        let when_to_use: String = registry()
+            .iter()
+            .map(|tool| tool.guidance())
+            .chain([Self::ocr_guidance()])
+            .map(|guidance| Self::when_to_use(&guidance))
+            .collect();
+
+        format!(
+            r#"# Shard Tool Usage Reasoning Guide
 
 You have access to several powerful research and data tools. Here's how to reason about when and how to use them:
 
@@ -68,48 +80,7 @@ You have access to several powerful research and data tools. Here's how to reaso
 
 ## Tool Decision Framework:
 
-### When to use Wikipedia Research:
-- For general knowledge and background information using GENERIC, foundational terms
-- To understand broad concepts, fields, and topics (not specific subtopics)
-- To get comprehensive overviews from authoritative articles
-- Use terms like "quantum computing", "artificial intelligence", "renewable energy"
-- AVOID specific subtopics like "quantum computing companies", "AI stocks", "solar manufacturers"
-
-### When to use ArXiv Search:
-- For cutting-edge research and academic papers
-- To find the latest scientific developments
-- When looking for technical, peer-reviewed information
-- For literature reviews or research summaries
-
-### When to use Weather Tools:
-- For current weather conditions
-- When location and time-sensitive data is needed
-- For travel planning or outdoor activity decisions
-
-### When to use Stock Price Tools:
-- For real-time stock prices and market data
-- To get current stock quotes, trading volumes, and price movements
-- When analyzing company stock performance or making investment decisions
-- ONLY when you have valid stock ticker symbols (AAPL, GOOGL, TSLA, etc.)
-
-### When NOT to use Stock Price Tools:
-- Company valuations or financial analysis beyond basic price data
-- Economic indicators (GDP, inflation, unemployment rates)
-- Market indices or sector performance
-- Investor sentiment or market analysis
-- Currency exchange rates or commodities
-- Financial news or market commentary
-- Company financial statements or earnings reports
-- Economic forecasts or predictions
-- Interest rates or bond data
-- For these topics, use Wikipedia Research instead for background information
-
-### When to use OCR Tools:
-- To extract text from images or screenshots
-- When dealing with PDFs, documents, or visual content
-- For digitizing printed or handwritten text
-
-## Multi-Tool Research Strategies:
+{}## Multi-Tool Research Strategies:
 
 1. **Foundational → Specific**: Start with GENERIC Wikipedia terms for broad context, then extract specifics for other tools
 2. **Current → Historical**: Use current data tools, then research historical context with generic terms
@@ -128,161 +99,22 @@ You have access to several powerful research and data tools. Here's how to reaso
 - ❌ Bad: "quantum computing companies", "AI stocks", "solar manufacturers", "Tokyo restaurants"
 
 Remember: Always explain your tool choices to help users understand your reasoning process.
-"#.to_string()
-    }
-
-    fn wikipedia_guidance() -> ToolGuidance {
-        ToolGuidance {
-            name: "Wikipedia Research".to_string(),
-            description: "Search and gather comprehensive information from Wikipedia using GENERIC, foundational terms".to_string(),
-            usage_pattern: "Use broad, foundational topics rather than specific subtopics. Let iterative research extract details.".to_string(),
-            parameters: vec![
-                ToolParameter {
-                    name: "query".to_string(),
-                    param_type: "string".to_string(),
-                    description: "Generic, foundational topic (NOT specific subtopics)".to_string(),
-                    required: true,
-                    default_value: None,
-                },
-                ToolParameter {
-                    name: "max_iterations".to_string(),
-                    param_type: "number".to_string(),
-                    description: "Maximum research depth (1-4)".to_string(),
-                    required: false,
-                    default_value: Some("3".to_string()),
-                }
-            ],
-            examples: vec![
-                ToolExample {
-                    scenario: "User asks about quantum computing companies and stock prices".to_string(),
-                    reasoning: "Start with foundational 'quantum computing' topic, not 'quantum computing companies'".to_string(),
-                    sequence: vec![
-                        "GOOD: Search for 'quantum computing' - broad, authoritative coverage".to_string(),
-                        "BAD: Search for 'quantum computing companies' - too specific, limited scope".to_string(),
-                        "Extract company details from broad article for follow-up financial queries".to_string(),
-                    ],
-                },
-                ToolExample {
-                    scenario: "User asks about AI developments".to_string(),
-                    reasoning: "Use generic 'artificial intelligence' rather than specific subtopics".to_string(),
-                    sequence: vec![
-                        "GOOD: 'artificial intelligence', 'machine learning'".to_string(),
-                        "BAD: 'AI stocks', 'machine learning companies', 'AI startups'".to_string(),
-                    ],
-                },
-            ],
-            reasoning_hints: vec![
-                "ALWAYS use generic, foundational terms for Wikipedia queries".to_string(),
-                "AVOID specific subtopics like 'companies', 'stocks', 'manufacturers'".to_string(),
-                "Let the iterative system extract specific details from broad articles".to_string(),
-                "Good examples: 'quantum computing', 'renewable energy', 'artificial intelligence'".to_string(),
-                "Bad examples: 'quantum computing companies', 'solar stocks', 'AI startups'".to_string(),
-                "Broad articles contain more comprehensive, authoritative information".to_string(),
-            ],
-        }
-    }
-
-    fn weather_guidance() -> ToolGuidance {
-        ToolGuidance {
-            name: "Weather Lookup".to_string(),
-            description: "Get current weather conditions for any location worldwide".to_string(),
-            usage_pattern: "Use when current weather information is needed for decision making"
-                .to_string(),
-            parameters: vec![ToolParameter {
-                name: "location".to_string(),
-                param_type: "string".to_string(),
-                description: "City, address, or geographic location".to_string(),
-                required: true,
-                default_value: None,
-            }],
-            examples: vec![ToolExample {
-                scenario: "User planning outdoor activities".to_string(),
-                reasoning: "Current weather affects planning decisions".to_string(),
-                sequence: vec![
-                    "Get weather for the specific location".to_string(),
-                    "Consider forecast implications for activities".to_string(),
-                ],
-            }],
-            reasoning_hints: vec![
-                "Essential for time-sensitive decisions".to_string(),
-                "Consider geographic accuracy of location".to_string(),
-                "Useful for travel and activity planning".to_string(),
-            ],
-        }
-    }
-
-    fn financial_guidance() -> ToolGuidance {
-        ToolGuidance {
-            name: "Stock Price Data Lookup".to_string(),
-            description: "Retrieve real-time stock prices, trading volumes, and market data for publicly traded companies"
-                .to_string(),
-            usage_pattern:
-                "Use for current stock quotes, price movements, and trading data. Requires stock ticker symbols (e.g., AAPL, GOOGL, TSLA)."
-                    .to_string(),
-            parameters: vec![ToolParameter {
-                name: "query".to_string(),
-                param_type: "string".to_string(),
-                description: "Stock ticker symbol (e.g., AAPL, GOOGL, TSLA)".to_string(),
-                required: true,
-                default_value: None,
-            }],
-            examples: vec![ToolExample {
-                scenario: "User asks about a company's current stock price".to_string(),
-                reasoning: "Need real-time stock price and trading data".to_string(),
-                sequence: vec![
-                    "Identify the correct stock ticker symbol".to_string(),
-                    "Retrieve current price, volume, and daily trading data".to_string(),
-                ],
-            }],
-            reasoning_hints: vec![
-                "Essential for investment discussions and stock analysis".to_string(),
-                "Combine with Wikipedia research for company context".to_string(),
-                "Stock prices are real-time but may have market hour limitations".to_string(),
-                "DO NOT use for: valuations, GDP, economic indicators, investor sentiment, market indices, or financial analysis beyond basic price data".to_string(),
-                "Use Wikipedia Research for broader financial topics and economic concepts".to_string(),
-            ],
-        }
+"#,
+            when_to_use
+        )
     }
 
-    fn arxiv_guidance() -> ToolGuidance {
-        ToolGuidance {
-            name: "ArXiv Research".to_string(),
-            description: "Search academic papers and research preprints on ArXiv".to_string(),
-            usage_pattern:
-                "Use for cutting-edge research, academic references, and technical information"
-                    .to_string(),
-            parameters: vec![
-                ToolParameter {
-                    name: "query".to_string(),
-                    param_type: "string".to_string(),
-                    description: "Research topic, keywords, or specific paper search".to_string(),
-                    required: true,
-                    default_value: None,
-                },
-                ToolParameter {
-                    name: "max_results".to_string(),
-                    param_type: "number".to_string(),
-                    description: "Maximum number of papers to return (1-20)".to_string(),
-                    required: false,
-                    default_value: Some("5".to_string()),
-                },
-            ],
-            examples: vec![ToolExample {
-                scenario: "User needs latest research on machine learning".to_string(),
-                reasoning: "ArXiv contains the most recent academic work".to_string(),
-                sequence: vec![
-                    "Search for relevant ML papers with appropriate keywords".to_string(),
-                    "Review abstracts for relevance and recency".to_string(),
-                    "Summarize key findings and trends".to_string(),
-                ],
-            }],
-            reasoning_hints: vec![
-                "Best source for cutting-edge research".to_string(),
-                "Use specific technical terms for better results".to_string(),
-                "Consider paper recency for rapidly evolving fields".to_string(),
-                "Good complement to Wikipedia for technical depth".to_string(),
-            ],
+    /// Render one tool's guidance as a "When to use" section
+    fn when_to_use(guidance: &ToolGuidance) -> String {
+        let mut section = format!(
+            "### When to use {}:\n- {}\n",
+            guidance.name, guidance.usage_pattern
+        );
+        for hint in &guidance.reasoning_hints {
+            section.push_str(&format!("- {}\n", hint));
         }
+        section.push('\n');
+        section
     }
 
     /// Guidance specifically for iterative research workflows
@@ -488,45 +320,16 @@ This approach ensures users understand your research process and can trust your
 
     /// Get tool capabilities summary for quick reference
     pub fn get_tool_capabilities() -> HashMap<String, serde_json::Value> {
-        let mut capabilities = HashMap::new();
-
-        capabilities.insert("wikipedia_research".to_string(), json!({
-            "description": "Comprehensive encyclopedia research with iterative search",
-            "best_for": ["general knowledge", "background information", "concept explanations"],
-            "strengths": ["authoritative sources", "comprehensive coverage", "reliable information"],
-            "limitations": ["may not have very recent information", "general rather than specialized"]
-        }));
-
-        capabilities.insert(
-            "arxiv_research".to_string(),
-            json!({
-                "description": "Academic paper search and research",
-                "best_for": ["cutting-edge research", "scientific papers", "technical information"],
-                "strengths": ["latest research", "peer-reviewed content", "technical depth"],
-                "limitations": ["highly technical", "may be too specialized for general audiences"]
-            }),
-        );
-
-        capabilities.insert(
-            "weather_lookup".to_string(),
-            json!({
-                "description": "Current weather conditions worldwide",
-                "best_for": ["travel planning", "outdoor activities", "location-based decisions"],
-                "strengths": ["real-time data", "global coverage", "detailed conditions"],
-                "limitations": ["current conditions only", "not extended forecasts"]
-            }),
-        );
-
-        capabilities.insert(
-            "financial_data".to_string(),
-            json!({
-                "description": "Real-time stock prices and trading data for publicly traded companies",
-                "best_for": ["stock quotes", "price movements", "trading volume", "market data"],
-                "strengths": ["real-time data", "accurate pricing", "comprehensive trading info"],
-                "limitations": ["requires valid ticker symbols", "public companies only", "market hours dependent"],
-                "do_not_use_for": ["company valuations", "GDP data", "economic indicators", "investor sentiment", "market indices", "currency exchange rates", "commodities", "financial news", "earnings reports", "economic forecasts", "interest rates", "bond data"]
-            }),
-        );
+        let mut capabilities: HashMap<String, serde_json::Value> = registry()
+            .iter()
+            .map(|tool| {
+                let mut summary = tool.capabilities();
+                if let Some(fields) = summary.as_object_mut() {
+                    fields.insert("input_schema".to_string(), tool.input_schema());
+                }
+                (tool.capability_key().to_string(), summary)
+            })
+            .collect();
 
         capabilities.insert("ocr_capture".to_string(), json!({
             "description": "Screen capture and text extraction",
@@ -588,6 +391,17 @@ mod tests {
         assert!(prompt.contains("reasoning"));
     }
 
+    #[test]
+    fn test_instructions_cover_every_tool() {
+        let instructions = McpToolReasoning::get_reasoning_instructions();
+        for tool in registry().iter() {
+            let heading = format!("### When to use {}:", tool.guidance().name);
+            assert!(instructions.contains(&heading), "missing {}", heading);
+        }
+        assert!(instructions.contains("### When to use OCR Screen Capture:"));
+        assert!(instructions.contains("## Multi-Tool Research Strategies:"));
+    }
+
     #[test]
     fn test_capabilities_export() {
         let capabilities = McpToolReasoning::get_tool_capabilities();
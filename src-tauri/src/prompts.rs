@@ -0,0 +1,240 @@
+//! User-editable system prompts for Shard
+//!
+//! Prompt presets ("personas") are stored in `prompts.toml` inside the app config
+//! directory, next to `config.toml`. Each preset is a template that may reference
+//! variables such as `{date}`, `{os}` or `{selected_text}`, which are filled in
+//! right before a request is sent to the model.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager};
+use time::OffsetDateTime;
+
+const PROMPTS_FILENAME: &str = "prompts.toml";
+
+/// ID of the built-in preset that wraps the compiled-in system instruction
+pub const DEFAULT_PROMPT_ID: &str = "default";
+
+/// A named system prompt that can be selected per conversation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PromptPreset {
+    pub id: String,
+    pub name: String,
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub built_in: bool,
+}
+
+/// On-disk representation of `prompts.toml`
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct PromptLibrary {
+    #[serde(default, rename = "preset")]
+    pub presets: Vec<PromptPreset>,
+}
+
+/// Values substituted into a preset when it is rendered
+#[derive(Debug, Default, Clone)]
+pub struct PromptVariables {
+    pub selected_text: Option<String>,
+}
+
+impl PromptLibrary {
+    /// Look up a preset by ID, falling back to the built-in default
+    pub fn resolve(&self, id: Option<&str>, default_content: &str) -> PromptPreset {
+        let requested = id.unwrap_or(DEFAULT_PROMPT_ID);
+        if let Some(preset) = self.presets.iter().find(|p| p.id == requested) {
+            return preset.clone();
+        }
+        if requested != DEFAULT_PROMPT_ID {
+            log::warn!(
+                "System prompt preset '{}' not found, using the default preset.",
+                requested
+            );
+        }
+        built_in_default(default_content)
+    }
+
+    /// All presets, with the built-in default first
+    pub fn with_built_ins(&self, default_content: &str) -> Vec<PromptPreset> {
+        let mut presets = vec![built_in_default(default_content)];
+        presets.extend(
+            self.presets
+                .iter()
+                .filter(|p| p.id != DEFAULT_PROMPT_ID)
+                .cloned(),
+        );
+        presets
+    }
+
+    /// Insert a new preset or replace the one with the same ID
+    pub fn upsert(&mut self, preset: PromptPreset) -> Result<(), String> {
+        if preset.id.trim().is_empty() {
+            return Err("Prompt preset ID cannot be empty".to_string());
+        }
+        if preset.id == DEFAULT_PROMPT_ID {
+            return Err("The default prompt preset cannot be modified".to_string());
+        }
+        let preset = PromptPreset {
+            built_in: false,
+            ..preset
+        };
+        match self.presets.iter_mut().find(|p| p.id == preset.id) {
+            Some(existing) => *existing = preset,
+            None => self.presets.push(preset),
+        }
+        Ok(())
+    }
+
+    /// Remove a preset, returning whether anything was deleted
+    pub fn remove(&mut self, id: &str) -> Result<bool, String> {
+        if id == DEFAULT_PROMPT_ID {
+            return Err("The default prompt preset cannot be deleted".to_string());
+        }
+        let before = self.presets.len();
+        self.presets.retain(|p| p.id != id);
+        Ok(self.presets.len() != before)
+    }
+}
+
+fn built_in_default(default_content: &str) -> PromptPreset {
+    PromptPreset {
+        id: DEFAULT_PROMPT_ID.to_string(),
+        name: "Default".to_string(),
+        content: default_content.to_string(),
+        description: Some("Shard's standard assistant persona".to_string()),
+        built_in: true,
+    }
+}
+
+fn os_display_name() -> &'static str {
+    match std::env::consts::OS {
+        "macos" => "macOS",
+        "windows" => "Windows",
+        "linux" => "Linux",
+        other => other,
+    }
+}
+
+/// Fill in the template variables of a preset
+///
+/// Only the known `{name}` tokens are replaced, so literal braces (e.g. JSON
+/// examples in the prompt) are left untouched.
+pub fn render_prompt(template: &str, vars: &PromptVariables) -> String {
+    let now = OffsetDateTime::now_utc();
+    let date = format!(
+        "{}-{:02}-{:02}",
+        now.year(),
+        u8::from(now.month()),
+        now.day()
+    );
+    let time = format!("{:02}:{:02} UTC", now.hour(), now.minute());
+    let selected_text = vars
+        .selected_text
+        .as_deref()
+        .map(str::trim)
+        .unwrap_or_default();
+
+    template
+        .replace("{date}", &date)
+        .replace("{time}", &time)
+        .replace("{os}", os_display_name())
+        .replace("{selected_text}", selected_text)
+}
+
+// --- Storage Helpers ---
+fn get_prompts_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    match app_handle.path().app_config_dir() {
+        Ok(dir) => Ok(dir.join(PROMPTS_FILENAME)),
+        Err(e) => Err(format!("Failed to get app config directory: {}", e)),
+    }
+}
+
+pub fn load_prompt_library(app_handle: &AppHandle) -> Result<PromptLibrary, String> {
+    let prompts_path = get_prompts_path(app_handle)?;
+    if !prompts_path.exists() {
+        return Ok(PromptLibrary::default());
+    }
+    let content = fs::read_to_string(&prompts_path)
+        .map_err(|e| format!("Failed to read prompts file: {}", e))?;
+    toml::from_str(&content).map_err(|e| format!("Failed to parse prompts file: {}", e))
+}
+
+pub fn save_prompt_library(app_handle: &AppHandle, library: &PromptLibrary) -> Result<(), String> {
+    let prompts_path = get_prompts_path(app_handle)?;
+    log::info!("Saving prompt presets to {:?}", prompts_path);
+    if let Some(parent_dir) = prompts_path.parent() {
+        if !parent_dir.exists() {
+            fs::create_dir_all(parent_dir)
+                .map_err(|e| format!("Failed to create config directory: {}", e))?;
+        }
+    }
+    let toml_string = toml::to_string_pretty(library)
+        .map_err(|e| format!("Failed to serialize prompts: {}", e))?;
+    fs::write(&prompts_path, toml_string)
+        .map_err(|e| format!("Failed to write prompts file: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preset(id: &str, content: &str) -> PromptPreset {
+        PromptPreset {
+            id: id.to_string(),
+            name: id.to_string(),
+            content: content.to_string(),
+            description: None,
+            built_in: false,
+        }
+    }
+
+    #[test]
+    fn test_render_replaces_known_variables_only() {
+        let vars = PromptVariables {
+            selected_text: Some("  hello world  ".to_string()),
+        };
+        let rendered = render_prompt(
+            "Today is {date} on {os}. Selected: {selected_text}. JSON: {\"tools\": []}",
+            &vars,
+        );
+        assert!(!rendered.contains("{date}"));
+        assert!(!rendered.contains("{os}"));
+        assert!(rendered.contains("Selected: hello world."));
+        assert!(rendered.contains("{\"tools\": []}"));
+    }
+
+    #[test]
+    fn test_resolve_falls_back_to_default() {
+        let mut library = PromptLibrary::default();
+        library
+            .upsert(preset("pirate", "Talk like a pirate"))
+            .unwrap();
+
+        assert_eq!(
+            library.resolve(Some("pirate"), "base").content,
+            "Talk like a pirate"
+        );
+        assert_eq!(
+            library.resolve(Some("missing"), "base").id,
+            DEFAULT_PROMPT_ID
+        );
+        assert_eq!(library.resolve(None, "base").content, "base");
+        assert!(library.upsert(preset(DEFAULT_PROMPT_ID, "nope")).is_err());
+        assert!(library.remove(DEFAULT_PROMPT_ID).is_err());
+    }
+
+    #[test]
+    fn test_library_toml_roundtrip() {
+        let mut library = PromptLibrary::default();
+        library
+            .upsert(preset("tutor", "Explain like a tutor"))
+            .unwrap();
+        let serialized = toml::to_string_pretty(&library).unwrap();
+        let parsed: PromptLibrary = toml::from_str(&serialized).unwrap();
+        assert_eq!(parsed.presets, library.presets);
+        assert_eq!(parsed.with_built_ins("base").len(), 2);
+    }
+}
@@ -0,0 +1,965 @@
+//! Deep research mode
+//!
+//! A deep research job answers one question with a structured report instead of
+//! a single chat reply. A helper model splits the question into sub-questions,
+//! then picks tools for the open ones round after round, keeping what the tools
+//! found in a scratchpad, until every sub-question is answered or the budget
+//! (rounds, tool calls, estimated tokens, minutes) runs out. The report is then
+//! written from the scratchpad, with numbered citations.
+//!
+//! Jobs run in the background and are saved after every step, so the frontend
+//! can catch up with `get_deep_research_job` after the window was hidden, and a
+//! job interrupted by quitting the app can be resumed where it stopped.
+
+use crate::tools::citations::{CitationList, CitedSource};
+use crate::tools::readability::truncate_chars;
+use crate::tools::{self, executor};
+use crate::{call_gemini_api_non_streaming, load_config, tool_policy, ChatMessage, ToolDecision};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Instant;
+use tauri::{AppHandle, Emitter, Manager, Window};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+const RESEARCH_DIRNAME: &str = "research";
+/// Model that plans the research and picks the tools
+const PLANNER_MODEL: &str = "gemini-2.0-flash";
+const MAX_SUB_QUESTIONS: usize = 6;
+/// Most tools requested in one round
+const MAX_TOOLS_PER_ROUND: usize = 5;
+/// Characters of one tool result kept in the scratchpad
+const MAX_NOTE_CHARS: usize = 4000;
+/// Characters of the scratchpad shown when choosing tools; the newest notes are kept
+const MAX_PLANNING_SCRATCHPAD_CHARS: usize = 24_000;
+/// Rough size of a token, for the token budget
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Limits of one job; research stops at the first one reached and the report
+/// is written from what was found so far
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ResearchBudget {
+    pub max_rounds: usize,
+    pub max_tool_calls: usize,
+    /// Estimated tokens sent to and received from the models
+    pub max_tokens: usize,
+    pub max_minutes: u64,
+}
+
+impl Default for ResearchBudget {
+    fn default() -> Self {
+        ResearchBudget {
+            max_rounds: 6,
+            max_tool_calls: 30,
+            max_tokens: 300_000,
+            max_minutes: 15,
+        }
+    }
+}
+
+impl ResearchBudget {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_rounds == 0 || self.max_rounds > 20 {
+            return Err("Deep research rounds must be between 1 and 20".to_string());
+        }
+        if self.max_tool_calls == 0 || self.max_tool_calls > 200 {
+            return Err("Deep research tool calls must be between 1 and 200".to_string());
+        }
+        if self.max_tokens < 10_000 {
+            return Err("Deep research needs a budget of at least 10000 tokens".to_string());
+        }
+        if self.max_minutes == 0 || self.max_minutes > 120 {
+            return Err("Deep research time must be between 1 and 120 minutes".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ResearchStatus {
+    Planning,
+    Researching,
+    Writing,
+    Completed,
+    Cancelled,
+    Failed,
+    /// Was running when the app quit; can be resumed
+    Interrupted,
+}
+
+impl ResearchStatus {
+    fn is_active(self) -> bool {
+        matches!(
+            self,
+            ResearchStatus::Planning | ResearchStatus::Researching | ResearchStatus::Writing
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SubQuestion {
+    pub question: String,
+    pub answered: bool,
+}
+
+/// What one tool call found, as kept between rounds
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScratchpadNote {
+    pub round: usize,
+    pub tool_type: String,
+    pub query: String,
+    /// Tool context with its numbered sources, shortened to `MAX_NOTE_CHARS`
+    pub text: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ResearchJob {
+    pub id: String,
+    pub conversation_id: Option<String>,
+    pub question: String,
+    /// Model that writes the report
+    pub model_name: String,
+    pub budget: ResearchBudget,
+    pub status: ResearchStatus,
+    pub created_at: String, // RFC 3339, UTC
+    pub sub_questions: Vec<SubQuestion>,
+    pub scratchpad: Vec<ScratchpadNote>,
+    /// Sources of the scratchpad notes, numbered for the report's citations
+    pub citations: CitationList,
+    pub rounds: usize,
+    pub tool_calls: usize,
+    /// Calls made per tool, checked against the tool policy's limits
+    #[serde(default)]
+    pub tool_budget: tool_policy::ToolCallBudget,
+    pub tokens_used: usize,
+    /// Time spent running, summed over resumes
+    pub elapsed_secs: u64,
+    /// Why research stopped before every sub-question was answered
+    pub stop_reason: Option<String>,
+    pub report: Option<String>,
+    pub error: Option<String>,
+}
+
+impl ResearchJob {
+    pub fn new(
+        question: &str,
+        conversation_id: Option<String>,
+        model_name: &str,
+        budget: ResearchBudget,
+    ) -> Self {
+        ResearchJob {
+            id: Uuid::new_v4().to_string(),
+            conversation_id,
+            question: question.trim().to_string(),
+            model_name: model_name.to_string(),
+            budget,
+            status: ResearchStatus::Planning,
+            created_at: OffsetDateTime::now_utc()
+                .format(&Rfc3339)
+                .unwrap_or_default(),
+            sub_questions: Vec::new(),
+            scratchpad: Vec::new(),
+            citations: CitationList::default(),
+            rounds: 0,
+            tool_calls: 0,
+            tool_budget: tool_policy::ToolCallBudget::default(),
+            tokens_used: 0,
+            elapsed_secs: 0,
+            stop_reason: None,
+            report: None,
+            error: None,
+        }
+    }
+
+    /// The budget limit that has been reached, if any
+    fn exhausted_budget(&self) -> Option<&'static str> {
+        if self.rounds >= self.budget.max_rounds {
+            Some("round limit reached")
+        } else if self.tool_calls >= self.budget.max_tool_calls {
+            Some("tool call limit reached")
+        } else if self.tokens_used >= self.budget.max_tokens {
+            Some("token budget used up")
+        } else if self.elapsed_secs >= self.budget.max_minutes * 60 {
+            Some("time limit reached")
+        } else {
+            None
+        }
+    }
+
+    fn open_questions(&self) -> usize {
+        self.sub_questions.iter().filter(|q| !q.answered).count()
+    }
+
+    fn render_sub_questions(&self) -> String {
+        self.sub_questions
+            .iter()
+            .enumerate()
+            .map(|(i, q)| {
+                let state = if q.answered { "answered" } else { "open" };
+                format!("{}. [{}] {}", i + 1, state, q.question)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// The scratchpad as text, keeping the newest notes within `max_chars`
+    fn render_scratchpad(&self, max_chars: usize) -> String {
+        let mut notes: Vec<String> = Vec::new();
+        let mut total = 0;
+        for note in self.scratchpad.iter().rev() {
+            let text = format!(
+                "### Round {}: {} '{}'\n{}\n",
+                note.round, note.tool_type, note.query, note.text
+            );
+            if total + text.len() > max_chars && !notes.is_empty() {
+                break;
+            }
+            total += text.len();
+            notes.push(text);
+        }
+        notes.reverse();
+        notes.join("\n")
+    }
+}
+
+// --- Deep Research Event Payloads ---
+#[derive(Serialize, Clone, Debug)]
+struct ResearchProgressPayload {
+    job_id: String,
+    status: ResearchStatus,
+    message: String,
+    round: usize,
+    tool_calls: usize,
+    tokens_used: usize,
+    elapsed_secs: u64,
+    answered: usize,
+    sub_questions: usize,
+}
+
+#[derive(Serialize, Clone, Debug)]
+struct ResearchCompletedPayload {
+    job_id: String,
+    conversation_id: Option<String>,
+    status: ResearchStatus,
+    report: Option<String>,
+    sources: Vec<CitedSource>,
+    error: Option<String>,
+}
+
+/// Sub-questions returned by the planning step
+#[derive(Deserialize, Debug)]
+struct ResearchPlan {
+    sub_questions: Vec<String>,
+}
+
+/// Tools chosen for one round, and the sub-questions the scratchpad now answers
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct RoundDecision {
+    /// 1-based sub-question numbers
+    answered: Vec<usize>,
+    tools: Vec<ToolDecision>,
+    reasoning: String,
+}
+
+/// Model that writes the report: the selected model if it is a Gemini model
+pub fn report_model(selected_model: Option<&str>) -> String {
+    match selected_model {
+        Some(model) if model.starts_with("gemini-") || model.starts_with("google/") => {
+            model.trim_start_matches("google/").to_string()
+        }
+        _ => PLANNER_MODEL.to_string(),
+    }
+}
+
+/// Cancellation flags of the jobs running in this process
+fn running_jobs() -> &'static Mutex<HashMap<String, Arc<AtomicBool>>> {
+    static RUNNING: OnceLock<Mutex<HashMap<String, Arc<AtomicBool>>>> = OnceLock::new();
+    RUNNING.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn is_running(job_id: &str) -> bool {
+    running_jobs()
+        .lock()
+        .map(|running| running.contains_key(job_id))
+        .unwrap_or(false)
+}
+
+/// Ask a running job to stop after its current step; false if it isn't running
+pub fn request_cancel(job_id: &str) -> bool {
+    match running_jobs().lock() {
+        Ok(running) => match running.get(job_id) {
+            Some(flag) => {
+                flag.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        },
+        Err(_) => false,
+    }
+}
+
+// --- Persistence ---
+/// Job ids are UUIDs; anything else is rejected before touching the filesystem
+fn job_path(app_handle: &AppHandle, job_id: &str) -> Result<PathBuf, String> {
+    if Uuid::parse_str(job_id).is_err() {
+        return Err(format!("Invalid research job id: {}", job_id));
+    }
+    research_dir(app_handle).map(|dir| dir.join(format!("{}.json", job_id)))
+}
+
+fn research_dir(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    match app_handle.path().app_data_dir() {
+        Ok(dir) => Ok(dir.join(RESEARCH_DIRNAME)),
+        Err(e) => Err(format!("Failed to get app data directory: {}", e)),
+    }
+}
+
+fn write_job(path: &Path, job: &ResearchJob) -> Result<(), String> {
+    if let Some(parent_dir) = path.parent() {
+        if !parent_dir.exists() {
+            fs::create_dir_all(parent_dir)
+                .map_err(|e| format!("Failed to create research directory: {}", e))?;
+        }
+    }
+    let json = serde_json::to_string_pretty(job)
+        .map_err(|e| format!("Failed to serialize research job: {}", e))?;
+    fs::write(path, json).map_err(|e| format!("Failed to write research job: {}", e))
+}
+
+/// Read a saved job; one saved mid-run that isn't running any more was interrupted
+fn read_job(path: &Path) -> Result<ResearchJob, String> {
+    let content =
+        fs::read_to_string(path).map_err(|e| format!("Failed to read research job: {}", e))?;
+    let mut job: ResearchJob = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse research job: {}", e))?;
+    if job.status.is_active() && !is_running(&job.id) {
+        job.status = ResearchStatus::Interrupted;
+    }
+    Ok(job)
+}
+
+pub fn save_job(app_handle: &AppHandle, job: &ResearchJob) -> Result<(), String> {
+    write_job(&job_path(app_handle, &job.id)?, job)
+}
+
+pub fn load_job(app_handle: &AppHandle, job_id: &str) -> Result<ResearchJob, String> {
+    read_job(&job_path(app_handle, job_id)?)
+}
+
+/// Saved jobs, newest first, optionally only those of one conversation
+pub fn list_jobs(
+    app_handle: &AppHandle,
+    conversation_id: Option<&str>,
+) -> Result<Vec<ResearchJob>, String> {
+    let dir = research_dir(app_handle)?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let entries =
+        fs::read_dir(&dir).map_err(|e| format!("Failed to read research directory: {}", e))?;
+    let mut jobs: Vec<ResearchJob> = entries
+        .flatten()
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|entry| match read_job(&entry.path()) {
+            Ok(job) => Some(job),
+            Err(e) => {
+                log::warn!("Skipping research job {:?}: {}", entry.path(), e);
+                None
+            }
+        })
+        .filter(|job| {
+            conversation_id.is_none() || job.conversation_id.as_deref() == conversation_id
+        })
+        .collect();
+    jobs.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    Ok(jobs)
+}
+
+pub fn delete_job(app_handle: &AppHandle, job_id: &str) -> Result<(), String> {
+    if is_running(job_id) {
+        return Err("Cancel the research job before deleting it".to_string());
+    }
+    let path = job_path(app_handle, job_id)?;
+    if path.exists() {
+        fs::remove_file(&path).map_err(|e| format!("Failed to delete research job: {}", e))?;
+    }
+    Ok(())
+}
+
+// --- Running a job ---
+/// Parse a JSON reply, ignoring a Markdown code fence around it
+fn parse_json_reply<T: DeserializeOwned>(reply: &str) -> Result<T, String> {
+    let cleaned = reply
+        .trim()
+        .trim_start_matches("```json")
+        .trim_start_matches("```")
+        .trim_end_matches("```")
+        .trim();
+    serde_json::from_str(cleaned).map_err(|e| e.to_string())
+}
+
+/// Apply a round's "answered" list to the sub-questions
+fn mark_answered(sub_questions: &mut [SubQuestion], answered: &[usize]) {
+    for number in answered {
+        if let Some(question) = number.checked_sub(1).and_then(|i| sub_questions.get_mut(i)) {
+            question.answered = true;
+        }
+    }
+}
+
+struct JobRunner {
+    app_handle: AppHandle,
+    window: Window,
+    client: reqwest::Client,
+    config: crate::AppConfig,
+    api_key: String,
+    cancelled: Arc<AtomicBool>,
+    /// When this run started, and the job's elapsed time before it
+    started: Instant,
+    elapsed_before: u64,
+}
+
+impl JobRunner {
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Save the job and tell the frontend how far it got
+    fn checkpoint(&self, job: &mut ResearchJob, message: &str) {
+        job.elapsed_secs = self.elapsed_before + self.started.elapsed().as_secs();
+        if let Err(e) = save_job(&self.app_handle, job) {
+            log::warn!("Failed to save research job {}: {}", job.id, e);
+        }
+        let payload = ResearchProgressPayload {
+            job_id: job.id.clone(),
+            status: job.status,
+            message: message.to_string(),
+            round: job.rounds,
+            tool_calls: job.tool_calls,
+            tokens_used: job.tokens_used,
+            elapsed_secs: job.elapsed_secs,
+            answered: job.sub_questions.len() - job.open_questions(),
+            sub_questions: job.sub_questions.len(),
+        };
+        if let Err(e) = self.window.emit("DEEP_RESEARCH_PROGRESS", payload) {
+            log::warn!("Failed to emit DEEP_RESEARCH_PROGRESS event: {}", e);
+        }
+    }
+
+    /// One helper model call, counted against the token budget
+    async fn ask(
+        &self,
+        job: &mut ResearchJob,
+        model: &str,
+        prompt: String,
+    ) -> Result<String, String> {
+        let prompt_chars = prompt.len();
+        let messages = vec![ChatMessage {
+            role: "user".to_string(),
+            content: prompt,
+            image_base64_data: None,
+            image_mime_type: None,
+            image_file_api_uri: None,
+            document_ids: None,
+        }];
+        let reply =
+            call_gemini_api_non_streaming(&self.client, messages, &self.api_key, model.to_string())
+                .await;
+        let reply_chars = reply.as_ref().map_or(0, |r| r.len());
+        job.tokens_used += (prompt_chars + reply_chars) / CHARS_PER_TOKEN;
+        reply
+    }
+
+    async fn plan(&self, job: &mut ResearchJob) {
+        let prompt = format!(
+            "You are planning a research report that answers this question: '{}'\n\n\
+            Break the question into 2 to {} sub-questions that together cover a complete answer. \
+            Each sub-question should be answerable with a few lookups (encyclopedia, web search, papers, data). \
+            Order them so background comes first.\n\n\
+            Respond only with JSON: {{\"sub_questions\": [\"...\", \"...\"]}}",
+            job.question, MAX_SUB_QUESTIONS
+        );
+        let sub_questions = match self.ask(job, PLANNER_MODEL, prompt).await {
+            Ok(reply) => match parse_json_reply::<ResearchPlan>(&reply) {
+                Ok(plan) => plan.sub_questions,
+                Err(e) => {
+                    log::warn!("Failed to parse research plan: {}. Reply was: {}", e, reply);
+                    Vec::new()
+                }
+            },
+            Err(e) => {
+                log::warn!("Research planning failed: {}", e);
+                Vec::new()
+            }
+        };
+        job.sub_questions = sub_questions
+            .into_iter()
+            .map(|q| q.trim().to_string())
+            .filter(|q| !q.is_empty())
+            .take(MAX_SUB_QUESTIONS)
+            .map(|question| SubQuestion {
+                question,
+                answered: false,
+            })
+            .collect();
+        // Without a plan the question itself is the only thing to research
+        if job.sub_questions.is_empty() {
+            job.sub_questions.push(SubQuestion {
+                question: job.question.clone(),
+                answered: false,
+            });
+        }
+        log::info!(
+            "Research job {} planned {} sub-question(s)",
+            job.id,
+            job.sub_questions.len()
+        );
+    }
+
+    /// Run one round of tools; false when research is done
+    async fn research_round(&self, job: &mut ResearchJob) -> bool {
+        let policy = self.config.tool_policy.clone().unwrap_or_default();
+        let has_knowledge_folders =
+            tools::knowledge::knowledge_base(&self.app_handle).has_folders();
+        let is_tool_enabled = |name: &str| {
+            policy.is_enabled(name)
+                && (name != crate::ToolType::LocalKnowledge.as_str() || has_knowledge_folders)
+        };
+        let tool_registry = tools::registry();
+        let remaining_calls = job.budget.max_tool_calls.saturating_sub(job.tool_calls);
+        let round_limit = MAX_TOOLS_PER_ROUND.min(remaining_calls);
+
+        let prompt = format!(
+            "You are researching this question for a report: '{}'\n\n\
+            Sub-questions:\n{}\n\n\
+            Scratchpad (findings so far):\n{}\n\n\
+            AVAILABLE TOOLS:\n{}\n\
+            Budget left: {} rounds, {} tool calls.\n\n\
+            Respond only with JSON:\n\
+            {{\"answered\": [numbers of the sub-questions the scratchpad now answers well], \
+            \"tools\": [{{\"tool_type\": \"...\", \"query\": \"...\", \"reasoning\": \"...\", \"priority\": 1}}], \
+            \"reasoning\": \"...\"}}\n\
+            Request at most {} tools, aimed at the open sub-questions and at gaps or contradictions in the scratchpad; \
+            don't repeat lookups that are already in it. Use an empty tools list when every sub-question is answered.",
+            job.question,
+            job.render_sub_questions(),
+            if job.scratchpad.is_empty() {
+                "(empty)".to_string()
+            } else {
+                job.render_scratchpad(MAX_PLANNING_SCRATCHPAD_CHARS)
+            },
+            tool_registry.prompt_tool_list(true, is_tool_enabled),
+            job.budget.max_rounds.saturating_sub(job.rounds),
+            remaining_calls,
+            round_limit
+        );
+        let decision = match self.ask(job, PLANNER_MODEL, prompt).await {
+            Ok(reply) => match parse_json_reply::<RoundDecision>(&reply) {
+                Ok(decision) => decision,
+                Err(e) => {
+                    log::warn!(
+                        "Failed to parse research round: {}. Reply was: {}",
+                        e,
+                        reply
+                    );
+                    job.stop_reason = Some("could not choose further tools".to_string());
+                    return false;
+                }
+            },
+            Err(e) => {
+                log::warn!("Research round failed: {}", e);
+                job.stop_reason = Some(format!("tool selection failed: {}", e));
+                return false;
+            }
+        };
+        mark_answered(&mut job.sub_questions, &decision.answered);
+        if decision.tools.is_empty() || job.open_questions() == 0 {
+            log::info!(
+                "Research job {} needs no more tools: {}",
+                job.id,
+                decision.reasoning
+            );
+            return false;
+        }
+
+        job.rounds += 1;
+        let round = job.rounds;
+        let mut approved: Vec<ToolDecision> = Vec::new();
+        let mut decisions = decision.tools;
+        decisions.sort_by_key(|d| d.priority);
+        for decision in decisions.into_iter().take(round_limit) {
+            let tool_name = decision.tool_type.as_str();
+            if !is_tool_enabled(tool_name) {
+                continue;
+            }
+            if let Err(reason) = job.tool_budget.try_acquire(&policy, tool_name) {
+                log::info!("Skipping {} in deep research: {}", tool_name, reason);
+                continue;
+            }
+            if policy.requires_confirmation(tool_name)
+                && !tool_policy::request_confirmation(
+                    &self.window,
+                    tool_name,
+                    &decision.query,
+                    &decision.reasoning,
+                )
+                .await
+            {
+                job.tool_budget.release(tool_name);
+                log::info!("{} was not approved for deep research", tool_name);
+                continue;
+            }
+            approved.push(decision);
+        }
+        job.tool_calls += approved.len();
+        self.checkpoint(
+            job,
+            &format!("Round {}: running {} tool(s)", round, approved.len()),
+        );
+
+        let tool_context = tools::ToolContext {
+            client: &self.client,
+            window: &self.window,
+            cache: tools::cache::tool_cache(&self.app_handle),
+            config: &self.config,
+            gemini_api_key: &self.api_key,
+            model_name: PLANNER_MODEL,
+            user_query: &job.question,
+            attached_paths: &[],
+        };
+        let max_parallel = self
+            .config
+            .max_parallel_tools
+            .unwrap_or(executor::DEFAULT_MAX_PARALLEL_TOOLS);
+        let runs = executor::run_tools(&tool_context, approved, max_parallel).await;
+        for run in runs {
+            if let Some(context) = run.context {
+                let text = job.citations.annotate(&context, &run.sources);
+                job.tokens_used += text.len() / CHARS_PER_TOKEN;
+                job.scratchpad.push(ScratchpadNote {
+                    round,
+                    tool_type: run.decision.tool_type.as_str().to_string(),
+                    query: run.decision.query.clone(),
+                    text: truncate_chars(&text, MAX_NOTE_CHARS),
+                });
+            }
+        }
+        true
+    }
+
+    async fn write_report(&self, job: &mut ResearchJob) -> Result<String, String> {
+        let prompt = format!(
+            "Write a research report that answers this question: '{}'\n\n\
+            Research notes, with numbered sources:\n{}\n\n\
+            Structure the report in Markdown:\n\
+            - A title line starting with '# '\n\
+            - '## Summary' with the direct answer in a few sentences\n\
+            - One '## ' section per sub-question, in this order:\n{}\n\
+            - '## Conclusion'\n\n\
+            Use only the research notes. Cite the numbered sources inline with markers like [1] or [2, 3] wherever you use them. \
+            Where the notes don't answer a sub-question, say so briefly. Do not add a list of sources at the end.{}",
+            job.question,
+            job.render_scratchpad(usize::MAX),
+            job.sub_questions
+                .iter()
+                .map(|q| format!("  - {}", q.question))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            match &job.stop_reason {
+                Some(reason) => format!(" Research stopped early ({}); mention that the report may be incomplete.", reason),
+                None => String::new(),
+            }
+        );
+        let model = job.model_name.clone();
+        let report = self.ask(job, &model, prompt).await?;
+        let sources = job.citations.cited_in(&report);
+        if sources.is_empty() {
+            return Ok(report.trim().to_string());
+        }
+        let source_list = sources
+            .iter()
+            .map(|cited| {
+                format!(
+                    "[{}] [{}]({})",
+                    cited.number, cited.source.title, cited.source.url
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        Ok(format!(
+            "{}\n\n## Sources\n\n{}",
+            report.trim(),
+            source_list
+        ))
+    }
+
+    async fn run(&self, job: &mut ResearchJob) -> Result<(), String> {
+        if job.sub_questions.is_empty() {
+            job.status = ResearchStatus::Planning;
+            self.checkpoint(job, "Planning sub-questions");
+            self.plan(job).await;
+        }
+
+        // A resumed job continues with the rounds and tool calls its budget has left
+        job.status = ResearchStatus::Researching;
+        self.checkpoint(job, "Researching");
+        while job.open_questions() > 0 {
+            if self.is_cancelled() {
+                return Ok(());
+            }
+            job.elapsed_secs = self.elapsed_before + self.started.elapsed().as_secs();
+            if let Some(reason) = job.exhausted_budget() {
+                log::info!("Research job {} stopped: {}", job.id, reason);
+                job.stop_reason = Some(reason.to_string());
+                break;
+            }
+            if !self.research_round(job).await {
+                break;
+            }
+            let message = format!(
+                "Round {} done: {} of {} sub-questions answered",
+                job.rounds,
+                job.sub_questions.len() - job.open_questions(),
+                job.sub_questions.len()
+            );
+            self.checkpoint(job, &message);
+        }
+        if self.is_cancelled() {
+            return Ok(());
+        }
+
+        job.status = ResearchStatus::Writing;
+        self.checkpoint(job, "Writing the report");
+        job.report = Some(self.write_report(job).await?);
+        job.status = ResearchStatus::Completed;
+        Ok(())
+    }
+}
+
+/// Run (or resume) a job until it completes, fails or is cancelled
+///
+/// Progress is emitted as DEEP_RESEARCH_PROGRESS, the outcome as DEEP_RESEARCH_COMPLETED.
+pub async fn run_job(app_handle: AppHandle, window: Window, mut job: ResearchJob) {
+    let cancelled = Arc::new(AtomicBool::new(false));
+    match running_jobs().lock() {
+        Ok(mut running) => {
+            if running.contains_key(&job.id) {
+                log::warn!("Research job {} is already running", job.id);
+                return;
+            }
+            running.insert(job.id.clone(), cancelled.clone());
+        }
+        Err(e) => {
+            log::error!("Research job state is poisoned: {}", e);
+            return;
+        }
+    }
+
+    let config = match load_config(&app_handle) {
+        Ok(config) => Some(config),
+        Err(e) => {
+            job.error = Some(e);
+            None
+        }
+    };
+    if let Some(config) = config {
+        let runner = JobRunner {
+            app_handle: app_handle.clone(),
+            window: window.clone(),
+            client: reqwest::Client::new(),
+            api_key: config.gemini_api_key.clone().unwrap_or_default(),
+            config,
+            cancelled,
+            started: Instant::now(),
+            elapsed_before: job.elapsed_secs,
+        };
+        log::info!("Running research job {}: '{}'", job.id, job.question);
+        match runner.run(&mut job).await {
+            Ok(()) if runner.is_cancelled() => job.status = ResearchStatus::Cancelled,
+            Ok(()) => {}
+            Err(e) => {
+                log::error!("Research job {} failed: {}", job.id, e);
+                job.error = Some(e);
+            }
+        }
+        job.elapsed_secs = runner.elapsed_before + runner.started.elapsed().as_secs();
+    }
+    if job.error.is_some() {
+        job.status = ResearchStatus::Failed;
+    }
+
+    if let Ok(mut running) = running_jobs().lock() {
+        running.remove(&job.id);
+    }
+    if let Err(e) = save_job(&app_handle, &job) {
+        log::warn!("Failed to save research job {}: {}", job.id, e);
+    }
+    log::info!(
+        "Research job {} finished as {:?}: {} rounds, {} tool calls, ~{} tokens, {}s",
+        job.id,
+        job.status,
+        job.rounds,
+        job.tool_calls,
+        job.tokens_used,
+        job.elapsed_secs
+    );
+    let payload = ResearchCompletedPayload {
+        job_id: job.id.clone(),
+        conversation_id: job.conversation_id.clone(),
+        status: job.status,
+        sources: job
+            .report
+            .as_deref()
+            .map(|report| job.citations.cited_in(report))
+            .unwrap_or_default(),
+        report: job.report,
+        error: job.error,
+    };
+    if let Err(e) = window.emit("DEEP_RESEARCH_COMPLETED", payload) {
+        log::warn!("Failed to emit DEEP_RESEARCH_COMPLETED event: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job_with_notes() -> ResearchJob {
+        let mut job = ResearchJob::new(
+            "How do heat pumps work in cold climates?",
+            Some("conv-1".to_string()),
+            "gemini-2.5-flash",
+            ResearchBudget::default(),
+        );
+        job.sub_questions = vec![
+            SubQuestion {
+                question: "How does a heat pump move heat?".to_string(),
+                answered: false,
+            },
+            SubQuestion {
+                question: "How does efficiency change below freezing?".to_string(),
+                answered: false,
+            },
+        ];
+        for round in 1..=3 {
+            job.scratchpad.push(ScratchpadNote {
+                round,
+                tool_type: "WEB_SEARCH".to_string(),
+                query: format!("query {}", round),
+                text: "x".repeat(100),
+            });
+        }
+        job
+    }
+
+    #[test]
+    fn test_round_decision_and_answered_questions() {
+        let decision: RoundDecision = parse_json_reply(
+            "```json\n{\"answered\": [2, 7], \"tools\": [{\"tool_type\": \"WEB_SEARCH\", \
+             \"query\": \"heat pump COP -15C\", \"reasoning\": \"efficiency data\", \"priority\": 1}]}\n```",
+        )
+        .unwrap();
+        assert_eq!(decision.tools.len(), 1);
+
+        let mut job = job_with_notes();
+        mark_answered(&mut job.sub_questions, &decision.answered);
+        assert!(!job.sub_questions[0].answered);
+        assert!(job.sub_questions[1].answered);
+        assert_eq!(job.open_questions(), 1);
+        assert_eq!(
+            job.render_sub_questions(),
+            "1. [open] How does a heat pump move heat?\n\
+             2. [answered] How does efficiency change below freezing?"
+        );
+    }
+
+    #[test]
+    fn test_scratchpad_keeps_newest_notes() {
+        let job = job_with_notes();
+        let full = job.render_scratchpad(usize::MAX);
+        assert!(full.starts_with("### Round 1: WEB_SEARCH 'query 1'\n"));
+
+        let recent = job.render_scratchpad(300);
+        assert!(!recent.contains("Round 1:"));
+        assert!(recent.starts_with("### Round 2: WEB_SEARCH 'query 2'\n"));
+        assert!(recent.contains("Round 3:"));
+    }
+
+    #[test]
+    fn test_tool_budget_is_saved_with_the_job() {
+        let mut job = job_with_notes();
+        let policy = tool_policy::ToolPolicy::default();
+        job.tool_budget.try_acquire(&policy, "WEB_SEARCH").unwrap();
+
+        let saved = serde_json::to_value(&job).unwrap();
+        let resumed: ResearchJob = serde_json::from_value(saved.clone()).unwrap();
+        assert_eq!(resumed.tool_budget, job.tool_budget);
+
+        // Jobs saved before the budget was recorded still load
+        let mut old = saved;
+        old.as_object_mut().unwrap().remove("tool_budget");
+        let resumed: ResearchJob = serde_json::from_value(old).unwrap();
+        assert_eq!(resumed.tool_budget, tool_policy::ToolCallBudget::default());
+    }
+
+    #[test]
+    fn test_budget_limits() {
+        let mut job = job_with_notes();
+        assert_eq!(job.exhausted_budget(), None);
+        job.tool_calls = job.budget.max_tool_calls;
+        assert_eq!(job.exhausted_budget(), Some("tool call limit reached"));
+        job.tool_calls = 0;
+        job.elapsed_secs = job.budget.max_minutes * 60;
+        assert_eq!(job.exhausted_budget(), Some("time limit reached"));
+
+        assert!(ResearchBudget::default().validate().is_ok());
+        let no_rounds = ResearchBudget {
+            max_rounds: 0,
+            ..ResearchBudget::default()
+        };
+        assert!(no_rounds.validate().is_err());
+    }
+
+    #[test]
+    fn test_saved_running_job_reads_as_interrupted() {
+        let mut job = job_with_notes();
+        job.status = ResearchStatus::Researching;
+        job.citations.add(&[tools::citations::ToolSource::new(
+            "Heat pump",
+            "https://en.wikipedia.org/wiki/Heat_pump",
+            None,
+        )]);
+        let path = std::env::temp_dir()
+            .join(format!("shard-research-test-{}", Uuid::new_v4()))
+            .join(format!("{}.json", job.id));
+        write_job(&path, &job).unwrap();
+
+        let loaded = read_job(&path).unwrap();
+        assert_eq!(loaded.status, ResearchStatus::Interrupted);
+        assert_eq!(loaded.scratchpad, job.scratchpad);
+        assert_eq!(loaded.citations.cited_in("see [1]").len(), 1);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_report_model() {
+        assert_eq!(
+            report_model(Some("google/gemini-2.5-pro")),
+            "gemini-2.5-pro"
+        );
+        assert_eq!(
+            report_model(Some("anthropic/claude-3.5-sonnet")),
+            PLANNER_MODEL
+        );
+        assert_eq!(report_model(None), PLANNER_MODEL);
+    }
+}
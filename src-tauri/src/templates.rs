@@ -0,0 +1,424 @@
+//! Slash-command prompt templates for Shard
+//!
+//! A template is invoked by starting a message with `/name`, e.g.
+//! `/translate Spanish Where is the station?`. Templates declare named parameters,
+//! may pin a model and may choose which research tools run (skipping the tool
+//! decider). User templates are stored in `templates.toml` in the app config
+//! directory and can be imported/exported as TOML.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager};
+
+const TEMPLATES_FILENAME: &str = "templates.toml";
+
+/// Placeholder that receives the free text following the command and its arguments
+pub const INPUT_PLACEHOLDER: &str = "{input}";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TemplateParameter {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub required: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PromptTemplate {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub body: String,
+    #[serde(default, rename = "parameter", skip_serializing_if = "Vec::is_empty")]
+    pub parameters: Vec<TemplateParameter>,
+    /// Tool types to run instead of asking the decider (`[]` disables tools)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<String>>,
+    /// Model to use for this message instead of the selected one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub built_in: bool,
+}
+
+/// On-disk / import-export representation of a set of templates
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct TemplateLibrary {
+    #[serde(default, rename = "template")]
+    pub templates: Vec<PromptTemplate>,
+}
+
+/// A `/name args` command parsed from the start of a message
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateInvocation {
+    pub name: String,
+    pub args: String,
+}
+
+/// Result of expanding a template invocation
+#[derive(Debug, Clone)]
+pub struct ExpandedTemplate {
+    pub content: String,
+    /// Text tools should search for (the free input, or the whole prompt if empty)
+    pub tool_query: String,
+}
+
+/// Parse a leading `/name` command; returns `None` for ordinary messages
+pub fn parse_invocation(message: &str) -> Option<TemplateInvocation> {
+    let trimmed = message.trim_start();
+    let rest = trimmed.strip_prefix('/')?;
+    let name_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+    let name = &rest[..name_end];
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return None;
+    }
+    Some(TemplateInvocation {
+        name: name.to_lowercase(),
+        args: rest[name_end..].trim().to_string(),
+    })
+}
+
+/// Replace every `{name}` in `body` in a single pass, so substituted values and
+/// user text are never expanded again; unknown names are left as they are
+fn substitute(body: &str, values: &HashMap<String, String>, input: &str) -> String {
+    let mut result = String::with_capacity(body.len());
+    let mut rest = body;
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let value = after.find('}').and_then(|end| {
+            let placeholder = &rest[start..start + end + 2];
+            values
+                .get(&after[..end])
+                .map(String::as_str)
+                .or((placeholder == INPUT_PLACEHOLDER).then_some(input))
+                .map(|value| (value, &after[end + 1..]))
+        });
+        match value {
+            Some((value, remainder)) => {
+                result.push_str(value);
+                rest = remainder;
+            }
+            None => {
+                result.push('{');
+                rest = after;
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Split the next word (or `"quoted phrase"`) off the front of `text`
+fn take_word(text: &str) -> Option<(String, &str)> {
+    let text = text.trim_start();
+    if text.is_empty() {
+        return None;
+    }
+    if let Some(quoted) = text.strip_prefix('"') {
+        if let Some(end) = quoted.find('"') {
+            return Some((quoted[..end].to_string(), &quoted[end + 1..]));
+        }
+    }
+    let end = text.find(char::is_whitespace).unwrap_or(text.len());
+    Some((text[..end].to_string(), &text[end..]))
+}
+
+impl PromptTemplate {
+    fn has_parameter(&self, name: &str) -> bool {
+        self.parameters.iter().any(|p| p.name == name)
+    }
+
+    /// Fill in the template from the invocation arguments
+    ///
+    /// Arguments are matched as `name=value` (or `name="quoted value"`) pairs first.
+    /// Required parameters that are still missing take the next positional word,
+    /// and whatever text remains becomes `{input}`.
+    pub fn expand(&self, args: &str) -> Result<ExpandedTemplate, String> {
+        let mut values: HashMap<String, String> = HashMap::new();
+        let mut rest = args.trim_start();
+
+        // Named arguments
+        while let Some((key, after_key)) = rest.split_once('=') {
+            let key = key.trim();
+            if key.contains(char::is_whitespace) || !self.has_parameter(key) {
+                break;
+            }
+            match take_word(after_key) {
+                Some((value, remainder)) => {
+                    values.insert(key.to_string(), value);
+                    rest = remainder.trim_start();
+                }
+                None => break,
+            }
+        }
+
+        // Positional arguments for required parameters
+        for param in &self.parameters {
+            if param.required && param.default.is_none() && !values.contains_key(&param.name) {
+                if let Some((value, remainder)) = take_word(rest) {
+                    values.insert(param.name.clone(), value);
+                    rest = remainder.trim_start();
+                }
+            }
+        }
+
+        for param in &self.parameters {
+            if values.contains_key(&param.name) {
+                continue;
+            }
+            match (&param.default, param.required) {
+                (Some(default), _) => {
+                    values.insert(param.name.clone(), default.clone());
+                }
+                (None, true) => {
+                    return Err(format!(
+                        "Template '/{}' is missing required parameter '{}'",
+                        self.name, param.name
+                    ));
+                }
+                (None, false) => {
+                    values.insert(param.name.clone(), String::new());
+                }
+            }
+        }
+
+        let input = rest.trim().to_string();
+        let body = substitute(&self.body, &values, &input);
+        let content = if self.body.contains(INPUT_PLACEHOLDER) || input.is_empty() {
+            body
+        } else {
+            format!("{}\n\n{}", body, input)
+        };
+
+        let tool_query = if input.is_empty() {
+            content.clone()
+        } else {
+            input
+        };
+        Ok(ExpandedTemplate {
+            content: content.trim().to_string(),
+            tool_query,
+        })
+    }
+}
+
+fn built_in_templates() -> Vec<PromptTemplate> {
+    vec![
+        PromptTemplate {
+            name: "summarize".to_string(),
+            description: "Summarize the given text".to_string(),
+            body: "Summarize the following text in a few concise bullet points:\n\n{input}"
+                .to_string(),
+            parameters: vec![],
+            tools: Some(vec![]),
+            model: None,
+            built_in: true,
+        },
+        PromptTemplate {
+            name: "explain".to_string(),
+            description: "Explain a piece of code step by step".to_string(),
+            body: "Explain what the following code does, step by step, and point out anything surprising:\n\n```\n{input}\n```".to_string(),
+            parameters: vec![],
+            tools: Some(vec![]),
+            model: None,
+            built_in: true,
+        },
+        PromptTemplate {
+            name: "translate".to_string(),
+            description: "Translate text into another language".to_string(),
+            body: "Translate the following text to {language}. Reply with the translation only:\n\n{input}".to_string(),
+            parameters: vec![TemplateParameter {
+                name: "language".to_string(),
+                description: "Target language, e.g. Spanish".to_string(),
+                required: true,
+                default: None,
+            }],
+            tools: Some(vec![]),
+            model: None,
+            built_in: true,
+        },
+    ]
+}
+
+impl TemplateLibrary {
+    /// Find a template by name; user templates override built-ins
+    pub fn find(&self, name: &str) -> Option<PromptTemplate> {
+        self.templates
+            .iter()
+            .find(|t| t.name == name)
+            .cloned()
+            .or_else(|| built_in_templates().into_iter().find(|t| t.name == name))
+    }
+
+    /// User templates plus any built-ins they don't override
+    pub fn all(&self) -> Vec<PromptTemplate> {
+        let mut all: Vec<PromptTemplate> = built_in_templates()
+            .into_iter()
+            .filter(|b| !self.templates.iter().any(|t| t.name == b.name))
+            .collect();
+        all.extend(self.templates.iter().cloned());
+        all.sort_by(|a, b| a.name.cmp(&b.name));
+        all
+    }
+
+    /// Insert a template or replace the user template with the same name
+    pub fn upsert(&mut self, template: PromptTemplate) -> Result<(), String> {
+        let template = PromptTemplate {
+            name: template.name.trim().trim_start_matches('/').to_lowercase(),
+            built_in: false,
+            ..template
+        };
+        if parse_invocation(&format!("/{}", template.name)).is_none() {
+            return Err(format!(
+                "Invalid template name '{}': use letters, digits, '-' or '_'",
+                template.name
+            ));
+        }
+        if template.parameters.iter().any(|p| p.name == "input") {
+            return Err("'input' is reserved for the text after the command".to_string());
+        }
+        match self.templates.iter_mut().find(|t| t.name == template.name) {
+            Some(existing) => *existing = template,
+            None => self.templates.push(template),
+        }
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        let before = self.templates.len();
+        self.templates.retain(|t| t.name != name);
+        self.templates.len() != before
+    }
+}
+
+/// Parse templates from TOML (`[[template]]` tables)
+pub fn templates_from_toml(content: &str) -> Result<Vec<PromptTemplate>, String> {
+    toml::from_str::<TemplateLibrary>(content)
+        .map(|library| library.templates)
+        .map_err(|e| format!("Failed to parse templates TOML: {}", e))
+}
+
+/// Serialize templates to TOML (`[[template]]` tables)
+pub fn templates_to_toml(templates: Vec<PromptTemplate>) -> Result<String, String> {
+    toml::to_string_pretty(&TemplateLibrary { templates })
+        .map_err(|e| format!("Failed to serialize templates: {}", e))
+}
+
+// --- Storage Helpers ---
+fn get_templates_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    match app_handle.path().app_config_dir() {
+        Ok(dir) => Ok(dir.join(TEMPLATES_FILENAME)),
+        Err(e) => Err(format!("Failed to get app config directory: {}", e)),
+    }
+}
+
+pub fn load_template_library(app_handle: &AppHandle) -> Result<TemplateLibrary, String> {
+    let templates_path = get_templates_path(app_handle)?;
+    if !templates_path.exists() {
+        return Ok(TemplateLibrary::default());
+    }
+    let content = fs::read_to_string(&templates_path)
+        .map_err(|e| format!("Failed to read templates file: {}", e))?;
+    Ok(TemplateLibrary {
+        templates: templates_from_toml(&content)?,
+    })
+}
+
+pub fn save_template_library(
+    app_handle: &AppHandle,
+    library: &TemplateLibrary,
+) -> Result<(), String> {
+    let templates_path = get_templates_path(app_handle)?;
+    log::info!("Saving prompt templates to {:?}", templates_path);
+    if let Some(parent_dir) = templates_path.parent() {
+        if !parent_dir.exists() {
+            fs::create_dir_all(parent_dir)
+                .map_err(|e| format!("Failed to create config directory: {}", e))?;
+        }
+    }
+    let toml_string = templates_to_toml(library.templates.clone())?;
+    fs::write(&templates_path, toml_string)
+        .map_err(|e| format!("Failed to write templates file: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_invocation() {
+        let invocation = parse_invocation("  /Translate Spanish hello").unwrap();
+        assert_eq!(invocation.name, "translate");
+        assert_eq!(invocation.args, "Spanish hello");
+        assert!(parse_invocation("what is 1/2?").is_none());
+        assert!(parse_invocation("/usr/bin/env is a path").is_none());
+        assert!(parse_invocation("/").is_none());
+    }
+
+    #[test]
+    fn test_expand_named_and_positional_arguments() {
+        let library = TemplateLibrary::default();
+        let translate = library.find("translate").unwrap();
+
+        let positional = translate.expand("Spanish Where is the station?").unwrap();
+        assert!(positional.content.contains("to Spanish."));
+        assert!(positional.content.ends_with("Where is the station?"));
+        assert_eq!(positional.tool_query, "Where is the station?");
+
+        let named = translate
+            .expand("language=\"Brazilian Portuguese\" Good morning")
+            .unwrap();
+        assert!(named.content.contains("to Brazilian Portuguese."));
+        assert!(named.content.ends_with("Good morning"));
+
+        assert!(translate.expand("").is_err());
+    }
+
+    #[test]
+    fn test_expand_does_not_reexpand_values() {
+        let template = PromptTemplate {
+            name: "pair".to_string(),
+            description: String::new(),
+            body: "{a} and {b}, {unknown}: {input}".to_string(),
+            parameters: ["a", "b"]
+                .iter()
+                .map(|name| TemplateParameter {
+                    name: name.to_string(),
+                    description: String::new(),
+                    required: true,
+                    default: None,
+                })
+                .collect(),
+            tools: None,
+            model: None,
+            built_in: false,
+        };
+        let expanded = template.expand("a={b} b={input} {a}").unwrap();
+        assert_eq!(expanded.content, "{b} and {input}, {unknown}: {a}");
+    }
+
+    #[test]
+    fn test_toml_roundtrip_and_overrides() {
+        let exported = templates_to_toml(built_in_templates()).unwrap();
+        let imported = templates_from_toml(&exported).unwrap();
+        assert_eq!(imported.len(), 3);
+        assert_eq!(imported[2].parameters[0].name, "language");
+
+        let mut library = TemplateLibrary::default();
+        let mut custom = imported[0].clone();
+        custom.body = "TL;DR: {input}".to_string();
+        library.upsert(custom).unwrap();
+        assert_eq!(library.find("summarize").unwrap().body, "TL;DR: {input}");
+        assert_eq!(library.all().len(), 3);
+    }
+}
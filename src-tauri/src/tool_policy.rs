@@ -0,0 +1,318 @@
+//! Per-tool execution policy for Shard
+//!
+//! `enable_web_search` remains the master switch for research tools. Within it, a
+//! `ToolPolicy` can disable individual tools, require the user to confirm a tool
+//! call before it runs, and cap how many times a tool may run for one message.
+//! The policy stored in `AppConfig` can be overridden per conversation.
+
+use crate::ToolType;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tauri::{Emitter, Window};
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+/// How long to wait for the user to answer a confirmation prompt before skipping the tool
+const CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Settings for a single tool; `None` means "inherit"
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ToolSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub require_confirmation: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_calls: Option<u32>,
+}
+
+/// Tool settings keyed by tool type name (e.g. "WEATHER_LOOKUP")
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ToolPolicy {
+    #[serde(default)]
+    pub tools: HashMap<String, ToolSettings>,
+}
+
+/// The wire name of a tool in any case ("web_search" is "WEB_SEARCH"), or the name
+/// as given if no tool has it
+fn canonical_name(tool_name: &str) -> String {
+    ToolType::from_name(tool_name)
+        .map_or_else(|| tool_name.to_string(), |tool| tool.as_str().to_string())
+}
+
+impl ToolPolicy {
+    /// The policy keyed by wire names, or an error naming a key that isn't a tool
+    pub fn normalized(self) -> Result<ToolPolicy, String> {
+        let mut tools = HashMap::new();
+        for (name, settings) in self.tools {
+            let tool = ToolType::from_name(&name)
+                .ok_or_else(|| format!("Unknown tool type in policy: {}", name))?;
+            if tools.insert(tool.as_str().to_string(), settings).is_some() {
+                return Err(format!(
+                    "{} is listed more than once in the policy",
+                    tool.as_str()
+                ));
+            }
+        }
+        Ok(ToolPolicy { tools })
+    }
+
+    /// Layer a per-conversation override on top of this policy
+    pub fn merged_with(&self, overrides: &ToolPolicy) -> ToolPolicy {
+        let mut merged = ToolPolicy {
+            tools: self
+                .tools
+                .iter()
+                .map(|(name, settings)| (canonical_name(name), settings.clone()))
+                .collect(),
+        };
+        for (name, settings) in &overrides.tools {
+            let entry = merged.tools.entry(canonical_name(name)).or_default();
+            if settings.enabled.is_some() {
+                entry.enabled = settings.enabled;
+            }
+            if settings.require_confirmation.is_some() {
+                entry.require_confirmation = settings.require_confirmation;
+            }
+            if settings.max_calls.is_some() {
+                entry.max_calls = settings.max_calls;
+            }
+        }
+        merged
+    }
+
+    /// Settings for a tool, whatever case its name was saved in
+    fn settings(&self, tool_name: &str) -> Option<&ToolSettings> {
+        let name = canonical_name(tool_name);
+        self.tools.get(&name).or_else(|| {
+            self.tools
+                .iter()
+                .find(|(key, _)| canonical_name(key) == name)
+                .map(|(_, settings)| settings)
+        })
+    }
+
+    pub fn is_enabled(&self, tool_name: &str) -> bool {
+        self.settings(tool_name)
+            .and_then(|s| s.enabled)
+            .unwrap_or(true)
+    }
+
+    pub fn requires_confirmation(&self, tool_name: &str) -> bool {
+        self.settings(tool_name)
+            .and_then(|s| s.require_confirmation)
+            .unwrap_or(false)
+    }
+
+    pub fn max_calls(&self, tool_name: &str) -> Option<u32> {
+        self.settings(tool_name).and_then(|s| s.max_calls)
+    }
+}
+
+/// Counts tool calls made while answering one message
+///
+/// Serializable so a resumed deep research job keeps the calls it already made.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct ToolCallBudget {
+    calls: HashMap<String, u32>,
+}
+
+impl ToolCallBudget {
+    /// Record a call if the policy allows it, otherwise explain why not
+    pub fn try_acquire(&mut self, policy: &ToolPolicy, tool_name: &str) -> Result<(), String> {
+        if !policy.is_enabled(tool_name) {
+            return Err(format!("{} is disabled in tool settings", tool_name));
+        }
+        let used = self.calls.entry(tool_name.to_string()).or_insert(0);
+        if let Some(max_calls) = policy.max_calls(tool_name) {
+            if *used >= max_calls {
+                return Err(format!(
+                    "{} reached its limit of {} call(s) for this message",
+                    tool_name, max_calls
+                ));
+            }
+        }
+        *used += 1;
+        Ok(())
+    }
+
+    /// Give back a call that was acquired but never made, e.g. one the user denied
+    pub fn release(&mut self, tool_name: &str) {
+        if let Some(used) = self.calls.get_mut(tool_name) {
+            *used = used.saturating_sub(1);
+        }
+    }
+}
+
+// --- Tool Confirmation ---
+#[derive(Serialize, Clone, Debug)]
+pub struct ToolConfirmationRequestPayload {
+    pub request_id: String,
+    pub tool_type: String,
+    pub query: String,
+    pub reasoning: String,
+}
+
+fn pending_confirmations() -> &'static Mutex<HashMap<String, oneshot::Sender<bool>>> {
+    static PENDING: OnceLock<Mutex<HashMap<String, oneshot::Sender<bool>>>> = OnceLock::new();
+    PENDING.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Ask the frontend to approve a tool call and wait for the answer
+///
+/// Emits TOOL_CONFIRMATION_REQUESTED; the frontend answers via the
+/// `respond_tool_confirmation` command. Times out as a denial.
+pub async fn request_confirmation(
+    window: &Window,
+    tool_type: &str,
+    query: &str,
+    reasoning: &str,
+) -> bool {
+    let request_id = Uuid::new_v4().to_string();
+    let (sender, receiver) = oneshot::channel();
+    match pending_confirmations().lock() {
+        Ok(mut pending) => {
+            pending.insert(request_id.clone(), sender);
+        }
+        Err(e) => {
+            log::error!("Tool confirmation state is poisoned: {}", e);
+            return false;
+        }
+    }
+
+    let payload = ToolConfirmationRequestPayload {
+        request_id: request_id.clone(),
+        tool_type: tool_type.to_string(),
+        query: query.to_string(),
+        reasoning: reasoning.to_string(),
+    };
+    if let Err(e) = window.emit("TOOL_CONFIRMATION_REQUESTED", payload) {
+        log::warn!("Failed to emit TOOL_CONFIRMATION_REQUESTED event: {}", e);
+    }
+    log::info!(
+        "Waiting for user confirmation of {} '{}' (request {})",
+        tool_type,
+        query,
+        request_id
+    );
+
+    let approved = match tokio::time::timeout(CONFIRMATION_TIMEOUT, receiver).await {
+        Ok(Ok(approved)) => approved,
+        Ok(Err(_)) => false,
+        Err(_) => {
+            log::warn!("Tool confirmation {} timed out", request_id);
+            false
+        }
+    };
+    if let Ok(mut pending) = pending_confirmations().lock() {
+        pending.remove(&request_id);
+    }
+    approved
+}
+
+/// Deliver the user's answer to a pending confirmation; false if it no longer exists
+pub fn resolve_confirmation(request_id: &str, approved: bool) -> bool {
+    let sender = match pending_confirmations().lock() {
+        Ok(mut pending) => pending.remove(request_id),
+        Err(_) => None,
+    };
+    match sender {
+        Some(sender) => sender.send(approved).is_ok(),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(enabled: Option<bool>, max_calls: Option<u32>) -> ToolSettings {
+        ToolSettings {
+            enabled,
+            require_confirmation: None,
+            max_calls,
+        }
+    }
+
+    #[test]
+    fn test_override_merging() {
+        let mut base = ToolPolicy::default();
+        base.tools
+            .insert("WEATHER_LOOKUP".to_string(), settings(Some(false), Some(2)));
+        let mut overrides = ToolPolicy::default();
+        overrides
+            .tools
+            .insert("WEATHER_LOOKUP".to_string(), settings(Some(true), None));
+
+        let merged = base.merged_with(&overrides);
+        assert!(merged.is_enabled("WEATHER_LOOKUP"));
+        assert_eq!(merged.max_calls("WEATHER_LOOKUP"), Some(2));
+        assert!(merged.is_enabled("ARXIV_LOOKUP"));
+        assert!(!merged.requires_confirmation("ARXIV_LOOKUP"));
+    }
+
+    #[test]
+    fn test_tool_names_are_normalized() {
+        let mut policy = ToolPolicy::default();
+        policy
+            .tools
+            .insert("Web_Search".to_string(), settings(Some(false), Some(3)));
+        let policy = policy.normalized().unwrap();
+        assert!(policy.tools.contains_key("WEB_SEARCH"));
+        assert!(!policy.is_enabled("WEB_SEARCH"));
+        assert_eq!(policy.max_calls("web_search"), Some(3));
+
+        let mut overrides = ToolPolicy::default();
+        overrides
+            .tools
+            .insert("web_search".to_string(), settings(Some(true), None));
+        let merged = policy.merged_with(&overrides);
+        assert_eq!(merged.tools.len(), 1);
+        assert!(merged.is_enabled("WEB_SEARCH"));
+
+        // Names saved before normalization still apply
+        let mut legacy = ToolPolicy::default();
+        legacy
+            .tools
+            .insert("Weather_Lookup".to_string(), settings(Some(false), None));
+        assert!(!legacy.is_enabled("WEATHER_LOOKUP"));
+
+        let mut unknown = ToolPolicy::default();
+        unknown
+            .tools
+            .insert("TELEPORT".to_string(), settings(None, None));
+        assert!(unknown.normalized().is_err());
+        let mut twice = ToolPolicy::default();
+        twice
+            .tools
+            .insert("ARXIV_LOOKUP".to_string(), settings(None, None));
+        twice
+            .tools
+            .insert("arxiv_lookup".to_string(), settings(None, None));
+        assert!(twice.normalized().is_err());
+    }
+
+    #[test]
+    fn test_call_budget() {
+        let mut policy = ToolPolicy::default();
+        policy
+            .tools
+            .insert("FINANCIAL_DATA".to_string(), settings(None, Some(1)));
+        policy
+            .tools
+            .insert("ARXIV_LOOKUP".to_string(), settings(Some(false), None));
+
+        let mut budget = ToolCallBudget::default();
+        assert!(budget.try_acquire(&policy, "FINANCIAL_DATA").is_ok());
+        assert!(budget.try_acquire(&policy, "FINANCIAL_DATA").is_err());
+        assert!(budget.try_acquire(&policy, "ARXIV_LOOKUP").is_err());
+        assert!(budget.try_acquire(&policy, "WIKIPEDIA_LOOKUP").is_ok());
+
+        // A denied call gives its slot back
+        budget.release("FINANCIAL_DATA");
+        assert!(budget.try_acquire(&policy, "FINANCIAL_DATA").is_ok());
+        assert!(budget.try_acquire(&policy, "FINANCIAL_DATA").is_err());
+    }
+}
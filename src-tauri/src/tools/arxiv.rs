@@ -0,0 +1,935 @@
+//! arXiv paper search tool
+//!
+//! Queries the arXiv Atom API and parses the feed into `arxiv_tools` papers.
+//! Plain queries are turned into a fielded search (title, author, abstract,
+//! category, submission dates) by a helper LLM, which also picks the sort
+//! order and how many papers to return; results are fetched page by page.
+//! Papers that have been published get their venue from Crossref.
+
+use super::crossref;
+use super::scholarly::{self, PaperSummary};
+use super::{Tool, ToolContext, ToolOutput};
+use crate::mcp_simple::{ToolExample, ToolGuidance, ToolParameter};
+use crate::{call_gemini_api_non_streaming, ChatMessage, ToolType};
+use arxiv_tools::Paper as ArXivPaper;
+use async_trait::async_trait;
+use futures_util::future::join_all;
+use quick_xml::de::from_str;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+const ARXIV_API_URL: &str = "http://export.arxiv.org/api/query";
+/// Papers returned when the request doesn't ask for a number
+const DEFAULT_MAX_RESULTS: usize = 5;
+/// Most papers one lookup returns
+const MAX_RESULTS_LIMIT: usize = 50;
+/// Papers requested per API call
+const PAGE_SIZE: usize = 25;
+/// arXiv asks clients to wait three seconds between calls
+const PAGE_DELAY: Duration = Duration::from_secs(3);
+
+// --- ADDED: Structs for parsing ArXiv Atom XML response ---
+
+// NEW Enum to represent children of the <feed> tag
+#[derive(Debug, Deserialize)]
+enum FeedChild {
+    #[serde(rename = "entry")]
+    Entry(ArxivEntry), // For <entry> tags
+    #[serde(other)] // Catches any other tags like <link>, <title>, <id>, <updated> under <feed>
+    Other,
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct ArxivFeed {
+    // This field will collect all direct children of the <feed> element.
+    // Each child will be deserialized into the appropriate FeedChild variant.
+    #[serde(rename = "$value", default)]
+    children: Vec<FeedChild>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct ArxivEntry {
+    id: Option<String>,
+    updated: Option<String>,
+    published: Option<String>,
+    title: Option<String>,
+    summary: Option<String>, // This is the abstract
+    #[serde(rename = "author", default)]
+    authors: Vec<ArxivAuthor>,
+    #[serde(rename = "link", default)] // XML tag is still "link"
+    entry_links: Vec<ArxivLink>, // Rust field name changed from 'links' to 'entry_links'
+    #[serde(rename = "primary_category", default)]
+    primary_category: Option<ArxivCategory>,
+    #[serde(rename = "category", default)]
+    categories: Vec<ArxivCategory>,
+    comment: Option<String>, // arxiv:comment
+    doi: Option<String>,     // arxiv:doi
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct ArxivAuthor {
+    name: Option<String>,
+    // Can add affiliation if needed: #[serde(rename = "arxiv:affiliation")] affiliation: Option<String>
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct ArxivLink {
+    #[serde(rename = "@href")]
+    href: Option<String>,
+    #[serde(rename = "@rel")]
+    rel: Option<String>,
+    #[serde(rename = "@title")]
+    title: Option<String>,
+    #[serde(rename = "@type")]
+    link_type: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct ArxivCategory {
+    #[serde(rename = "@term")]
+    term: Option<String>,
+}
+// --- End of ArXiv Atom XML structs ---
+
+// --- ADDED: Struct for LLM to output ArXiv search parameters ---
+/// Fielded search extracted from the request; every field is optional
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+struct ArxivSearchParameters {
+    /// Words matched anywhere (`all:`)
+    keywords: Option<String>,
+    title: Option<String>,
+    author: Option<String>,
+    abstract_text: Option<String>,
+    /// arXiv category such as `cs.LG` or `quant-ph`
+    category: Option<String>,
+    /// Earliest submission date, `YYYY`, `YYYY-MM` or `YYYY-MM-DD`
+    submitted_after: Option<String>,
+    /// Latest submission date, same formats
+    submitted_before: Option<String>,
+    sort_by: Option<ArxivSort>,
+    max_results: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+enum ArxivSort {
+    #[default]
+    Relevance,
+    SubmittedDate,
+    LastUpdatedDate,
+}
+
+impl ArxivSort {
+    fn as_str(self) -> &'static str {
+        match self {
+            ArxivSort::Relevance => "relevance",
+            ArxivSort::SubmittedDate => "submittedDate",
+            ArxivSort::LastUpdatedDate => "lastUpdatedDate",
+        }
+    }
+
+    /// Relevance results only change as new papers are posted; newest-first
+    /// listings change with every new paper
+    fn cache_ttl(self) -> Duration {
+        match self {
+            ArxivSort::Relevance => Duration::from_secs(3 * 24 * 60 * 60),
+            ArxivSort::SubmittedDate | ArxivSort::LastUpdatedDate => Duration::from_secs(60 * 60),
+        }
+    }
+
+    /// The sort recorded in a completed payload
+    fn of_output(output: &ToolOutput) -> ArxivSort {
+        output
+            .completed_payload
+            .get("sort")
+            .and_then(|sort| serde_json::from_value(sort.clone()).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// A ready-to-send arXiv API search
+#[derive(Debug, Clone, PartialEq)]
+struct ArxivSearch {
+    search_query: String,
+    sort: ArxivSort,
+    max_results: usize,
+}
+
+impl ArxivSearch {
+    /// Build the API query from extracted parameters; `None` if no field is set
+    fn from_parameters(params: &ArxivSearchParameters) -> Option<Self> {
+        let mut clauses: Vec<String> = Vec::new();
+        for (prefix, value) in [
+            ("all", &params.keywords),
+            ("ti", &params.title),
+            ("abs", &params.abstract_text),
+        ] {
+            if let Some(value) = value {
+                clauses.extend(query_words(value).map(|word| format!("{}:{}", prefix, word)));
+            }
+        }
+        if let Some(author) = params.author.as_deref() {
+            // Names are matched as a phrase so "Yann LeCun" doesn't match any Yann
+            let name = query_words(author).collect::<Vec<_>>().join(" ");
+            if !name.is_empty() {
+                clauses.push(format!("au:\"{}\"", name));
+            }
+        }
+        if let Some(category) = params
+            .category
+            .as_deref()
+            .and_then(|c| query_words(c).next())
+        {
+            clauses.push(format!("cat:{}", category));
+        }
+        let from = params
+            .submitted_after
+            .as_deref()
+            .and_then(|date| api_date(date, false));
+        let to = params
+            .submitted_before
+            .as_deref()
+            .and_then(|date| api_date(date, true));
+        if from.is_some() || to.is_some() {
+            clauses.push(format!(
+                "submittedDate:[{} TO {}]",
+                from.unwrap_or_else(|| "199101010000".to_string()),
+                to.unwrap_or_else(|| "999912312359".to_string())
+            ));
+        }
+        if clauses.is_empty() {
+            return None;
+        }
+        Some(ArxivSearch {
+            search_query: clauses.join(" AND "),
+            sort: params.sort_by.unwrap_or_default(),
+            max_results: params
+                .max_results
+                .unwrap_or(DEFAULT_MAX_RESULTS)
+                .clamp(1, MAX_RESULTS_LIMIT),
+        })
+    }
+
+    /// Search every field for the words of a plain query
+    fn keywords(query: &str) -> Self {
+        let words: Vec<String> = query_words(query)
+            .map(|word| format!("all:{}", word))
+            .collect();
+        ArxivSearch {
+            search_query: if words.is_empty() {
+                format!("all:{}", query.trim())
+            } else {
+                words.join(" AND ")
+            },
+            sort: ArxivSort::Relevance,
+            max_results: DEFAULT_MAX_RESULTS,
+        }
+    }
+}
+
+/// Words of a value with the characters that have a meaning in arXiv queries removed
+fn query_words(value: &str) -> impl Iterator<Item = &str> {
+    value
+        .split(|c: char| c.is_whitespace() || "\"():[]".contains(c))
+        .filter(|word| !word.is_empty() && !["AND", "OR", "ANDNOT"].contains(word))
+}
+
+/// `YYYY`, `YYYY-MM` or `YYYY-MM-DD` as an arXiv `YYYYMMDDHHMM` bound
+fn api_date(date: &str, end_of_period: bool) -> Option<String> {
+    let parts: Vec<&str> = date.trim().split(['-', '/', '.']).collect();
+    let numeric =
+        |part: &str, len: usize| part.len() == len && part.chars().all(|c| c.is_ascii_digit());
+    let (year, month, day) = match parts[..] {
+        [y] if numeric(y, 4) => (y, None, None),
+        [y, m] if numeric(y, 4) && numeric(m, 2) => (y, Some(m), None),
+        [y, m, d] if numeric(y, 4) && numeric(m, 2) && numeric(d, 2) => (y, Some(m), Some(d)),
+        _ => return None,
+    };
+    let (default_month, default_day, time) = if end_of_period {
+        ("12", "31", "2359")
+    } else {
+        ("01", "01", "0000")
+    };
+    Some(format!(
+        "{}{}{}{}",
+        year,
+        month.unwrap_or(default_month),
+        // arXiv compares the bounds as numbers, so day 31 also closes shorter months
+        day.unwrap_or(default_day),
+        time
+    ))
+}
+
+/// Whether the query already uses arXiv's field prefixes and should be sent as-is
+fn is_fielded_query(query: &str) -> bool {
+    static FIELD: OnceLock<Regex> = OnceLock::new();
+    FIELD
+        .get_or_init(|| {
+            Regex::new(r"\b(ti|au|abs|cat|all|co|jr|rn|id|submittedDate):\S")
+                .expect("arXiv field regex is valid")
+        })
+        .is_match(query)
+}
+
+/// The paper in the shape shared by the academic search tools
+pub fn paper_summary(paper: &ArXivPaper) -> PaperSummary {
+    PaperSummary {
+        title: paper.title.clone(),
+        summary: paper.abstract_text.clone(),
+        authors: paper.authors.clone(),
+        id: paper.id.clone(),
+        published_date: paper.published.get(..10).map(str::to_string),
+        pdf_url: paper.pdf_url.clone(),
+        doi: Some(paper.doi.clone()).filter(|doi| !doi.is_empty()),
+        // The primary category first, as reference managers expect
+        fields: std::iter::once(&paper.primary_category)
+            .chain(
+                paper
+                    .categories
+                    .iter()
+                    .filter(|c| **c != paper.primary_category),
+            )
+            .filter(|c| !c.is_empty())
+            .cloned()
+            .collect(),
+        ..PaperSummary::default()
+    }
+}
+
+/// Fill in the venue of papers that have been published, from their DOI
+async fn add_publication_details(client: &reqwest::Client, papers: &mut [PaperSummary]) {
+    let lookups = papers.iter().map(|paper| async move {
+        match &paper.doi {
+            Some(doi) => Some(crossref::resolve_doi(client, doi).await),
+            None => None,
+        }
+    });
+    for (paper, lookup) in papers.iter_mut().zip(join_all(lookups).await) {
+        match lookup {
+            Some(Ok(published)) => {
+                paper.venue = published.venue;
+                paper.citation_count = published.citation_count;
+            }
+            Some(Err(e)) => log::warn!("Could not resolve DOI of '{}': {}", paper.title, e),
+            None => {}
+        }
+    }
+}
+
+// Helper function to clean titles for ArXiv search
+fn clean_title(title_str: &str) -> String {
+    title_str
+        .replace('-', " ")
+        .replace('–', " ")
+        .replace('—', " ")
+        .replace('_', " ")
+        .to_string()
+}
+
+// --- ADDED: ArXiv Lookup Function ---
+/// Run a search, requesting further pages until `max_results` papers are found
+async fn perform_arxiv_lookup(
+    client: &reqwest::Client,
+    search: &ArxivSearch,
+) -> Result<Vec<ArXivPaper>, String> {
+    log::info!(
+        "Performing ArXiv lookup: '{}' (sort: {}, max results: {})",
+        search.search_query,
+        search.sort.as_str(),
+        search.max_results
+    );
+
+    let mut papers: Vec<ArXivPaper> = Vec::new();
+    while papers.len() < search.max_results {
+        if !papers.is_empty() {
+            tokio::time::sleep(PAGE_DELAY).await;
+        }
+        let count = PAGE_SIZE.min(search.max_results - papers.len());
+        let page = fetch_arxiv_page(client, search, papers.len(), count).await?;
+        let last_page = page.len() < count;
+        papers.extend(page);
+        if last_page {
+            break;
+        }
+    }
+    log::info!("ArXiv lookup returned {} papers.", papers.len());
+    Ok(papers)
+}
+
+async fn fetch_arxiv_page(
+    client: &reqwest::Client,
+    search: &ArxivSearch,
+    start: usize,
+    count: usize,
+) -> Result<Vec<ArXivPaper>, String> {
+    let start = start.to_string();
+    let count = count.to_string();
+    let mut params: Vec<(&str, &str)> = vec![
+        ("search_query", search.search_query.as_str()),
+        ("start", start.as_str()),
+        ("max_results", count.as_str()),
+        ("sortBy", search.sort.as_str()),
+    ];
+    if search.sort != ArxivSort::Relevance {
+        params.push(("sortOrder", "descending"));
+    }
+    query_arxiv_api(client, &params).await
+}
+
+/// Call the arXiv API and parse the returned feed
+async fn query_arxiv_api(
+    client: &reqwest::Client,
+    params: &[(&str, &str)],
+) -> Result<Vec<ArXivPaper>, String> {
+    let response = client
+        .get(ARXIV_API_URL)
+        .query(params)
+        .send()
+        .await
+        .map_err(|e| {
+            log::error!("Network request to ArXiv API failed: {}", e);
+            format!("ArXiv API network request failed: {}", e)
+        })?;
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response
+            .text()
+            .await
+            .unwrap_or_else(|_| "Could not read error body from ArXiv".to_string());
+        log::error!(
+            "ArXiv API request failed with status {}: {}",
+            status,
+            error_text
+        );
+        return Err(format!(
+            "ArXiv API request failed: {} - {}",
+            status, error_text
+        ));
+    }
+    let xml_text = response.text().await.map_err(|e| {
+        log::error!("Failed to read ArXiv response text: {}", e);
+        format!("Failed to read ArXiv response text: {}", e)
+    })?;
+    log::info!(
+        "Successfully fetched ArXiv XML response. Length: {}",
+        xml_text.len()
+    );
+    log::debug!("ArXiv XML Response:\n{}", xml_text);
+    parse_arxiv_feed(&xml_text)
+}
+
+/// Convert the entries of an Atom feed into papers
+fn parse_arxiv_feed(xml_text: &str) -> Result<Vec<ArXivPaper>, String> {
+    let parsed_feed = from_str::<ArxivFeed>(xml_text).map_err(|e| {
+        log::error!(
+            "Failed to parse ArXiv XML: {}. XML was: {:.500}",
+            e,
+            xml_text
+        );
+        format!("Failed to parse ArXiv XML: {}", e)
+    })?;
+
+    // Iterate through children of <feed> and collect only Entry variants
+    let entries = parsed_feed
+        .children
+        .into_iter()
+        .filter_map(|child| match child {
+            FeedChild::Entry(entry) => Some(entry),
+            FeedChild::Other => None,
+        });
+
+    let mut papers: Vec<ArXivPaper> = Vec::new();
+    for entry in entries {
+        let paper_id = entry.id.unwrap_or_default();
+        let mut title = entry.title.unwrap_or_default();
+        title = clean_title(&title);
+        let abstract_text = entry.summary.unwrap_or_default(); // 'summary' in Atom is the abstract
+        let published = entry.published.unwrap_or_default();
+        let updated = entry.updated.unwrap_or_default();
+        let comments = entry.comment;
+        let doi = entry.doi;
+
+        let authors: Vec<String> = entry
+            .authors
+            .into_iter()
+            .filter_map(|auth| auth.name)
+            .collect();
+
+        let mut pdf_url_option: Option<String> = None;
+        for link in entry.entry_links {
+            // MODIFIED: was entry.links
+            // MODIFIED: Clone link.href for the first check to avoid move issues
+            if let (Some(href), Some(title_attr)) = (link.href.clone(), link.title) {
+                if title_attr == "pdf" {
+                    pdf_url_option = Some(href);
+                    break;
+                }
+            }
+            // Fallback if title attribute is not present but rel="alternate" and type="application/pdf"
+            else if let (Some(href), Some(rel_attr), Some(type_attr)) =
+                (link.href.clone(), link.rel, link.link_type)
+            {
+                if rel_attr == "alternate" && type_attr == "application/pdf" {
+                    pdf_url_option = Some(href);
+                    break;
+                }
+            }
+        }
+        let pdf_url = pdf_url_option.unwrap_or_else(|| {
+            format!(
+                "http://arxiv.org/pdf/{}",
+                paper_id.split('/').last().unwrap_or_default()
+            )
+        });
+
+        let categories: Vec<String> = entry
+            .categories
+            .into_iter()
+            .filter_map(|cat| cat.term)
+            .collect();
+
+        let primary_category = entry.primary_category.and_then(|pc| pc.term);
+
+        // Note: arxiv_tools::Paper has more fields like `journal_ref`, `links` (which is a specific struct in arxiv_tools not just a string list).
+        // We are populating the core ones. `links` in ArXivPaper is more for related links, not just the PDF.
+        // `journal_ref` is not directly available in the standard Atom entry without specific arxiv: namespace parsing for it.
+        papers.push(ArXivPaper {
+            id: paper_id,
+            title: clean_title(&title),
+            authors,
+            abstract_text,
+            categories,
+            comment: comments.map_or_else(Vec::new, |c| vec![c]), // MODIFIED: Convert Option<String> to Vec<String>
+            doi: doi.unwrap_or_default(),
+            journal_ref: String::new(),
+            pdf_url,
+            published,
+            updated,
+            primary_category: primary_category.unwrap_or_default(), // ArxivPaper expects String, not Option<String>
+        });
+    }
+    log::info!("Parsed {} papers from ArXiv XML feed.", papers.len());
+    Ok(papers)
+}
+
+/// The arXiv identifier in an id or an abs/pdf URL, e.g. `2301.07041v2` or `hep-th/9901001`
+pub fn arxiv_id(text: &str) -> Option<String> {
+    static ID: OnceLock<Regex> = OnceLock::new();
+    ID.get_or_init(|| {
+        Regex::new(r"\b(\d{4}\.\d{4,5}(?:v\d+)?|[a-z][a-z\-]*(?:\.[A-Z]{2})?/\d{7}(?:v\d+)?)\b")
+            .expect("arXiv id regex is valid")
+    })
+    .captures(text)
+    .map(|captures| captures[1].to_string())
+}
+
+/// An arXiv id without its `v2`-style version suffix
+pub fn without_version(id: &str) -> &str {
+    match id.rsplit_once('v') {
+        Some((base, version))
+            if !version.is_empty() && version.chars().all(|c| c.is_ascii_digit()) =>
+        {
+            base
+        }
+        _ => id,
+    }
+}
+
+/// Look up one paper by its arXiv id or URL, or else by its title
+pub async fn find_paper(
+    client: &reqwest::Client,
+    reference: &str,
+) -> Result<Option<ArXivPaper>, String> {
+    let papers = match arxiv_id(reference) {
+        Some(id) => query_arxiv_api(client, &[("id_list", id.as_str())]).await?,
+        None => {
+            let params = ArxivSearchParameters {
+                title: Some(reference.to_string()),
+                max_results: Some(1),
+                ..ArxivSearchParameters::default()
+            };
+            match ArxivSearch::from_parameters(&params) {
+                Some(search) => perform_arxiv_lookup(client, &search).await?,
+                None => Vec::new(),
+            }
+        }
+    };
+    Ok(papers.into_iter().next())
+}
+
+/// Key the results of a query are cached under: fielded queries are sent as they
+/// are, plain ones are resolved with the user's message
+fn search_cache_key(query: &str, user_query: &str) -> String {
+    if is_fielded_query(query) {
+        query.trim().to_string()
+    } else {
+        format!("{} [{}]", query.trim(), user_query.trim())
+    }
+}
+
+/// Turn the request into an arXiv search
+///
+/// Queries that already use field prefixes (`ti:`, `au:`, ...) are sent as-is;
+/// otherwise a helper LLM fills [`ArxivSearchParameters`] from the query and
+/// the user's message, falling back to a keyword search.
+async fn resolve_arxiv_search(ctx: &ToolContext<'_>, query: &str) -> ArxivSearch {
+    if is_fielded_query(query) {
+        return ArxivSearch {
+            search_query: query.trim().to_string(),
+            ..ArxivSearch::keywords(query)
+        };
+    }
+    match extract_arxiv_search_parameters(ctx, query).await {
+        Ok(params) => match ArxivSearch::from_parameters(&params) {
+            Some(search) => search,
+            None => {
+                log::warn!(
+                    "ArXiv parameter extractor returned no fields for '{}'. Falling back to keywords.",
+                    query
+                );
+                ArxivSearch::keywords(query)
+            }
+        },
+        Err(e) => {
+            log::error!(
+                "Error extracting ArXiv search parameters for '{}': {}. Falling back to keywords.",
+                query,
+                e
+            );
+            ArxivSearch::keywords(query)
+        }
+    }
+}
+
+async fn extract_arxiv_search_parameters(
+    ctx: &ToolContext<'_>,
+    query: &str,
+) -> Result<ArxivSearchParameters, String> {
+    let today = time::OffsetDateTime::now_utc().date();
+    let prompt = format!(
+        "You turn research requests into arXiv search parameters.\n\
+        Return ONLY a JSON object with these optional fields (omit or use null for anything not asked for):\n\
+        - \"keywords\": topic words matched anywhere in the paper\n\
+        - \"title\": words that must appear in the title\n\
+        - \"author\": one author's name\n\
+        - \"abstract_text\": words that must appear in the abstract\n\
+        - \"category\": an arXiv category such as \"cs.LG\", \"cs.CL\", \"quant-ph\", \"astro-ph.GA\"\n\
+        - \"submitted_after\" / \"submitted_before\": dates as YYYY, YYYY-MM or YYYY-MM-DD (today is {})\n\
+        - \"sort_by\": \"relevance\", \"submittedDate\" (newest first, for latest/recent papers) or \"lastUpdatedDate\"\n\
+        - \"max_results\": number of papers, only if a number is asked for (at most {})\n\
+        Prefer \"keywords\" for the topic; use the other fields only when the request names them.\n\n\
+        Examples:\n\
+        - \"latest papers on diffusion models for video\" → {{\"keywords\": \"diffusion models video\", \"sort_by\": \"submittedDate\"}}\n\
+        - \"10 papers by Yoshua Bengio on attention\" → {{\"author\": \"Yoshua Bengio\", \"keywords\": \"attention\", \"max_results\": 10}}\n\
+        - \"quantum error correction surveys from 2023\" → {{\"keywords\": \"quantum error correction\", \"title\": \"survey\", \"category\": \"quant-ph\", \"submitted_after\": \"2023\", \"submitted_before\": \"2023\"}}\n\n\
+        User's message: '{}'\n\
+        Search request: '{}'\n\
+        JSON:",
+        today, MAX_RESULTS_LIMIT, ctx.user_query, query
+    );
+    let messages = vec![ChatMessage {
+        role: "user".to_string(),
+        content: prompt,
+        image_base64_data: None,
+        image_mime_type: None,
+        image_file_api_uri: None,
+        document_ids: None,
+    }];
+
+    let response_text = call_gemini_api_non_streaming(
+        ctx.client,
+        messages,
+        ctx.gemini_api_key,
+        ctx.model_name.to_string(),
+    )
+    .await?;
+    let cleaned_response = response_text
+        .trim()
+        .trim_start_matches("```json")
+        .trim_start_matches("```")
+        .trim_end_matches("```")
+        .trim();
+    let params = serde_json::from_str::<ArxivSearchParameters>(cleaned_response).map_err(|e| {
+        format!(
+            "Failed to parse ArXiv search parameters from '{}': {}",
+            cleaned_response, e
+        )
+    })?;
+    log::info!("Extracted ArXiv search parameters: {:?}", params);
+    Ok(params)
+}
+
+pub struct ArxivTool;
+
+#[async_trait]
+impl Tool for ArxivTool {
+    fn tool_type(&self) -> ToolType {
+        ToolType::ArxivLookup
+    }
+
+    fn description(&self) -> &'static str {
+        "Academic papers and research from arXiv repository"
+    }
+
+    fn follow_up_hint(&self) -> &'static str {
+        "Academic papers"
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "Research topic, keywords, or specific paper search; may name authors, categories, dates, a number of papers or ask for the latest ones"
+                }
+            },
+            "required": ["query"]
+        })
+    }
+
+    fn guidance(&self) -> ToolGuidance {
+        ToolGuidance {
+            name: "ArXiv Research".to_string(),
+            description: "Search academic papers and research preprints on ArXiv".to_string(),
+            usage_pattern:
+                "Use for cutting-edge research, academic references, and technical information"
+                    .to_string(),
+            parameters: vec![
+                ToolParameter {
+                    name: "query".to_string(),
+                    param_type: "string".to_string(),
+                    description: "Research topic, keywords, or specific paper search. Fielded arXiv syntax (ti:, au:, abs:, cat:, submittedDate:[...]) is sent as-is".to_string(),
+                    required: true,
+                    default_value: None,
+                },
+                ToolParameter {
+                    name: "max_results".to_string(),
+                    param_type: "number".to_string(),
+                    description: "Maximum number of papers to return (1-50), stated in the query".to_string(),
+                    required: false,
+                    default_value: Some("5".to_string()),
+                },
+            ],
+            examples: vec![ToolExample {
+                scenario: "User needs latest research on machine learning".to_string(),
+                reasoning: "ArXiv contains the most recent academic work".to_string(),
+                sequence: vec![
+                    "Search for relevant ML papers with appropriate keywords".to_string(),
+                    "Review abstracts for relevance and recency".to_string(),
+                    "Summarize key findings and trends".to_string(),
+                ],
+            }],
+            reasoning_hints: vec![
+                "Best source for cutting-edge research".to_string(),
+                "Use specific technical terms for better results".to_string(),
+                "Consider paper recency for rapidly evolving fields; ask for the latest papers to sort by submission date".to_string(),
+                "Good complement to Wikipedia for technical depth".to_string(),
+                "Only abstracts are returned; use ARXIV_PAPER to read a paper's full text".to_string(),
+            ],
+        }
+    }
+
+    fn capability_key(&self) -> &'static str {
+        "arxiv_research"
+    }
+
+    fn capabilities(&self) -> Value {
+        json!({
+            "description": "Academic paper search and research",
+            "best_for": ["cutting-edge research", "scientific papers", "technical information"],
+            "strengths": ["latest research", "peer-reviewed content", "technical depth"],
+            "limitations": ["highly technical", "may be too specialized for general audiences"]
+        })
+    }
+
+    fn started_event(&self) -> &'static str {
+        "ARXIV_LOOKUP_STARTED"
+    }
+
+    fn completed_event(&self) -> &'static str {
+        "ARXIV_LOOKUP_COMPLETED"
+    }
+
+    fn started_payload(&self, query: &str) -> Value {
+        scholarly::started_payload(query)
+    }
+
+    fn failed_payload(&self, query: &str, error: &str) -> Value {
+        scholarly::failed_payload(query, error)
+    }
+
+    /// The longest any search is kept; see `output_cache_ttl`
+    fn cache_ttl(&self) -> Option<Duration> {
+        Some(ArxivSort::Relevance.cache_ttl())
+    }
+
+    /// Plain queries are turned into a search using the user's message as well,
+    /// so both are in the key; a hit then also saves the extractor call
+    fn cache_key(&self, ctx: &ToolContext<'_>, query: &str) -> Option<String> {
+        Some(search_cache_key(query, ctx.user_query))
+    }
+
+    fn output_cache_ttl(&self, output: &ToolOutput) -> Option<Duration> {
+        Some(ArxivSort::of_output(output).cache_ttl())
+    }
+
+    async fn execute(&self, ctx: &ToolContext<'_>, query: &str) -> Result<ToolOutput, String> {
+        let search = resolve_arxiv_search(ctx, query).await;
+        let papers = perform_arxiv_lookup(ctx.client, &search).await?;
+        let mut papers: Vec<PaperSummary> = papers.iter().map(paper_summary).collect();
+        add_publication_details(ctx.client, &mut papers).await;
+        let heading = format!(
+            "ArXiv Research for '{}' ({} papers, search: {}, sorted by {})",
+            query,
+            papers.len(),
+            search.search_query,
+            search.sort.as_str()
+        );
+        let mut output = scholarly::papers_output(query, &heading, papers);
+        if let Some(fields) = output.completed_payload.as_object_mut() {
+            fields.insert("sort".to_string(), Value::from(search.sort.as_str()));
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_from_parameters() {
+        let params = ArxivSearchParameters {
+            keywords: Some("graph neural networks".to_string()),
+            author: Some("Yann LeCun".to_string()),
+            category: Some("cs.LG".to_string()),
+            submitted_after: Some("2023".to_string()),
+            submitted_before: Some("2024-06".to_string()),
+            sort_by: Some(ArxivSort::SubmittedDate),
+            max_results: Some(200),
+            ..ArxivSearchParameters::default()
+        };
+        let search = ArxivSearch::from_parameters(&params).unwrap();
+        assert_eq!(
+            search.search_query,
+            "all:graph AND all:neural AND all:networks AND au:\"Yann LeCun\" AND cat:cs.LG \
+             AND submittedDate:[202301010000 TO 202406312359]"
+        );
+        assert_eq!(search.sort, ArxivSort::SubmittedDate);
+        assert_eq!(search.max_results, MAX_RESULTS_LIMIT);
+
+        assert_eq!(
+            ArxivSearch::from_parameters(&ArxivSearchParameters::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_extracted_parameters_deserialize() {
+        let params: ArxivSearchParameters = serde_json::from_str(
+            r#"{"title": "survey: (transformers)", "sort_by": "lastUpdatedDate", "max_results": 10, "author": null}"#,
+        )
+        .unwrap();
+        let search = ArxivSearch::from_parameters(&params).unwrap();
+        assert_eq!(search.search_query, "ti:survey AND ti:transformers");
+        assert_eq!(search.sort, ArxivSort::LastUpdatedDate);
+        assert_eq!(search.max_results, 10);
+    }
+
+    #[test]
+    fn test_api_date() {
+        assert_eq!(
+            api_date("2021-03-04", false).as_deref(),
+            Some("202103040000")
+        );
+        assert_eq!(api_date("2021", true).as_deref(), Some("202112312359"));
+        assert_eq!(api_date("last year", false), None);
+        assert_eq!(api_date("21-03", false), None);
+    }
+
+    #[test]
+    fn test_fielded_queries_and_keywords() {
+        assert!(is_fielded_query("au:Hinton AND ti:capsule"));
+        assert!(is_fielded_query("cat:quant-ph"));
+        assert!(!is_fielded_query("Attention: is it all you need?"));
+        assert_eq!(
+            ArxivSearch::keywords("protein folding").search_query,
+            "all:protein AND all:folding"
+        );
+    }
+
+    #[test]
+    fn test_cache_key_covers_the_search_inputs() {
+        let latest = "latest papers on protein folding";
+        assert_ne!(
+            search_cache_key(latest, "what's new in protein folding?"),
+            search_cache_key(latest, "protein folding papers from 2019")
+        );
+        assert_eq!(
+            search_cache_key("au:Hinton AND ti:capsule", "anything"),
+            search_cache_key("au:Hinton AND ti:capsule ", "something else")
+        );
+
+        let mut output = scholarly::papers_output("q", "heading", Vec::new());
+        assert_eq!(ArxivSort::of_output(&output), ArxivSort::Relevance);
+        output.completed_payload["sort"] = Value::from(ArxivSort::SubmittedDate.as_str());
+        assert_eq!(ArxivSort::of_output(&output), ArxivSort::SubmittedDate);
+        assert!(ArxivSort::SubmittedDate.cache_ttl() < ArxivSort::Relevance.cache_ttl());
+    }
+
+    #[test]
+    fn test_arxiv_id() {
+        assert_eq!(
+            arxiv_id("https://arxiv.org/abs/2301.07041v2").as_deref(),
+            Some("2301.07041v2")
+        );
+        assert_eq!(
+            arxiv_id("http://arxiv.org/pdf/1706.03762").as_deref(),
+            Some("1706.03762")
+        );
+        assert_eq!(
+            arxiv_id("arxiv.org/abs/hep-th/9901001").as_deref(),
+            Some("hep-th/9901001")
+        );
+        assert_eq!(arxiv_id("Attention Is All You Need"), None);
+        assert_eq!(without_version("2301.07041v2"), "2301.07041");
+        assert_eq!(without_version("hep-th/9901001"), "hep-th/9901001");
+    }
+
+    #[test]
+    fn test_parse_arxiv_feed() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>ArXiv Query</title>
+  <entry>
+    <id>http://arxiv.org/abs/1706.03762v7</id>
+    <updated>2023-08-02T00:41:18Z</updated>
+    <published>2017-06-12T17:57:34Z</published>
+    <title>Attention Is All You Need</title>
+    <summary>The dominant sequence transduction models...</summary>
+    <author><name>Ashish Vaswani</name></author>
+    <author><name>Noam Shazeer</name></author>
+    <link href="http://arxiv.org/abs/1706.03762v7" rel="alternate" type="text/html"/>
+    <link title="pdf" href="http://arxiv.org/pdf/1706.03762v7" rel="related" type="application/pdf"/>
+    <category term="cs.CL"/>
+    <category term="cs.LG"/>
+  </entry>
+</feed>"#;
+        let papers = parse_arxiv_feed(xml).unwrap();
+        assert_eq!(papers.len(), 1);
+        assert_eq!(papers[0].title, "Attention Is All You Need");
+        assert_eq!(papers[0].authors, vec!["Ashish Vaswani", "Noam Shazeer"]);
+        assert_eq!(papers[0].pdf_url, "http://arxiv.org/pdf/1706.03762v7");
+        assert_eq!(papers[0].categories, vec!["cs.CL", "cs.LG"]);
+
+        let summary = paper_summary(&papers[0]);
+        assert_eq!(summary.published_date.as_deref(), Some("2017-06-12"));
+        assert_eq!(summary.fields, vec!["cs.CL", "cs.LG"]);
+        assert_eq!(summary.doi, None);
+    }
+}
@@ -0,0 +1,851 @@
+//! arXiv full-text reading tool
+//!
+//! Finds a paper by arXiv id, URL or title, downloads its PDF from `pdf_url`
+//! and splits the text into sections by their headings. When the PDF has no
+//! usable text layer, the LaTeX source tarball is read instead. Only the
+//! passages that best match the user's question are given to the model, each
+//! cited with its section and page.
+
+use super::arxiv::find_paper;
+use super::citations::ToolSource;
+use super::fetch_url::{extract_pdf_pages, read_limited, USER_AGENT};
+use super::{to_payload, Tool, ToolContext, ToolOutput};
+use crate::documents::{select_chunks, split_text};
+use crate::mcp_simple::{ToolExample, ToolGuidance, ToolParameter};
+use crate::ToolType;
+use arxiv_tools::Paper as ArXivPaper;
+use async_trait::async_trait;
+use flate2::read::GzDecoder;
+use regex::Regex;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::io::{Cursor, Read};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Largest PDF or source archive downloaded
+const MAX_PAPER_BYTES: usize = 50 * 1024 * 1024;
+/// Target size of one passage, in characters
+const PASSAGE_CHARS: usize = 1500;
+/// Characters of passages given to the model per question
+const MAX_CONTEXT_CHARS: usize = 16000;
+/// PDFs with less text than this per page are treated as scanned
+const MIN_CHARS_PER_PAGE: usize = 300;
+/// Extracted papers kept in memory for follow-up questions
+const MAX_CACHED_PAPERS: usize = 8;
+/// `\input` files are followed this many levels deep
+const MAX_INPUT_DEPTH: usize = 3;
+
+/// Headings recognized without a section number
+const SECTION_NAMES: &[&str] = &[
+    "abstract",
+    "introduction",
+    "related work",
+    "background",
+    "preliminaries",
+    "method",
+    "methods",
+    "methodology",
+    "approach",
+    "experiments",
+    "experimental setup",
+    "evaluation",
+    "results",
+    "discussion",
+    "limitations",
+    "conclusion",
+    "conclusions",
+    "future work",
+    "acknowledgments",
+    "acknowledgements",
+    "references",
+    "bibliography",
+    "appendix",
+];
+
+/// Where the text of a paper came from
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum TextSource {
+    Pdf,
+    Latex,
+}
+
+/// A run of text under one heading; PDF sections are also split at page breaks
+#[derive(Debug, Clone, PartialEq)]
+struct PaperSection {
+    /// `None` for the title block before the first heading
+    heading: Option<String>,
+    /// 1-based PDF page; `None` for LaTeX source
+    page: Option<usize>,
+    text: String,
+}
+
+#[derive(Debug)]
+struct PaperText {
+    source: TextSource,
+    sections: Vec<PaperSection>,
+}
+
+impl PaperText {
+    /// Headings in reading order, without repeats from page breaks
+    fn headings(&self) -> Vec<String> {
+        let mut headings: Vec<String> = Vec::new();
+        for heading in self.sections.iter().filter_map(|s| s.heading.as_ref()) {
+            if headings.last() != Some(heading) {
+                headings.push(heading.clone());
+            }
+        }
+        headings
+    }
+}
+
+// --- ArXiv Paper Event Payloads ---
+#[derive(Serialize, Clone, Debug)]
+struct ArxivPaperStartedPayload {
+    query: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+struct ArxivPaperCompletedPayload {
+    query: String,
+    success: bool,
+    title: Option<String>,
+    paper_id: Option<String>,
+    pdf_url: Option<String>,
+    text_source: Option<TextSource>,
+    sections: Option<Vec<String>>,
+    passages: Option<usize>,
+    error: Option<String>,
+}
+
+// --- Section Extraction ---
+
+/// The heading on a line of PDF text, if the line is one
+///
+/// Accepts numbered headings ("2 Method", "3.1 Setup", "IV. Results") and
+/// the usual unnumbered ones ("Abstract", "References").
+fn section_heading(line: &str) -> Option<String> {
+    static NUMBERED: OnceLock<Regex> = OnceLock::new();
+    let numbered = NUMBERED.get_or_init(|| {
+        Regex::new(r"^(?:\d{1,2}(?:\.\d{1,2}){0,2}\.?|[IVX]{1,4}\.)\s+(\p{Lu}[^.,;:]*)$")
+            .expect("section heading regex is valid")
+    });
+
+    let line = line.trim();
+    if line.is_empty() || line.chars().count() > 80 {
+        return None;
+    }
+    let name = line.trim_end_matches(':').to_lowercase();
+    if SECTION_NAMES.contains(&name.as_str()) {
+        return Some(line.trim_end_matches(':').to_string());
+    }
+    let title = numbered.captures(line)?.get(1)?.as_str();
+    // Table rows and wrapped sentences have numbers or many words
+    let words = title.split_whitespace().count();
+    if words > 12 || title.chars().any(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some(line.to_string())
+}
+
+/// The heading without its number, lowercased
+fn heading_name(heading: &str) -> String {
+    let heading = heading.trim();
+    let is_number = |number: &str| {
+        number.chars().all(|c| c.is_ascii_digit() || c == '.')
+            || (number.ends_with('.')
+                && number
+                    .trim_end_matches('.')
+                    .chars()
+                    .all(|c| "IVX".contains(c)))
+    };
+    match heading.split_once(' ') {
+        Some((number, name)) if is_number(number) => name.trim().to_lowercase(),
+        _ => heading.to_lowercase(),
+    }
+}
+
+fn is_references(heading: &str) -> bool {
+    matches!(
+        heading_name(heading).as_str(),
+        "references" | "bibliography"
+    )
+}
+
+fn is_appendix(heading: &str) -> bool {
+    let name = heading_name(heading);
+    name.starts_with("appendix") || name.starts_with("supplementary")
+}
+
+/// Split the text of each PDF page into sections, leaving out the references
+fn pdf_sections(pages: &[String]) -> Vec<PaperSection> {
+    let mut sections: Vec<PaperSection> = Vec::new();
+    let mut heading: Option<String> = None;
+    let mut in_references = false;
+    let mut push = |section: PaperSection, in_references: bool| {
+        if !in_references && !section.text.is_empty() {
+            sections.push(section);
+        }
+    };
+
+    for (index, page) in pages.iter().enumerate() {
+        let mut current = PaperSection {
+            heading: heading.clone(),
+            page: Some(index + 1),
+            text: String::new(),
+        };
+        for line in page.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            if let Some(found) = section_heading(line) {
+                let next = PaperSection {
+                    heading: Some(found.clone()),
+                    page: Some(index + 1),
+                    text: String::new(),
+                };
+                push(std::mem::replace(&mut current, next), in_references);
+                // Appendices may follow the reference list
+                in_references = is_references(&found) || (in_references && !is_appendix(&found));
+                heading = Some(found);
+                continue;
+            }
+            if !current.text.is_empty() {
+                current.text.push('\n');
+            }
+            current.text.push_str(line);
+        }
+        push(current, in_references);
+    }
+    sections
+}
+
+/// The main `.tex` file of an arXiv source download, with `\input` files inlined
+///
+/// The download is a gzipped tarball, or a single gzipped `.tex` file for
+/// one-file submissions.
+fn read_latex_source(bytes: &[u8]) -> Result<String, String> {
+    let mut data: Vec<u8> = Vec::new();
+    if GzDecoder::new(bytes).read_to_end(&mut data).is_err() {
+        data = bytes.to_vec();
+    }
+    if data.starts_with(b"%PDF") {
+        return Err("The paper has no LaTeX source".to_string());
+    }
+
+    let is_tar = data.get(257..262) == Some(b"ustar".as_slice());
+    if !is_tar {
+        let text = String::from_utf8_lossy(&data).to_string();
+        if !text.contains("\\begin{document}") {
+            return Err("The source download is not a LaTeX file".to_string());
+        }
+        return Ok(text);
+    }
+
+    let mut files: HashMap<String, String> = HashMap::new();
+    let mut archive = tar::Archive::new(Cursor::new(data));
+    let entries = archive
+        .entries()
+        .map_err(|e| format!("Failed to read source archive: {}", e))?;
+    for entry in entries {
+        let mut entry = entry.map_err(|e| format!("Failed to read source archive: {}", e))?;
+        let path = entry
+            .path()
+            .map(|p| p.to_string_lossy().trim_start_matches("./").to_string())
+            .unwrap_or_default();
+        if !path.ends_with(".tex") {
+            continue;
+        }
+        let mut content = Vec::new();
+        if entry.read_to_end(&mut content).is_ok() {
+            files.insert(path, String::from_utf8_lossy(&content).to_string());
+        }
+    }
+
+    let main = files
+        .iter()
+        .filter(|(_, text)| text.contains("\\begin{document}"))
+        .min_by_key(|(path, _)| (path.matches('/').count(), path.len()))
+        .map(|(_, text)| text.clone())
+        .ok_or_else(|| "No main .tex file in the source archive".to_string())?;
+    Ok(inline_inputs(&main, &files, 0))
+}
+
+/// Replace `\input{file}` and `\include{file}` with the file's content
+fn inline_inputs(text: &str, files: &HashMap<String, String>, depth: usize) -> String {
+    static INPUT: OnceLock<Regex> = OnceLock::new();
+    let input = INPUT.get_or_init(|| {
+        Regex::new(r"\\(?:input|include)\{([^}]+)\}").expect("input regex is valid")
+    });
+    if depth >= MAX_INPUT_DEPTH {
+        return text.to_string();
+    }
+    input
+        .replace_all(text, |captures: &regex::Captures| {
+            let name = captures[1].trim().trim_start_matches("./");
+            let file = files
+                .get(name)
+                .or_else(|| files.get(&format!("{}.tex", name)));
+            match file {
+                Some(content) => inline_inputs(content, files, depth + 1),
+                None => String::new(),
+            }
+        })
+        .to_string()
+}
+
+/// Split LaTeX source into sections of plain text, leaving out the bibliography
+fn latex_sections(source: &str) -> Vec<PaperSection> {
+    static SECTION: OnceLock<Regex> = OnceLock::new();
+    static ABSTRACT: OnceLock<Regex> = OnceLock::new();
+    let section = SECTION.get_or_init(|| {
+        Regex::new(r"\\(?:sub)*section\*?\s*\{([^{}]*)\}").expect("section regex is valid")
+    });
+    let abstract_env = ABSTRACT.get_or_init(|| {
+        Regex::new(r"(?s)\\begin\{abstract\}(.*?)\\end\{abstract\}")
+            .expect("abstract regex is valid")
+    });
+
+    let source = strip_latex_comments(source);
+    let body = match source.split_once("\\begin{document}") {
+        Some((_, body)) => body,
+        None => source.as_str(),
+    };
+    let body = body.split("\\end{document}").next().unwrap_or_default();
+    let body = [
+        "\\begin{thebibliography}",
+        "\\bibliography{",
+        "\\printbibliography",
+    ]
+    .iter()
+    .filter_map(|marker| body.find(marker))
+    .min()
+    .map_or(body, |end| &body[..end]);
+    let body = abstract_env
+        .replace(body, "\\section*{Abstract}$1")
+        .to_string();
+
+    let mut sections: Vec<PaperSection> = Vec::new();
+    let mut heading: Option<String> = None;
+    let mut last_end = 0;
+    for captures in section.captures_iter(&body) {
+        let whole = captures.get(0).expect("match has a whole group");
+        push_latex_section(
+            &mut sections,
+            heading.take(),
+            &body[last_end..whole.start()],
+        );
+        heading = Some(latex_to_text(&captures[1]));
+        last_end = whole.end();
+    }
+    push_latex_section(&mut sections, heading, &body[last_end..]);
+    sections
+}
+
+fn push_latex_section(sections: &mut Vec<PaperSection>, heading: Option<String>, body: &str) {
+    let text = latex_to_text(body);
+    if !text.is_empty() {
+        sections.push(PaperSection {
+            heading,
+            page: None,
+            text,
+        });
+    }
+}
+
+/// Drop `%` comments, keeping escaped `\%`
+fn strip_latex_comments(source: &str) -> String {
+    source
+        .lines()
+        .map(|line| {
+            let mut previous = ' ';
+            for (i, c) in line.char_indices() {
+                if c == '%' && previous != '\\' {
+                    return &line[..i];
+                }
+                previous = c;
+            }
+            line
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Rough plain text of a LaTeX fragment: markup, figures and references are removed
+fn latex_to_text(latex: &str) -> String {
+    static FLOATS: OnceLock<Regex> = OnceLock::new();
+    static REFERENCES: OnceLock<Regex> = OnceLock::new();
+    static STYLED: OnceLock<Regex> = OnceLock::new();
+    static COMMANDS: OnceLock<Regex> = OnceLock::new();
+    let floats = FLOATS.get_or_init(|| {
+        Regex::new(r"(?s)\\begin\{(?:figure|table|algorithm)\*?\}.*?\\end\{(?:figure|table|algorithm)\*?\}")
+            .expect("float regex is valid")
+    });
+    let references = REFERENCES.get_or_init(|| {
+        Regex::new(
+            r"~?\\(?:cite[a-z]*|ref|eqref|autoref|cref|Cref|label)\*?(?:\[[^\]]*\])*\{[^}]*\}",
+        )
+        .expect("reference regex is valid")
+    });
+    let styled = STYLED.get_or_init(|| {
+        Regex::new(
+            r"\\(?:emph|textbf|textit|texttt|textsc|underline|mathrm|mathbf|text|url)\{([^{}]*)\}",
+        )
+        .expect("styled text regex is valid")
+    });
+    let commands = COMMANDS.get_or_init(|| {
+        Regex::new(r"\\(?:begin|end)\{[^}]*\}|\\[a-zA-Z]+\*?(?:\[[^\]]*\])?")
+            .expect("command regex is valid")
+    });
+
+    let mut text = floats.replace_all(latex, "").to_string();
+    text = references.replace_all(&text, "").to_string();
+    // Nested styles are unwrapped from the inside out
+    for _ in 0..3 {
+        text = styled.replace_all(&text, "$1").to_string();
+    }
+    text = text
+        .replace("\\item", "- ")
+        .replace("\\\\", "\n")
+        .replace("\\%", "%");
+    text = commands.replace_all(&text, "").to_string();
+    text = text.replace(['{', '}'], "").replace('~', " ");
+
+    let mut lines: Vec<String> = Vec::new();
+    for line in text
+        .lines()
+        .map(|l| l.split_whitespace().collect::<Vec<_>>().join(" "))
+    {
+        // Blank lines separate paragraphs; keep at most one
+        if !line.is_empty() || lines.last().is_some_and(|l| !l.is_empty()) {
+            lines.push(line);
+        }
+    }
+    lines.join("\n").trim().to_string()
+}
+
+// --- Retrieval ---
+
+/// A passage of a section, the unit given to the model and cited
+#[derive(Debug, Clone, PartialEq)]
+struct Passage {
+    heading: Option<String>,
+    page: Option<usize>,
+    text: String,
+}
+
+impl Passage {
+    /// Where the passage is in the paper, e.g. "2 Method, page 3"
+    fn location(&self) -> String {
+        match (&self.heading, self.page) {
+            (Some(heading), Some(page)) => format!("{}, page {}", heading, page),
+            (Some(heading), None) => heading.clone(),
+            (None, Some(page)) => format!("page {}", page),
+            (None, None) => "Front matter".to_string(),
+        }
+    }
+
+    fn source(&self, paper: &ArXivPaper) -> ToolSource {
+        let url = match self.page {
+            Some(page) => format!("{}#page={}", paper.pdf_url, page),
+            None => paper.id.clone(),
+        };
+        ToolSource::new(
+            &format!("{}, {}", paper.title, self.location()),
+            &url,
+            Some(&self.text),
+        )
+    }
+}
+
+/// The passages that best match the question, in reading order
+fn select_passages(sections: &[PaperSection], question: &str) -> Vec<Passage> {
+    let passages: Vec<Passage> = sections
+        .iter()
+        .flat_map(|section| {
+            split_text(&section.text, PASSAGE_CHARS)
+                .into_iter()
+                .map(|text| Passage {
+                    heading: section.heading.clone(),
+                    page: section.page,
+                    text,
+                })
+        })
+        .collect();
+    // Headings count towards relevance, so "what did they conclude" finds the conclusion
+    let texts: Vec<String> = passages
+        .iter()
+        .map(|p| format!("{}\n{}", p.heading.as_deref().unwrap_or_default(), p.text))
+        .collect();
+    let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+    select_chunks(&texts, question, MAX_CONTEXT_CHARS)
+        .into_iter()
+        .map(|i| passages[i].clone())
+        .collect()
+}
+
+// --- Download ---
+
+/// Extracted papers by PDF URL, oldest first
+///
+/// The tool's output depends on the question, so it is not cached; the
+/// extracted text is kept here so follow-up questions skip the download.
+fn extracted_papers() -> &'static Mutex<VecDeque<(String, Arc<PaperText>)>> {
+    static PAPERS: OnceLock<Mutex<VecDeque<(String, Arc<PaperText>)>>> = OnceLock::new();
+    PAPERS.get_or_init(|| Mutex::new(VecDeque::new()))
+}
+
+async fn download(client: &reqwest::Client, url: &str) -> Result<Vec<u8>, String> {
+    let response = client
+        .get(url)
+        .header("User-Agent", USER_AGENT)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to download {}: {}", url, e))?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("Failed to download {}: HTTP {}", url, status));
+    }
+    let (body, truncated) = read_limited(response, MAX_PAPER_BYTES).await?;
+    if truncated {
+        return Err(format!(
+            "{} is larger than the {} byte limit",
+            url, MAX_PAPER_BYTES
+        ));
+    }
+    Ok(body)
+}
+
+/// The paper's text from its PDF, or from the LaTeX source if the PDF has too little text
+async fn read_paper(client: &reqwest::Client, paper: &ArXivPaper) -> Result<PaperText, String> {
+    let pdf_result = match download(client, &paper.pdf_url).await {
+        Ok(bytes) => extract_pdf_pages(bytes).await,
+        Err(e) => Err(e),
+    };
+    match pdf_result {
+        Ok(pages) => {
+            let characters: usize = pages.iter().map(|p| p.trim().chars().count()).sum();
+            if characters >= MIN_CHARS_PER_PAGE * pages.len().max(1) {
+                log::info!(
+                    "Extracted {} pages ({} characters) from {}",
+                    pages.len(),
+                    characters,
+                    paper.pdf_url
+                );
+                return Ok(PaperText {
+                    source: TextSource::Pdf,
+                    sections: pdf_sections(&pages),
+                });
+            }
+            log::info!(
+                "PDF {} has little text ({} characters in {} pages), reading the LaTeX source",
+                paper.pdf_url,
+                characters,
+                pages.len()
+            );
+        }
+        Err(e) => log::warn!(
+            "Failed to read PDF {}: {}. Reading the LaTeX source.",
+            paper.pdf_url,
+            e
+        ),
+    }
+
+    let id = paper.id.rsplit("/abs/").next().unwrap_or(&paper.id);
+    let bytes = download(client, &format!("https://arxiv.org/e-print/{}", id)).await?;
+    let source = tokio::task::spawn_blocking(move || read_latex_source(&bytes))
+        .await
+        .map_err(|e| format!("LaTeX source extraction failed: {}", e))??;
+    Ok(PaperText {
+        source: TextSource::Latex,
+        sections: latex_sections(&source),
+    })
+}
+
+/// The paper's extracted text, downloading it unless it was read recently
+async fn paper_text(
+    client: &reqwest::Client,
+    paper: &ArXivPaper,
+) -> Result<Arc<PaperText>, String> {
+    if let Ok(papers) = extracted_papers().lock() {
+        if let Some((_, text)) = papers.iter().find(|(url, _)| *url == paper.pdf_url) {
+            return Ok(text.clone());
+        }
+    }
+    let text = Arc::new(read_paper(client, paper).await?);
+    if text.sections.is_empty() {
+        return Err(format!("No text could be extracted from '{}'", paper.title));
+    }
+    if let Ok(mut papers) = extracted_papers().lock() {
+        if papers.len() >= MAX_CACHED_PAPERS {
+            papers.pop_front();
+        }
+        papers.push_back((paper.pdf_url.clone(), text.clone()));
+    }
+    Ok(text)
+}
+
+pub struct ArxivPaperTool;
+
+#[async_trait]
+impl Tool for ArxivPaperTool {
+    fn tool_type(&self) -> ToolType {
+        ToolType::ArxivPaper
+    }
+
+    fn description(&self) -> &'static str {
+        "Read the full text of one arXiv paper (query: its arXiv id, URL or exact title) to answer questions about its body, with section and page citations"
+    }
+
+    fn follow_up_hint(&self) -> &'static str {
+        "Full text of an arXiv paper found earlier (use its arXiv id)"
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "arXiv id (e.g. 1706.03762), abs/pdf URL or exact paper title"
+                }
+            },
+            "required": ["query"]
+        })
+    }
+
+    fn guidance(&self) -> ToolGuidance {
+        ToolGuidance {
+            name: "ArXiv Paper Reader".to_string(),
+            description: "Download an arXiv paper and read the sections relevant to the user's question".to_string(),
+            usage_pattern: "Use when the answer is in a paper's body (methods, results, numbers) rather than its abstract".to_string(),
+            parameters: vec![ToolParameter {
+                name: "query".to_string(),
+                param_type: "string".to_string(),
+                description: "arXiv id (e.g. 1706.03762), abs/pdf URL or exact paper title".to_string(),
+                required: true,
+                default_value: None,
+            }],
+            examples: vec![ToolExample {
+                scenario: "User asks which learning rate a paper used".to_string(),
+                reasoning: "Training details are in the paper's body, not its abstract".to_string(),
+                sequence: vec![
+                    "ARXIV_LOOKUP to find the paper if its id is unknown".to_string(),
+                    "ARXIV_PAPER with the paper's arXiv id".to_string(),
+                    "Answer citing the section and page".to_string(),
+                ],
+            }],
+            reasoning_hints: vec![
+                "Passages are chosen by the user's question, so no question is needed in the query".to_string(),
+                "Prefer ARXIV_LOOKUP for finding papers; this tool reads one paper".to_string(),
+                "Cite the section and page given with each passage".to_string(),
+            ],
+        }
+    }
+
+    fn capability_key(&self) -> &'static str {
+        "arxiv_paper"
+    }
+
+    fn capabilities(&self) -> Value {
+        json!({
+            "description": "Full-text reading of arXiv papers",
+            "best_for": ["methods and results of a paper", "exact numbers and settings", "questions about one paper"],
+            "strengths": ["section and page citations", "LaTeX source fallback"],
+            "limitations": ["one paper per call", "tables and equations are extracted as rough text"]
+        })
+    }
+
+    fn started_event(&self) -> &'static str {
+        "ARXIV_PAPER_STARTED"
+    }
+
+    fn completed_event(&self) -> &'static str {
+        "ARXIV_PAPER_COMPLETED"
+    }
+
+    fn started_payload(&self, query: &str) -> Value {
+        to_payload(ArxivPaperStartedPayload {
+            query: query.trim().to_string(),
+        })
+    }
+
+    fn failed_payload(&self, query: &str, error: &str) -> Value {
+        to_payload(ArxivPaperCompletedPayload {
+            query: query.trim().to_string(),
+            success: false,
+            title: None,
+            paper_id: None,
+            pdf_url: None,
+            text_source: None,
+            sections: None,
+            passages: None,
+            error: Some(error.to_string()),
+        })
+    }
+
+    async fn execute(&self, ctx: &ToolContext<'_>, query: &str) -> Result<ToolOutput, String> {
+        let paper = find_paper(ctx.client, query.trim())
+            .await?
+            .ok_or_else(|| format!("No arXiv paper found for '{}'", query.trim()))?;
+        let text = paper_text(ctx.client, &paper).await?;
+        let question = format!("{} {}", ctx.user_query, query);
+        let passages = select_passages(&text.sections, &question);
+        log::info!(
+            "Read '{}' from its {:?}: {} sections, {} passages selected",
+            paper.title,
+            text.source,
+            text.sections.len(),
+            passages.len()
+        );
+
+        let mut context = format!(
+            "Full Text of '{}' by {} (arXiv {}), passages most relevant to the question:\n\n",
+            paper.title,
+            paper.authors.join(", "),
+            paper.id
+        );
+        for passage in &passages {
+            context.push_str(&format!("{}:\n{}\n\n", passage.location(), passage.text));
+        }
+        let context = context.trim_end().to_string();
+
+        Ok(ToolOutput {
+            content: context.clone(),
+            context: Some(context),
+            sources: passages.iter().map(|p| p.source(&paper)).collect(),
+            completed_payload: to_payload(ArxivPaperCompletedPayload {
+                query: query.trim().to_string(),
+                success: true,
+                title: Some(paper.title.clone()),
+                paper_id: Some(paper.id.clone()),
+                pdf_url: Some(paper.pdf_url.clone()),
+                text_source: Some(text.source),
+                sections: Some(text.headings()),
+                passages: Some(passages.len()),
+                error: None,
+            }),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const SAMPLE_PDF: &[u8] = include_bytes!("../../tests/fixtures/sample_paper.pdf");
+
+    #[test]
+    fn test_section_heading() {
+        assert_eq!(section_heading("2 Method").as_deref(), Some("2 Method"));
+        assert_eq!(
+            section_heading("3.1 Training Setup").as_deref(),
+            Some("3.1 Training Setup")
+        );
+        assert_eq!(
+            section_heading("IV. RESULTS").as_deref(),
+            Some("IV. RESULTS")
+        );
+        assert_eq!(section_heading("References").as_deref(), Some("References"));
+        assert_eq!(
+            section_heading("64 global tokens. Training uses a rate"),
+            None
+        );
+        assert_eq!(section_heading("1 Baseline 71.2 68.0"), None);
+        assert_eq!(section_heading("A sparse attention mechanism"), None);
+    }
+
+    #[test]
+    fn test_fixture_pdf_sections() {
+        let pages = pdf_extract::extract_text_from_mem_by_pages(SAMPLE_PDF).unwrap();
+        assert_eq!(pages.len(), 2);
+        let sections = pdf_sections(&pages);
+        let outline: Vec<(Option<&str>, Option<usize>)> = sections
+            .iter()
+            .map(|s| (s.heading.as_deref(), s.page))
+            .collect();
+        assert_eq!(
+            outline,
+            vec![
+                (None, Some(1)),
+                (Some("Abstract"), Some(1)),
+                (Some("1 Introduction"), Some(1)),
+                (Some("2 Method"), Some(2)),
+                (Some("3 Results"), Some(2)),
+            ]
+        );
+        // The reference list is left out
+        assert!(!sections.iter().any(|s| s.text.contains("Vaswani")));
+
+        let passages = select_passages(&sections, "What learning rate was used for training?");
+        assert_eq!(passages[0].heading.as_deref(), Some("2 Method"));
+        assert_eq!(passages[0].location(), "2 Method, page 2");
+        assert!(passages[0].text.contains("0.0003"));
+    }
+
+    const SAMPLE_TEX: &str = r"\documentclass{article}
+\title{Sparse Attention}
+\begin{document}
+\maketitle
+\begin{abstract}
+We propose \emph{sparse attention}. % TODO: shorten
+\end{abstract}
+\section{Introduction}
+Attention is quadratic~\cite{vaswani2017}. See Figure~\ref{fig:cost}.
+\begin{figure}[t]\caption{Cost}\label{fig:cost}\end{figure}
+\input{method}
+\bibliography{refs}
+\end{document}
+";
+
+    #[test]
+    fn test_latex_source_archive() {
+        let mut tarball = tar::Builder::new(Vec::new());
+        for (path, content) in [
+            ("main.tex", SAMPLE_TEX),
+            (
+                "method.tex",
+                "\\subsection{Local windows}\nEach token sees \\textbf{256} neighbours, 50\\% fewer.",
+            ),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            tarball
+                .append_data(&mut header, path, content.as_bytes())
+                .unwrap();
+        }
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&tarball.into_inner().unwrap()).unwrap();
+        let bytes = encoder.finish().unwrap();
+
+        let source = read_latex_source(&bytes).unwrap();
+        assert!(source.contains("Each token sees"));
+        let sections = latex_sections(&source);
+        assert_eq!(
+            sections,
+            vec![
+                PaperSection {
+                    heading: Some("Abstract".to_string()),
+                    page: None,
+                    text: "We propose sparse attention.".to_string(),
+                },
+                PaperSection {
+                    heading: Some("Introduction".to_string()),
+                    page: None,
+                    text: "Attention is quadratic. See Figure.".to_string(),
+                },
+                PaperSection {
+                    heading: Some("Local windows".to_string()),
+                    page: None,
+                    text: "Each token sees 256 neighbours, 50% fewer.".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pdf_only_submission_has_no_source() {
+        assert!(read_latex_source(SAMPLE_PDF).is_err());
+    }
+}
@@ -0,0 +1,400 @@
+//! Bibliography export
+//!
+//! Turns papers found by the academic search tools into BibTeX, RIS or
+//! CSL-JSON for reference managers. Papers can also be named by arXiv id or
+//! DOI, in which case their metadata is looked up first.
+
+use super::arxiv::{self, arxiv_id, without_version};
+use super::crossref;
+use super::scholarly::{extract_doi, PaperSummary};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BibliographyFormat {
+    Bibtex,
+    Ris,
+    CslJson,
+}
+
+impl BibliographyFormat {
+    /// Parse a format name such as "bibtex", "ris" or "csl-json"
+    pub fn from_name(name: &str) -> Option<BibliographyFormat> {
+        match name.trim().to_lowercase().as_str() {
+            "bibtex" | "bib" => Some(BibliographyFormat::Bibtex),
+            "ris" => Some(BibliographyFormat::Ris),
+            "csl-json" | "csljson" | "csl" | "json" => Some(BibliographyFormat::CslJson),
+            _ => None,
+        }
+    }
+}
+
+/// Words skipped when building a citation key from a title
+const KEY_STOP_WORDS: [&str; 6] = ["a", "an", "the", "on", "of", "towards"];
+
+/// The arXiv id of a paper found on arXiv, without its version
+fn eprint(paper: &PaperSummary) -> Option<String> {
+    if !paper.id.contains("arxiv.org/") {
+        return None;
+    }
+    arxiv_id(&paper.id).map(|id| without_version(&id).to_string())
+}
+
+/// Year, month and day of the publication date, as far as it is known
+fn date_parts(paper: &PaperSummary) -> Vec<u32> {
+    paper
+        .published_date
+        .as_deref()
+        .unwrap_or_default()
+        .splitn(3, '-')
+        .map_while(|part| part.parse().ok())
+        .collect()
+}
+
+/// `(family, given)`; single-word names such as collaborations have no given name
+fn split_name(name: &str) -> (&str, Option<&str>) {
+    match name.trim().rsplit_once(' ') {
+        Some((given, family)) => (family, Some(given)),
+        None => (name.trim(), None),
+    }
+}
+
+/// Keys like `vaswani2017attention`, made unique with a letter suffix
+fn citation_keys(papers: &[PaperSummary]) -> Vec<String> {
+    let mut used = HashSet::new();
+    papers
+        .iter()
+        .map(|paper| {
+            let ascii = |text: &str| {
+                text.chars()
+                    .filter(char::is_ascii_alphanumeric)
+                    .collect::<String>()
+                    .to_lowercase()
+            };
+            let author = paper
+                .authors
+                .first()
+                .map(|name| ascii(split_name(name).0))
+                .unwrap_or_default();
+            let year = date_parts(paper)
+                .first()
+                .map(u32::to_string)
+                .unwrap_or_default();
+            let word = paper
+                .title
+                .split_whitespace()
+                .map(ascii)
+                .find(|word| !word.is_empty() && !KEY_STOP_WORDS.contains(&word.as_str()))
+                .unwrap_or_default();
+            let mut base = format!("{}{}{}", author, year, word);
+            if base.is_empty() {
+                base = "paper".to_string();
+            }
+            let mut key = base.clone();
+            for suffix in 'a'..='z' {
+                if used.insert(key.clone()) {
+                    break;
+                }
+                key = format!("{}{}", base, suffix);
+            }
+            key
+        })
+        .collect()
+}
+
+fn bibtex_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' | '%' | '$' | '#' | '_' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '{' | '}' => {}
+            c if c.is_whitespace() => {
+                if !escaped.ends_with(' ') {
+                    escaped.push(' ');
+                }
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped.trim().to_string()
+}
+
+fn to_bibtex(paper: &PaperSummary, key: &str) -> String {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+    let entry_type = if paper.venue.is_some() {
+        "article"
+    } else {
+        "misc"
+    };
+    let authors = paper
+        .authors
+        .iter()
+        .map(|name| match split_name(name) {
+            (family, Some(given)) => {
+                format!("{}, {}", bibtex_escape(family), bibtex_escape(given))
+            }
+            // Braces keep a collaboration's name from being split into parts
+            (family, None) => format!("{{{}}}", bibtex_escape(family)),
+        })
+        .collect::<Vec<_>>()
+        .join(" and ");
+    let date = date_parts(paper);
+
+    let mut fields = vec![
+        ("title", format!("{{{}}}", bibtex_escape(&paper.title))),
+        ("author", format!("{{{}}}", authors)),
+    ];
+    if let Some(venue) = &paper.venue {
+        fields.push(("journal", format!("{{{}}}", bibtex_escape(venue))));
+    }
+    if let Some(year) = date.first() {
+        fields.push(("year", year.to_string()));
+    }
+    let month = date
+        .get(1)
+        .and_then(|m| MONTHS.get((*m as usize).checked_sub(1)?));
+    if let Some(month) = month {
+        fields.push(("month", month.to_string()));
+    }
+    if let Some(id) = eprint(paper) {
+        fields.push(("eprint", format!("{{{}}}", id)));
+        fields.push(("archivePrefix", "{arXiv}".to_string()));
+        if let Some(category) = paper.fields.first() {
+            fields.push(("primaryClass", format!("{{{}}}", category)));
+        }
+    }
+    if let Some(doi) = &paper.doi {
+        fields.push(("doi", format!("{{{}}}", doi)));
+    }
+    fields.push(("url", format!("{{{}}}", paper.id)));
+    if !paper.summary.is_empty() {
+        fields.push(("abstract", format!("{{{}}}", bibtex_escape(&paper.summary))));
+    }
+
+    let body = fields
+        .iter()
+        .map(|(name, value)| format!("  {} = {}", name, value))
+        .collect::<Vec<_>>()
+        .join(",\n");
+    format!("@{}{{{},\n{}\n}}\n", entry_type, key, body)
+}
+
+fn to_ris(paper: &PaperSummary) -> String {
+    let mut lines = Vec::new();
+    let mut tag = |tag: &str, value: &str| {
+        let value = value.split_whitespace().collect::<Vec<_>>().join(" ");
+        if !value.is_empty() {
+            lines.push(format!("{}  - {}", tag, value));
+        }
+    };
+    // Papers without a venue are preprints, i.e. unpublished work
+    let entry_type = if paper.venue.is_some() {
+        "JOUR"
+    } else {
+        "UNPB"
+    };
+    tag("TY", entry_type);
+    tag("TI", &paper.title);
+    for name in &paper.authors {
+        match split_name(name) {
+            (family, Some(given)) => tag("AU", &format!("{}, {}", family, given)),
+            (family, None) => tag("AU", family),
+        }
+    }
+    let date = date_parts(paper);
+    if let Some(year) = date.first() {
+        tag("PY", &year.to_string());
+        let part = |i: usize| date.get(i).map(|p| format!("{:02}", p)).unwrap_or_default();
+        tag("DA", &format!("{}/{}/{}/", year, part(1), part(2)));
+    }
+    if let Some(venue) = &paper.venue {
+        tag("JO", venue);
+    }
+    if let Some(id) = eprint(paper) {
+        tag("PB", "arXiv");
+        tag("M1", &format!("arXiv:{}", id));
+    }
+    if let Some(doi) = &paper.doi {
+        tag("DO", doi);
+    }
+    tag("UR", &paper.id);
+    if !paper.pdf_url.is_empty() {
+        tag("L1", &paper.pdf_url);
+    }
+    for field in &paper.fields {
+        tag("KW", field);
+    }
+    tag("AB", &paper.summary);
+    // The end-of-record tag has no value
+    lines.push("ER  - ".to_string());
+    lines.join("\r\n") + "\r\n"
+}
+
+fn to_csl_json(paper: &PaperSummary, key: &str) -> Value {
+    let authors: Vec<Value> = paper
+        .authors
+        .iter()
+        .map(|name| match split_name(name) {
+            (family, Some(given)) => json!({ "family": family, "given": given }),
+            (literal, None) => json!({ "literal": literal }),
+        })
+        .collect();
+    let mut item = json!({
+        "id": key,
+        "type": if paper.venue.is_some() { "article-journal" } else { "article" },
+        "title": paper.title,
+        "author": authors,
+        "URL": paper.id,
+    });
+    let date = date_parts(paper);
+    if !date.is_empty() {
+        item["issued"] = json!({ "date-parts": [date] });
+    }
+    if let Some(venue) = &paper.venue {
+        item["container-title"] = json!(venue);
+    }
+    if let Some(id) = eprint(paper) {
+        item["publisher"] = json!("arXiv");
+        item["number"] = json!(id);
+    }
+    if let Some(doi) = &paper.doi {
+        item["DOI"] = json!(doi);
+    }
+    if !paper.fields.is_empty() {
+        item["keyword"] = json!(paper.fields.join(", "));
+    }
+    if !paper.summary.is_empty() {
+        item["abstract"] = json!(paper.summary);
+    }
+    item
+}
+
+/// The papers as one bibliography, leaving out repeated papers
+pub fn export(papers: &[PaperSummary], format: BibliographyFormat) -> String {
+    let mut seen = HashSet::new();
+    let papers: Vec<PaperSummary> = papers
+        .iter()
+        .filter(|paper| seen.insert(paper.doi.clone().unwrap_or_else(|| paper.id.clone())))
+        .cloned()
+        .collect();
+    let keys = citation_keys(&papers);
+    match format {
+        BibliographyFormat::Bibtex => papers
+            .iter()
+            .zip(&keys)
+            .map(|(paper, key)| to_bibtex(paper, key))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        BibliographyFormat::Ris => papers.iter().map(to_ris).collect::<Vec<_>>().join("\r\n"),
+        BibliographyFormat::CslJson => {
+            let items: Vec<Value> = papers
+                .iter()
+                .zip(&keys)
+                .map(|(paper, key)| to_csl_json(paper, key))
+                .collect();
+            serde_json::to_string_pretty(&items).unwrap_or_default()
+        }
+    }
+}
+
+/// Look up a paper named by arXiv id, arXiv URL or DOI
+pub async fn resolve_reference(
+    client: &reqwest::Client,
+    reference: &str,
+) -> Result<PaperSummary, String> {
+    // arXiv's own DOIs (10.48550/arXiv.*) are registered with DataCite, not Crossref
+    let doi = extract_doi(reference).filter(|doi| !doi.starts_with("10.48550/"));
+    if let Some(doi) = doi {
+        return crossref::resolve_doi(client, &doi).await;
+    }
+    if arxiv_id(reference).is_some() {
+        return arxiv::find_paper(client, reference)
+            .await?
+            .map(|paper| arxiv::paper_summary(&paper))
+            .ok_or_else(|| format!("No arXiv paper found for '{}'", reference));
+    }
+    Err(format!("'{}' is not an arXiv id or DOI", reference))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attention() -> PaperSummary {
+        PaperSummary {
+            title: "Attention Is All You Need".to_string(),
+            summary: "The dominant sequence transduction models...".to_string(),
+            authors: vec!["Ashish Vaswani".to_string(), "Noam Shazeer".to_string()],
+            id: "http://arxiv.org/abs/1706.03762v7".to_string(),
+            published_date: Some("2017-06-12".to_string()),
+            pdf_url: "http://arxiv.org/pdf/1706.03762v7".to_string(),
+            fields: vec!["cs.CL".to_string(), "cs.LG".to_string()],
+            ..PaperSummary::default()
+        }
+    }
+
+    fn alphafold() -> PaperSummary {
+        PaperSummary {
+            title: "Highly accurate protein structure prediction with AlphaFold".to_string(),
+            authors: vec!["John Jumper".to_string(), "DeepMind".to_string()],
+            id: "https://doi.org/10.1038/s41586-021-03819-2".to_string(),
+            published_date: Some("2021-07".to_string()),
+            doi: Some("10.1038/s41586-021-03819-2".to_string()),
+            venue: Some("Nature".to_string()),
+            ..PaperSummary::default()
+        }
+    }
+
+    #[test]
+    fn test_bibtex() {
+        let bibtex = export(&[attention(), alphafold()], BibliographyFormat::Bibtex);
+        assert!(bibtex.starts_with(
+            "@misc{vaswani2017attention,\n  title = {Attention Is All You Need},\n  \
+             author = {Vaswani, Ashish and Shazeer, Noam},\n  year = 2017,\n  month = jun,\n  \
+             eprint = {1706.03762},\n  archivePrefix = {arXiv},\n  primaryClass = {cs.CL},\n"
+        ));
+        assert!(bibtex.contains("@article{jumper2021highly,\n"));
+        assert!(bibtex.contains("author = {Jumper, John and {DeepMind}}"));
+        assert!(bibtex.contains("journal = {Nature},\n  year = 2021,\n  month = jul,\n"));
+    }
+
+    #[test]
+    fn test_ris_and_csl_json() {
+        let ris = export(&[alphafold()], BibliographyFormat::Ris);
+        assert!(ris.starts_with("TY  - JOUR\r\nTI  - Highly accurate"));
+        assert!(ris.contains("AU  - Jumper, John\r\nAU  - DeepMind\r\n"));
+        assert!(ris.contains("DA  - 2021/07//\r\n"));
+        assert!(ris.ends_with("ER  - \r\n"));
+
+        let csl: Value =
+            serde_json::from_str(&export(&[attention()], BibliographyFormat::CslJson)).unwrap();
+        assert_eq!(csl[0]["id"], "vaswani2017attention");
+        assert_eq!(csl[0]["type"], "article");
+        assert_eq!(csl[0]["author"][0]["family"], "Vaswani");
+        assert_eq!(csl[0]["issued"]["date-parts"], json!([[2017, 6, 12]]));
+        assert_eq!(csl[0]["number"], "1706.03762");
+    }
+
+    #[test]
+    fn test_keys_are_unique_and_papers_not_repeated() {
+        let mut other = attention();
+        other.id = "http://arxiv.org/abs/1706.99999".to_string();
+        let csl: Value = serde_json::from_str(&export(
+            &[attention(), attention(), other],
+            BibliographyFormat::CslJson,
+        ))
+        .unwrap();
+        let keys: Vec<&str> = csl
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["id"].as_str().unwrap())
+            .collect();
+        assert_eq!(keys, vec!["vaswani2017attention", "vaswani2017attentiona"]);
+    }
+}
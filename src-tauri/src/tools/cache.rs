@@ -0,0 +1,266 @@
+//! Tool result cache
+//!
+//! Successful tool outputs are kept in memory and mirrored to `tool_cache.json`
+//! in the app cache directory, keyed by tool name and normalized query. Each
+//! tool chooses its own TTL via `Tool::cache_ttl`; tools without one are never
+//! cached. Expired entries are dropped on load and on every insert, and the
+//! file is rewritten by a background thread once changes settle.
+
+use super::ToolOutput;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use time::OffsetDateTime;
+
+const CACHE_FILENAME: &str = "tool_cache.json";
+/// How long the cache must go without changes before it is written
+const PERSIST_DEBOUNCE: Duration = Duration::from_secs(2);
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CacheEntry {
+    pub tool: String,
+    pub query: String,
+    pub stored_at: i64, // Unix timestamp (seconds)
+    pub expires_at: i64,
+    pub output: ToolOutput,
+}
+
+pub struct ToolCache {
+    entries: Arc<Mutex<HashMap<String, CacheEntry>>>,
+    /// Wakes the writer thread; None for a memory-only cache
+    writer: Option<Sender<()>>,
+}
+
+/// Lowercase, trim and collapse whitespace so trivially different queries share an entry
+pub fn normalize_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+fn cache_key(tool: &str, query: &str) -> String {
+    format!("{}:{}", tool, normalize_query(query))
+}
+
+fn now() -> i64 {
+    OffsetDateTime::now_utc().unix_timestamp()
+}
+
+impl ToolCache {
+    /// Create a cache backed by `path`, dropping entries that already expired
+    pub fn open(path: Option<PathBuf>) -> Self {
+        let mut entries: HashMap<String, CacheEntry> = HashMap::new();
+        if let Some(path) = path.as_ref().filter(|p| p.exists()) {
+            match fs::read_to_string(path)
+                .map_err(|e| e.to_string())
+                .and_then(|content| serde_json::from_str(&content).map_err(|e| e.to_string()))
+            {
+                Ok(loaded) => entries = loaded,
+                Err(e) => log::warn!("Failed to load tool cache from {:?}: {}", path, e),
+            }
+        }
+        let now = now();
+        entries.retain(|_, entry| entry.expires_at > now);
+        let entries = Arc::new(Mutex::new(entries));
+        let writer = path.map(|path| spawn_writer(path, entries.clone()));
+        ToolCache { entries, writer }
+    }
+
+    /// A fresh cached output for this tool and query, if any
+    pub fn get(&self, tool: &str, query: &str) -> Option<CacheEntry> {
+        let entries = self.entries.lock().ok()?;
+        entries
+            .get(&cache_key(tool, query))
+            .filter(|entry| entry.expires_at > now())
+            .cloned()
+    }
+
+    pub fn insert(&self, tool: &str, query: &str, ttl: Duration, output: ToolOutput) {
+        let stored_at = now();
+        let entry = CacheEntry {
+            tool: tool.to_string(),
+            query: normalize_query(query),
+            stored_at,
+            expires_at: stored_at + ttl.as_secs() as i64,
+            output,
+        };
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.retain(|_, entry| entry.expires_at > stored_at);
+            entries.insert(cache_key(tool, query), entry);
+        }
+        self.persist();
+    }
+
+    /// Remove all entries, or only those of one tool; returns how many were removed
+    pub fn clear(&self, tool: Option<&str>) -> usize {
+        let mut entries = match self.entries.lock() {
+            Ok(entries) => entries,
+            Err(_) => return 0,
+        };
+        let before = entries.len();
+        match tool {
+            Some(tool) => entries.retain(|_, entry| entry.tool != tool),
+            None => entries.clear(),
+        }
+        let removed = before - entries.len();
+        drop(entries);
+        self.persist();
+        removed
+    }
+
+    /// Ask the writer thread to save the cache once changes settle
+    fn persist(&self) {
+        if let Some(writer) = &self.writer {
+            let _ = writer.send(());
+        }
+    }
+}
+
+/// Start the thread that mirrors `entries` to `path`; it writes a final time
+/// when the cache is dropped
+fn spawn_writer(path: PathBuf, entries: Arc<Mutex<HashMap<String, CacheEntry>>>) -> Sender<()> {
+    let (sender, receiver) = mpsc::channel::<()>();
+    std::thread::spawn(move || {
+        // Wait for a change, then for the cache to be quiet before writing
+        while receiver.recv().is_ok() {
+            loop {
+                match receiver.recv_timeout(PERSIST_DEBOUNCE) {
+                    Ok(()) => continue,
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => {
+                        write_entries(&path, &entries);
+                        return;
+                    }
+                }
+            }
+            write_entries(&path, &entries);
+        }
+    });
+    sender
+}
+
+fn write_entries(path: &Path, entries: &Mutex<HashMap<String, CacheEntry>>) {
+    let json = match entries.lock() {
+        Ok(entries) => serde_json::to_string(&*entries),
+        Err(_) => return,
+    };
+    let json = match json {
+        Ok(json) => json,
+        Err(e) => {
+            log::warn!("Failed to serialize tool cache: {}", e);
+            return;
+        }
+    };
+    if let Some(parent_dir) = path.parent() {
+        if !parent_dir.exists() {
+            if let Err(e) = fs::create_dir_all(parent_dir) {
+                log::warn!("Failed to create cache directory: {}", e);
+                return;
+            }
+        }
+    }
+    if let Err(e) = fs::write(path, json) {
+        log::warn!("Failed to write tool cache: {}", e);
+    }
+}
+
+/// The shared cache, loaded from the app cache directory on first use
+pub fn tool_cache(app_handle: &AppHandle) -> &'static ToolCache {
+    static CACHE: OnceLock<ToolCache> = OnceLock::new();
+    CACHE.get_or_init(|| {
+        let path = match app_handle.path().app_cache_dir() {
+            Ok(dir) => Some(dir.join(CACHE_FILENAME)),
+            Err(e) => {
+                log::warn!(
+                    "Failed to get app cache directory: {}. Tool cache will be memory-only.",
+                    e
+                );
+                None
+            }
+        };
+        ToolCache::open(path)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn output(content: &str) -> ToolOutput {
+        ToolOutput {
+            content: content.to_string(),
+            context: Some(content.to_string()),
+            sources: Vec::new(),
+            completed_payload: json!({ "success": true }),
+        }
+    }
+
+    #[test]
+    fn test_lookup_uses_normalized_query() {
+        let cache = ToolCache::open(None);
+        cache.insert(
+            "FINANCIAL_DATA",
+            "  AAPL ",
+            Duration::from_secs(300),
+            output("Latest data for AAPL"),
+        );
+        let hit = cache.get("FINANCIAL_DATA", "aapl").unwrap();
+        assert_eq!(hit.output.content, "Latest data for AAPL");
+        assert!(cache.get("ARXIV_LOOKUP", "aapl").is_none());
+        assert!(cache.get("FINANCIAL_DATA", "MSFT").is_none());
+    }
+
+    #[test]
+    fn test_expired_entries_are_ignored_and_clear_by_tool() {
+        let cache = ToolCache::open(None);
+        cache.insert("WIKIPEDIA_LOOKUP", "Tokyo", Duration::ZERO, output("old"));
+        assert!(cache.get("WIKIPEDIA_LOOKUP", "Tokyo").is_none());
+
+        cache.insert(
+            "WIKIPEDIA_LOOKUP",
+            "Paris",
+            Duration::from_secs(60),
+            output("a"),
+        );
+        cache.insert(
+            "ARXIV_LOOKUP",
+            "transformers",
+            Duration::from_secs(60),
+            output("b"),
+        );
+        // Inserting dropped the expired entry
+        assert_eq!(cache.entries.lock().unwrap().len(), 2);
+        assert_eq!(cache.clear(Some("WIKIPEDIA_LOOKUP")), 1);
+        assert!(cache.get("ARXIV_LOOKUP", "transformers").is_some());
+        assert_eq!(cache.clear(None), 1);
+    }
+
+    #[test]
+    fn test_cache_is_written_in_the_background() {
+        let path = std::env::temp_dir()
+            .join(format!("shard-cache-test-{}", uuid::Uuid::new_v4()))
+            .join(CACHE_FILENAME);
+        let cache = ToolCache::open(Some(path.clone()));
+        cache.insert("WEB_SEARCH", "rust", Duration::from_secs(60), output("r"));
+        // Dropping the cache makes the writer save right away
+        drop(cache);
+        let mut saved = None;
+        for _ in 0..100 {
+            saved = ToolCache::open(Some(path.clone())).get("WEB_SEARCH", "rust");
+            if saved.is_some() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(saved.unwrap().output.content, "r");
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,178 @@
+//! Typed tool sources and numbered citations
+//!
+//! Every source a tool consulted gets a number for the duration of one request.
+//! The numbers are written into the research context next to the tool output,
+//! the model is asked to cite them as `[n]`, and once the answer has streamed
+//! the markers are mapped back to sources for the STREAM_SOURCES event.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+/// Maximum length of a source snippet, in characters
+const SNIPPET_CHARS: usize = 240;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ToolSource {
+    pub title: String,
+    pub url: String,
+    pub retrieved_at: String, // RFC 3339, UTC
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
+}
+
+impl ToolSource {
+    /// A source retrieved just now; the snippet is shortened to a preview
+    pub fn new(title: &str, url: &str, snippet: Option<&str>) -> Self {
+        ToolSource {
+            title: title.trim().to_string(),
+            url: url.to_string(),
+            retrieved_at: OffsetDateTime::now_utc()
+                .format(&Rfc3339)
+                .unwrap_or_default(),
+            snippet: snippet
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(shorten),
+        }
+    }
+}
+
+fn shorten(text: &str) -> String {
+    if text.chars().count() <= SNIPPET_CHARS {
+        return text.to_string();
+    }
+    let short: String = text.chars().take(SNIPPET_CHARS).collect();
+    format!("{}…", short.trim_end())
+}
+
+/// A source referenced by the answer, with its citation number
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct CitedSource {
+    pub number: usize,
+    #[serde(flatten)]
+    pub source: ToolSource,
+}
+
+/// Sources gathered while answering one message, numbered from 1
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(transparent)]
+pub struct CitationList {
+    sources: Vec<ToolSource>,
+}
+
+impl CitationList {
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    /// Number the given sources, reusing the number of a URL that was already cited
+    pub fn add(&mut self, sources: &[ToolSource]) -> Vec<usize> {
+        sources
+            .iter()
+            .map(
+                |source| match self.sources.iter().position(|s| s.url == source.url) {
+                    Some(index) => index + 1,
+                    None => {
+                        self.sources.push(source.clone());
+                        self.sources.len()
+                    }
+                },
+            )
+            .collect()
+    }
+
+    /// Append a numbered source list to a tool's context text
+    pub fn annotate(&mut self, context_text: &str, sources: &[ToolSource]) -> String {
+        if sources.is_empty() {
+            return context_text.to_string();
+        }
+        let numbers = self.add(sources);
+        let mut annotated = format!("{}\nSources:\n", context_text.trim_end());
+        for (number, source) in numbers.iter().zip(sources) {
+            annotated.push_str(&format!("[{}] {} ({})\n", number, source.title, source.url));
+        }
+        annotated.trim_end().to_string()
+    }
+
+    /// Sources referenced by `[n]` or `[n, m]` markers, in order of first citation
+    pub fn cited_in(&self, answer: &str) -> Vec<CitedSource> {
+        static MARKER: OnceLock<Regex> = OnceLock::new();
+        let marker = MARKER.get_or_init(|| {
+            Regex::new(r"\[(\d+(?:\s*,\s*\d+)*)\]").expect("citation marker regex is valid")
+        });
+
+        let mut cited: Vec<CitedSource> = Vec::new();
+        for captures in marker.captures_iter(answer) {
+            for number in captures[1].split(',') {
+                let number: usize = match number.trim().parse() {
+                    Ok(n) => n,
+                    Err(_) => continue,
+                };
+                if number == 0 || cited.iter().any(|c| c.number == number) {
+                    continue;
+                }
+                if let Some(source) = self.sources.get(number - 1) {
+                    cited.push(CitedSource {
+                        number,
+                        source: source.clone(),
+                    });
+                }
+            }
+        }
+        cited
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(title: &str, url: &str) -> ToolSource {
+        ToolSource::new(title, url, Some("snippet"))
+    }
+
+    #[test]
+    fn test_sources_are_numbered_once_per_url() {
+        let mut citations = CitationList::default();
+        let annotated = citations.annotate(
+            "Wikipedia Research Results for 'Tokyo':",
+            &[
+                source("Tokyo", "https://en.wikipedia.org/wiki/Tokyo"),
+                source("Japan", "https://en.wikipedia.org/wiki/Japan"),
+            ],
+        );
+        assert!(annotated.contains("[1] Tokyo (https://en.wikipedia.org/wiki/Tokyo)"));
+        assert!(annotated.contains("[2] Japan"));
+        assert_eq!(
+            citations.add(&[source("Japan again", "https://en.wikipedia.org/wiki/Japan")]),
+            vec![2]
+        );
+        assert_eq!(citations.annotate("No sources", &[]), "No sources");
+    }
+
+    #[test]
+    fn test_markers_map_back_to_sources() {
+        let mut citations = CitationList::default();
+        citations.add(&[
+            source("A", "https://a.example"),
+            source("B", "https://b.example"),
+            source("C", "https://c.example"),
+        ]);
+        let cited = citations.cited_in("Tokyo is large [3]. It is the capital [1, 3][7]. See [x].");
+        let numbers: Vec<usize> = cited.iter().map(|c| c.number).collect();
+        assert_eq!(numbers, vec![3, 1]);
+        assert_eq!(cited[0].source.title, "C");
+    }
+
+    #[test]
+    fn test_long_snippets_are_shortened() {
+        let long_text = "word ".repeat(100);
+        let source = ToolSource::new("Long", "https://long.example", Some(&long_text));
+        let snippet = source.snippet.unwrap();
+        assert!(snippet.ends_with('…'));
+        assert!(snippet.chars().count() <= SNIPPET_CHARS + 1);
+    }
+}
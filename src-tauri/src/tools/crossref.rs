@@ -0,0 +1,337 @@
+//! Crossref metadata search tool
+//!
+//! Searches the Crossref works API for published papers, or looks one up by
+//! its DOI. DOI lookups are also used to find the published version of arXiv
+//! preprints.
+
+use super::fetch_url::USER_AGENT;
+use super::scholarly::{self, clean_abstract, doi_url, extract_doi, PaperSummary};
+use super::{Tool, ToolContext, ToolOutput};
+use crate::mcp_simple::{ToolExample, ToolGuidance, ToolParameter};
+use crate::ToolType;
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+const CROSSREF_API_URL: &str = "https://api.crossref.org/works";
+/// Fields requested from Crossref, leaving out the (long) reference lists
+const CROSSREF_FIELDS: &str =
+    "DOI,title,author,container-title,abstract,issued,is-referenced-by-count,URL,link,subject";
+
+#[derive(Debug, Deserialize)]
+struct CrossrefResponse<T> {
+    message: T,
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+struct CrossrefItems {
+    items: Vec<CrossrefWork>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default, rename_all = "kebab-case")]
+struct CrossrefWork {
+    #[serde(rename = "DOI")]
+    doi: String,
+    title: Vec<String>,
+    author: Vec<CrossrefAuthor>,
+    container_title: Vec<String>,
+    #[serde(rename = "abstract")]
+    abstract_text: Option<String>,
+    issued: CrossrefDate,
+    is_referenced_by_count: Option<u64>,
+    link: Vec<CrossrefLink>,
+    subject: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+struct CrossrefAuthor {
+    given: Option<String>,
+    family: Option<String>,
+    /// Organizations are listed by name only
+    name: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default, rename_all = "kebab-case")]
+struct CrossrefDate {
+    /// `[[year, month, day]]`, with missing parts left out or null
+    date_parts: Vec<Vec<Option<i64>>>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default, rename_all = "kebab-case")]
+struct CrossrefLink {
+    #[serde(rename = "URL")]
+    url: String,
+    content_type: Option<String>,
+}
+
+impl CrossrefDate {
+    /// `YYYY`, `YYYY-MM` or `YYYY-MM-DD`, as far as the date is known
+    fn to_iso(&self) -> Option<String> {
+        let parts: Vec<i64> = self.date_parts.first()?.iter().map_while(|p| *p).collect();
+        match parts.as_slice() {
+            [year] => Some(format!("{:04}", year)),
+            [year, month] => Some(format!("{:04}-{:02}", year, month)),
+            [year, month, day, ..] => Some(format!("{:04}-{:02}-{:02}", year, month, day)),
+            [] => None,
+        }
+    }
+}
+
+impl CrossrefWork {
+    fn into_summary(self) -> PaperSummary {
+        let authors = self
+            .author
+            .into_iter()
+            .filter_map(|author| match (author.given, author.family, author.name) {
+                (Some(given), Some(family), _) => Some(format!("{} {}", given, family)),
+                (None, Some(family), _) => Some(family),
+                (_, None, name) => name,
+            })
+            .collect();
+        let pdf_url = self
+            .link
+            .iter()
+            .find(|link| link.content_type.as_deref() == Some("application/pdf"))
+            .map(|link| link.url.clone())
+            .unwrap_or_default();
+        PaperSummary {
+            title: self.title.into_iter().next().unwrap_or_default(),
+            summary: self
+                .abstract_text
+                .as_deref()
+                .map(clean_abstract)
+                .unwrap_or_default(),
+            authors,
+            id: doi_url(&self.doi),
+            published_date: self.issued.to_iso(),
+            pdf_url,
+            venue: self.container_title.into_iter().next(),
+            citation_count: self.is_referenced_by_count,
+            fields: self.subject,
+            doi: Some(self.doi),
+            ..PaperSummary::default()
+        }
+    }
+}
+
+async fn get_crossref<T: DeserializeOwned>(
+    client: &reqwest::Client,
+    url: &str,
+    params: &[(&str, &str)],
+) -> Result<T, String> {
+    let response = client
+        .get(url)
+        .header("User-Agent", USER_AGENT)
+        .query(params)
+        .send()
+        .await
+        .map_err(|e| format!("Crossref request failed: {}", e))?;
+    let status = response.status();
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Err("Crossref has no record of this DOI".to_string());
+    }
+    if !status.is_success() {
+        return Err(format!("Crossref request failed: HTTP {}", status));
+    }
+    response
+        .json::<CrossrefResponse<T>>()
+        .await
+        .map(|response| response.message)
+        .map_err(|e| format!("Failed to parse Crossref response: {}", e))
+}
+
+/// Metadata of the published work with this DOI
+pub async fn resolve_doi(client: &reqwest::Client, doi: &str) -> Result<PaperSummary, String> {
+    let url = format!("{}/{}", CROSSREF_API_URL, urlencoding::encode(doi));
+    let work: CrossrefWork = get_crossref(client, &url, &[]).await?;
+    Ok(work.into_summary())
+}
+
+async fn search_crossref(
+    client: &reqwest::Client,
+    query: &str,
+    rows: usize,
+) -> Result<Vec<PaperSummary>, String> {
+    let rows = rows.to_string();
+    let items: CrossrefItems = get_crossref(
+        client,
+        CROSSREF_API_URL,
+        &[
+            ("query.bibliographic", query),
+            ("rows", rows.as_str()),
+            ("select", CROSSREF_FIELDS),
+        ],
+    )
+    .await?;
+    Ok(items
+        .items
+        .into_iter()
+        .map(CrossrefWork::into_summary)
+        .collect())
+}
+
+pub struct CrossrefTool;
+
+#[async_trait]
+impl Tool for CrossrefTool {
+    fn tool_type(&self) -> ToolType {
+        ToolType::CrossrefLookup
+    }
+
+    fn description(&self) -> &'static str {
+        "Published papers and DOI metadata from Crossref, across all fields (query: a DOI, or title/author/topic words)"
+    }
+
+    fn follow_up_hint(&self) -> &'static str {
+        "Journal and conference papers, or metadata for a DOI"
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "A DOI (e.g. 10.1038/s41586-021-03819-2), or title, author and topic words"
+                }
+            },
+            "required": ["query"]
+        })
+    }
+
+    fn guidance(&self) -> ToolGuidance {
+        ToolGuidance {
+            name: "Crossref Search".to_string(),
+            description: "Find published journal articles, conference papers and books, and resolve DOIs to their metadata".to_string(),
+            usage_pattern: "Use for peer-reviewed publications outside arXiv, or to look up the venue, date and authors of a DOI".to_string(),
+            parameters: vec![ToolParameter {
+                name: "query".to_string(),
+                param_type: "string".to_string(),
+                description: "A DOI, or title, author and topic words".to_string(),
+                required: true,
+                default_value: None,
+            }],
+            examples: vec![ToolExample {
+                scenario: "User shares a DOI and asks where it was published".to_string(),
+                reasoning: "Crossref holds the registered metadata of every DOI".to_string(),
+                sequence: vec![
+                    "CROSSREF_LOOKUP with the DOI".to_string(),
+                    "Answer with the venue, date and authors".to_string(),
+                ],
+            }],
+            reasoning_hints: vec![
+                "Covers all fields, but many records have no abstract".to_string(),
+                "Citation counts are Crossref's own and lower than Semantic Scholar's".to_string(),
+            ],
+        }
+    }
+
+    fn capability_key(&self) -> &'static str {
+        "crossref_search"
+    }
+
+    fn capabilities(&self) -> Value {
+        json!({
+            "description": "Publication metadata search and DOI resolution",
+            "best_for": ["published papers", "DOI lookups", "venues and publication dates"],
+            "strengths": ["all disciplines", "authoritative metadata"],
+            "limitations": ["abstracts often missing", "no full text"]
+        })
+    }
+
+    fn started_event(&self) -> &'static str {
+        "CROSSREF_LOOKUP_STARTED"
+    }
+
+    fn completed_event(&self) -> &'static str {
+        "CROSSREF_LOOKUP_COMPLETED"
+    }
+
+    fn started_payload(&self, query: &str) -> Value {
+        scholarly::started_payload(query)
+    }
+
+    fn failed_payload(&self, query: &str, error: &str) -> Value {
+        scholarly::failed_payload(query, error)
+    }
+
+    /// Registered metadata rarely changes
+    fn cache_ttl(&self) -> Option<Duration> {
+        Some(Duration::from_secs(7 * 24 * 60 * 60))
+    }
+
+    async fn execute(&self, ctx: &ToolContext<'_>, query: &str) -> Result<ToolOutput, String> {
+        let papers = match extract_doi(query) {
+            Some(doi) => vec![resolve_doi(ctx.client, &doi).await?],
+            None => {
+                search_crossref(ctx.client, query.trim(), scholarly::DEFAULT_MAX_PAPERS).await?
+            }
+        };
+        log::info!("Crossref lookup '{}': {} papers", query, papers.len());
+        Ok(scholarly::papers_output(
+            query,
+            &format!("Crossref Results for '{}'", query),
+            papers,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_work_into_summary() {
+        let response: CrossrefResponse<CrossrefWork> = serde_json::from_str(
+            r#"{"status": "ok", "message": {
+                "DOI": "10.1038/s41586-021-03819-2",
+                "title": ["Highly accurate protein structure prediction with AlphaFold"],
+                "author": [
+                    {"given": "John", "family": "Jumper", "sequence": "first"},
+                    {"name": "DeepMind Team"}
+                ],
+                "container-title": ["Nature"],
+                "abstract": "<jats:p>Proteins are essential to life.</jats:p>",
+                "issued": {"date-parts": [[2021, 7, 15]]},
+                "is-referenced-by-count": 20000,
+                "link": [{"URL": "https://www.nature.com/articles/s41586-021-03819-2.pdf", "content-type": "application/pdf"}],
+                "subject": ["Multidisciplinary"]
+            }}"#,
+        )
+        .unwrap();
+        let paper = response.message.into_summary();
+        assert_eq!(
+            paper.title,
+            "Highly accurate protein structure prediction with AlphaFold"
+        );
+        assert_eq!(paper.authors, vec!["John Jumper", "DeepMind Team"]);
+        assert_eq!(paper.summary, "Proteins are essential to life.");
+        assert_eq!(paper.id, "https://doi.org/10.1038/s41586-021-03819-2");
+        assert_eq!(paper.published_date.as_deref(), Some("2021-07-15"));
+        assert_eq!(paper.venue.as_deref(), Some("Nature"));
+        assert_eq!(paper.citation_count, Some(20000));
+        assert!(paper.pdf_url.ends_with(".pdf"));
+    }
+
+    #[test]
+    fn test_partial_dates() {
+        let date = |json: &str| serde_json::from_str::<CrossrefDate>(json).unwrap().to_iso();
+        assert_eq!(
+            date(r#"{"date-parts": [[2019, 3]]}"#).as_deref(),
+            Some("2019-03")
+        );
+        assert_eq!(
+            date(r#"{"date-parts": [[2019, null]]}"#).as_deref(),
+            Some("2019")
+        );
+        assert_eq!(date(r#"{"date-parts": [[null]]}"#), None);
+        assert_eq!(date("{}"), None);
+    }
+}
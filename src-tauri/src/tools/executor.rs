@@ -0,0 +1,318 @@
+//! Concurrent execution of one round of tool calls
+//!
+//! Tools chosen in the same round rarely depend on each other, so they run
+//! concurrently up to a configurable limit. A decision may name the tool types
+//! it depends on (`depends_on`); those run in a later wave, after every tool of
+//! those types has finished. Results are returned in the original priority order
+//! so the assembled context does not depend on which lookup finished first.
+//! Tools with a cache TTL are answered from the tool cache when possible.
+
+use super::citations::ToolSource;
+use super::{registry, ToolContext};
+use crate::{ToolDecision, ToolExecutionResult, ToolType};
+use futures_util::stream::{self, StreamExt};
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use tauri::Emitter;
+
+/// Default number of tools allowed to run at the same time
+pub const DEFAULT_MAX_PARALLEL_TOOLS: usize = 3;
+
+/// Outcome of a single tool call
+pub struct ToolRun {
+    pub decision: ToolDecision,
+    pub result: ToolExecutionResult,
+    /// Text for the research context, if the tool found anything
+    pub context: Option<String>,
+    pub sources: Vec<ToolSource>,
+    pub elapsed: Duration,
+    pub cache_hit: bool,
+    /// Completed event payload of a successful run
+    pub completed_payload: Option<Value>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+struct ToolProgressPayload {
+    tool_type: String,
+    query: String,
+    status: String, // "running", "completed" or "failed"
+    completed: usize,
+    total: usize,
+}
+
+/// Group decisions into waves; each wave only depends on earlier waves
+///
+/// Dependencies on tool types that are not part of this round are ignored, and
+/// circular dependencies are broken by running the remaining tools together.
+pub fn dependency_waves(decisions: Vec<(usize, ToolDecision)>) -> Vec<Vec<(usize, ToolDecision)>> {
+    let mut waves = Vec::new();
+    let mut pending = decisions;
+    while !pending.is_empty() {
+        let pending_types: Vec<ToolType> =
+            pending.iter().map(|(_, d)| d.tool_type.clone()).collect();
+        let (ready, blocked): (Vec<_>, Vec<_>) = pending.into_iter().partition(|(_, d)| {
+            !d.depends_on
+                .iter()
+                .any(|dep| *dep != d.tool_type && pending_types.contains(dep))
+        });
+        if ready.is_empty() {
+            log::warn!("Circular tool dependencies detected, running remaining tools together");
+            waves.push(blocked);
+            break;
+        }
+        waves.push(ready);
+        pending = blocked;
+    }
+    waves
+}
+
+/// Run the given tool calls, at most `max_parallel` at a time
+///
+/// `decisions` must already be sorted by priority; the returned runs keep that order.
+pub async fn run_tools(
+    ctx: &ToolContext<'_>,
+    decisions: Vec<ToolDecision>,
+    max_parallel: usize,
+) -> Vec<ToolRun> {
+    let total = decisions.len();
+    let completed = AtomicUsize::new(0);
+    let mut runs: Vec<(usize, ToolRun)> = Vec::with_capacity(total);
+
+    let waves = dependency_waves(decisions.into_iter().enumerate().collect());
+    let wave_count = waves.len();
+    for (wave_index, wave) in waves.into_iter().enumerate() {
+        log::info!(
+            "Running tool wave {}/{} with {} tool(s), up to {} in parallel",
+            wave_index + 1,
+            wave_count,
+            wave.len(),
+            max_parallel
+        );
+        let completed = &completed;
+        let wave_runs: Vec<(usize, ToolRun)> = stream::iter(wave)
+            .map(|(index, decision)| async move {
+                emit_progress(
+                    ctx,
+                    &decision,
+                    "running",
+                    completed.load(Ordering::SeqCst),
+                    total,
+                );
+                let run = run_tool(ctx, &decision).await;
+                let done = completed.fetch_add(1, Ordering::SeqCst) + 1;
+                let status = if run.result.success {
+                    "completed"
+                } else {
+                    "failed"
+                };
+                emit_progress(ctx, &decision, status, done, total);
+                (index, run)
+            })
+            .buffer_unordered(max_parallel.max(1))
+            .collect()
+            .await;
+        runs.extend(wave_runs);
+    }
+
+    runs.sort_by_key(|(index, _)| *index);
+    runs.into_iter().map(|(_, run)| run).collect()
+}
+
+fn emit_progress(
+    ctx: &ToolContext<'_>,
+    decision: &ToolDecision,
+    status: &str,
+    completed: usize,
+    total: usize,
+) {
+    let payload = ToolProgressPayload {
+        tool_type: decision.tool_type.as_str().to_string(),
+        query: decision.query.clone(),
+        status: status.to_string(),
+        completed,
+        total,
+    };
+    if let Err(e) = ctx.window.emit("TOOL_EXECUTION_PROGRESS", payload) {
+        log::warn!("Failed to emit TOOL_EXECUTION_PROGRESS event: {}", e);
+    }
+}
+
+/// Run one tool call, emitting its started and completed events
+async fn run_tool(ctx: &ToolContext<'_>, decision: &ToolDecision) -> ToolRun {
+    let started = Instant::now();
+    let failed = |error: String| ToolRun {
+        decision: decision.clone(),
+        result: ToolExecutionResult {
+            tool_type: decision.tool_type.clone(),
+            query: decision.query.clone(),
+            success: false,
+            content: None,
+            error: Some(error),
+        },
+        context: None,
+        sources: Vec::new(),
+        elapsed: started.elapsed(),
+        cache_hit: false,
+        completed_payload: None,
+    };
+
+    let tool = match registry().get(&decision.tool_type) {
+        Some(tool) => tool,
+        None => {
+            log::error!("No tool registered for {:?}, skipping", decision.tool_type);
+            return failed(format!("{} is not available", decision.tool_type.as_str()));
+        }
+    };
+
+    log::info!(
+        "Executing tool {:?} with query: '{}' (priority: {})",
+        decision.tool_type,
+        decision.query,
+        decision.priority
+    );
+
+    if let Err(e) = ctx
+        .window
+        .emit(tool.started_event(), tool.started_payload(&decision.query))
+    {
+        log::warn!("Failed to emit {} event: {}", tool.started_event(), e);
+    }
+
+    let cache_key = tool
+        .cache_ttl()
+        .and_then(|_| tool.cache_key(ctx, &decision.query));
+    let cached = cache_key
+        .as_ref()
+        .and_then(|key| ctx.cache.get(tool.name(), key));
+    let outcome = match cached {
+        Some(entry) => {
+            log::info!(
+                "Using cached {} result for '{}' (stored at {})",
+                tool.name(),
+                decision.query,
+                entry.stored_at
+            );
+            Ok((entry.output, Some(entry.stored_at)))
+        }
+        None => tool.execute(ctx, &decision.query).await.map(|output| {
+            if let (Some(ttl), Some(key)) = (tool.output_cache_ttl(&output), &cache_key) {
+                ctx.cache.insert(tool.name(), key, ttl, output.clone());
+            }
+            (output, None)
+        }),
+    };
+
+    match outcome {
+        Ok((output, cached_at)) => {
+            let completed_payload = with_cache_info(output.completed_payload, cached_at);
+            if let Err(e) = ctx
+                .window
+                .emit(tool.completed_event(), completed_payload.clone())
+            {
+                log::warn!("Failed to emit {} event: {}", tool.completed_event(), e);
+            }
+            ToolRun {
+                decision: decision.clone(),
+                result: ToolExecutionResult {
+                    tool_type: decision.tool_type.clone(),
+                    query: decision.query.clone(),
+                    success: true,
+                    content: Some(output.content),
+                    error: None,
+                },
+                context: output.context,
+                sources: output.sources,
+                elapsed: started.elapsed(),
+                cache_hit: cached_at.is_some(),
+                completed_payload: Some(completed_payload),
+            }
+        }
+        Err(e) => {
+            log::error!("{} failed for '{}': {}", tool.name(), decision.query, e);
+            if let Err(emit_error) = ctx.window.emit(
+                tool.completed_event(),
+                tool.failed_payload(&decision.query, &e),
+            ) {
+                log::warn!(
+                    "Failed to emit {} error event: {}",
+                    tool.completed_event(),
+                    emit_error
+                );
+            }
+            failed(e)
+        }
+    }
+}
+
+/// Mark a completed payload as served from the cache or freshly fetched
+fn with_cache_info(mut payload: Value, cached_at: Option<i64>) -> Value {
+    if let Some(fields) = payload.as_object_mut() {
+        fields.insert("cache_hit".to_string(), Value::Bool(cached_at.is_some()));
+        if let Some(cached_at) = cached_at {
+            fields.insert("cached_at".to_string(), Value::from(cached_at));
+        }
+    }
+    payload
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decision(tool_type: ToolType, depends_on: Vec<ToolType>) -> ToolDecision {
+        ToolDecision {
+            tool_type,
+            query: "test".to_string(),
+            reasoning: String::new(),
+            priority: 1,
+            depends_on,
+        }
+    }
+
+    fn wave_indices(waves: &[Vec<(usize, ToolDecision)>]) -> Vec<Vec<usize>> {
+        waves
+            .iter()
+            .map(|wave| wave.iter().map(|(i, _)| *i).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_independent_tools_share_a_wave() {
+        let waves = dependency_waves(vec![
+            (0, decision(ToolType::WeatherLookup, vec![])),
+            (1, decision(ToolType::WikipediaLookup, vec![])),
+            (
+                2,
+                decision(ToolType::FinancialData, vec![ToolType::ArxivLookup]),
+            ),
+        ]);
+        assert_eq!(wave_indices(&waves), vec![vec![0, 1, 2]]);
+    }
+
+    #[test]
+    fn test_dependent_tools_run_later() {
+        let waves = dependency_waves(vec![
+            (
+                0,
+                decision(ToolType::FinancialData, vec![ToolType::WikipediaLookup]),
+            ),
+            (1, decision(ToolType::WikipediaLookup, vec![])),
+            (2, decision(ToolType::WeatherLookup, vec![])),
+        ]);
+        assert_eq!(wave_indices(&waves), vec![vec![1, 2], vec![0]]);
+
+        let circular = dependency_waves(vec![
+            (
+                0,
+                decision(ToolType::FinancialData, vec![ToolType::ArxivLookup]),
+            ),
+            (
+                1,
+                decision(ToolType::ArxivLookup, vec![ToolType::FinancialData]),
+            ),
+        ]);
+        assert_eq!(wave_indices(&circular), vec![vec![0, 1]]);
+    }
+}
@@ -0,0 +1,745 @@
+//! URL fetch-and-read tool
+//!
+//! Downloads a single HTML page, PDF or plain-text document and extracts its
+//! readable text. Downloads stop at a configurable size and time limit,
+//! robots.txt is honoured unless disabled, and domains can be restricted with
+//! allow/block lists. Redirects are followed one hop at a time so each hop is
+//! checked, and loopback, private and link-local addresses are refused unless
+//! listed in `private_hosts`. The tool also runs automatically for links pasted
+//! into a user message.
+
+use super::citations::ToolSource;
+use super::readability::{extract_readable_text, truncate_chars};
+use super::transcript::youtube_video_id;
+use super::{to_payload, Tool, ToolContext, ToolOutput};
+use crate::mcp_simple::{ToolExample, ToolGuidance, ToolParameter};
+use crate::{ToolDecision, ToolType};
+use async_trait::async_trait;
+use regex::Regex;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::net::IpAddr;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+pub const USER_AGENT: &str = "Shard/1.0 (https://github.com/shard-app/shard)";
+/// Product token matched against robots.txt user-agent lines
+const ROBOTS_AGENT: &str = "shard";
+const DEFAULT_MAX_BYTES: usize = 5 * 1024 * 1024;
+/// Readable text added to the research context per document
+const MAX_CONTEXT_CHARS: usize = 12000;
+/// Links read automatically from one user message
+const MAX_AUTO_URLS: usize = 3;
+/// Time allowed for one request, body included
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_REDIRECTS: usize = 5;
+
+/// URL fetching settings stored in `AppConfig`; `None` fields use the defaults
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct UrlFetchSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_bytes: Option<usize>, // Download size limit, 5 MB by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub respect_robots: Option<bool>, // Check robots.txt before fetching, on by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_domains: Option<Vec<String>>, // If non-empty, only these domains (and subdomains)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocked_domains: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_fetch: Option<bool>, // Read links pasted into a message, on by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub private_hosts: Option<Vec<String>>, // Loopback or private-network hosts that may be fetched, e.g. "localhost"
+}
+
+impl UrlFetchSettings {
+    /// Identifies the settings that decide what a fetch returns, for cache keys
+    pub fn cache_scope(&self) -> String {
+        let list = |domains: &Option<Vec<String>>| domains.as_deref().unwrap_or_default().join(",");
+        format!(
+            "max:{} robots:{} allow:{} block:{} private:{}",
+            self.max_bytes.unwrap_or(DEFAULT_MAX_BYTES),
+            self.respect_robots.unwrap_or(true),
+            list(&self.allowed_domains),
+            list(&self.blocked_domains),
+            list(&self.private_hosts)
+        )
+    }
+}
+
+// --- URL Fetch Event Payloads ---
+#[derive(Serialize, Clone, Debug)]
+struct UrlFetchStartedPayload {
+    url: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+struct UrlFetchCompletedPayload {
+    url: String,
+    success: bool,
+    title: Option<String>,
+    content_type: Option<String>,
+    characters: Option<usize>,
+    truncated: bool,
+    error: Option<String>,
+}
+
+/// Readable text of a fetched document
+#[derive(Debug, Clone)]
+pub struct FetchedDocument {
+    /// Final URL after redirects
+    pub url: String,
+    pub title: Option<String>,
+    pub content_type: String,
+    pub text: String,
+    /// Whether the download stopped at the size limit
+    pub truncated: bool,
+}
+
+/// Links in a message, in order of appearance and without duplicates
+pub fn extract_urls(text: &str) -> Vec<String> {
+    static URL: OnceLock<Regex> = OnceLock::new();
+    let url_pattern =
+        URL.get_or_init(|| Regex::new(r#"https?://[^\s<>"'`]+"#).expect("URL regex is valid"));
+    let mut urls: Vec<String> = Vec::new();
+    for found in url_pattern.find_iter(text) {
+        let mut url = found
+            .as_str()
+            .trim_end_matches(['.', ',', ';', ':', '!', '?']);
+        // Drop a closing bracket that belongs to the surrounding text, e.g. "(see https://...)"
+        if url.ends_with(')') && !url.contains('(') {
+            url = &url[..url.len() - 1];
+        }
+        if Url::parse(url).is_ok() && !urls.iter().any(|u| u == url) {
+            urls.push(url.to_string());
+        }
+    }
+    urls
+}
+
+/// Calls that read the links in a user message which the decider did not already request
+///
+/// YouTube links go to VIDEO_TRANSCRIPT, everything else to FETCH_URL.
+pub fn auto_fetch_decisions(user_query: &str, existing: &[ToolDecision]) -> Vec<ToolDecision> {
+    extract_urls(user_query)
+        .into_iter()
+        .map(|url| {
+            if youtube_video_id(&url).is_some() {
+                (ToolType::VideoTranscript, url)
+            } else {
+                (ToolType::FetchUrl, url)
+            }
+        })
+        .filter(|(tool_type, url)| {
+            !existing
+                .iter()
+                .any(|d| d.tool_type == *tool_type && d.query.trim() == url)
+        })
+        .take(MAX_AUTO_URLS)
+        .map(|(tool_type, url)| ToolDecision {
+            tool_type,
+            query: url,
+            reasoning: "The message links to this page".to_string(),
+            priority: 1,
+            depends_on: Vec::new(),
+        })
+        .collect()
+}
+
+fn domain_matches(host: &str, domain: &str) -> bool {
+    let domain = domain.trim().trim_start_matches("*.").to_lowercase();
+    !domain.is_empty() && (host == domain || host.ends_with(&format!(".{}", domain)))
+}
+
+/// Check a URL against the scheme and the domain allow/block lists
+pub fn check_url_allowed(url: &Url, settings: &UrlFetchSettings) -> Result<(), String> {
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err(format!("Only http and https URLs can be fetched: {}", url));
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| format!("URL has no host: {}", url))?
+        .to_lowercase();
+    if let Some(blocked) = &settings.blocked_domains {
+        if blocked.iter().any(|domain| domain_matches(&host, domain)) {
+            return Err(format!("{} is blocked in URL fetch settings", host));
+        }
+    }
+    if let Some(allowed) = settings.allowed_domains.as_ref().filter(|a| !a.is_empty()) {
+        if !allowed.iter().any(|domain| domain_matches(&host, domain)) {
+            return Err(format!("{} is not in the allowed domains", host));
+        }
+    }
+    Ok(())
+}
+
+/// Whether an address is reachable on the public internet, as opposed to
+/// loopback, private, link-local, shared or otherwise reserved ranges
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                || a == 0
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ip(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    || (first & 0xfe00) == 0xfc00 // Unique local
+                    || (first & 0xffc0) == 0xfe80) // Link-local
+            }
+        },
+    }
+}
+
+/// Refuse hosts that are, or resolve to, non-public addresses unless the user
+/// listed them in `private_hosts`
+async fn check_public_host(url: &Url, settings: &UrlFetchSettings) -> Result<(), String> {
+    let host = url
+        .host_str()
+        .ok_or_else(|| format!("URL has no host: {}", url))?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_lowercase();
+    if let Some(private_hosts) = &settings.private_hosts {
+        if private_hosts
+            .iter()
+            .any(|allowed| domain_matches(&host, allowed))
+        {
+            return Ok(());
+        }
+    }
+    let addresses: Vec<IpAddr> = match host.parse::<IpAddr>() {
+        Ok(ip) => vec![ip],
+        Err(_) => {
+            tokio::net::lookup_host((host.as_str(), url.port_or_known_default().unwrap_or(80)))
+                .await
+                .map_err(|e| format!("Could not resolve {}: {}", host, e))?
+                .map(|address| address.ip())
+                .collect()
+        }
+    };
+    if addresses.iter().all(|ip| is_public_ip(*ip)) {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} is a local or private address; add it to the private hosts in URL fetch settings to allow it",
+            host
+        ))
+    }
+}
+
+/// Client for document fetches; redirects are followed by hand so that every
+/// hop is checked before it is contacted
+fn fetch_client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .timeout(FETCH_TIMEOUT)
+            .build()
+            .expect("fetch client settings are valid")
+    })
+}
+
+/// One `User-agent` group of a robots.txt file
+#[derive(Default)]
+struct RobotsGroup {
+    agents: Vec<String>,
+    rules: Vec<(bool, String)>, // (allow, path prefix)
+}
+
+/// Whether robots.txt lets Shard fetch `path`
+///
+/// Uses the group naming Shard, or the `*` group, and the longest matching
+/// Allow/Disallow prefix; ties go to Allow.
+pub fn robots_allows(robots_txt: &str, path: &str) -> bool {
+    let mut groups: Vec<RobotsGroup> = Vec::new();
+    let mut reading_agents = false;
+    for raw_line in robots_txt.lines() {
+        let line = raw_line.split('#').next().unwrap_or_default().trim();
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let key = key.trim().to_lowercase();
+        let value = value.trim();
+        match key.as_str() {
+            "user-agent" => {
+                if !reading_agents {
+                    groups.push(RobotsGroup::default());
+                    reading_agents = true;
+                }
+                if let Some(group) = groups.last_mut() {
+                    group.agents.push(value.to_lowercase());
+                }
+            }
+            "allow" | "disallow" => {
+                reading_agents = false;
+                if let Some(group) = groups.last_mut() {
+                    if !value.is_empty() {
+                        group
+                            .rules
+                            .push((key == "allow", value.trim_end_matches('*').to_string()));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let group = groups
+        .iter()
+        .find(|g| g.agents.iter().any(|a| a.contains(ROBOTS_AGENT)))
+        .or_else(|| groups.iter().find(|g| g.agents.iter().any(|a| a == "*")));
+    let group = match group {
+        Some(group) => group,
+        None => return true,
+    };
+    let longest_match = group
+        .rules
+        .iter()
+        .filter(|(_, prefix)| path.starts_with(prefix.as_str()))
+        .max_by_key(|(allow, prefix)| (prefix.len(), *allow));
+    match longest_match {
+        Some((allow, _)) => *allow,
+        None => true,
+    }
+}
+
+async fn check_robots(client: &reqwest::Client, url: &Url) -> Result<(), String> {
+    let mut robots_url = url.clone();
+    robots_url.set_path("/robots.txt");
+    robots_url.set_query(None);
+    robots_url.set_fragment(None);
+    let robots_txt = match client
+        .get(robots_url.as_str())
+        .header("User-Agent", USER_AGENT)
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await
+    {
+        Ok(response) if response.status().is_success() => response.text().await.unwrap_or_default(),
+        // No robots.txt (or an unreachable one) means no restrictions
+        _ => return Ok(()),
+    };
+    let mut path = url.path().to_string();
+    if let Some(query) = url.query() {
+        path.push('?');
+        path.push_str(query);
+    }
+    if robots_allows(&robots_txt, &path) {
+        Ok(())
+    } else {
+        Err(format!(
+            "robots.txt of {} does not allow fetching {}",
+            url.host_str().unwrap_or_default(),
+            path
+        ))
+    }
+}
+
+/// Read a response body, stopping at `max_bytes`; returns the bytes and whether it was cut
+pub async fn read_limited(
+    mut response: reqwest::Response,
+    max_bytes: usize,
+) -> Result<(Vec<u8>, bool), String> {
+    let mut body: Vec<u8> = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("Failed to read response body: {}", e))?
+    {
+        if body.len() + chunk.len() > max_bytes {
+            body.extend_from_slice(&chunk[..max_bytes - body.len()]);
+            return Ok((body, true));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok((body, false))
+}
+
+/// Extract the text of each page of a PDF
+///
+/// Runs on a blocking thread, which also contains panics from malformed files.
+pub async fn extract_pdf_pages(bytes: Vec<u8>) -> Result<Vec<String>, String> {
+    tokio::task::spawn_blocking(move || pdf_extract::extract_text_from_mem_by_pages(&bytes))
+        .await
+        .map_err(|e| format!("PDF extraction failed: {}", e))?
+        .map_err(|e| format!("Failed to extract PDF text: {}", e))
+}
+
+/// Join PDF pages into one text with page markers the model can cite
+pub fn join_pdf_pages(pages: &[String]) -> String {
+    pages
+        .iter()
+        .enumerate()
+        .filter(|(_, text)| !text.trim().is_empty())
+        .map(|(i, text)| format!("[Page {}]\n{}", i + 1, text.trim()))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Download a document and extract its readable text, applying the fetch settings
+pub async fn fetch_document(
+    url: &str,
+    settings: &UrlFetchSettings,
+) -> Result<FetchedDocument, String> {
+    let client = fetch_client();
+    let parsed = Url::parse(url.trim()).map_err(|e| format!("Invalid URL '{}': {}", url, e))?;
+
+    // Every hop is checked before it is contacted, since redirects may lead
+    // somewhere the settings do not allow
+    let mut final_url = parsed.clone();
+    let mut redirects = 0;
+    let response = loop {
+        check_url_allowed(&final_url, settings)?;
+        check_public_host(&final_url, settings).await?;
+        if settings.respect_robots.unwrap_or(true) {
+            check_robots(client, &final_url).await?;
+        }
+        let response = client
+            .get(final_url.as_str())
+            .header("User-Agent", USER_AGENT)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to fetch {}: {}", final_url, e))?;
+        if !response.status().is_redirection() {
+            break response;
+        }
+        if redirects == MAX_REDIRECTS {
+            return Err(format!("Too many redirects fetching {}", parsed));
+        }
+        let location = response
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| format!("Redirect from {} has no location", final_url))?;
+        final_url = final_url
+            .join(location)
+            .map_err(|e| format!("Invalid redirect from {}: {}", final_url, e))?;
+        redirects += 1;
+    };
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("Failed to fetch {}: HTTP {}", final_url, status));
+    }
+
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_lowercase();
+    let is_pdf = content_type.contains("application/pdf")
+        || final_url.path().to_lowercase().ends_with(".pdf");
+    let max_bytes = settings.max_bytes.unwrap_or(DEFAULT_MAX_BYTES);
+    let (body, truncated) = read_limited(response, max_bytes).await?;
+
+    if is_pdf {
+        if truncated {
+            return Err(format!(
+                "PDF at {} is larger than the {} byte limit",
+                final_url, max_bytes
+            ));
+        }
+        let pages = extract_pdf_pages(body).await?;
+        return Ok(FetchedDocument {
+            url: final_url.to_string(),
+            title: None,
+            content_type: "application/pdf".to_string(),
+            text: join_pdf_pages(&pages),
+            truncated,
+        });
+    }
+
+    let body = String::from_utf8_lossy(&body);
+    let (title, text) = if content_type.is_empty() || content_type.contains("html") {
+        let readable = extract_readable_text(&body);
+        (readable.title, readable.text)
+    } else if content_type.starts_with("text/")
+        || content_type.contains("json")
+        || content_type.contains("xml")
+    {
+        (None, body.trim().to_string())
+    } else {
+        return Err(format!(
+            "Unsupported content type for {}: {}",
+            final_url, content_type
+        ));
+    };
+    Ok(FetchedDocument {
+        url: final_url.to_string(),
+        title,
+        content_type: if content_type.is_empty() {
+            "text/html".to_string()
+        } else {
+            content_type
+        },
+        text,
+        truncated,
+    })
+}
+
+pub struct FetchUrlTool;
+
+#[async_trait]
+impl Tool for FetchUrlTool {
+    fn tool_type(&self) -> ToolType {
+        ToolType::FetchUrl
+    }
+
+    fn description(&self) -> &'static str {
+        "Read a specific web page, PDF or text document; the query must be the full URL"
+    }
+
+    fn follow_up_hint(&self) -> &'static str {
+        "Read a specific page by its full URL (e.g. a promising search result)"
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "Full http(s) URL of the page or document"
+                }
+            },
+            "required": ["query"]
+        })
+    }
+
+    fn guidance(&self) -> ToolGuidance {
+        ToolGuidance {
+            name: "URL Reader".to_string(),
+            description: "Download a web page, PDF or text file and extract its main content".to_string(),
+            usage_pattern: "Use when the user shares a link or when a specific page is needed. Links in the user's message are read automatically.".to_string(),
+            parameters: vec![ToolParameter {
+                name: "query".to_string(),
+                param_type: "string".to_string(),
+                description: "Full http(s) URL of the page or document".to_string(),
+                required: true,
+                default_value: None,
+            }],
+            examples: vec![ToolExample {
+                scenario: "User pastes a blog post link and asks for a summary".to_string(),
+                reasoning: "The answer depends entirely on the linked page".to_string(),
+                sequence: vec![
+                    "FETCH_URL with the exact link from the message".to_string(),
+                    "Summarize the extracted text, citing the URL".to_string(),
+                ],
+            }],
+            reasoning_hints: vec![
+                "The query must be a complete URL, not a search phrase".to_string(),
+                "Use WEB_SEARCH first when no specific URL is known".to_string(),
+                "Some sites block automated access or are excluded in settings".to_string(),
+            ],
+        }
+    }
+
+    fn capability_key(&self) -> &'static str {
+        "url_reader"
+    }
+
+    fn capabilities(&self) -> Value {
+        json!({
+            "description": "Read web pages, PDFs and text documents by URL",
+            "best_for": ["summarizing shared links", "reading documentation pages", "reading papers and reports"],
+            "strengths": ["main-content extraction", "PDF support"],
+            "limitations": ["no JavaScript rendering", "size limit on downloads", "honours robots.txt"]
+        })
+    }
+
+    fn started_event(&self) -> &'static str {
+        "URL_FETCH_STARTED"
+    }
+
+    fn completed_event(&self) -> &'static str {
+        "URL_FETCH_COMPLETED"
+    }
+
+    fn started_payload(&self, query: &str) -> Value {
+        to_payload(UrlFetchStartedPayload {
+            url: query.trim().to_string(),
+        })
+    }
+
+    fn failed_payload(&self, query: &str, error: &str) -> Value {
+        to_payload(UrlFetchCompletedPayload {
+            url: query.trim().to_string(),
+            success: false,
+            title: None,
+            content_type: None,
+            characters: None,
+            truncated: false,
+            error: Some(error.to_string()),
+        })
+    }
+
+    fn cache_ttl(&self) -> Option<Duration> {
+        Some(Duration::from_secs(60 * 60))
+    }
+
+    /// Pages fetched under other limits or domain lists aren't reused
+    fn cache_key(&self, ctx: &ToolContext<'_>, query: &str) -> Option<String> {
+        let settings = ctx.config.url_fetch.clone().unwrap_or_default();
+        Some(format!("{} [{}]", query.trim(), settings.cache_scope()))
+    }
+
+    async fn execute(&self, ctx: &ToolContext<'_>, query: &str) -> Result<ToolOutput, String> {
+        let settings = ctx.config.url_fetch.clone().unwrap_or_default();
+        log::info!("Fetching URL: {}", query.trim());
+        let document = fetch_document(query, &settings).await?;
+        log::info!(
+            "Fetched {} ({}, {} chars{})",
+            document.url,
+            document.content_type,
+            document.text.len(),
+            if document.truncated {
+                ", truncated"
+            } else {
+                ""
+            }
+        );
+        if document.text.trim().is_empty() {
+            return Err(format!("No readable text found at {}", document.url));
+        }
+
+        let title = document
+            .title
+            .clone()
+            .unwrap_or_else(|| document.url.clone());
+        let context_text = format!(
+            "Content of {} ({}):\n\n{}",
+            document.url,
+            title,
+            truncate_chars(&document.text, MAX_CONTEXT_CHARS)
+        );
+
+        Ok(ToolOutput {
+            content: context_text.clone(),
+            context: Some(context_text),
+            sources: vec![ToolSource::new(&title, &document.url, Some(&document.text))],
+            completed_payload: to_payload(UrlFetchCompletedPayload {
+                url: document.url.clone(),
+                success: true,
+                title: document.title.clone(),
+                content_type: Some(document.content_type.clone()),
+                characters: Some(document.text.chars().count()),
+                truncated: document.truncated,
+                error: None,
+            }),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_urls_from_message() {
+        let urls = extract_urls(
+            "Summarize https://example.com/post?id=1. Also (see https://docs.rs/regex) and https://example.com/post?id=1",
+        );
+        assert_eq!(
+            urls,
+            vec!["https://example.com/post?id=1", "https://docs.rs/regex"]
+        );
+
+        let existing = vec![ToolDecision {
+            tool_type: ToolType::FetchUrl,
+            query: "https://docs.rs/regex".to_string(),
+            reasoning: String::new(),
+            priority: 1,
+            depends_on: Vec::new(),
+        }];
+        let decisions = auto_fetch_decisions(
+            "read https://docs.rs/regex and https://a.example/",
+            &existing,
+        );
+        assert_eq!(decisions.len(), 1);
+        assert_eq!(decisions[0].query, "https://a.example/");
+    }
+
+    #[test]
+    fn test_domain_allow_and_block_lists() {
+        let settings = UrlFetchSettings {
+            allowed_domains: Some(vec!["example.com".to_string()]),
+            blocked_domains: Some(vec!["private.example.com".to_string()]),
+            ..UrlFetchSettings::default()
+        };
+        let check = |url: &str| check_url_allowed(&Url::parse(url).unwrap(), &settings);
+        assert!(check("https://example.com/a").is_ok());
+        assert!(check("https://docs.example.com/a").is_ok());
+        assert!(check("https://private.example.com/a").is_err());
+        assert!(check("https://notexample.com/a").is_err());
+        assert!(check("ftp://example.com/file").is_err());
+        assert!(check_url_allowed(
+            &Url::parse("https://any.org").unwrap(),
+            &UrlFetchSettings::default()
+        )
+        .is_ok());
+
+        // Tightened settings don't reuse pages cached under the old ones
+        assert_ne!(
+            settings.cache_scope(),
+            UrlFetchSettings::default().cache_scope()
+        );
+        assert_eq!(
+            UrlFetchSettings::default().cache_scope(),
+            UrlFetchSettings {
+                max_bytes: Some(DEFAULT_MAX_BYTES),
+                respect_robots: Some(true),
+                ..UrlFetchSettings::default()
+            }
+            .cache_scope()
+        );
+    }
+
+    #[test]
+    fn test_private_addresses_are_not_public() {
+        let public = |ip: &str| is_public_ip(ip.parse().unwrap());
+        assert!(public("93.184.216.34"));
+        assert!(public("2606:2800:220:1:248:1893:25c8:1946"));
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.10",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fe80::1",
+            "fd00::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!public(ip), "{} should not be public", ip);
+        }
+    }
+
+    #[test]
+    fn test_robots_rules() {
+        let robots = "User-agent: *\nDisallow: /private\nAllow: /private/public\n\n# Shard gets its own rules\nUser-agent: Shard\nUser-agent: OtherBot\nDisallow: /drafts/\n";
+        assert!(robots_allows(robots, "/private/page"));
+        assert!(!robots_allows(robots, "/drafts/post"));
+
+        let generic = "User-agent: *\nDisallow: /private\nAllow: /private/public\nDisallow:\n";
+        assert!(!robots_allows(generic, "/private/page"));
+        assert!(robots_allows(generic, "/private/public/page"));
+        assert!(robots_allows(generic, "/"));
+        assert!(robots_allows("", "/anything"));
+    }
+}
@@ -0,0 +1,1301 @@
+//! Stock price tool
+//!
+//! Fetches quotes for one or more ticker symbols from Yahoo Finance (stocks,
+//! indices like `^GSPC`, currency pairs like `EURUSD=X`, crypto pairs like
+//! `BTC-USD` and futures like `GC=F`; common names such as "S&P 500", "gold"
+//! or "EUR/USD" are resolved to these symbols): the latest daily quote, or the
+//! history over a range (`6mo`, `ytd`, `from:2024-01-01`) at a daily or intraday
+//! interval. Results give the percentage change over the period, a comparison
+//! table when several tickers are asked for, and the price series as chart data
+//! in FINANCIAL_DATA_COMPLETED for the UI to plot.
+//!
+//! Queries that name companies rather than symbols are resolved with the ticker
+//! table (see `tickers`); only names it doesn't know are sent to the model, and
+//! every symbol is checked before it's looked up.
+
+use super::citations::ToolSource;
+use super::tickers::{self, TickerTable};
+use super::{to_payload, Tool, ToolContext, ToolOutput};
+use crate::mcp_simple::{ToolExample, ToolGuidance, ToolParameter};
+use crate::{call_gemini_api_non_streaming, ChatMessage, ToolDecision, ToolType};
+use async_trait::async_trait;
+use futures_util::future::join_all;
+use regex::Regex;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tauri::Manager;
+use time::macros::format_description;
+use time::{Date, OffsetDateTime};
+use yahoo_finance_api as yfa; // Using an alias for brevity
+
+/// Ranges accepted by the Yahoo chart API
+const RANGES: [&str; 11] = [
+    "1d", "5d", "1mo", "3mo", "6mo", "1y", "2y", "5y", "10y", "ytd", "max",
+];
+/// Intervals accepted by the Yahoo chart API
+const INTERVALS: [&str; 13] = [
+    "1m", "2m", "5m", "15m", "30m", "60m", "90m", "1h", "1d", "5d", "1wk", "1mo", "3mo",
+];
+/// Most tickers compared in one lookup
+const MAX_SYMBOLS: usize = 8;
+/// Rows of the price table given to the model; longer series are sampled
+const MAX_TABLE_ROWS: usize = 20;
+/// Points per series in the chart payload
+const MAX_CHART_POINTS: usize = 500;
+/// Words joining the symbols of a comparison, never tickers themselves
+const CONNECTORS: [&str; 10] = [
+    "vs", "vs.", "versus", "and", "or", "the", "with", "against", "compared", "to",
+];
+/// Words around company names that don't name a company themselves
+const FILLER_WORDS: [&str; 11] = [
+    "stock", "stocks", "share", "shares", "price", "prices", "quote", "quotes", "chart", "of",
+    "for",
+];
+
+/// Indices, futures and crypto pairs by Yahoo symbol, with their names and the
+/// ways people write them.
+///
+/// Lowercase aliases don't match all-caps text, which is read as a stock ticker
+/// ("GOLD" is Barrick Gold, "DOW" is Dow Inc.); uppercase ones match any case.
+const MARKETS: [(&str, &str, &[&str]); 31] = [
+    (
+        "^GSPC",
+        "S&P 500",
+        &["S&P 500", "S&P500", "S&P", "SPX", "sp500"],
+    ),
+    (
+        "^DJI",
+        "Dow Jones Industrial Average",
+        &["dow jones", "DJIA", "the dow"],
+    ),
+    ("^IXIC", "Nasdaq Composite", &["nasdaq composite", "nasdaq"]),
+    ("^NDX", "Nasdaq-100", &["nasdaq 100", "nasdaq-100", "NDX"]),
+    ("^RUT", "Russell 2000", &["russell 2000"]),
+    (
+        "^VIX",
+        "CBOE Volatility Index",
+        &["VIX", "volatility index"],
+    ),
+    ("^FTSE", "FTSE 100", &["FTSE 100", "FTSE"]),
+    ("^GDAXI", "DAX", &["DAX"]),
+    ("^FCHI", "CAC 40", &["CAC 40"]),
+    ("^STOXX50E", "Euro Stoxx 50", &["euro stoxx 50", "stoxx 50"]),
+    ("^N225", "Nikkei 225", &["nikkei 225", "nikkei"]),
+    ("^HSI", "Hang Seng Index", &["hang seng"]),
+    (
+        "^TNX",
+        "US 10-year Treasury yield",
+        &["10-year treasury", "10 year treasury"],
+    ),
+    ("GC=F", "Gold futures", &["gold"]),
+    ("SI=F", "Silver futures", &["silver"]),
+    ("PL=F", "Platinum futures", &["platinum"]),
+    ("HG=F", "Copper futures", &["copper"]),
+    (
+        "CL=F",
+        "WTI crude oil futures",
+        &["crude oil", "WTI", "oil"],
+    ),
+    ("BZ=F", "Brent crude oil futures", &["brent crude", "brent"]),
+    ("NG=F", "Natural gas futures", &["natural gas"]),
+    ("ZC=F", "Corn futures", &["corn"]),
+    ("ZW=F", "Wheat futures", &["wheat"]),
+    ("ZS=F", "Soybean futures", &["soybeans", "soybean"]),
+    ("KC=F", "Coffee futures", &["coffee"]),
+    ("BTC-USD", "Bitcoin", &["bitcoin"]),
+    ("ETH-USD", "Ethereum", &["ethereum", "ether"]),
+    ("SOL-USD", "Solana", &["solana"]),
+    ("XRP-USD", "XRP", &["ripple"]),
+    ("ADA-USD", "Cardano", &["cardano"]),
+    ("DOGE-USD", "Dogecoin", &["dogecoin"]),
+    ("LTC-USD", "Litecoin", &["litecoin"]),
+];
+
+/// ISO codes of currencies Yahoo quotes exchange rates for
+const CURRENCIES: [&str; 24] = [
+    "USD", "EUR", "JPY", "GBP", "CHF", "CAD", "AUD", "NZD", "CNY", "HKD", "SGD", "SEK", "NOK",
+    "DKK", "PLN", "CZK", "HUF", "TRY", "ZAR", "MXN", "BRL", "INR", "KRW", "RUB",
+];
+
+/// Crypto tickers quoted as `BASE-QUOTE` pairs
+const CRYPTOCURRENCIES: [&str; 12] = [
+    "BTC", "ETH", "SOL", "XRP", "ADA", "DOGE", "LTC", "DOT", "AVAX", "LINK", "BNB", "USDT",
+];
+
+/// Replaces market names in a query ("S&P 500 vs gold") with their Yahoo symbols
+fn resolve_market_names(query: &str) -> String {
+    static NAMES: OnceLock<Regex> = OnceLock::new();
+    let names = NAMES.get_or_init(|| {
+        let mut aliases: Vec<&str> = MARKETS
+            .iter()
+            .flat_map(|(_, _, aliases)| aliases.iter().copied())
+            .collect();
+        // Longest first, so "nasdaq 100" wins over "nasdaq"
+        aliases.sort_by_key(|alias| std::cmp::Reverse(alias.len()));
+        let pattern = aliases
+            .iter()
+            .map(|alias| regex::escape(alias))
+            .collect::<Vec<_>>()
+            .join("|");
+        Regex::new(&format!(r"(?i)\b(?:{})\b", pattern)).expect("market name regex is valid")
+    });
+    names
+        .replace_all(query, |captures: &regex::Captures| {
+            let text = &captures[0];
+            MARKETS
+                .iter()
+                .find_map(|(symbol, _, aliases)| {
+                    aliases.iter().find(|alias| {
+                        alias.eq_ignore_ascii_case(text)
+                            && (!alias.chars().any(|c| c.is_lowercase())
+                                || text.chars().any(|c| c.is_lowercase()))
+                    })?;
+                    Some(symbol.to_string())
+                })
+                .unwrap_or_else(|| text.to_string())
+        })
+        .into_owned()
+}
+
+/// The Yahoo symbol for a currency or crypto pair written as `EUR/USD`, `eurusd`
+/// or `btc/usd`
+fn normalize_pair(token: &str) -> Option<String> {
+    let upper = token.to_uppercase();
+    let (base, quote) = match upper.split_once(['/', '-']) {
+        Some(pair) => pair,
+        None if upper.len() == 6 && upper.is_ascii() => upper.split_at(3),
+        None => return None,
+    };
+    if CRYPTOCURRENCIES.contains(&base)
+        && (CURRENCIES.contains(&quote) || CRYPTOCURRENCIES.contains(&quote))
+    {
+        Some(format!("{}-{}", base, quote))
+    } else if CURRENCIES.contains(&base) && CURRENCIES.contains(&quote) && base != quote {
+        Some(format!("{}{}=X", base, quote))
+    } else {
+        None
+    }
+}
+
+/// The symbol with the name of what it quotes, e.g. "^GSPC (S&P 500)" or
+/// "EURUSD=X (EUR/USD exchange rate)"
+fn symbol_label(symbol: &str) -> String {
+    if let Some((_, name, _)) = MARKETS.iter().find(|(s, _, _)| *s == symbol) {
+        return format!("{} ({})", symbol, name);
+    }
+    match symbol.strip_suffix("=X") {
+        Some(pair) if pair.len() == 6 => {
+            format!("{} ({}/{} exchange rate)", symbol, &pair[..3], &pair[3..])
+        }
+        _ => symbol.to_string(),
+    }
+}
+
+/// Prices below 10 (exchange rates, small coins) get four decimals
+fn price(value: f64) -> String {
+    if value.abs() < 10.0 {
+        format!("{:.4}", value)
+    } else {
+        format!("{:.2}", value)
+    }
+}
+
+// --- Financial Data Event Payloads ---
+#[derive(Serialize, Clone, Debug)]
+struct FinancialDataStartedPayload {
+    query: String,
+    symbol: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+struct FinancialDataCompletedPayload {
+    query: String,
+    symbol: String,
+    success: bool,
+    data: Option<String>, // Formatted financial data string
+    error: Option<String>,
+    /// Tickers looked up, in the order asked for
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    symbols: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    chart: Option<ChartData>,
+}
+
+/// Price series for the UI to plot, one per ticker
+#[derive(Serialize, Clone, Debug, PartialEq)]
+struct ChartData {
+    range: String,
+    interval: String,
+    series: Vec<PriceSeries>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+struct PriceSeries {
+    symbol: String,
+    /// Change from the first to the last close, in percent
+    change_percent: Option<f64>,
+    points: Vec<PricePoint>,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+struct PricePoint {
+    /// Unix seconds
+    timestamp: i64,
+    open: f64,
+    high: f64,
+    low: f64,
+    close: f64,
+    volume: u64,
+}
+
+/// Tickers and the period a query asks for
+///
+/// Without a range, interval or dates the latest daily quote is looked up.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct QuoteRequest {
+    symbols: Vec<String>,
+    range: Option<String>,
+    interval: Option<String>,
+    start: Option<Date>,
+    end: Option<Date>,
+    /// Words that are neither symbols nor part of the period, e.g. company names
+    unmatched: Vec<String>,
+}
+
+fn parse_date(text: &str) -> Option<Date> {
+    Date::parse(text, format_description!("[year]-[month]-[day]")).ok()
+}
+
+/// Smallest Yahoo range covering the given number of days
+fn range_for_days(days: u32) -> &'static str {
+    match days {
+        0..=1 => "1d",
+        2..=5 => "5d",
+        6..=31 => "1mo",
+        32..=92 => "3mo",
+        93..=183 => "6mo",
+        184..=366 => "1y",
+        367..=731 => "2y",
+        732..=1827 => "5y",
+        _ => "10y",
+    }
+}
+
+/// Days in "day(s)", "week(s)", "month(s)" or "year(s)", rounded up
+fn days_in_unit(unit: &str) -> Option<u32> {
+    match unit.trim_end_matches('s') {
+        "day" => Some(1),
+        "week" => Some(7),
+        "month" => Some(30),
+        "year" => Some(365),
+        _ => None,
+    }
+}
+
+fn looks_like_ticker(token: &str) -> bool {
+    token.len() <= 12
+        && token.chars().any(|c| c.is_ascii_uppercase())
+        && !token.chars().any(|c| c.is_ascii_lowercase())
+        && token
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '=' | '^'))
+        && !CONNECTORS.contains(&token.to_lowercase().as_str())
+}
+
+impl QuoteRequest {
+    fn parse(query: &str) -> QuoteRequest {
+        let mut request = QuoteRequest::default();
+        let resolved = resolve_market_names(query);
+        let tokens: Vec<&str> = resolved
+            .split(|c: char| c.is_whitespace() || c == ',' || c == ';')
+            .filter(|t| !t.is_empty())
+            .collect();
+        let mut candidates: Vec<String> = Vec::new();
+        let mut previous = String::new();
+        for (i, token) in tokens.iter().enumerate() {
+            let lower = token.to_lowercase();
+            let next = tokens
+                .get(i + 1)
+                .map(|t| t.to_lowercase())
+                .unwrap_or_default();
+            if let Some((key, value)) = lower.split_once(':') {
+                match key {
+                    "range" if RANGES.contains(&value) => request.range = Some(value.to_string()),
+                    "interval" if INTERVALS.contains(&value) => {
+                        request.interval = Some(value.to_string())
+                    }
+                    "from" | "since" | "start" => request.start = parse_date(value),
+                    "to" | "until" | "end" => request.end = parse_date(value),
+                    _ => {}
+                }
+            } else if let Some(date) = parse_date(&lower) {
+                if request.start.is_none() {
+                    request.start = Some(date);
+                } else {
+                    request.end = Some(date);
+                }
+            } else if RANGES.contains(&lower.as_str()) {
+                request.range = Some(lower.clone());
+            } else if let (Ok(count), Some(days)) = (lower.parse::<u32>(), days_in_unit(&next)) {
+                // "6 months", "2 years"
+                request.range = Some(range_for_days(count.saturating_mul(days)).to_string());
+            } else if let Some(days) = days_in_unit(&lower) {
+                // "past month", "last year", "this year"
+                if previous == "this" && days == 365 {
+                    request.range = Some("ytd".to_string());
+                } else if matches!(previous.as_str(), "past" | "last") {
+                    request.range = Some(range_for_days(days).to_string());
+                }
+            } else {
+                match lower.as_str() {
+                    "today" | "intraday" => {
+                        request.range.get_or_insert_with(|| "1d".to_string());
+                    }
+                    "hourly" => request.interval = Some("1h".to_string()),
+                    "daily" => request.interval = Some("1d".to_string()),
+                    "weekly" => request.interval = Some("1wk".to_string()),
+                    "monthly" => request.interval = Some("1mo".to_string()),
+                    connector if CONNECTORS.contains(&connector) => {}
+                    _ => {
+                        candidates.push(normalize_pair(token).unwrap_or_else(|| token.to_string()))
+                    }
+                }
+            }
+            if lower != "the" {
+                previous = lower;
+            }
+        }
+
+        let (symbols, unmatched): (Vec<String>, Vec<String>) =
+            candidates.into_iter().partition(|t| looks_like_ticker(t));
+        request.unmatched = unmatched;
+        for symbol in symbols {
+            request.add_symbol(symbol);
+        }
+        request
+    }
+
+    fn add_symbol(&mut self, symbol: String) {
+        if !self.symbols.contains(&symbol) && self.symbols.len() < MAX_SYMBOLS {
+            self.symbols.push(symbol);
+        }
+    }
+
+    /// Resolves the words that aren't symbols: company names from the ticker table
+    /// ("Apple", "Tesla 1y"), otherwise a lone lowercase word such as "aapl" is
+    /// taken as a ticker. Names the table doesn't know stay in `unmatched`.
+    fn resolve_names(&mut self, query: &str, table: &TickerTable) {
+        if self.unmatched.is_empty() {
+            return;
+        }
+        let (names, rest) = table.resolve_with_rest(query, false);
+        if !names.is_empty() {
+            for symbol in names {
+                self.add_symbol(symbol);
+            }
+            self.unmatched.retain(|token| {
+                let word = token.trim_matches(|c: char| !c.is_alphanumeric());
+                rest.iter()
+                    .any(|rest| rest.trim_matches(|c: char| !c.is_alphanumeric()) == word)
+                    && !FILLER_WORDS.contains(&word.to_lowercase().as_str())
+            });
+        } else if let [word] = &self.unmatched[..] {
+            let upper = word.to_uppercase();
+            if self.symbols.is_empty() && upper.len() <= 5 && looks_like_ticker(&upper) {
+                self.symbols.push(upper);
+                self.unmatched.clear();
+            }
+        }
+    }
+
+    fn is_history(&self) -> bool {
+        self.range.is_some() || self.interval.is_some() || self.start.is_some()
+    }
+
+    fn range(&self) -> &str {
+        match (&self.range, self.is_history()) {
+            (Some(range), _) => range,
+            (None, true) => "1mo",
+            // The last few days give the latest quote and the previous close
+            (None, false) => "5d",
+        }
+    }
+
+    /// The requested interval, or one giving a readable number of points for the period
+    fn interval(&self) -> &str {
+        if let Some(interval) = &self.interval {
+            return interval;
+        }
+        if self.start.is_some() {
+            return "1d";
+        }
+        match self.range() {
+            "1d" => "5m",
+            "5d" if self.is_history() => "30m",
+            "2y" | "5y" => "1wk",
+            "10y" | "max" => "1mo",
+            _ => "1d",
+        }
+    }
+
+    fn is_intraday(&self) -> bool {
+        !matches!(self.interval(), "1d" | "5d" | "1wk" | "1mo" | "3mo")
+    }
+
+    /// The period in words, e.g. "6mo at 1d interval"
+    fn describe(&self) -> String {
+        match (self.start, self.end) {
+            (Some(start), Some(end)) => {
+                format!("{} to {} at {} interval", start, end, self.interval())
+            }
+            (Some(start), None) => format!("since {} at {} interval", start, self.interval()),
+            _ => format!("{} at {} interval", self.range(), self.interval()),
+        }
+    }
+
+    /// The request as a query again, for follow-up lookups with resolved tickers
+    fn to_query(&self) -> String {
+        let mut parts = self.symbols.clone();
+        if let Some(range) = &self.range {
+            parts.push(format!("range:{}", range));
+        }
+        if let Some(interval) = &self.interval {
+            parts.push(format!("interval:{}", interval));
+        }
+        if let Some(start) = self.start {
+            parts.push(format!("from:{}", start));
+        }
+        if let Some(end) = self.end {
+            parts.push(format!("to:{}", end));
+        }
+        parts.join(" ")
+    }
+}
+
+// --- ADDED: Stock Price Data Lookup Function ---
+async fn perform_financial_data_lookup(
+    provider: &yfa::YahooConnector,
+    symbol: &str,
+    request: &QuoteRequest,
+) -> Result<Vec<PricePoint>, String> {
+    log::info!(
+        "Performing stock price data lookup for symbol: '{}' ({}) using yahoo_finance_api",
+        symbol,
+        request.describe()
+    );
+
+    let response = match request.start {
+        Some(start) => {
+            let end = request
+                .end
+                .and_then(|end| end.next_day())
+                .map(|end| end.midnight().assume_utc())
+                .unwrap_or_else(OffsetDateTime::now_utc);
+            provider
+                .get_quote_history_interval(
+                    symbol,
+                    start.midnight().assume_utc(),
+                    end,
+                    request.interval(),
+                )
+                .await
+        }
+        None => {
+            provider
+                .get_quote_range(symbol, request.interval(), request.range())
+                .await
+        }
+    };
+    let quotes = response
+        .and_then(|response| response.quotes())
+        .map_err(|e| {
+            let err_msg = format!(
+                "Failed to retrieve stock price data for {} from yahoo_finance_api: {}",
+                symbol, e
+            );
+            log::error!("{}", err_msg);
+            err_msg
+        })?;
+
+    // Yahoo fills gaps (halts, the current bar) with zero prices
+    let points: Vec<PricePoint> = quotes
+        .into_iter()
+        .filter(|quote| quote.close > 0.0)
+        .map(|quote| PricePoint {
+            timestamp: quote.timestamp as i64,
+            open: quote.open,
+            high: quote.high,
+            low: quote.low,
+            close: quote.close,
+            volume: quote.volume,
+        })
+        .collect();
+    if points.is_empty() {
+        let msg = format!("No stock price data found for symbol {}.", symbol);
+        log::warn!("Stock price data lookup for symbol '{}': {}", symbol, msg);
+        return Err(msg);
+    }
+    log::info!(
+        "Stock price data lookup successful for symbol: '{}'. {} quotes",
+        symbol,
+        points.len()
+    );
+    Ok(points)
+}
+
+fn format_timestamp(timestamp: i64, intraday: bool) -> String {
+    let formatted = OffsetDateTime::from_unix_timestamp(timestamp).map(|dt| {
+        if intraday {
+            dt.format(format_description!(
+                "[year]-[month]-[day] [hour]:[minute] UTC"
+            ))
+        } else {
+            dt.format(format_description!("[year]-[month]-[day]"))
+        }
+    });
+    match formatted {
+        Ok(Ok(text)) => text,
+        _ => timestamp.to_string(),
+    }
+}
+
+fn change_percent(from: f64, to: f64) -> Option<f64> {
+    (from > 0.0).then(|| (to - from) / from * 100.0)
+}
+
+/// Every n-th element so at most `max` remain, always keeping the last one
+fn sample<T: Copy>(items: &[T], max: usize) -> Vec<T> {
+    if items.len() <= max || max < 2 {
+        return items.to_vec();
+    }
+    let step = (items.len() - 1) as f64 / (max - 1) as f64;
+    (0..max)
+        .map(|i| items[((i as f64 * step).round() as usize).min(items.len() - 1)])
+        .collect()
+}
+
+/// The latest daily quote, as the tool has always reported it, with the day's change
+fn latest_quote_text(symbol: &str, points: &[PricePoint]) -> String {
+    let quote = points[points.len() - 1];
+    let mut text = format!(
+        "Latest data for {}: Date: {}, Open: {}, High: {}, Low: {}, Close: {}, Volume: {}",
+        symbol_label(symbol),
+        format_timestamp(quote.timestamp, false),
+        price(quote.open),
+        price(quote.high),
+        price(quote.low),
+        price(quote.close),
+        quote.volume
+    );
+    if let Some(previous) = points.len().checked_sub(2).map(|i| points[i]) {
+        if let Some(change) = change_percent(previous.close, quote.close) {
+            text.push_str(&format!(
+                ", Change: {:+.2}% from previous close ({})",
+                change,
+                price(previous.close)
+            ));
+        }
+    }
+    text
+}
+
+/// Period summary of one ticker, optionally followed by a (sampled) price table
+fn history_text(
+    symbol: &str,
+    request: &QuoteRequest,
+    points: &[PricePoint],
+    table: bool,
+) -> String {
+    let intraday = request.is_intraday();
+    let first = points[0];
+    let last = points[points.len() - 1];
+    let high = points.iter().map(|p| p.high).fold(f64::MIN, f64::max);
+    let low = points.iter().map(|p| p.low).fold(f64::MAX, f64::min);
+    let average_volume = points.iter().map(|p| p.volume).sum::<u64>() / points.len() as u64;
+    let mut text = format!(
+        "{}, {} ({} to {}, {} quotes): Close {} -> {}",
+        symbol_label(symbol),
+        request.describe(),
+        format_timestamp(first.timestamp, intraday),
+        format_timestamp(last.timestamp, intraday),
+        points.len(),
+        price(first.close),
+        price(last.close)
+    );
+    if let Some(change) = change_percent(first.close, last.close) {
+        text.push_str(&format!(" ({:+.2}%)", change));
+    }
+    text.push_str(&format!(
+        ", High: {}, Low: {}, Average volume: {}",
+        price(high),
+        price(low),
+        average_volume
+    ));
+    if table {
+        text.push_str("\nDate | Open | High | Low | Close | Volume");
+        for point in sample(points, MAX_TABLE_ROWS) {
+            text.push_str(&format!(
+                "\n{} | {} | {} | {} | {} | {}",
+                format_timestamp(point.timestamp, intraday),
+                price(point.open),
+                price(point.high),
+                price(point.low),
+                price(point.close),
+                point.volume
+            ));
+        }
+    }
+    text
+}
+
+/// Side-by-side table of several tickers over the same period
+fn comparison_text(request: &QuoteRequest, series: &[PriceSeries]) -> String {
+    let mut text = if request.is_history() {
+        format!(
+            "Comparison, {}:\nSymbol | First close | Last close | Change | High | Low",
+            request.describe()
+        )
+    } else {
+        "Comparison of latest quotes:\nSymbol | Previous close | Last close | Change | High | Low"
+            .to_string()
+    };
+    for s in series {
+        // Latest quotes compare with the previous close, histories with the period's start
+        let points = if request.is_history() {
+            &s.points[..]
+        } else {
+            &s.points[s.points.len().saturating_sub(2)..]
+        };
+        let (first, last) = (points[0], points[points.len() - 1]);
+        let change = change_percent(first.close, last.close)
+            .map(|c| format!("{:+.2}%", c))
+            .unwrap_or_else(|| "n/a".to_string());
+        text.push_str(&format!(
+            "\n{} | {} | {} | {} | {} | {}",
+            symbol_label(&s.symbol),
+            price(first.close),
+            price(last.close),
+            change,
+            price(points.iter().map(|p| p.high).fold(f64::MIN, f64::max)),
+            price(points.iter().map(|p| p.low).fold(f64::MAX, f64::min))
+        ));
+    }
+    text
+}
+
+/// The research text for the tickers that were found, plus errors for those that weren't
+fn quotes_text(request: &QuoteRequest, series: &[PriceSeries], errors: &[String]) -> String {
+    let mut sections: Vec<String> = Vec::new();
+    if series.len() > 1 {
+        sections.push(comparison_text(request, series));
+    }
+    for s in series {
+        sections.push(if request.is_history() {
+            history_text(&s.symbol, request, &s.points, series.len() == 1)
+        } else {
+            latest_quote_text(&s.symbol, &s.points)
+        });
+    }
+    sections.extend(errors.iter().cloned());
+    sections.join("\n\n")
+}
+
+/// Keeps the symbols that exist: known to the ticker table or the market names,
+/// or quoted by Yahoo. Model-suggested symbols are checked before they're looked up.
+async fn validate_symbols(table: &TickerTable, symbols: Vec<String>) -> Vec<String> {
+    let candidates: Vec<String> = symbols
+        .iter()
+        .filter_map(|symbol| {
+            let symbol = symbol.trim();
+            normalize_pair(symbol).or_else(|| {
+                let upper = symbol.to_uppercase();
+                looks_like_ticker(&upper).then_some(upper)
+            })
+        })
+        .collect();
+    let provider = match yfa::YahooConnector::new() {
+        Ok(provider) => provider,
+        Err(e) => {
+            log::warn!("Failed to create YahooConnector: {}", e);
+            return candidates;
+        }
+    };
+    let checks = candidates.iter().map(|symbol| {
+        let provider = &provider;
+        async move {
+            if table.contains(symbol)
+                || MARKETS
+                    .iter()
+                    .any(|(market, _, _)| *market == symbol.as_str())
+                || symbol.ends_with("=X")
+            {
+                return true;
+            }
+            provider
+                .get_latest_quotes(symbol, "1d")
+                .await
+                .and_then(|response| response.last_quote())
+                .is_ok()
+        }
+    });
+    let valid = join_all(checks).await;
+    let mut validated = Vec::new();
+    for (symbol, valid) in candidates.into_iter().zip(valid) {
+        if !valid {
+            log::warn!("Dropping unknown ticker symbol: {}", symbol);
+        } else if !validated.contains(&symbol) {
+            validated.push(symbol);
+        }
+    }
+    validated
+}
+
+/// Parses a query and resolves its symbols: symbols and market names as written,
+/// company names from the ticker table (the query, then the research so far), and
+/// the model only for names the table doesn't know. Every symbol is validated.
+async fn resolve_request(
+    ctx: &ToolContext<'_>,
+    query: &str,
+    research_context: &str,
+) -> Result<QuoteRequest, String> {
+    let table = tickers::ticker_table(ctx.window.app_handle());
+    let mut request = QuoteRequest::parse(query);
+    request.resolve_names(query, &table);
+    // Only the names the table doesn't know go to the model
+    let mut unknown = request.unmatched.join(" ");
+    if request.symbols.is_empty() {
+        // "compare their stock prices": the companies are in the research so far
+        let mentioned = table.resolve(research_context, true);
+        if mentioned.is_empty() {
+            unknown = format!("{} {}", query, research_context);
+        } else {
+            unknown.clear();
+        }
+        for symbol in mentioned {
+            request.add_symbol(symbol);
+        }
+    }
+    if !request.symbols.is_empty() {
+        log::info!("Resolved ticker symbols: {:?}", request.symbols);
+    }
+    if !unknown.trim().is_empty() {
+        match extract_ticker_symbols_from_companies(
+            ctx.client,
+            unknown.trim(),
+            ctx.gemini_api_key,
+            ctx.model_name.to_string(),
+        )
+        .await
+        {
+            Ok(symbols) => {
+                for symbol in symbols {
+                    request.add_symbol(symbol);
+                }
+            }
+            // The symbols already resolved are still worth looking up
+            Err(_) if !request.symbols.is_empty() => {}
+            Err(e) => return Err(e),
+        }
+    }
+    request.symbols = validate_symbols(&table, std::mem::take(&mut request.symbols)).await;
+    Ok(request)
+}
+
+// --- ADDED: Ticker Symbol Extractor Function ---
+async fn extract_ticker_symbols_from_companies(
+    client: &reqwest::Client,
+    company_text: &str,
+    api_key: &str,
+    model_name: String,
+) -> Result<Vec<String>, String> {
+    // Input that only names symbols ("IBM IONQ", "S&P 500, gold") needs no model call
+    let request = QuoteRequest::parse(company_text);
+    if !request.symbols.is_empty() && request.unmatched.is_empty() {
+        log::info!("Input appears to be ticker symbols: {:?}", request.symbols);
+        return Ok(request.symbols);
+    }
+
+    let prompt = format!(
+        "Extract Yahoo Finance ticker symbols from the following text about companies and markets. Return ONLY a JSON array of ticker symbols (e.g., [\"AAPL\", \"GOOGL\", \"TSLA\"]). \
+        Only include publicly traded companies with valid stock symbols, and market indices (^GSPC, ^DJI, ^IXIC, ^FTSE, ^N225), currency pairs (EURUSD=X), crypto pairs (BTC-USD) and futures (GC=F for gold, CL=F for crude oil). \
+        Use the exchange suffix for non-US listings (7203.T, SAP.DE, SHEL.L). If no public companies or markets are found, return an empty array [].\n\n\
+        IMPORTANT: If the input is already ticker symbols (like 'GOOGL', 'IBM', 'TSLA'), just return them as-is in the array.\n\n\
+        Examples:\n\
+        - Apple Inc. → [\"AAPL\"]\n\
+        - Google and Microsoft → [\"GOOGL\", \"MSFT\"]\n\
+        - IBM, Tesla, and Amazon → [\"IBM\", \"TSLA\", \"AMZN\"]\n\
+        - GOOGL → [\"GOOGL\"]\n\
+        - IBM IONQ → [\"IBM\", \"IONQ\"]\n\
+        - the S&P 500 and the Japanese yen against the dollar → [\"^GSPC\", \"USDJPY=X\"]\n\
+        - Bitcoin and crude oil → [\"BTC-USD\", \"CL=F\"]\n\
+        - Private company XYZ → []\n\n\
+        Text to analyze:\n{}",
+        company_text
+    );
+
+    let messages = vec![ChatMessage {
+        role: "user".to_string(),
+        content: prompt,
+        image_base64_data: None,
+        image_mime_type: None,
+        image_file_api_uri: None,
+        document_ids: None,
+    }];
+
+    match call_gemini_api_non_streaming(client, messages, api_key, model_name).await {
+        Ok(response_text) => {
+            let cleaned_response = response_text
+                .trim()
+                .trim_start_matches("```json")
+                .trim_start_matches("```")
+                .trim_end_matches("```")
+                .trim();
+
+            match serde_json::from_str::<Vec<String>>(cleaned_response) {
+                Ok(symbols) => {
+                    log::info!("Extracted ticker symbols: {:?}", symbols);
+                    Ok(symbols)
+                }
+                Err(e) => {
+                    log::warn!(
+                        "Failed to parse ticker symbols from response '{}': {}",
+                        cleaned_response,
+                        e
+                    );
+                    Ok(vec![]) // Return empty vec on parse failure
+                }
+            }
+        }
+        Err(e) => {
+            log::error!("Failed to extract ticker symbols: {}", e);
+            Err(e)
+        }
+    }
+}
+
+pub struct FinancialTool;
+
+#[async_trait]
+impl Tool for FinancialTool {
+    fn tool_type(&self) -> ToolType {
+        ToolType::FinancialData
+    }
+
+    fn description(&self) -> &'static str {
+        "Prices, price history and comparisons of stocks, market indices, exchange rates, crypto and commodity futures (use Yahoo symbols like AAPL, ^GSPC, EURUSD=X, BTC-USD, GC=F or names like 'S&P 500', 'gold', 'EUR/USD', optionally with a range like 6mo or from:2024-01-01) - NOT for valuations, GDP, economic indicators, investor sentiment, etc."
+    }
+
+    fn follow_up_hint(&self) -> &'static str {
+        "Prices and price history of stocks, indices, currencies, crypto and commodities (use symbols like AAPL, ^GSPC, EURUSD=X, BTC-USD, GC=F, plus a range like 1y) - NOT for valuations, GDP, or economic data"
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "One or more Yahoo Finance symbols (stocks like AAPL, indices like ^GSPC, currency pairs like EURUSD=X or EUR/USD, crypto pairs like BTC-USD, futures like GC=F) or market names (S&P 500, Nasdaq, gold, crude oil, bitcoin), optionally followed by a period: a range (1d, 5d, 1mo, 3mo, 6mo, 1y, 2y, 5y, 10y, ytd, max), dates (from:2024-01-01 to:2024-06-30) and an interval (interval:1h). Examples: 'AAPL', 'AAPL 6mo', 'NVDA AMD INTC 1y', 'TSLA 1d interval:15m', '^GSPC ^IXIC ytd', 'EUR/USD 3mo', 'BTC-USD gold 1y'"
+                }
+            },
+            "required": ["query"]
+        })
+    }
+
+    fn guidance(&self) -> ToolGuidance {
+        ToolGuidance {
+            name: "Stock Price Data Lookup".to_string(),
+            description: "Retrieve prices, trading volumes and price history for stocks, market indices, exchange rates, crypto and commodity futures, and compare several over the same period"
+                .to_string(),
+            usage_pattern:
+                "Use for current quotes, price movements over a period, and comparisons. Requires Yahoo symbols (AAPL, ^GSPC, EURUSD=X, BTC-USD, GC=F) or common market names (S&P 500, gold, EUR/USD); add a range (6mo, 1y, ytd) or dates (from:2024-01-01) for history."
+                    .to_string(),
+            parameters: vec![ToolParameter {
+                name: "query".to_string(),
+                param_type: "string".to_string(),
+                description: "Ticker symbols or market names, optionally with a range (1d to max, ytd), from:/to: dates and interval:1h".to_string(),
+                required: true,
+                default_value: None,
+            }],
+            examples: vec![ToolExample {
+                scenario: "User asks about a company's current stock price".to_string(),
+                reasoning: "Need real-time stock price and trading data".to_string(),
+                sequence: vec![
+                    "Identify the correct stock ticker symbol".to_string(),
+                    "Retrieve current price, volume, and daily trading data".to_string(),
+                ],
+            },
+            ToolExample {
+                scenario: "User asks how several companies' stocks did this year".to_string(),
+                reasoning: "One lookup with all tickers and the period gives a comparison table".to_string(),
+                sequence: vec![
+                    "Identify the ticker symbols, e.g. 'NVDA AMD INTC'".to_string(),
+                    "Add the period, e.g. 'NVDA AMD INTC ytd'".to_string(),
+                    "Compare the percentage changes over the period".to_string(),
+                ],
+            },
+            ToolExample {
+                scenario: "User asks how the stock market and the euro moved this month".to_string(),
+                reasoning: "Indices and exchange rates are quoted like stocks".to_string(),
+                sequence: vec![
+                    "Use index and currency pair symbols, e.g. '^GSPC EURUSD=X 1mo'".to_string(),
+                    "Report the change of each over the month".to_string(),
+                ],
+            }],
+            reasoning_hints: vec![
+                "Essential for investment discussions and stock analysis".to_string(),
+                "Combine with Wikipedia research for company context".to_string(),
+                "Stock prices are real-time but may have market hour limitations".to_string(),
+                "Put all tickers to compare in one query so they share a period".to_string(),
+                "Indices use a ^ prefix (^GSPC, ^DJI), currency pairs end in =X (EURUSD=X), crypto pairs are BASE-USD (BTC-USD) and futures end in =F (GC=F, CL=F)".to_string(),
+                "DO NOT use for: valuations, GDP, economic indicators, investor sentiment, or financial analysis beyond basic price data".to_string(),
+                "Use Wikipedia Research for broader financial topics and economic concepts".to_string(),
+            ],
+        }
+    }
+
+    fn capability_key(&self) -> &'static str {
+        "financial_data"
+    }
+
+    fn capabilities(&self) -> Value {
+        json!({
+            "description": "Real-time prices, price history and comparisons for stocks, market indices, exchange rates, crypto and commodity futures",
+            "best_for": ["stock quotes", "price movements", "price history", "stock comparisons", "trading volume", "market indices", "currency exchange rates", "cryptocurrency prices", "commodity prices"],
+            "strengths": ["real-time data", "accurate pricing", "historical ranges and intraday intervals", "percentage change over a period"],
+            "limitations": ["requires valid ticker symbols or well-known market names", "public companies only", "market hours dependent"],
+            "do_not_use_for": ["company valuations", "GDP data", "economic indicators", "investor sentiment", "financial news", "earnings reports", "economic forecasts", "interest rates", "bond data"]
+        })
+    }
+
+    fn started_event(&self) -> &'static str {
+        "FINANCIAL_DATA_STARTED"
+    }
+
+    fn completed_event(&self) -> &'static str {
+        "FINANCIAL_DATA_COMPLETED"
+    }
+
+    fn started_payload(&self, query: &str) -> Value {
+        to_payload(FinancialDataStartedPayload {
+            query: query.to_string(),
+            symbol: query.to_string(),
+        })
+    }
+
+    fn failed_payload(&self, query: &str, error: &str) -> Value {
+        to_payload(FinancialDataCompletedPayload {
+            query: query.to_string(),
+            symbol: query.to_string(),
+            success: false,
+            data: None,
+            error: Some(error.to_string()),
+            symbols: QuoteRequest::parse(query).symbols,
+            chart: None,
+        })
+    }
+
+    /// Quotes move quickly, so only reuse them for a few minutes
+    fn cache_ttl(&self) -> Option<Duration> {
+        Some(Duration::from_secs(5 * 60))
+    }
+
+    /// Keyed on the resolved symbols and period. Queries that need the model to
+    /// find their symbols aren't cached, so they can't pick up another
+    /// conversation's companies.
+    fn cache_key(&self, ctx: &ToolContext<'_>, query: &str) -> Option<String> {
+        let table = tickers::ticker_table(ctx.window.app_handle());
+        let mut request = QuoteRequest::parse(query);
+        request.resolve_names(query, &table);
+        (!request.symbols.is_empty() && request.unmatched.is_empty()).then(|| request.to_query())
+    }
+
+    async fn execute(&self, ctx: &ToolContext<'_>, query: &str) -> Result<ToolOutput, String> {
+        let request = resolve_request(ctx, query, "").await?;
+        if request.symbols.is_empty() {
+            return Err(format!("No ticker symbol found in '{}'", query));
+        }
+        let provider = yfa::YahooConnector::new()
+            .map_err(|e| format!("Failed to create YahooConnector: {}", e))?;
+
+        let lookups = request
+            .symbols
+            .iter()
+            .map(|symbol| perform_financial_data_lookup(&provider, symbol, &request));
+        let mut series = Vec::new();
+        let mut errors = Vec::new();
+        for (symbol, result) in request.symbols.iter().zip(join_all(lookups).await) {
+            match result {
+                Ok(points) => series.push(PriceSeries {
+                    symbol: symbol.clone(),
+                    change_percent: change_percent(points[0].close, points[points.len() - 1].close),
+                    points,
+                }),
+                Err(e) => errors.push(e),
+            }
+        }
+        if series.is_empty() {
+            return Err(errors.join("\n"));
+        }
+
+        let financial_data = quotes_text(&request, &series, &errors);
+        let sources = series
+            .iter()
+            .map(|s| {
+                ToolSource::new(
+                    &format!("Yahoo Finance: {}", s.symbol),
+                    &format!(
+                        "https://finance.yahoo.com/quote/{}",
+                        urlencoding::encode(&s.symbol)
+                    ),
+                    Some(&quotes_text(&request, std::slice::from_ref(s), &[])),
+                )
+            })
+            .collect();
+        let chart = ChartData {
+            range: request.range().to_string(),
+            interval: request.interval().to_string(),
+            series: series
+                .into_iter()
+                .map(|s| PriceSeries {
+                    points: sample(&s.points, MAX_CHART_POINTS),
+                    ..s
+                })
+                .collect(),
+        };
+        Ok(ToolOutput {
+            content: financial_data.clone(),
+            context: Some(format!(
+                "Financial Data for '{}':\n{}",
+                query, financial_data
+            )),
+            sources,
+            completed_payload: to_payload(FinancialDataCompletedPayload {
+                query: query.to_string(),
+                symbol: query.to_string(),
+                success: true,
+                data: Some(financial_data),
+                error: None,
+                symbols: request.symbols.clone(),
+                chart: Some(chart),
+            }),
+        })
+    }
+
+    /// Follow-up requests often name companies; resolve them to ticker symbols first
+    async fn expand_follow_up(
+        &self,
+        ctx: &ToolContext<'_>,
+        decision: ToolDecision,
+        research_context: &str,
+    ) -> Vec<ToolDecision> {
+        match resolve_request(ctx, &decision.query, research_context).await {
+            // One lookup for all symbols, so they are compared over the same period
+            Ok(request) if !request.symbols.is_empty() => vec![ToolDecision {
+                tool_type: ToolType::FinancialData,
+                query: request.to_query(),
+                reasoning: format!(
+                    "Stock data for {} (resolved from: {})",
+                    request.symbols.join(", "),
+                    decision.reasoning
+                ),
+                priority: decision.priority,
+                depends_on: decision.depends_on.clone(),
+            }],
+            Ok(_) => {
+                // No valid symbols found, try the original query as fallback
+                log::warn!(
+                    "No valid ticker symbols resolved from: {}, trying original query",
+                    decision.query
+                );
+                vec![decision]
+            }
+            // Fallback: use the original query if extraction fails
+            Err(_) => vec![decision],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(timestamp: i64, close: f64) -> PricePoint {
+        PricePoint {
+            timestamp,
+            open: close - 1.0,
+            high: close + 2.0,
+            low: close - 2.0,
+            close,
+            volume: 1000,
+        }
+    }
+
+    fn series(symbol: &str, closes: &[f64]) -> PriceSeries {
+        let points: Vec<PricePoint> = closes
+            .iter()
+            .enumerate()
+            .map(|(i, close)| point(1_704_153_600 + i as i64 * 86_400, *close))
+            .collect();
+        PriceSeries {
+            symbol: symbol.to_string(),
+            change_percent: change_percent(closes[0], closes[closes.len() - 1]),
+            points,
+        }
+    }
+
+    #[test]
+    fn test_parses_tickers_ranges_and_dates() {
+        let latest = QuoteRequest::parse("AAPL");
+        assert_eq!(latest.symbols, vec!["AAPL"]);
+        assert!(!latest.is_history());
+        assert_eq!((latest.range(), latest.interval()), ("5d", "1d"));
+
+        let compare = QuoteRequest::parse("NVDA, AMD vs INTC 6mo");
+        assert_eq!(compare.symbols, vec!["NVDA", "AMD", "INTC"]);
+        assert_eq!((compare.range(), compare.interval()), ("6mo", "1d"));
+
+        let special = QuoteRequest::parse("^GSPC BRK-B 7203.T 5y");
+        assert_eq!(special.symbols, vec!["^GSPC", "BRK-B", "7203.T"]);
+        assert_eq!(special.interval(), "1wk");
+
+        let intraday = QuoteRequest::parse("TSLA today interval:15m");
+        assert_eq!((intraday.range(), intraday.interval()), ("1d", "15m"));
+        assert!(intraday.is_intraday());
+
+        let dates = QuoteRequest::parse("AAPL from:2024-01-01 2024-06-30");
+        assert_eq!(dates.start, parse_date("2024-01-01"));
+        assert_eq!(dates.end, parse_date("2024-06-30"));
+        assert_eq!(dates.interval(), "1d");
+        assert_eq!(dates.to_query(), "AAPL from:2024-01-01 to:2024-06-30");
+    }
+
+    #[test]
+    fn test_parses_periods_in_words() {
+        assert_eq!(QuoteRequest::parse("AAPL last 6 months").range(), "6mo");
+        assert_eq!(QuoteRequest::parse("AAPL over the past year").range(), "1y");
+        assert_eq!(QuoteRequest::parse("AAPL this year").range(), "ytd");
+        assert_eq!(QuoteRequest::parse("AAPL 2 weeks").range(), "1mo");
+        assert_eq!(QuoteRequest::parse("AAPL weekly 2y").interval(), "1wk");
+        assert_eq!(QuoteRequest::parse("AAPL 99999999 years").range(), "10y");
+    }
+
+    #[test]
+    fn test_resolves_indices_currencies_crypto_and_futures() {
+        let markets = QuoteRequest::parse("S&P 500 vs Nasdaq 100 and gold ytd");
+        assert_eq!(markets.symbols, vec!["^GSPC", "^NDX", "GC=F"]);
+        assert_eq!(markets.range(), "ytd");
+        assert!(markets.unmatched.is_empty());
+
+        let pairs = QuoteRequest::parse("eur/usd, USDJPY btc/usd 3mo");
+        assert_eq!(pairs.symbols, vec!["EURUSD=X", "USDJPY=X", "BTC-USD"]);
+
+        // All-caps words stay stock tickers
+        assert_eq!(QuoteRequest::parse("GOLD DOW").symbols, vec!["GOLD", "DOW"]);
+        assert_eq!(QuoteRequest::parse("vix 1mo").symbols, vec!["^VIX"]);
+
+        let names = QuoteRequest::parse("Apple and Microsoft stock");
+        assert_eq!(names.unmatched, vec!["Apple", "Microsoft", "stock"]);
+        assert!(names.symbols.is_empty());
+    }
+
+    #[test]
+    fn test_resolves_company_names_before_lone_words() {
+        let table = TickerTable::new(
+            tickers::parse_table("AAPL\tApple Inc.\tApple\nTSLA\tTesla Inc.\tTesla").unwrap(),
+        );
+        let resolve = |query: &str| {
+            let mut request = QuoteRequest::parse(query);
+            request.resolve_names(query, &table);
+            request
+        };
+
+        assert_eq!(resolve("Apple").symbols, vec!["AAPL"]);
+        let tesla = resolve("Tesla 1y");
+        assert_eq!(tesla.symbols, vec!["TSLA"]);
+        assert_eq!(tesla.range(), "1y");
+        assert!(tesla.unmatched.is_empty());
+        assert_eq!(
+            resolve("Apple and Tesla stock ytd").to_query(),
+            "AAPL TSLA range:ytd"
+        );
+
+        // A lone word the table doesn't know is still a ticker
+        assert_eq!(resolve("msft").symbols, vec!["MSFT"]);
+        assert_eq!(resolve("NVDA 6mo").symbols, vec!["NVDA"]);
+        let unknown = resolve("Foocorp and Barcorp");
+        assert!(unknown.symbols.is_empty());
+        assert_eq!(unknown.unmatched, vec!["Foocorp", "Barcorp"]);
+
+        // Names the table doesn't know are kept for the model
+        let mixed = resolve("Apple vs Foocorp stock 1y");
+        assert_eq!(mixed.symbols, vec!["AAPL"]);
+        assert_eq!(mixed.unmatched, vec!["Foocorp"]);
+        let mixed = resolve("AAPL and Foocorp");
+        assert_eq!(mixed.symbols, vec!["AAPL"]);
+        assert_eq!(mixed.unmatched, vec!["Foocorp"]);
+        assert!(resolve("Apple's stock price, 6mo").unmatched.is_empty());
+
+        assert_eq!(normalize_pair("GBP-CHF").as_deref(), Some("GBPCHF=X"));
+        assert_eq!(normalize_pair("ETH/BTC").as_deref(), Some("ETH-BTC"));
+        assert_eq!(normalize_pair("USDUSD"), None);
+        assert_eq!(normalize_pair("GOOGL"), None);
+
+        assert_eq!(symbol_label("^GSPC"), "^GSPC (S&P 500)");
+        assert_eq!(symbol_label("EURUSD=X"), "EURUSD=X (EUR/USD exchange rate)");
+        assert_eq!(symbol_label("AAPL"), "AAPL");
+        assert_eq!(
+            (price(1.08345), price(5123.456)),
+            ("1.0835".to_string(), "5123.46".to_string())
+        );
+    }
+
+    #[test]
+    fn test_computes_change_and_samples() {
+        assert_eq!(change_percent(100.0, 110.0), Some(10.0));
+        assert_eq!(change_percent(0.0, 110.0), None);
+
+        let items: Vec<u32> = (0..100).collect();
+        let sampled = sample(&items, 10);
+        assert_eq!(sampled.len(), 10);
+        assert_eq!((sampled[0], sampled[9]), (0, 99));
+        assert_eq!(sample(&items[..5], 10).len(), 5);
+    }
+
+    #[test]
+    fn test_formats_latest_quote_with_daily_change() {
+        let request = QuoteRequest::parse("AAPL");
+        let text = quotes_text(&request, &[series("AAPL", &[200.0, 190.0])], &[]);
+        assert!(text.starts_with("Latest data for AAPL: Date: 2024-01-03, Open: 189.00"));
+        assert!(text.ends_with("Change: -5.00% from previous close (200.00)"));
+    }
+
+    #[test]
+    fn test_formats_history_and_comparison_tables() {
+        let request = QuoteRequest::parse("AAPL MSFT 1mo");
+        let history = quotes_text(
+            &QuoteRequest::parse("AAPL 1mo"),
+            &[series("AAPL", &[100.0, 105.0, 120.0])],
+            &[],
+        );
+        assert!(history.starts_with(
+            "AAPL, 1mo at 1d interval (2024-01-02 to 2024-01-04, 3 quotes): Close 100.00 -> 120.00 (+20.00%), High: 122.00, Low: 98.00"
+        ));
+        assert!(history.contains("\n2024-01-03 | 104.00 | 107.00 | 103.00 | 105.00 | 1000"));
+
+        let comparison = quotes_text(
+            &request,
+            &[
+                series("AAPL", &[100.0, 120.0]),
+                series("MSFT", &[400.0, 380.0]),
+            ],
+            &["No stock price data found for symbol XYZ.".to_string()],
+        );
+        assert!(comparison.starts_with("Comparison, 1mo at 1d interval:"));
+        assert!(comparison.contains("\nAAPL | 100.00 | 120.00 | +20.00% | 122.00 | 98.00"));
+        assert!(comparison.contains("\nMSFT | 400.00 | 380.00 | -5.00% | 402.00 | 378.00"));
+        assert!(comparison.ends_with("No stock price data found for symbol XYZ."));
+    }
+}
@@ -0,0 +1,757 @@
+//! Local knowledge base
+//!
+//! Folders registered in settings (notes, repositories) are indexed into
+//! line-range chunks of their text files. Indexing is incremental: files whose
+//! size and modification time are unchanged are skipped, and a changed file is
+//! only re-chunked if its content hash differs. A file watcher re-runs the
+//! update shortly after anything in the folders changes. LOCAL_KNOWLEDGE ranks
+//! the chunks with BM25, from term counts kept with the index, and cites the
+//! file path and line range of each hit.
+
+use super::citations::ToolSource;
+use super::{to_payload, Tool, ToolContext, ToolOutput};
+use crate::documents::{score_term_counts, DocumentKind, TermCounts};
+use crate::mcp_simple::{ToolExample, ToolGuidance, ToolParameter};
+use crate::ToolType;
+use async_trait::async_trait;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, UNIX_EPOCH};
+use tauri::{AppHandle, Manager};
+
+const INDEX_FILENAME: &str = "knowledge_index.json";
+/// Characters per indexed chunk
+const CHUNK_CHARS: usize = 1200;
+/// Files larger than this are not indexed
+const MAX_FILE_BYTES: u64 = 1024 * 1024;
+/// Files indexed per folder, so pointing at a huge tree stays bounded
+const MAX_FILES_PER_FOLDER: usize = 20000;
+/// Characters of matching chunks added to the research context
+const MAX_CONTEXT_CHARS: usize = 8000;
+const MAX_HITS: usize = 8;
+/// Quiet period after a change before the index is updated
+const WATCH_DEBOUNCE: Duration = Duration::from_secs(2);
+/// Build output, dependencies and caches that are never worth indexing
+const IGNORED_DIRS: &[&str] = &[
+    "node_modules",
+    "target",
+    "dist",
+    "build",
+    "__pycache__",
+    "venv",
+];
+
+/// Knowledge base settings stored in `AppConfig`
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct KnowledgeBaseSettings {
+    #[serde(default)]
+    pub folders: Vec<String>, // Absolute folder paths
+}
+
+// --- Local Knowledge Event Payloads ---
+#[derive(Serialize, Clone, Debug)]
+struct KnowledgeSearchStartedPayload {
+    query: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+struct KnowledgeSearchCompletedPayload {
+    query: String,
+    success: bool,
+    hits: Option<Vec<KnowledgeHit>>,
+    error: Option<String>,
+}
+
+/// Lines `start_line..=end_line` (1-based) of an indexed file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct KnowledgeChunk {
+    pub start_line: usize,
+    pub end_line: usize,
+    pub text: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct IndexedFile {
+    folder: String,
+    size: u64,
+    modified: u64, // Seconds since the Unix epoch
+    hash: u64,     // FNV-1a of the content
+    chunks: Vec<KnowledgeChunk>,
+    /// Term counts of each chunk for scoring; rebuilt when the index is loaded
+    #[serde(skip)]
+    terms: Vec<TermCounts>,
+}
+
+impl IndexedFile {
+    fn new(folder: &str, size: u64, modified: u64, hash: u64, text: &str) -> IndexedFile {
+        let mut file = IndexedFile {
+            folder: folder.to_string(),
+            size,
+            modified,
+            hash,
+            chunks: chunk_lines(text, CHUNK_CHARS),
+            terms: Vec::new(),
+        };
+        file.count_terms();
+        file
+    }
+
+    fn count_terms(&mut self) {
+        self.terms = self
+            .chunks
+            .iter()
+            .map(|chunk| TermCounts::of(&chunk.text))
+            .collect();
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+struct KnowledgeIndex {
+    files: HashMap<String, IndexedFile>, // By absolute path
+}
+
+/// Outcome of an index update
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct IndexStats {
+    pub files: usize,
+    pub chunks: usize,
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+}
+
+/// A chunk matching a search
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct KnowledgeHit {
+    pub path: String,
+    /// Path relative to its registered folder, including the folder name
+    pub display_path: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub text: String,
+}
+
+impl KnowledgeHit {
+    fn source(&self) -> ToolSource {
+        let url = Url::from_file_path(&self.path)
+            .map(|url| url.to_string())
+            .unwrap_or_else(|_| format!("file://{}", self.path));
+        ToolSource::new(
+            &format!(
+                "{} (lines {}-{})",
+                self.display_path, self.start_line, self.end_line
+            ),
+            &format!("{}#L{}-L{}", url, self.start_line, self.end_line),
+            Some(&self.text),
+        )
+    }
+}
+
+/// 64-bit FNV-1a; stable across builds, unlike `DefaultHasher`
+pub fn content_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Split text into chunks of whole lines, preferring to end a chunk at a blank line
+pub fn chunk_lines(text: &str, max_chars: usize) -> Vec<KnowledgeChunk> {
+    let mut chunks: Vec<KnowledgeChunk> = Vec::new();
+    let mut current = String::new();
+    let mut start_line = 1;
+    let mut flush = |current: &mut String, start: usize, end: usize| {
+        if !current.trim().is_empty() {
+            chunks.push(KnowledgeChunk {
+                start_line: start,
+                end_line: end,
+                text: current.trim_end().to_string(),
+            });
+        }
+        current.clear();
+    };
+
+    for (i, line) in text.lines().enumerate() {
+        let number = i + 1;
+        // A single overlong line (e.g. minified code) is cut to the chunk size
+        let line: String = line.chars().take(max_chars).collect();
+        let length = current.chars().count();
+        if length > 0 && length + line.chars().count() + 1 > max_chars {
+            flush(&mut current, start_line, number - 1);
+        } else if length > max_chars / 2 && line.trim().is_empty() {
+            flush(&mut current, start_line, number);
+            start_line = number + 1;
+            continue;
+        }
+        if current.is_empty() {
+            start_line = number;
+        }
+        current.push_str(&line);
+        current.push('\n');
+    }
+    let last_line = text.lines().count();
+    flush(&mut current, start_line, last_line);
+    chunks
+}
+
+fn is_indexable(path: &Path) -> bool {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    matches!(
+        DocumentKind::from_extension(&extension),
+        Some(DocumentKind::Markdown | DocumentKind::Code | DocumentKind::Text | DocumentKind::Csv)
+    )
+}
+
+/// Text files under `folder`, skipping hidden entries and build/dependency directories
+fn walk_folder(folder: &Path) -> Vec<(PathBuf, fs::Metadata)> {
+    let mut files: Vec<(PathBuf, fs::Metadata)> = Vec::new();
+    let mut pending = vec![folder.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                log::warn!("Failed to read knowledge folder {}: {}", dir.display(), e);
+                continue;
+            }
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') {
+                continue;
+            }
+            let path = entry.path();
+            let metadata = match entry.metadata() {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+            if metadata.is_dir() {
+                if !IGNORED_DIRS.contains(&name.as_str()) {
+                    pending.push(path);
+                }
+            } else if metadata.is_file() && metadata.len() <= MAX_FILE_BYTES && is_indexable(&path)
+            {
+                files.push((path, metadata));
+                if files.len() >= MAX_FILES_PER_FOLDER {
+                    log::warn!(
+                        "Knowledge folder {} has more than {} files; indexing the first {}",
+                        folder.display(),
+                        MAX_FILES_PER_FOLDER,
+                        MAX_FILES_PER_FOLDER
+                    );
+                    return files;
+                }
+            }
+        }
+    }
+    files
+}
+
+fn modified_secs(metadata: &fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |duration| duration.as_secs())
+}
+
+impl KnowledgeIndex {
+    /// Bring the index up to date with `folders`, re-reading only files that changed
+    fn update(&mut self, folders: &[String]) -> IndexStats {
+        let mut stats = IndexStats::default();
+        let mut seen: HashSet<String> = HashSet::new();
+        for folder in folders {
+            for (path, metadata) in walk_folder(Path::new(folder)) {
+                let key = path.to_string_lossy().into_owned();
+                let size = metadata.len();
+                let modified = modified_secs(&metadata);
+                seen.insert(key.clone());
+                if let Some(existing) = self.files.get(&key) {
+                    if existing.size == size && existing.modified == modified {
+                        continue;
+                    }
+                }
+
+                let bytes = match fs::read(&path) {
+                    Ok(bytes) => bytes,
+                    Err(e) => {
+                        log::warn!("Failed to read {}: {}", key, e);
+                        continue;
+                    }
+                };
+                let hash = content_hash(&bytes);
+                if let Some(existing) = self.files.get_mut(&key) {
+                    if existing.hash == hash {
+                        // Touched but not changed
+                        existing.size = size;
+                        existing.modified = modified;
+                        continue;
+                    }
+                }
+                let text = match String::from_utf8(bytes) {
+                    Ok(text) => text,
+                    Err(_) => continue, // Binary file with a text extension
+                };
+                let previous = self
+                    .files
+                    .insert(key, IndexedFile::new(folder, size, modified, hash, &text));
+                if previous.is_some() {
+                    stats.updated += 1;
+                } else {
+                    stats.added += 1;
+                }
+            }
+        }
+
+        let before = self.files.len();
+        self.files.retain(|path, _| seen.contains(path));
+        stats.removed = before - self.files.len();
+        stats.files = self.files.len();
+        stats.chunks = self.files.values().map(|f| f.chunks.len()).sum();
+        stats
+    }
+
+    /// Best-matching chunks for a query, most relevant first
+    fn search(&self, query: &str, max_chars: usize) -> Vec<KnowledgeHit> {
+        let mut paths: Vec<&String> = self.files.keys().collect();
+        paths.sort(); // Stable order for equal scores
+        let chunks: Vec<(&String, &IndexedFile, &KnowledgeChunk, &TermCounts)> = paths
+            .into_iter()
+            .flat_map(|path| {
+                let file = &self.files[path];
+                file.chunks
+                    .iter()
+                    .zip(&file.terms)
+                    .map(move |(chunk, terms)| (path, file, chunk, terms))
+            })
+            .collect();
+        let terms: Vec<&TermCounts> = chunks.iter().map(|(_, _, _, terms)| *terms).collect();
+        let scores = score_term_counts(&terms, query);
+
+        let mut order: Vec<usize> = (0..chunks.len()).filter(|&i| scores[i] > 0.0).collect();
+        order.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+        let mut hits: Vec<KnowledgeHit> = Vec::new();
+        let mut used = 0;
+        for i in order {
+            let (path, file, chunk, _) = chunks[i];
+            let length = chunk.text.chars().count();
+            if used + length > max_chars && !hits.is_empty() {
+                continue;
+            }
+            used += length;
+            let folder = Path::new(&file.folder);
+            let relative = Path::new(path)
+                .strip_prefix(folder)
+                .unwrap_or(Path::new(path));
+            let display_path = match folder.file_name() {
+                Some(name) => Path::new(name).join(relative),
+                None => relative.to_path_buf(),
+            };
+            hits.push(KnowledgeHit {
+                path: path.clone(),
+                display_path: display_path.to_string_lossy().into_owned(),
+                start_line: chunk.start_line,
+                end_line: chunk.end_line,
+                text: chunk.text.clone(),
+            });
+            if hits.len() >= MAX_HITS {
+                break;
+            }
+        }
+        hits
+    }
+}
+
+/// The knowledge index with its on-disk copy and folder watcher
+pub struct KnowledgeBase {
+    path: Option<PathBuf>,
+    /// Shared with running searches, which score without holding the lock
+    index: Mutex<Arc<KnowledgeIndex>>,
+    folders: Mutex<Vec<String>>,
+    watcher: Mutex<Option<RecommendedWatcher>>,
+    /// Serializes index updates; searches only wait for the swap at the end
+    updating: Mutex<()>,
+}
+
+impl KnowledgeBase {
+    fn open(path: Option<PathBuf>) -> Self {
+        let mut index: KnowledgeIndex = path
+            .as_ref()
+            .filter(|path| path.exists())
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| match serde_json::from_str(&content) {
+                Ok(index) => Some(index),
+                Err(e) => {
+                    log::warn!("Failed to parse knowledge index, rebuilding: {}", e);
+                    None
+                }
+            })
+            .unwrap_or_default();
+        for file in index.files.values_mut() {
+            file.count_terms();
+        }
+        KnowledgeBase {
+            path,
+            index: Mutex::new(Arc::new(index)),
+            folders: Mutex::new(Vec::new()),
+            watcher: Mutex::new(None),
+            updating: Mutex::new(()),
+        }
+    }
+
+    /// Update the index for the current folders and save it
+    pub fn reindex(&self) -> IndexStats {
+        let _updating = self.updating.lock();
+        let folders = self.folders.lock().map(|f| f.clone()).unwrap_or_default();
+        let mut index = match self.index.lock() {
+            Ok(index) => KnowledgeIndex::clone(&index),
+            Err(_) => KnowledgeIndex::default(),
+        };
+        let stats = index.update(&folders);
+        log::info!(
+            "Knowledge index: {} files, {} chunks ({} added, {} updated, {} removed)",
+            stats.files,
+            stats.chunks,
+            stats.added,
+            stats.updated,
+            stats.removed
+        );
+        if stats.added + stats.updated + stats.removed > 0 {
+            self.persist(&index);
+        }
+        if let Ok(mut current) = self.index.lock() {
+            *current = Arc::new(index);
+        }
+        stats
+    }
+
+    /// Files and chunks currently indexed
+    pub fn stats(&self) -> IndexStats {
+        match self.index.lock() {
+            Ok(index) => IndexStats {
+                files: index.files.len(),
+                chunks: index.files.values().map(|f| f.chunks.len()).sum(),
+                ..IndexStats::default()
+            },
+            Err(_) => IndexStats::default(),
+        }
+    }
+
+    /// Scores every chunk, so run it off the async runtime
+    pub fn search(&self, query: &str) -> Vec<KnowledgeHit> {
+        let index = match self.index.lock() {
+            Ok(index) => Arc::clone(&index),
+            Err(_) => return Vec::new(),
+        };
+        index.search(query, MAX_CONTEXT_CHARS)
+    }
+
+    pub fn has_folders(&self) -> bool {
+        self.folders.lock().is_ok_and(|f| !f.is_empty())
+    }
+
+    fn persist(&self, index: &KnowledgeIndex) {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return,
+        };
+        if let Some(parent_dir) = path.parent() {
+            if let Err(e) = fs::create_dir_all(parent_dir) {
+                log::warn!("Failed to create knowledge index directory: {}", e);
+                return;
+            }
+        }
+        match serde_json::to_string(index) {
+            Ok(json) => {
+                if let Err(e) = fs::write(path, json) {
+                    log::warn!("Failed to write knowledge index: {}", e);
+                }
+            }
+            Err(e) => log::warn!("Failed to serialize knowledge index: {}", e),
+        }
+    }
+
+    /// Replace the watched folders and restart the watcher
+    ///
+    /// Does not update the index; call `reindex` afterwards.
+    pub fn set_folders(&'static self, folders: Vec<String>) {
+        if let Ok(mut watcher) = self.watcher.lock() {
+            // Dropping the old watcher ends its debounce thread
+            *watcher = None;
+            if !folders.is_empty() {
+                *watcher = self.watch(&folders);
+            }
+        }
+        if let Ok(mut current) = self.folders.lock() {
+            *current = folders;
+        }
+    }
+
+    fn watch(&'static self, folders: &[String]) -> Option<RecommendedWatcher> {
+        let (sender, receiver) = mpsc::channel::<()>();
+        let mut watcher =
+            match notify::recommended_watcher(move |result: notify::Result<notify::Event>| {
+                if let Ok(event) = result {
+                    if matches!(
+                        event.kind,
+                        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+                    ) {
+                        let _ = sender.send(());
+                    }
+                }
+            }) {
+                Ok(watcher) => watcher,
+                Err(e) => {
+                    log::warn!("Failed to start knowledge folder watcher: {}", e);
+                    return None;
+                }
+            };
+        for folder in folders {
+            if let Err(e) = watcher.watch(Path::new(folder), RecursiveMode::Recursive) {
+                log::warn!("Failed to watch knowledge folder {}: {}", folder, e);
+            }
+        }
+
+        std::thread::spawn(move || {
+            // Wait for a change, then for the folders to be quiet before updating
+            while receiver.recv().is_ok() {
+                loop {
+                    match receiver.recv_timeout(WATCH_DEBOUNCE) {
+                        Ok(()) => continue,
+                        Err(RecvTimeoutError::Timeout) => break,
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+                self.reindex();
+            }
+        });
+        Some(watcher)
+    }
+}
+
+/// The shared knowledge base, loaded from the app data directory on first use
+pub fn knowledge_base(app_handle: &AppHandle) -> &'static KnowledgeBase {
+    static KNOWLEDGE_BASE: OnceLock<KnowledgeBase> = OnceLock::new();
+    KNOWLEDGE_BASE.get_or_init(|| {
+        let path = match app_handle.path().app_data_dir() {
+            Ok(dir) => Some(dir.join(INDEX_FILENAME)),
+            Err(e) => {
+                log::warn!(
+                    "Failed to get app data directory: {}. Knowledge index will be memory-only.",
+                    e
+                );
+                None
+            }
+        };
+        KnowledgeBase::open(path)
+    })
+}
+
+pub struct LocalKnowledgeTool;
+
+#[async_trait]
+impl Tool for LocalKnowledgeTool {
+    fn tool_type(&self) -> ToolType {
+        ToolType::LocalKnowledge
+    }
+
+    fn description(&self) -> &'static str {
+        "Search the user's own notes, documents and code repositories in their knowledge folders"
+    }
+
+    fn follow_up_hint(&self) -> &'static str {
+        "Search the user's notes and repositories with different keywords"
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "Keywords to look for in the user's files, e.g. a function name or topic"
+                }
+            },
+            "required": ["query"]
+        })
+    }
+
+    fn guidance(&self) -> ToolGuidance {
+        ToolGuidance {
+            name: "Local Knowledge".to_string(),
+            description: "Keyword search over the user's indexed notes and code folders".to_string(),
+            usage_pattern: "Use when the question is about the user's own projects, notes or codebase".to_string(),
+            parameters: vec![ToolParameter {
+                name: "query".to_string(),
+                param_type: "string".to_string(),
+                description: "Keywords to look for, e.g. a function name or topic".to_string(),
+                required: true,
+                default_value: None,
+            }],
+            examples: vec![ToolExample {
+                scenario: "User asks how authentication works in their project".to_string(),
+                reasoning: "The answer is in the user's repository, not on the web".to_string(),
+                sequence: vec![
+                    "LOCAL_KNOWLEDGE with 'authentication login token'".to_string(),
+                    "Answer from the matching files, citing paths and line ranges".to_string(),
+                ],
+            }],
+            reasoning_hints: vec![
+                "Use specific identifiers and distinctive words; the search matches keywords, not meaning".to_string(),
+                "Only folders the user registered in settings are searched".to_string(),
+            ],
+        }
+    }
+
+    fn capability_key(&self) -> &'static str {
+        "local_knowledge"
+    }
+
+    fn capabilities(&self) -> Value {
+        json!({
+            "description": "Search over the user's local notes and code folders",
+            "best_for": ["questions about the user's projects", "finding notes", "locating code"],
+            "strengths": ["file paths and line ranges as sources", "index stays current as files change"],
+            "limitations": ["text files only", "keyword matching"]
+        })
+    }
+
+    fn started_event(&self) -> &'static str {
+        "LOCAL_KNOWLEDGE_STARTED"
+    }
+
+    fn completed_event(&self) -> &'static str {
+        "LOCAL_KNOWLEDGE_COMPLETED"
+    }
+
+    fn started_payload(&self, query: &str) -> Value {
+        to_payload(KnowledgeSearchStartedPayload {
+            query: query.to_string(),
+        })
+    }
+
+    fn failed_payload(&self, query: &str, error: &str) -> Value {
+        to_payload(KnowledgeSearchCompletedPayload {
+            query: query.to_string(),
+            success: false,
+            hits: None,
+            error: Some(error.to_string()),
+        })
+    }
+
+    async fn execute(&self, ctx: &ToolContext<'_>, query: &str) -> Result<ToolOutput, String> {
+        let knowledge = knowledge_base(ctx.window.app_handle());
+        if !knowledge.has_folders() {
+            return Err("No knowledge folders are configured".to_string());
+        }
+        let owned_query = query.to_string();
+        let hits = tokio::task::spawn_blocking(move || knowledge.search(&owned_query))
+            .await
+            .map_err(|e| format!("Knowledge search failed: {}", e))?;
+        log::info!("Local knowledge search '{}': {} hit(s)", query, hits.len());
+
+        let context = if hits.is_empty() {
+            None
+        } else {
+            let mut text = format!("Local Knowledge Results for '{}':\n\n", query);
+            for hit in &hits {
+                text.push_str(&format!(
+                    "{} (lines {}-{}):\n{}\n\n",
+                    hit.display_path, hit.start_line, hit.end_line, hit.text
+                ));
+            }
+            Some(text.trim_end().to_string())
+        };
+
+        Ok(ToolOutput {
+            content: context
+                .clone()
+                .unwrap_or_else(|| format!("No local files matched '{}'", query)),
+            context,
+            sources: hits.iter().map(KnowledgeHit::source).collect(),
+            completed_payload: to_payload(KnowledgeSearchCompletedPayload {
+                query: query.to_string(),
+                success: true,
+                hits: Some(hits),
+                error: None,
+            }),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_chunks_keep_line_ranges() {
+        let text = "fn a() {\n    1\n}\n\nfn b() {\n    2\n}\n";
+        let chunks = chunk_lines(text, 20);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!((chunks[0].start_line, chunks[0].end_line), (1, 4));
+        assert_eq!(chunks[0].text, "fn a() {\n    1\n}");
+        assert_eq!((chunks[1].start_line, chunks[1].end_line), (5, 7));
+        assert!(chunk_lines("", 20).is_empty());
+        assert_eq!(content_hash(b"abc"), content_hash(b"abc"));
+        assert_ne!(content_hash(b"abc"), content_hash(b"abd"));
+    }
+
+    #[test]
+    fn test_incremental_update_and_search() {
+        let root = std::env::temp_dir().join(format!("shard-knowledge-test-{}", Uuid::new_v4()));
+        let notes = root.join("notes");
+        fs::create_dir_all(notes.join("node_modules")).unwrap();
+        fs::write(
+            notes.join("auth.md"),
+            "# Auth\nTokens are refreshed hourly.\n",
+        )
+        .unwrap();
+        fs::write(notes.join("todo.txt"), "Buy milk\n").unwrap();
+        fs::write(notes.join("logo.png"), [0x89, 0x50]).unwrap();
+        fs::write(notes.join("node_modules").join("dep.js"), "tokens").unwrap();
+        let folders = vec![notes.to_string_lossy().into_owned()];
+
+        let mut index = KnowledgeIndex::default();
+        let stats = index.update(&folders);
+        assert_eq!((stats.files, stats.added), (2, 2));
+        assert_eq!(
+            index.update(&folders),
+            IndexStats {
+                files: 2,
+                chunks: 2,
+                ..IndexStats::default()
+            }
+        );
+
+        fs::remove_file(notes.join("todo.txt")).unwrap();
+        let stats = index.update(&folders);
+        assert_eq!((stats.files, stats.removed), (1, 1));
+
+        let hits = index.search("When are tokens refreshed?", 1000);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(
+            hits[0].display_path,
+            Path::new("notes").join("auth.md").to_string_lossy()
+        );
+        assert_eq!((hits[0].start_line, hits[0].end_line), (1, 2));
+        assert!(hits[0].source().url.ends_with("auth.md#L1-L2"));
+        assert!(index.search("unrelated words", 1000).is_empty());
+
+        // Term counts aren't saved with the index and are rebuilt when it's loaded
+        let saved = root.join("index.json");
+        fs::write(&saved, serde_json::to_string(&index).unwrap()).unwrap();
+        let loaded = KnowledgeBase::open(Some(saved));
+        assert_eq!(loaded.search("When are tokens refreshed?"), hits);
+        let _ = fs::remove_dir_all(root);
+    }
+}
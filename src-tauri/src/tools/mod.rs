@@ -0,0 +1,242 @@
+//! Research tools for Shard
+//!
+//! Every research tool implements [`Tool`] and is listed in the [`ToolRegistry`].
+//! The registry drives tool execution in `send_text_to_model`, the tool lists in
+//! the decider prompts and the guidance/capability export in `mcp_simple`, so a
+//! new tool only needs a `Tool` impl and an entry in `ToolRegistry::new`.
+
+pub mod arxiv;
+pub mod arxiv_paper;
+pub mod bibliography;
+pub mod cache;
+pub mod citations;
+pub mod crossref;
+pub mod executor;
+pub mod fetch_url;
+pub mod financial;
+pub mod knowledge;
+pub mod pubmed;
+pub mod readability;
+pub mod scholarly;
+pub mod semantic_scholar;
+pub mod tickers;
+pub mod trace;
+pub mod transcript;
+pub mod weather;
+pub mod web_search;
+pub mod wikipedia;
+
+use crate::mcp_simple::ToolGuidance;
+use crate::{AppConfig, ToolDecision, ToolType};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tauri::Window;
+
+/// Shared state handed to a tool while it runs
+pub struct ToolContext<'a> {
+    pub client: &'a reqwest::Client,
+    pub window: &'a Window,
+    pub cache: &'a cache::ToolCache,
+    pub config: &'a AppConfig,
+    pub gemini_api_key: &'a str,
+    /// Model used for the tool's own helper LLM calls (term extraction, page analysis)
+    pub model_name: &'a str,
+    /// The user's message that led to the tool calls
+    pub user_query: &'a str,
+    /// Disk paths of documents attached to the conversation
+    pub attached_paths: &'a [String],
+}
+
+/// Result of a successful tool run
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ToolOutput {
+    /// Recorded as the tool's result for this request
+    pub content: String,
+    /// Added to the research context given to the model; `None` if nothing was found
+    pub context: Option<String>,
+    /// Sources the result was built from, cited by number in the context
+    #[serde(default)]
+    pub sources: Vec<citations::ToolSource>,
+    /// Payload of the tool's completed event
+    pub completed_payload: Value,
+}
+
+#[async_trait]
+pub trait Tool: Send + Sync {
+    fn tool_type(&self) -> ToolType;
+
+    /// Wire name, e.g. "WIKIPEDIA_LOOKUP"
+    fn name(&self) -> &'static str {
+        self.tool_type().as_str()
+    }
+
+    /// One-line description shown to the decider
+    fn description(&self) -> &'static str;
+
+    /// Shorter hint shown when the decider plans follow-up calls
+    fn follow_up_hint(&self) -> &'static str;
+
+    /// JSON schema of the tool's input
+    fn input_schema(&self) -> Value;
+
+    /// Detailed usage guidance for `mcp_simple`
+    fn guidance(&self) -> ToolGuidance;
+
+    /// Key of this tool in the capability summary (e.g. "wikipedia_research")
+    fn capability_key(&self) -> &'static str;
+
+    fn capabilities(&self) -> Value;
+
+    fn started_event(&self) -> &'static str;
+
+    fn completed_event(&self) -> &'static str;
+
+    fn started_payload(&self, query: &str) -> Value;
+
+    /// Completed event payload for a failed run
+    fn failed_payload(&self, query: &str, error: &str) -> Value;
+
+    /// How long successful results stay cached; `None` disables caching
+    fn cache_ttl(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Key a result is cached under, which must cover everything besides the
+    /// query that changes the result; `None` skips the cache for this call
+    fn cache_key(&self, _ctx: &ToolContext<'_>, query: &str) -> Option<String> {
+        Some(query.to_string())
+    }
+
+    /// How long this result stays cached, for tools whose results age at
+    /// different rates; `None` doesn't cache it
+    fn output_cache_ttl(&self, _output: &ToolOutput) -> Option<Duration> {
+        self.cache_ttl()
+    }
+
+    async fn execute(&self, ctx: &ToolContext<'_>, query: &str) -> Result<ToolOutput, String>;
+
+    /// Turn a follow-up decision into the calls to run; most tools run it as-is
+    async fn expand_follow_up(
+        &self,
+        _ctx: &ToolContext<'_>,
+        decision: ToolDecision,
+        _research_context: &str,
+    ) -> Vec<ToolDecision> {
+        vec![decision]
+    }
+}
+
+/// All tools known to Shard, in the order they are presented to the decider
+pub struct ToolRegistry {
+    tools: Vec<Box<dyn Tool>>,
+}
+
+impl ToolRegistry {
+    fn new() -> Self {
+        ToolRegistry {
+            tools: vec![
+                Box::new(wikipedia::WikipediaTool),
+                Box::new(weather::WeatherTool),
+                Box::new(financial::FinancialTool),
+                Box::new(arxiv::ArxivTool),
+                Box::new(arxiv_paper::ArxivPaperTool),
+                Box::new(crossref::CrossrefTool),
+                Box::new(semantic_scholar::SemanticScholarTool),
+                Box::new(pubmed::PubmedTool),
+                Box::new(web_search::WebSearchTool),
+                Box::new(fetch_url::FetchUrlTool),
+                Box::new(transcript::VideoTranscriptTool),
+                Box::new(knowledge::LocalKnowledgeTool),
+            ],
+        }
+    }
+
+    pub fn get(&self, tool_type: &ToolType) -> Option<&dyn Tool> {
+        self.iter().find(|tool| tool.tool_type() == *tool_type)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Tool> {
+        self.tools.iter().map(|tool| tool.as_ref())
+    }
+
+    /// Numbered tool list for the decider prompts, leaving out disabled tools
+    pub fn prompt_tool_list(&self, follow_up: bool, is_enabled: impl Fn(&str) -> bool) -> String {
+        self.iter()
+            .filter(|tool| is_enabled(tool.name()))
+            .enumerate()
+            .map(|(i, tool)| {
+                let text = if follow_up {
+                    tool.follow_up_hint()
+                } else {
+                    tool.description()
+                };
+                format!("{}. {}: {}\n", i + 1, tool.name(), text)
+            })
+            .collect()
+    }
+
+    /// Comma-separated wire names of the enabled tools
+    pub fn tool_names(&self, is_enabled: impl Fn(&str) -> bool) -> String {
+        self.iter()
+            .map(|tool| tool.name())
+            .filter(|name| is_enabled(name))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+pub fn registry() -> &'static ToolRegistry {
+    static REGISTRY: OnceLock<ToolRegistry> = OnceLock::new();
+    REGISTRY.get_or_init(ToolRegistry::new)
+}
+
+/// Serialize an event payload, logging instead of failing
+pub fn to_payload<T: Serialize>(payload: T) -> Value {
+    serde_json::to_value(payload).unwrap_or_else(|e| {
+        log::warn!("Failed to serialize tool event payload: {}", e);
+        Value::Null
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_tool_type_is_registered() {
+        for name in [
+            "WIKIPEDIA_LOOKUP",
+            "WEATHER_LOOKUP",
+            "FINANCIAL_DATA",
+            "ARXIV_LOOKUP",
+            "ARXIV_PAPER",
+            "CROSSREF_LOOKUP",
+            "SEMANTIC_SCHOLAR_LOOKUP",
+            "PUBMED_LOOKUP",
+            "WEB_SEARCH",
+            "FETCH_URL",
+            "VIDEO_TRANSCRIPT",
+            "LOCAL_KNOWLEDGE",
+        ] {
+            let tool_type = ToolType::from_name(name).unwrap();
+            let tool = registry().get(&tool_type).unwrap();
+            assert_eq!(tool.name(), name);
+            assert_eq!(tool.input_schema()["type"], "object");
+        }
+    }
+
+    #[test]
+    fn test_prompt_tool_list_skips_disabled_tools() {
+        let list = registry().prompt_tool_list(false, |name| name != "WEATHER_LOOKUP");
+        assert!(list.starts_with("1. WIKIPEDIA_LOOKUP: "));
+        assert!(list.contains("2. FINANCIAL_DATA: "));
+        assert!(!list.contains("WEATHER_LOOKUP"));
+        assert_eq!(
+            registry().tool_names(|name| name != "WEATHER_LOOKUP"),
+            "WIKIPEDIA_LOOKUP, FINANCIAL_DATA, ARXIV_LOOKUP, ARXIV_PAPER, CROSSREF_LOOKUP, SEMANTIC_SCHOLAR_LOOKUP, PUBMED_LOOKUP, WEB_SEARCH, FETCH_URL, VIDEO_TRANSCRIPT, LOCAL_KNOWLEDGE"
+        );
+    }
+}
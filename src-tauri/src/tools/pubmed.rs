@@ -0,0 +1,434 @@
+//! PubMed search tool
+//!
+//! Searches biomedical literature through the NCBI E-utilities: `esearch`
+//! finds the PubMed ids for a query (PubMed's own syntax such as `[ti]` or
+//! `[dp]` works), `efetch` returns the articles with their abstracts.
+
+use super::fetch_url::USER_AGENT;
+use super::readability::{decode_entities, strip_tags};
+use super::scholarly::{self, clean_abstract, PaperSummary};
+use super::{Tool, ToolContext, ToolOutput};
+use crate::mcp_simple::{ToolExample, ToolGuidance, ToolParameter};
+use crate::ToolType;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+const EUTILS_URL: &str = "https://eutils.ncbi.nlm.nih.gov/entrez/eutils";
+/// MeSH terms listed per article
+const MAX_MESH_TERMS: usize = 8;
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+struct ESearchResponse {
+    esearchresult: ESearchResult,
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+struct ESearchResult {
+    idlist: Vec<String>,
+}
+
+/// Text of the first `<tag ...>...</tag>` in `xml`
+fn element<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("<{}", tag))?;
+    let open_end = start + xml[start..].find('>')? + 1;
+    // `<Tag/>` has no content
+    if xml[..open_end].ends_with("/>") {
+        return None;
+    }
+    let close = open_end + xml[open_end..].find(&format!("</{}>", tag))?;
+    Some(&xml[open_end..close])
+}
+
+/// Text of every `<tag ...>...</tag>` in `xml`, with the opening tag
+fn elements<'a>(xml: &'a str, tag: &str) -> Vec<(&'a str, &'a str)> {
+    let mut found = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(&format!("<{}", tag)) {
+        let after = &rest[start..];
+        // Skip longer tag names sharing the prefix, e.g. <AuthorList> for <Author>
+        let next = after[tag.len() + 1..].chars().next();
+        if !matches!(next, Some(' ' | '>')) {
+            rest = &after[tag.len() + 1..];
+            continue;
+        }
+        let Some(open_end) = after.find('>').map(|i| i + 1) else {
+            break;
+        };
+        let Some(close) = after.find(&format!("</{}>", tag)) else {
+            break;
+        };
+        found.push((&after[..open_end], &after[open_end..close]));
+        rest = &after[close..];
+    }
+    found
+}
+
+/// Value of `name="..."` in an opening tag
+fn attribute<'a>(open_tag: &'a str, name: &str) -> Option<&'a str> {
+    let start = open_tag.find(&format!("{}=\"", name))? + name.len() + 2;
+    let end = start + open_tag[start..].find('"')?;
+    Some(&open_tag[start..end])
+}
+
+fn text_of(xml: &str) -> String {
+    decode_entities(&strip_tags(xml)).trim().to_string()
+}
+
+/// `YYYY-MM-DD` as far as the publication date is known
+fn publication_date(pub_date: &str) -> Option<String> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    if let Some(medline_date) = element(pub_date, "MedlineDate") {
+        // Free text such as "2019 Nov-Dec"; the year is enough
+        return medline_date.get(..4).map(str::to_string);
+    }
+    let year = element(pub_date, "Year")?;
+    let month = element(pub_date, "Month").and_then(|month| {
+        MONTHS
+            .iter()
+            .position(|name| *name == month)
+            .map(|i| i + 1)
+            .or_else(|| month.parse().ok())
+    });
+    let day: Option<u32> = element(pub_date, "Day").and_then(|day| day.parse().ok());
+    Some(match (month, day) {
+        (Some(month), Some(day)) => format!("{}-{:02}-{:02}", year, month, day),
+        (Some(month), None) => format!("{}-{:02}", year, month),
+        _ => year.to_string(),
+    })
+}
+
+/// Parse an `efetch` XML response into papers
+fn parse_pubmed_articles(xml: &str) -> Vec<PaperSummary> {
+    elements(xml, "PubmedArticle")
+        .into_iter()
+        .filter_map(|(_, article)| {
+            let pmid = element(article, "PMID")?.trim().to_string();
+            let title = text_of(element(article, "ArticleTitle").unwrap_or_default());
+            let summary = elements(
+                element(article, "Abstract").unwrap_or_default(),
+                "AbstractText",
+            )
+            .into_iter()
+            .map(|(open_tag, text)| match attribute(open_tag, "Label") {
+                Some(label) => format!("{}: {}", label, clean_abstract(text)),
+                None => clean_abstract(text),
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+            let authors = elements(element(article, "AuthorList").unwrap_or_default(), "Author")
+                .into_iter()
+                .filter_map(|(_, author)| {
+                    match (element(author, "ForeName"), element(author, "LastName")) {
+                        (Some(first), Some(last)) => {
+                            Some(format!("{} {}", text_of(first), text_of(last)))
+                        }
+                        (None, Some(last)) => Some(text_of(last)),
+                        _ => element(author, "CollectiveName").map(text_of),
+                    }
+                })
+                .collect();
+            let journal = element(article, "Journal").and_then(|journal| element(journal, "Title"));
+            let article_ids = element(article, "ArticleIdList").unwrap_or_default();
+            let article_id = |id_type: &str| {
+                elements(article_ids, "ArticleId")
+                    .into_iter()
+                    .find(|(open_tag, _)| attribute(open_tag, "IdType") == Some(id_type))
+                    .map(|(_, id)| id.trim().to_string())
+            };
+            let pdf_url = article_id("pmc")
+                .map(|pmc| format!("https://www.ncbi.nlm.nih.gov/pmc/articles/{}/pdf/", pmc))
+                .unwrap_or_default();
+            let fields = elements(
+                element(article, "MeshHeadingList").unwrap_or_default(),
+                "DescriptorName",
+            )
+            .into_iter()
+            .map(|(_, name)| text_of(name))
+            .take(MAX_MESH_TERMS)
+            .collect();
+
+            Some(PaperSummary {
+                title,
+                summary,
+                authors,
+                id: format!("https://pubmed.ncbi.nlm.nih.gov/{}/", pmid),
+                published_date: element(article, "PubDate").and_then(publication_date),
+                pdf_url,
+                doi: article_id("doi"),
+                venue: journal.map(text_of),
+                fields,
+                ..PaperSummary::default()
+            })
+        })
+        .collect()
+}
+
+async fn get_eutils(
+    client: &reqwest::Client,
+    endpoint: &str,
+    params: &[(&str, &str)],
+) -> Result<reqwest::Response, String> {
+    let response = client
+        .get(format!("{}/{}", EUTILS_URL, endpoint))
+        .header("User-Agent", USER_AGENT)
+        .query(&[("db", "pubmed"), ("tool", "shard")])
+        .query(params)
+        .send()
+        .await
+        .map_err(|e| format!("PubMed request failed: {}", e))?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("PubMed request failed: HTTP {}", status));
+    }
+    Ok(response)
+}
+
+async fn search_pubmed(
+    client: &reqwest::Client,
+    query: &str,
+    max_results: usize,
+) -> Result<Vec<PaperSummary>, String> {
+    let max_results = max_results.to_string();
+    let search: ESearchResponse = get_eutils(
+        client,
+        "esearch.fcgi",
+        &[
+            ("term", query),
+            ("retmax", max_results.as_str()),
+            ("retmode", "json"),
+            ("sort", "relevance"),
+        ],
+    )
+    .await?
+    .json()
+    .await
+    .map_err(|e| format!("Failed to parse PubMed search response: {}", e))?;
+    let ids = search.esearchresult.idlist;
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let xml = get_eutils(
+        client,
+        "efetch.fcgi",
+        &[("id", ids.join(",").as_str()), ("retmode", "xml")],
+    )
+    .await?
+    .text()
+    .await
+    .map_err(|e| format!("Failed to read PubMed articles: {}", e))?;
+    let mut papers = parse_pubmed_articles(&xml);
+    // efetch returns the articles by id, not by relevance
+    papers.sort_by_key(|paper| {
+        ids.iter()
+            .position(|id| paper.id.contains(&format!("/{}/", id)))
+            .unwrap_or(usize::MAX)
+    });
+    Ok(papers)
+}
+
+pub struct PubmedTool;
+
+#[async_trait]
+impl Tool for PubmedTool {
+    fn tool_type(&self) -> ToolType {
+        ToolType::PubmedLookup
+    }
+
+    fn description(&self) -> &'static str {
+        "Biomedical and life-science papers from PubMed, with abstracts (PubMed query syntax like [ti], [au], [dp] works)"
+    }
+
+    fn follow_up_hint(&self) -> &'static str {
+        "Medical and life-science literature"
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "Biomedical topic keywords, optionally with PubMed field tags (e.g. 'statins[ti] AND 2023[dp]')"
+                }
+            },
+            "required": ["query"]
+        })
+    }
+
+    fn guidance(&self) -> ToolGuidance {
+        ToolGuidance {
+            name: "PubMed Search".to_string(),
+            description:
+                "Search MEDLINE and life-science journals for studies, reviews and clinical trials"
+                    .to_string(),
+            usage_pattern: "Use for medicine, biology, pharmacology and public health questions"
+                .to_string(),
+            parameters: vec![ToolParameter {
+                name: "query".to_string(),
+                param_type: "string".to_string(),
+                description: "Biomedical keywords, optionally with PubMed field tags".to_string(),
+                required: true,
+                default_value: None,
+            }],
+            examples: vec![ToolExample {
+                scenario: "User asks what studies say about a drug's side effects".to_string(),
+                reasoning: "PubMed indexes the clinical literature with structured abstracts"
+                    .to_string(),
+                sequence: vec![
+                    "PUBMED_LOOKUP with the drug and the effect".to_string(),
+                    "Summarize the findings, noting study types and dates".to_string(),
+                ],
+            }],
+            reasoning_hints: vec![
+                "Use medical terms (MeSH vocabulary) rather than lay terms".to_string(),
+                "Add 'review[pt]' for reviews or a year with [dp] for recent work".to_string(),
+                "Findings are not medical advice; say so when relevant".to_string(),
+            ],
+        }
+    }
+
+    fn capability_key(&self) -> &'static str {
+        "pubmed_search"
+    }
+
+    fn capabilities(&self) -> Value {
+        json!({
+            "description": "Biomedical literature search",
+            "best_for": ["clinical studies", "biology and medicine", "drug and disease research"],
+            "strengths": ["structured abstracts", "MeSH terms", "links to free full text in PMC"],
+            "limitations": ["biomedical fields only", "no citation counts"]
+        })
+    }
+
+    fn started_event(&self) -> &'static str {
+        "PUBMED_LOOKUP_STARTED"
+    }
+
+    fn completed_event(&self) -> &'static str {
+        "PUBMED_LOOKUP_COMPLETED"
+    }
+
+    fn started_payload(&self, query: &str) -> Value {
+        scholarly::started_payload(query)
+    }
+
+    fn failed_payload(&self, query: &str, error: &str) -> Value {
+        scholarly::failed_payload(query, error)
+    }
+
+    /// New articles are indexed daily
+    fn cache_ttl(&self) -> Option<Duration> {
+        Some(Duration::from_secs(24 * 60 * 60))
+    }
+
+    async fn execute(&self, ctx: &ToolContext<'_>, query: &str) -> Result<ToolOutput, String> {
+        let papers = search_pubmed(ctx.client, query.trim(), scholarly::DEFAULT_MAX_PAPERS).await?;
+        log::info!("PubMed lookup '{}': {} papers", query, papers.len());
+        Ok(scholarly::papers_output(
+            query,
+            &format!("PubMed Results for '{}'", query),
+            papers,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EFETCH_XML: &str = r#"<?xml version="1.0" ?>
+<!DOCTYPE PubmedArticleSet PUBLIC "-//NLM//DTD PubMedArticle, 1st January 2024//EN" "https://dtd.nlm.nih.gov/ncbi/pubmed/out/pubmed_240101.dtd">
+<PubmedArticleSet>
+<PubmedArticle>
+  <MedlineCitation Status="MEDLINE" Owner="NLM">
+    <PMID Version="1">31978945</PMID>
+    <Article PubModel="Print-Electronic">
+      <Journal>
+        <JournalIssue CitedMedium="Internet">
+          <PubDate><Year>2020</Year><Month>Feb</Month><Day>20</Day></PubDate>
+        </JournalIssue>
+        <Title>The New England journal of medicine</Title>
+      </Journal>
+      <ArticleTitle>A Novel Coronavirus from Patients with Pneumonia in China, 2019.</ArticleTitle>
+      <Abstract>
+        <AbstractText Label="BACKGROUND" NlmCategory="BACKGROUND">In December 2019, a cluster of patients with <i>pneumonia</i> was linked to a market.</AbstractText>
+        <AbstractText Label="METHODS">Samples were sequenced &amp; analyzed.</AbstractText>
+      </Abstract>
+      <AuthorList CompleteYN="Y">
+        <Author ValidYN="Y"><LastName>Zhu</LastName><ForeName>Na</ForeName></Author>
+        <Author ValidYN="Y"><CollectiveName>China Novel Coronavirus Investigating and Research Team</CollectiveName></Author>
+      </AuthorList>
+    </Article>
+    <MeshHeadingList>
+      <MeshHeading><DescriptorName UI="D000073640" MajorTopicYN="N">Betacoronavirus</DescriptorName></MeshHeading>
+      <MeshHeading><DescriptorName UI="D006801" MajorTopicYN="N">Humans</DescriptorName></MeshHeading>
+    </MeshHeadingList>
+  </MedlineCitation>
+  <PubmedData>
+    <ArticleIdList>
+      <ArticleId IdType="pubmed">31978945</ArticleId>
+      <ArticleId IdType="doi">10.1056/NEJMoa2001017</ArticleId>
+      <ArticleId IdType="pmc">PMC7092803</ArticleId>
+    </ArticleIdList>
+  </PubmedData>
+</PubmedArticle>
+</PubmedArticleSet>"#;
+
+    #[test]
+    fn test_parse_pubmed_articles() {
+        let papers = parse_pubmed_articles(EFETCH_XML);
+        assert_eq!(papers.len(), 1);
+        let paper = &papers[0];
+        assert_eq!(
+            paper.title,
+            "A Novel Coronavirus from Patients with Pneumonia in China, 2019."
+        );
+        assert_eq!(
+            paper.summary,
+            "BACKGROUND: In December 2019, a cluster of patients with pneumonia was linked to a market. \
+             METHODS: Samples were sequenced & analyzed."
+        );
+        assert_eq!(
+            paper.authors,
+            vec![
+                "Na Zhu",
+                "China Novel Coronavirus Investigating and Research Team"
+            ]
+        );
+        assert_eq!(paper.id, "https://pubmed.ncbi.nlm.nih.gov/31978945/");
+        assert_eq!(paper.published_date.as_deref(), Some("2020-02-20"));
+        assert_eq!(
+            paper.venue.as_deref(),
+            Some("The New England journal of medicine")
+        );
+        assert_eq!(paper.doi.as_deref(), Some("10.1056/NEJMoa2001017"));
+        assert_eq!(
+            paper.pdf_url,
+            "https://www.ncbi.nlm.nih.gov/pmc/articles/PMC7092803/pdf/"
+        );
+        assert_eq!(paper.fields, vec!["Betacoronavirus", "Humans"]);
+    }
+
+    #[test]
+    fn test_medline_dates() {
+        assert_eq!(
+            publication_date("<MedlineDate>2019 Nov-Dec</MedlineDate>").as_deref(),
+            Some("2019")
+        );
+        assert_eq!(
+            publication_date("<Year>2021</Year><Month>05</Month>").as_deref(),
+            Some("2021-05")
+        );
+        assert_eq!(
+            publication_date("<Year>2021</Year>").as_deref(),
+            Some("2021")
+        );
+    }
+}
@@ -0,0 +1,200 @@
+//! Readable-text extraction for fetched web pages
+//!
+//! A lightweight, regex-based take on readability: boilerplate elements
+//! (scripts, navigation, headers, footers, forms) are dropped, the `<article>`
+//! or `<main>` element is preferred when present, and short menu-like lines are
+//! filtered out of the remaining text.
+
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Elements that never contain article text
+const BOILERPLATE_TAGS: &[&str] = &[
+    "script", "style", "noscript", "svg", "nav", "header", "footer", "aside", "form", "iframe",
+    "template", "button", "select",
+];
+
+/// Lines with fewer words are kept only if they end like a sentence or heading
+const MIN_LINE_WORDS: usize = 4;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReadableText {
+    pub title: Option<String>,
+    pub text: String,
+}
+
+fn boilerplate_patterns() -> &'static Vec<Regex> {
+    static PATTERNS: OnceLock<Vec<Regex>> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        let mut patterns = vec![Regex::new(r"(?s)<!--.*?-->").expect("comment regex is valid")];
+        patterns.extend(BOILERPLATE_TAGS.iter().map(|tag| {
+            Regex::new(&format!(r"(?is)<{}\b[^>]*>.*?</{}\s*>", tag, tag))
+                .expect("boilerplate regex is valid")
+        }));
+        patterns
+    })
+}
+
+/// Extract the title and main text of an HTML document
+pub fn extract_readable_text(html: &str) -> ReadableText {
+    static TITLE: OnceLock<Regex> = OnceLock::new();
+    let title = TITLE
+        .get_or_init(|| {
+            Regex::new(r"(?is)<title[^>]*>(.*?)</title>").expect("title regex is valid")
+        })
+        .captures(html)
+        .map(|captures| collapse_whitespace(&decode_entities(&strip_tags(&captures[1]))))
+        .filter(|title| !title.is_empty());
+
+    let mut cleaned = html.to_string();
+    for pattern in boilerplate_patterns() {
+        cleaned = pattern.replace_all(&cleaned, " ").into_owned();
+    }
+    let main_content = ["article", "main", "body"]
+        .iter()
+        .find_map(|tag| element_content(&cleaned, tag))
+        .unwrap_or(&cleaned);
+
+    static BLOCK: OnceLock<Regex> = OnceLock::new();
+    let block = BLOCK.get_or_init(|| {
+        Regex::new(
+            r"(?i)</?(p|div|section|h[1-6]|li|tr|br|blockquote|pre|ul|ol|table|dd|dt)\b[^>]*>",
+        )
+        .expect("block regex is valid")
+    });
+    let with_breaks = block.replace_all(main_content, "\n");
+    let plain = decode_entities(&strip_tags(&with_breaks));
+
+    let text = plain
+        .lines()
+        .map(collapse_whitespace)
+        .filter(|line| is_content_line(line))
+        .collect::<Vec<_>>()
+        .join("\n");
+    ReadableText { title, text }
+}
+
+/// Inner HTML of the first `<tag>` up to its last closing tag
+fn element_content<'a>(html: &'a str, tag: &str) -> Option<&'a str> {
+    // ASCII lowercasing keeps byte offsets valid for slicing `html`
+    let lower = html.to_ascii_lowercase();
+    let open = lower.find(&format!("<{}", tag))?;
+    let start = open + lower[open..].find('>')? + 1;
+    let end = lower
+        .rfind(&format!("</{}", tag))
+        .filter(|end| *end > start)?;
+    Some(&html[start..end])
+}
+
+fn is_content_line(line: &str) -> bool {
+    if line.is_empty() {
+        return false;
+    }
+    line.split_whitespace().count() >= MIN_LINE_WORDS || line.ends_with(['.', '!', '?', ':'])
+}
+
+/// Remove all HTML tags, leaving their text
+pub fn strip_tags(html: &str) -> String {
+    static TAG: OnceLock<Regex> = OnceLock::new();
+    TAG.get_or_init(|| Regex::new(r"<[^>]*>").expect("tag regex is valid"))
+        .replace_all(html, "")
+        .into_owned()
+}
+
+/// Decode the HTML entities that commonly appear in page text
+pub fn decode_entities(text: &str) -> String {
+    static ENTITY: OnceLock<Regex> = OnceLock::new();
+    ENTITY
+        .get_or_init(|| {
+            Regex::new(r"&(#[0-9]+|#[xX][0-9a-fA-F]+|[a-zA-Z]+);").expect("entity regex is valid")
+        })
+        .replace_all(text, |captures: &regex::Captures| {
+            let entity = &captures[1];
+            let decoded = if let Some(hex) = entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+            {
+                u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+            } else if let Some(decimal) = entity.strip_prefix('#') {
+                decimal.parse().ok().and_then(char::from_u32)
+            } else {
+                match entity {
+                    "amp" => Some('&'),
+                    "lt" => Some('<'),
+                    "gt" => Some('>'),
+                    "quot" => Some('"'),
+                    "apos" => Some('\''),
+                    "nbsp" => Some(' '),
+                    "ndash" => Some('–'),
+                    "mdash" => Some('—'),
+                    "hellip" => Some('…'),
+                    _ => None,
+                }
+            };
+            decoded.map_or_else(|| captures[0].to_string(), |c| c.to_string())
+        })
+        .into_owned()
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Cut text to at most `max_chars` characters, marking the cut
+pub fn truncate_chars(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let truncated: String = text.chars().take(max_chars).collect();
+    format!("{}\n[truncated]", truncated.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_boilerplate_is_removed() {
+        let html = r#"<html><head><title>Rust &amp; Tauri</title><style>p { color: red; }</style></head>
+            <body>
+              <nav><a href="/">Home</a> <a href="/about">About us and other links</a></nav>
+              <script>var tracking = "do not keep this text around";</script>
+              <p>Tauri apps are built with a Rust backend and a web frontend.</p>
+              <div>Login</div>
+              <footer>Copyright 2024 Example Corp, all rights reserved</footer>
+            </body></html>"#;
+        let readable = extract_readable_text(html);
+        assert_eq!(readable.title.as_deref(), Some("Rust & Tauri"));
+        assert_eq!(
+            readable.text,
+            "Tauri apps are built with a Rust backend and a web frontend."
+        );
+    }
+
+    #[test]
+    fn test_article_is_preferred_and_entities_decoded() {
+        let html = r#"<body><div>Related stories you might also enjoy reading</div>
+            <article><h1>Main story:</h1><p>It&#39;s 20&#x2013;25&nbsp;degrees in Tokyo today.</p></article>
+            </body>"#;
+        let readable = extract_readable_text(html);
+        assert_eq!(readable.title, None);
+        assert_eq!(
+            readable.text,
+            "Main story:\nIt's 20–25 degrees in Tokyo today."
+        );
+    }
+
+    #[test]
+    fn test_non_ascii_page() {
+        let html = "<title>İ</title><BODY><Article>ü<p>İstanbul'da hava bugün çok güzel.</p>é</ARTICLE></body>";
+        let readable = extract_readable_text(html);
+        assert_eq!(readable.title.as_deref(), Some("İ"));
+        assert_eq!(readable.text, "İstanbul'da hava bugün çok güzel.");
+    }
+
+    #[test]
+    fn test_truncate_chars() {
+        assert_eq!(truncate_chars("short", 10), "short");
+        assert_eq!(truncate_chars("abcdef", 3), "abc\n[truncated]");
+    }
+}
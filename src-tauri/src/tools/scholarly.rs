@@ -0,0 +1,236 @@
+//! Shared pieces of the academic search tools
+//!
+//! arXiv, Crossref, Semantic Scholar and PubMed results are all reported as
+//! [`PaperSummary`], so the completed events, the research context and the
+//! citations look the same whichever tool found a paper.
+
+use super::citations::ToolSource;
+use super::readability::{decode_entities, strip_tags};
+use super::{to_payload, ToolOutput};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::OnceLock;
+
+/// Papers returned by a search when the query doesn't ask for a number
+pub const DEFAULT_MAX_PAPERS: usize = 5;
+
+/// One paper as shown in the UI and given to the model
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct PaperSummary {
+    pub title: String,
+    /// The abstract; empty when the source has none
+    pub summary: String,
+    pub authors: Vec<String>,
+    /// Link to the paper's landing page (arXiv abs page, DOI, PubMed entry)
+    pub id: String,
+    pub published_date: Option<String>,
+    /// Empty when no open-access PDF is known
+    pub pdf_url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doi: Option<String>,
+    /// Journal or conference
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub venue: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub citation_count: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub influential_citation_count: Option<u64>,
+    /// arXiv categories, fields of study or MeSH terms
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<String>,
+}
+
+impl PaperSummary {
+    pub fn source(&self) -> ToolSource {
+        ToolSource::new(&self.title, &self.id, Some(&self.summary))
+    }
+
+    /// The paper as a block of the research context
+    fn render(&self) -> String {
+        let mut text = format!(
+            "Title: {}\nAuthors: {}\n",
+            self.title,
+            self.authors.join(", ")
+        );
+        if let Some(date) = &self.published_date {
+            text.push_str(&format!("Published: {}\n", date));
+        }
+        if let Some(venue) = &self.venue {
+            text.push_str(&format!("Venue: {}\n", venue));
+        }
+        match (self.citation_count, self.influential_citation_count) {
+            (Some(count), Some(influential)) => text.push_str(&format!(
+                "Citations: {} ({} influential)\n",
+                count, influential
+            )),
+            (Some(count), None) => text.push_str(&format!("Citations: {}\n", count)),
+            _ => {}
+        }
+        if let Some(doi) = &self.doi {
+            text.push_str(&format!("DOI: {}\n", doi));
+        }
+        if !self.fields.is_empty() {
+            text.push_str(&format!("Fields: {}\n", self.fields.join(", ")));
+        }
+        text.push_str(&format!("Summary: {}\n", self.summary));
+        text
+    }
+}
+
+// --- Academic Search Event Payloads ---
+#[derive(Serialize, Clone, Debug)]
+pub struct PaperSearchStartedPayload {
+    pub query: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PaperSearchCompletedPayload {
+    pub query: String,
+    pub success: bool,
+    pub results: Option<Vec<PaperSummary>>,
+    pub error: Option<String>,
+}
+
+pub fn started_payload(query: &str) -> Value {
+    to_payload(PaperSearchStartedPayload {
+        query: query.to_string(),
+    })
+}
+
+pub fn failed_payload(query: &str, error: &str) -> Value {
+    to_payload(PaperSearchCompletedPayload {
+        query: query.to_string(),
+        success: false,
+        results: Some(vec![]),
+        error: Some(error.to_string()),
+    })
+}
+
+/// Papers reported in a completed event payload; empty for other tools' payloads
+pub fn papers_in_payload(payload: &Value) -> Vec<PaperSummary> {
+    payload
+        .get("results")
+        .and_then(|results| serde_json::from_value(results.clone()).ok())
+        .unwrap_or_default()
+}
+
+/// Tool output for a list of papers; `heading` introduces them in the research context
+pub fn papers_output(query: &str, heading: &str, papers: Vec<PaperSummary>) -> ToolOutput {
+    let completed_payload = |results: Vec<PaperSummary>| {
+        to_payload(PaperSearchCompletedPayload {
+            query: query.to_string(),
+            success: true,
+            results: Some(results),
+            error: None,
+        })
+    };
+    if papers.is_empty() {
+        return ToolOutput {
+            content: "No papers found.".to_string(),
+            context: None,
+            sources: Vec::new(),
+            completed_payload: completed_payload(papers),
+        };
+    }
+
+    let text = papers
+        .iter()
+        .map(PaperSummary::render)
+        .collect::<Vec<_>>()
+        .join("\n");
+    ToolOutput {
+        content: text.clone(),
+        context: Some(format!("{}:\n{}", heading, text)),
+        sources: papers.iter().map(PaperSummary::source).collect(),
+        completed_payload: completed_payload(papers),
+    }
+}
+
+/// The first DOI in a query, DOI URL or `doi:` reference
+pub fn extract_doi(text: &str) -> Option<String> {
+    static DOI: OnceLock<Regex> = OnceLock::new();
+    let doi = DOI
+        .get_or_init(|| Regex::new(r#"\b10\.\d{4,9}/[^\s"<>]+"#).expect("DOI regex is valid"))
+        .find(text)?
+        .as_str();
+    // Sentence punctuation after a DOI isn't part of it
+    Some(doi.trim_end_matches(['.', ',', ';', ')', ']']).to_string())
+}
+
+pub fn doi_url(doi: &str) -> String {
+    format!("https://doi.org/{}", doi)
+}
+
+/// Plain text of an abstract that may contain JATS or HTML markup
+pub fn clean_abstract(text: &str) -> String {
+    // Tags become spaces so "<title>Abstract</title><p>Text" doesn't run together
+    let text = decode_entities(&strip_tags(&text.replace('<', " <")));
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    // Crossref abstracts often start with a literal "Abstract" heading
+    match text.strip_prefix("Abstract ") {
+        Some(rest) => rest.to_string(),
+        None => text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_doi() {
+        assert_eq!(
+            extract_doi("https://doi.org/10.1038/s41586-021-03819-2").as_deref(),
+            Some("10.1038/s41586-021-03819-2")
+        );
+        assert_eq!(
+            extract_doi("see doi:10.1145/3065386.").as_deref(),
+            Some("10.1145/3065386")
+        );
+        assert_eq!(extract_doi("protein structure prediction"), None);
+    }
+
+    #[test]
+    fn test_clean_abstract() {
+        assert_eq!(
+            clean_abstract("<jats:title>Abstract</jats:title><jats:p>Proteins &amp; their\n  structures.</jats:p>"),
+            "Proteins & their structures."
+        );
+    }
+
+    #[test]
+    fn test_papers_output() {
+        let paper = PaperSummary {
+            title: "Highly accurate protein structure prediction with AlphaFold".to_string(),
+            summary: "Proteins are essential to life.".to_string(),
+            authors: vec!["John Jumper".to_string()],
+            id: doi_url("10.1038/s41586-021-03819-2"),
+            published_date: Some("2021-07-15".to_string()),
+            doi: Some("10.1038/s41586-021-03819-2".to_string()),
+            venue: Some("Nature".to_string()),
+            citation_count: Some(20000),
+            influential_citation_count: Some(1500),
+            ..PaperSummary::default()
+        };
+        let output = papers_output("alphafold", "Crossref Results for 'alphafold'", vec![paper]);
+        let context = output.context.unwrap();
+        assert!(context.starts_with("Crossref Results for 'alphafold':\nTitle: Highly accurate"));
+        assert!(context.contains("Venue: Nature\nCitations: 20000 (1500 influential)\n"));
+        assert_eq!(
+            output.sources[0].url,
+            "https://doi.org/10.1038/s41586-021-03819-2"
+        );
+        assert_eq!(output.completed_payload["results"][0]["venue"], "Nature");
+        assert!(output.completed_payload["results"][0]
+            .get("fields")
+            .is_none());
+        let recorded = papers_in_payload(&output.completed_payload);
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(recorded[0].venue.as_deref(), Some("Nature"));
+        assert!(papers_in_payload(&serde_json::json!({"location": "Paris"})).is_empty());
+
+        let empty = papers_output("nothing", "Crossref Results", Vec::new());
+        assert!(empty.context.is_none());
+    }
+}
@@ -0,0 +1,470 @@
+//! Semantic Scholar search tool
+//!
+//! Searches the Semantic Scholar Graph API, and follows a paper's citation
+//! graph: `citations: <paper>` lists the papers citing it and
+//! `references: <paper>` the papers it cites, influential ones first.
+//! `influential: <topic>` ranks a search by influential citations. Papers are
+//! named by DOI, arXiv id, Semantic Scholar id or title.
+
+use super::arxiv::{arxiv_id, without_version};
+use super::fetch_url::USER_AGENT;
+use super::scholarly::{self, doi_url, extract_doi, PaperSummary};
+use super::{Tool, ToolContext, ToolOutput};
+use crate::mcp_simple::{ToolExample, ToolGuidance, ToolParameter};
+use crate::ToolType;
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::time::Duration;
+
+const S2_API_URL: &str = "https://api.semanticscholar.org/graph/v1";
+const PAPER_FIELDS: &str = "title,abstract,authors,year,publicationDate,venue,citationCount,influentialCitationCount,externalIds,url,openAccessPdf,fieldsOfStudy";
+/// Citing or cited papers fetched before ranking
+const MAX_GRAPH_PAPERS: usize = 100;
+/// Search results ranked by influence for `influential:` queries
+const MAX_RANKED_PAPERS: usize = 50;
+/// Papers listed for a citation graph query
+const MAX_LISTED_PAPERS: usize = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+enum S2Request {
+    Search(String),
+    /// Search ranked by influential citations
+    Influential(String),
+    Citations(String),
+    References(String),
+}
+
+impl S2Request {
+    fn parse(query: &str) -> Self {
+        let query = query.trim();
+        let (prefix, rest) = match query.split_once(':') {
+            // DOIs and arXiv ids contain no prefix like these
+            Some((prefix, rest)) if !prefix.contains(' ') || prefix.starts_with("cited") => {
+                (prefix.trim().to_lowercase(), rest.trim().to_string())
+            }
+            _ => return S2Request::Search(query.to_string()),
+        };
+        match prefix.as_str() {
+            "citations" | "cited by" | "citing" => S2Request::Citations(rest),
+            "references" | "refs" => S2Request::References(rest),
+            "influential" => S2Request::Influential(rest),
+            _ => S2Request::Search(query.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+struct S2List<T> {
+    data: Vec<T>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct S2Edge {
+    is_influential: bool,
+    /// Set for citations
+    citing_paper: Option<S2Paper>,
+    /// Set for references
+    cited_paper: Option<S2Paper>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct S2Paper {
+    paper_id: Option<String>,
+    title: Option<String>,
+    #[serde(rename = "abstract")]
+    abstract_text: Option<String>,
+    authors: Vec<S2Author>,
+    year: Option<i32>,
+    publication_date: Option<String>,
+    venue: Option<String>,
+    citation_count: Option<u64>,
+    influential_citation_count: Option<u64>,
+    /// Values are strings except `CorpusId`
+    external_ids: Option<HashMap<String, Value>>,
+    url: Option<String>,
+    open_access_pdf: Option<S2Pdf>,
+    fields_of_study: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+struct S2Author {
+    name: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+struct S2Pdf {
+    url: Option<String>,
+}
+
+impl S2Paper {
+    fn into_summary(self) -> PaperSummary {
+        let external_id = |key: &str| {
+            self.external_ids
+                .as_ref()
+                .and_then(|ids| ids.get(key))
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        };
+        let doi = external_id("DOI");
+        let id = self
+            .url
+            .clone()
+            .or_else(|| doi.as_deref().map(doi_url))
+            .unwrap_or_default();
+        PaperSummary {
+            title: self.title.unwrap_or_default(),
+            summary: self.abstract_text.unwrap_or_default(),
+            authors: self.authors.into_iter().filter_map(|a| a.name).collect(),
+            id,
+            published_date: self
+                .publication_date
+                .or_else(|| self.year.map(|year| year.to_string())),
+            pdf_url: self
+                .open_access_pdf
+                .and_then(|pdf| pdf.url)
+                .unwrap_or_default(),
+            doi,
+            venue: self.venue.filter(|venue| !venue.is_empty()),
+            citation_count: self.citation_count,
+            influential_citation_count: self.influential_citation_count,
+            fields: self.fields_of_study.unwrap_or_default(),
+        }
+    }
+}
+
+/// The Graph API's id for a paper reference: `DOI:...`, `ARXIV:...` or a Semantic Scholar id
+fn paper_id(reference: &str) -> Option<String> {
+    if let Some(doi) = extract_doi(reference) {
+        return Some(format!("DOI:{}", doi));
+    }
+    if let Some(id) = arxiv_id(reference) {
+        // The Graph API wants the id without its version suffix
+        return Some(format!("ARXIV:{}", without_version(&id)));
+    }
+    let reference = reference.trim();
+    let is_s2_id = reference.len() == 40 && reference.chars().all(|c| c.is_ascii_hexdigit());
+    is_s2_id.then(|| reference.to_string())
+}
+
+async fn get_s2<T: DeserializeOwned>(
+    client: &reqwest::Client,
+    path: &str,
+    params: &[(&str, &str)],
+) -> Result<T, String> {
+    let response = client
+        .get(format!("{}{}", S2_API_URL, path))
+        .header("User-Agent", USER_AGENT)
+        .query(params)
+        .send()
+        .await
+        .map_err(|e| format!("Semantic Scholar request failed: {}", e))?;
+    let status = response.status();
+    if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return Err("Semantic Scholar rate limit reached; try again in a minute".to_string());
+    }
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Err("Semantic Scholar has no record of this paper".to_string());
+    }
+    if !status.is_success() {
+        return Err(format!("Semantic Scholar request failed: HTTP {}", status));
+    }
+    response
+        .json::<T>()
+        .await
+        .map_err(|e| format!("Failed to parse Semantic Scholar response: {}", e))
+}
+
+async fn search_papers(
+    client: &reqwest::Client,
+    query: &str,
+    limit: usize,
+) -> Result<Vec<S2Paper>, String> {
+    let limit = limit.to_string();
+    let list: S2List<S2Paper> = get_s2(
+        client,
+        "/paper/search",
+        &[
+            ("query", query),
+            ("limit", limit.as_str()),
+            ("fields", PAPER_FIELDS),
+        ],
+    )
+    .await?;
+    Ok(list.data)
+}
+
+/// Resolve a paper reference to a Graph API id, searching by title if needed
+async fn resolve_paper_id(client: &reqwest::Client, reference: &str) -> Result<String, String> {
+    if let Some(id) = paper_id(reference) {
+        return Ok(id);
+    }
+    search_papers(client, reference, 1)
+        .await?
+        .into_iter()
+        .find_map(|paper| paper.paper_id)
+        .ok_or_else(|| format!("No paper found on Semantic Scholar for '{}'", reference))
+}
+
+/// Citing (or cited) papers, influential ones first, then by citation count
+fn rank_edges(edges: Vec<S2Edge>) -> Vec<PaperSummary> {
+    let mut papers: Vec<(bool, S2Paper)> = edges
+        .into_iter()
+        .filter_map(|edge| {
+            let paper = edge.citing_paper.or(edge.cited_paper)?;
+            paper
+                .title
+                .is_some()
+                .then_some((edge.is_influential, paper))
+        })
+        .collect();
+    papers.sort_by_key(|(influential, paper)| {
+        (
+            std::cmp::Reverse(*influential),
+            std::cmp::Reverse(paper.citation_count.unwrap_or(0)),
+        )
+    });
+    papers
+        .into_iter()
+        .take(MAX_LISTED_PAPERS)
+        .map(|(_, paper)| paper.into_summary())
+        .collect()
+}
+
+async fn run_request(
+    client: &reqwest::Client,
+    request: &S2Request,
+) -> Result<(String, Vec<PaperSummary>), String> {
+    match request {
+        S2Request::Search(query) => {
+            let papers = search_papers(client, query, scholarly::DEFAULT_MAX_PAPERS).await?;
+            Ok((
+                format!("Semantic Scholar Results for '{}'", query),
+                papers.into_iter().map(S2Paper::into_summary).collect(),
+            ))
+        }
+        S2Request::Influential(query) => {
+            let mut papers = search_papers(client, query, MAX_RANKED_PAPERS).await?;
+            papers.sort_by_key(|paper| {
+                std::cmp::Reverse(paper.influential_citation_count.unwrap_or(0))
+            });
+            papers.truncate(scholarly::DEFAULT_MAX_PAPERS);
+            Ok((
+                format!(
+                    "Most influential papers on '{}' (by influential citations, Semantic Scholar)",
+                    query
+                ),
+                papers.into_iter().map(S2Paper::into_summary).collect(),
+            ))
+        }
+        S2Request::Citations(reference) | S2Request::References(reference) => {
+            let citations = matches!(request, S2Request::Citations(_));
+            let id = resolve_paper_id(client, reference).await?;
+            let limit = MAX_GRAPH_PAPERS.to_string();
+            let fields = format!("isInfluential,{}", PAPER_FIELDS);
+            let path = format!(
+                "/paper/{}/{}",
+                id,
+                if citations { "citations" } else { "references" }
+            );
+            let list: S2List<S2Edge> = get_s2(
+                client,
+                &path,
+                &[("limit", limit.as_str()), ("fields", fields.as_str())],
+            )
+            .await?;
+            let heading = if citations {
+                format!(
+                    "Papers citing '{}' (influential first, Semantic Scholar)",
+                    reference
+                )
+            } else {
+                format!(
+                    "Papers cited by '{}' (influential first, Semantic Scholar)",
+                    reference
+                )
+            };
+            Ok((heading, rank_edges(list.data)))
+        }
+    }
+}
+
+pub struct SemanticScholarTool;
+
+#[async_trait]
+impl Tool for SemanticScholarTool {
+    fn tool_type(&self) -> ToolType {
+        ToolType::SemanticScholarLookup
+    }
+
+    fn description(&self) -> &'static str {
+        "Papers from Semantic Scholar with citation counts across all fields; 'citations: <paper>' or 'references: <paper>' follow the citation graph, 'influential: <topic>' ranks by influence"
+    }
+
+    fn follow_up_hint(&self) -> &'static str {
+        "Citation counts, citing papers ('citations: <DOI or arXiv id>') and influential papers"
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "Topic words, or 'citations: <paper>', 'references: <paper>' or 'influential: <topic>' where <paper> is a DOI, arXiv id or title"
+                }
+            },
+            "required": ["query"]
+        })
+    }
+
+    fn guidance(&self) -> ToolGuidance {
+        ToolGuidance {
+            name: "Semantic Scholar Search".to_string(),
+            description: "Search papers across disciplines with citation counts, and explore who cites a paper and what it builds on".to_string(),
+            usage_pattern: "Use to judge a paper's impact, find follow-up work or the foundational papers of a topic".to_string(),
+            parameters: vec![ToolParameter {
+                name: "query".to_string(),
+                param_type: "string".to_string(),
+                description: "Topic words, or 'citations: <paper>', 'references: <paper>' or 'influential: <topic>'".to_string(),
+                required: true,
+                default_value: None,
+            }],
+            examples: vec![ToolExample {
+                scenario: "User asks which work built on a well-known paper".to_string(),
+                reasoning: "The papers citing it, influential ones first, show its follow-up work".to_string(),
+                sequence: vec![
+                    "SEMANTIC_SCHOLAR_LOOKUP 'citations: 1706.03762'".to_string(),
+                    "Summarize the most influential citing papers".to_string(),
+                ],
+            }],
+            reasoning_hints: vec![
+                "Name papers by DOI or arXiv id when known; titles are matched by search".to_string(),
+                "Influential citations are those that build on the paper rather than mention it".to_string(),
+                "Shared public rate limit: prefer one well-aimed query".to_string(),
+            ],
+        }
+    }
+
+    fn capability_key(&self) -> &'static str {
+        "semantic_scholar_search"
+    }
+
+    fn capabilities(&self) -> Value {
+        json!({
+            "description": "Paper search and citation graph",
+            "best_for": ["citation counts", "citing and cited papers", "influential papers of a field"],
+            "strengths": ["all disciplines", "influential citation signal", "open-access PDF links"],
+            "limitations": ["rate limited", "no full text"]
+        })
+    }
+
+    fn started_event(&self) -> &'static str {
+        "SEMANTIC_SCHOLAR_LOOKUP_STARTED"
+    }
+
+    fn completed_event(&self) -> &'static str {
+        "SEMANTIC_SCHOLAR_LOOKUP_COMPLETED"
+    }
+
+    fn started_payload(&self, query: &str) -> Value {
+        scholarly::started_payload(query)
+    }
+
+    fn failed_payload(&self, query: &str, error: &str) -> Value {
+        scholarly::failed_payload(query, error)
+    }
+
+    /// Citation counts move slowly
+    fn cache_ttl(&self) -> Option<Duration> {
+        Some(Duration::from_secs(24 * 60 * 60))
+    }
+
+    async fn execute(&self, ctx: &ToolContext<'_>, query: &str) -> Result<ToolOutput, String> {
+        let request = S2Request::parse(query);
+        let (heading, papers) = run_request(ctx.client, &request).await?;
+        log::info!("Semantic Scholar {:?}: {} papers", request, papers.len());
+        Ok(scholarly::papers_output(query, &heading, papers))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_request() {
+        assert_eq!(
+            S2Request::parse("citations: 10.1145/3065386"),
+            S2Request::Citations("10.1145/3065386".to_string())
+        );
+        assert_eq!(
+            S2Request::parse("References:1706.03762"),
+            S2Request::References("1706.03762".to_string())
+        );
+        assert_eq!(
+            S2Request::parse("influential: graph neural networks"),
+            S2Request::Influential("graph neural networks".to_string())
+        );
+        assert_eq!(
+            S2Request::parse("Attention: is it all you need?"),
+            S2Request::Search("Attention: is it all you need?".to_string())
+        );
+        assert_eq!(
+            S2Request::parse("protein folding"),
+            S2Request::Search("protein folding".to_string())
+        );
+    }
+
+    #[test]
+    fn test_paper_id() {
+        assert_eq!(
+            paper_id("https://doi.org/10.1145/3065386").as_deref(),
+            Some("DOI:10.1145/3065386")
+        );
+        assert_eq!(
+            paper_id("arxiv.org/abs/1706.03762v7").as_deref(),
+            Some("ARXIV:1706.03762")
+        );
+        assert_eq!(
+            paper_id("204e3073870fae3d05bcbc2f6a8e263d9b72e776").as_deref(),
+            Some("204e3073870fae3d05bcbc2f6a8e263d9b72e776")
+        );
+        assert_eq!(paper_id("Attention Is All You Need"), None);
+    }
+
+    #[test]
+    fn test_rank_citations() {
+        let list: S2List<S2Edge> = serde_json::from_str(
+            r#"{"offset": 0, "data": [
+                {"isInfluential": false, "citingPaper": {"paperId": "a", "title": "Widely cited mention", "citationCount": 900, "authors": []}},
+                {"isInfluential": true, "citingPaper": {"paperId": "b", "title": "Builds on it", "citationCount": 40,
+                    "abstract": "Extends the method.",
+                    "authors": [{"authorId": "1", "name": "Grace Hopper"}],
+                    "externalIds": {"DOI": "10.1000/xyz", "CorpusId": 12345},
+                    "openAccessPdf": {"url": "https://example.org/b.pdf", "status": "GREEN"},
+                    "fieldsOfStudy": null, "year": 2022, "venue": ""}},
+                {"isInfluential": true, "citingPaper": {"paperId": "c", "title": null, "authors": []}}
+            ]}"#,
+        )
+        .unwrap();
+        let papers = rank_edges(list.data);
+        assert_eq!(papers.len(), 2);
+        assert_eq!(papers[0].title, "Builds on it");
+        assert_eq!(papers[0].summary, "Extends the method.");
+        assert_eq!(papers[0].authors, vec!["Grace Hopper"]);
+        assert_eq!(papers[0].doi.as_deref(), Some("10.1000/xyz"));
+        assert_eq!(papers[0].id, "https://doi.org/10.1000/xyz");
+        assert_eq!(papers[0].published_date.as_deref(), Some("2022"));
+        assert_eq!(papers[0].venue, None);
+        assert_eq!(papers[0].pdf_url, "https://example.org/b.pdf");
+        assert_eq!(papers[1].title, "Widely cited mention");
+    }
+}
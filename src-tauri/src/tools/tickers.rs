@@ -0,0 +1,405 @@
+//! Company name to ticker symbol resolution
+//!
+//! A table of company names, aliases and their Yahoo Finance symbols is bundled
+//! with the app (`resources/tickers.tsv`). A `tickers.tsv` in the app data
+//! directory, in the same format, adds entries and overrides bundled ones, so the
+//! table can be updated without a new release. Names are matched on normalized
+//! text ("Microsoft Corp." and "Microsoft" are the same) and, for short queries,
+//! with a small edit-distance tolerance for misspellings.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use tauri::{AppHandle, Manager};
+
+const BUNDLED_TABLE: &str = include_str!("../../resources/tickers.tsv");
+const USER_TABLE_FILENAME: &str = "tickers.tsv";
+/// Longest company name matched, in words
+const MAX_NAME_WORDS: usize = 5;
+/// Shortest normalized name matched with typos allowed
+const MIN_FUZZY_LEN: usize = 6;
+/// Similarity (1 - edit distance / length) a misspelled name needs
+const MIN_FUZZY_SIMILARITY: f64 = 0.8;
+/// Legal-form words dropped from the end of names
+const LEGAL_SUFFIXES: [&str; 16] = [
+    "inc",
+    "incorporated",
+    "corp",
+    "corporation",
+    "co",
+    "ltd",
+    "limited",
+    "plc",
+    "sa",
+    "ag",
+    "nv",
+    "se",
+    "as",
+    "llc",
+    "lp",
+    "and",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TickerEntry {
+    pub symbol: String,
+    pub name: String,
+    pub aliases: Vec<String>,
+}
+
+/// Whether a string has the shape of a Yahoo symbol (AAPL, BRK-B, 7203.T, ^GSPC, GC=F)
+pub fn is_valid_symbol(symbol: &str) -> bool {
+    !symbol.is_empty()
+        && symbol.len() <= 12
+        && symbol.chars().all(|c| {
+            c.is_ascii_uppercase() || c.is_ascii_digit() || matches!(c, '.' | '-' | '=' | '^')
+        })
+        && symbol.chars().any(|c| c.is_ascii_alphanumeric())
+}
+
+/// Parses the tab-separated table: symbol, name and `|`-separated aliases per line
+pub fn parse_table(text: &str) -> Result<Vec<TickerEntry>, String> {
+    let mut entries = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') || line.starts_with("symbol\t") {
+            continue;
+        }
+        let mut columns = line.split('\t').map(str::trim);
+        let symbol = columns.next().unwrap_or_default();
+        let name = columns.next().unwrap_or_default();
+        if !is_valid_symbol(symbol) {
+            return Err(format!(
+                "Line {}: '{}' is not a valid ticker symbol",
+                number + 1,
+                symbol
+            ));
+        }
+        if name.is_empty() {
+            return Err(format!("Line {}: missing company name", number + 1));
+        }
+        entries.push(TickerEntry {
+            symbol: symbol.to_string(),
+            name: name.to_string(),
+            aliases: columns
+                .next()
+                .unwrap_or_default()
+                .split('|')
+                .map(str::trim)
+                .filter(|alias| !alias.is_empty())
+                .map(str::to_string)
+                .collect(),
+        });
+    }
+    Ok(entries)
+}
+
+/// Lowercase words without punctuation, possessives or a trailing legal form,
+/// e.g. "The Coca-Cola Co." and "Coca Cola's" both become "coca cola"
+fn normalize_name(text: &str) -> String {
+    let text = text.replace('&', " and ").replace('’', "'");
+    let mut words: Vec<String> = text
+        .split(|c: char| c.is_whitespace() || c == '-' || c == '/')
+        .map(|word| {
+            let word = word.strip_suffix("'s").unwrap_or(word);
+            word.chars()
+                .filter(|c| c.is_alphanumeric())
+                .flat_map(char::to_lowercase)
+                .collect::<String>()
+        })
+        .filter(|word| !word.is_empty())
+        .collect();
+    if words.first().map(String::as_str) == Some("the") && words.len() > 1 {
+        words.remove(0);
+    }
+    while words.len() > 1 && LEGAL_SUFFIXES.contains(&words[words.len() - 1].as_str()) {
+        words.pop();
+    }
+    words.join(" ")
+}
+
+/// Levenshtein distance over characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+pub struct TickerTable {
+    /// Normalized names and aliases to symbols
+    names: HashMap<String, String>,
+    symbols: HashSet<String>,
+}
+
+impl TickerTable {
+    pub fn new(entries: Vec<TickerEntry>) -> TickerTable {
+        let mut names = HashMap::new();
+        let mut symbols = HashSet::new();
+        for entry in entries {
+            for name in std::iter::once(&entry.name).chain(&entry.aliases) {
+                let key = normalize_name(name);
+                if !key.is_empty() {
+                    // Later entries (the user's table) win
+                    names.insert(key, entry.symbol.clone());
+                }
+            }
+            symbols.insert(entry.symbol);
+        }
+        TickerTable { names, symbols }
+    }
+
+    pub fn contains(&self, symbol: &str) -> bool {
+        self.symbols.contains(symbol)
+    }
+
+    fn fuzzy_match(&self, key: &str) -> Option<&String> {
+        if key.len() < MIN_FUZZY_LEN {
+            return None;
+        }
+        self.names
+            .iter()
+            .filter(|(name, _)| {
+                name.len() >= MIN_FUZZY_LEN
+                    && name.len().abs_diff(key.len()) <= 2
+                    && name.chars().next() == key.chars().next()
+            })
+            .map(|(name, symbol)| {
+                let distance = edit_distance(name, key);
+                (
+                    1.0 - distance as f64 / name.len().max(key.len()) as f64,
+                    symbol,
+                )
+            })
+            .filter(|(similarity, _)| *similarity >= MIN_FUZZY_SIMILARITY)
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, symbol)| symbol)
+    }
+
+    /// Symbols of the companies named in a text, in order of first mention
+    ///
+    /// Longer names win over the words inside them ("Bank of America" over
+    /// "America"). In `proper_nouns_only` mode, used for long research text, names
+    /// must be capitalized and spelled exactly; otherwise lowercase names and small
+    /// typos are accepted. All-caps words that are symbols in the table also count.
+    pub fn resolve(&self, text: &str, proper_nouns_only: bool) -> Vec<String> {
+        self.resolve_with_rest(text, proper_nouns_only).0
+    }
+
+    /// Like `resolve`, also returning the words that aren't part of a known name,
+    /// stripped of surrounding punctuation
+    pub fn resolve_with_rest<'t>(
+        &self,
+        text: &'t str,
+        proper_nouns_only: bool,
+    ) -> (Vec<String>, Vec<&'t str>) {
+        let words: Vec<&str> = text
+            .split_whitespace()
+            .map(|word| {
+                word.trim_matches(|c: char| !c.is_alphanumeric() && !matches!(c, '&' | '\'' | '’'))
+            })
+            .collect();
+        let mut found: Vec<String> = Vec::new();
+        let mut rest: Vec<&str> = Vec::new();
+        let mut i = 0;
+        while i < words.len() {
+            let capitalized = words[i].chars().next().is_some_and(char::is_uppercase);
+            if words[i].is_empty() || (proper_nouns_only && !capitalized) {
+                rest.extend(Some(words[i]).filter(|word| !word.is_empty()));
+                i += 1;
+                continue;
+            }
+            let longest = MAX_NAME_WORDS.min(words.len() - i);
+            let keys: Vec<String> = (1..=longest)
+                .rev()
+                .map(|length| normalize_name(&words[i..i + length].join(" ")))
+                .collect();
+            // Exact names first, so a typo tolerance never beats a real match
+            let mut matched = keys
+                .iter()
+                .zip((1..=longest).rev())
+                .find_map(|(key, length)| Some((self.names.get(key)?.clone(), length)));
+            if matched.is_none() && !proper_nouns_only {
+                matched = keys
+                    .iter()
+                    .zip((1..=longest).rev())
+                    .find_map(|(key, length)| Some((self.fuzzy_match(key)?.clone(), length)));
+            }
+            if matched.is_none() && words[i].len() >= 2 && self.symbols.contains(words[i]) {
+                matched = Some((words[i].to_string(), 1));
+            }
+            match matched {
+                Some((symbol, length)) => {
+                    if !found.contains(&symbol) {
+                        found.push(symbol);
+                    }
+                    i += length;
+                }
+                None => {
+                    rest.push(words[i]);
+                    i += 1;
+                }
+            }
+        }
+        (found, rest)
+    }
+}
+
+static TABLE: RwLock<Option<Arc<TickerTable>>> = RwLock::new(None);
+
+fn user_table_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    match app_handle.path().app_data_dir() {
+        Ok(dir) => Ok(dir.join(USER_TABLE_FILENAME)),
+        Err(e) => Err(format!("Failed to get app data directory: {}", e)),
+    }
+}
+
+fn load_table(app_handle: &AppHandle) -> TickerTable {
+    let mut entries = parse_table(BUNDLED_TABLE).unwrap_or_else(|e| {
+        log::error!("Bundled ticker table is invalid: {}", e);
+        Vec::new()
+    });
+    match user_table_path(app_handle) {
+        Ok(path) if path.exists() => match fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| parse_table(&text))
+        {
+            Ok(user_entries) => {
+                log::info!(
+                    "Loaded {} ticker(s) from {}",
+                    user_entries.len(),
+                    path.display()
+                );
+                entries.extend(user_entries);
+            }
+            Err(e) => log::warn!("Ignoring ticker table {}: {}", path.display(), e),
+        },
+        Ok(_) => {}
+        Err(e) => log::warn!("{}", e),
+    }
+    TickerTable::new(entries)
+}
+
+/// The bundled table merged with the user's, loaded on first use
+pub fn ticker_table(app_handle: &AppHandle) -> Arc<TickerTable> {
+    if let Some(table) = TABLE.read().ok().and_then(|table| table.clone()) {
+        return table;
+    }
+    let table = Arc::new(load_table(app_handle));
+    if let Ok(mut cached) = TABLE.write() {
+        *cached = Some(table.clone());
+    }
+    table
+}
+
+fn invalidate() {
+    if let Ok(mut cached) = TABLE.write() {
+        *cached = None;
+    }
+}
+
+/// Replaces the user's ticker table, returning the number of entries imported
+pub fn import_table(app_handle: &AppHandle, text: &str) -> Result<usize, String> {
+    let entries = parse_table(text)?;
+    let path = user_table_path(app_handle)?;
+    if let Some(parent_dir) = path.parent() {
+        fs::create_dir_all(parent_dir)
+            .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+    }
+    fs::write(&path, text).map_err(|e| format!("Failed to write ticker table: {}", e))?;
+    invalidate();
+    Ok(entries.len())
+}
+
+/// Removes the user's ticker table, going back to the bundled one
+pub fn reset_table(app_handle: &AppHandle) -> Result<(), String> {
+    let path = user_table_path(app_handle)?;
+    if path.exists() {
+        fs::remove_file(&path).map_err(|e| format!("Failed to remove ticker table: {}", e))?;
+    }
+    invalidate();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bundled() -> TickerTable {
+        TickerTable::new(parse_table(BUNDLED_TABLE).expect("bundled table parses"))
+    }
+
+    #[test]
+    fn test_bundled_table_is_valid() {
+        let entries = parse_table(BUNDLED_TABLE).unwrap();
+        assert!(entries.len() > 200);
+        let symbols: HashSet<&str> = entries.iter().map(|e| e.symbol.as_str()).collect();
+        assert_eq!(symbols.len(), entries.len(), "duplicate symbols");
+        assert!(parse_table("aapl\tApple").is_err());
+        assert!(parse_table("AAPL\t").is_err());
+    }
+
+    #[test]
+    fn test_normalizes_legal_forms_and_possessives() {
+        assert_eq!(
+            normalize_name("The Coca-Cola Company Inc."),
+            "coca cola company"
+        );
+        assert_eq!(normalize_name("Coca Cola's"), "coca cola");
+        assert_eq!(normalize_name("Merck & Co. Inc."), "merck");
+        assert_eq!(normalize_name("McDonald’s"), "mcdonald");
+        assert_eq!(edit_distance("nvidia", "nvidea"), 1);
+    }
+
+    #[test]
+    fn test_resolves_company_names() {
+        let table = bundled();
+        assert_eq!(
+            table.resolve("how are apple and microsoft corp. doing", false),
+            vec!["AAPL", "MSFT"]
+        );
+        assert_eq!(
+            table.resolve("Bank of America vs Wells Fargo & Co", false),
+            vec!["BAC", "WFC"]
+        );
+        assert_eq!(
+            table.resolve_with_rest("Apple, Foocorp and Bank of America stock", false),
+            (
+                vec!["AAPL".to_string(), "BAC".to_string()],
+                vec!["Foocorp", "and", "stock"]
+            )
+        );
+        // Misspellings are only forgiven outside long research text
+        assert_eq!(table.resolve("Nvidea", false), vec!["NVDA"]);
+        assert!(table.resolve("Nvidea", true).is_empty());
+        assert_eq!(
+            table.resolve(
+                "Quantum computers are built by IBM, Google and IonQ; an apple a day.",
+                true
+            ),
+            vec!["IBM", "GOOGL", "IONQ"]
+        );
+    }
+
+    #[test]
+    fn test_user_entries_extend_and_override_the_bundled_table() {
+        let mut entries = parse_table(BUNDLED_TABLE).unwrap();
+        entries.extend(
+            parse_table("SQ\tBlock Inc.\tSquare\nGOOG\tAlphabet Inc. Class C\tGoogle").unwrap(),
+        );
+        let table = TickerTable::new(entries);
+        assert_eq!(
+            table.resolve("Square and Google", false),
+            vec!["SQ", "GOOG"]
+        );
+        assert!(table.contains("SQ"));
+    }
+}
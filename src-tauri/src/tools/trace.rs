@@ -0,0 +1,467 @@
+//! Per-request tool trace
+//!
+//! Records what happened during the research phase of one message: the decider
+//! prompt and its raw response, the parsed decisions, every tool call with its
+//! timing and outcome, the follow-up rounds and the size of the final context.
+//! The most recent traces are kept in memory for `get_tool_trace`; when
+//! `persist_tool_traces` is enabled they are also stored per conversation in the
+//! app data directory. Papers found by a call are kept with it, and stored per
+//! conversation regardless of that setting, so a conversation's papers can be
+//! exported later.
+
+use super::executor::ToolRun;
+use super::scholarly::{papers_in_payload, PaperSummary};
+use crate::{MultiToolDecisionResponse, ToolDecision, ToolType};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+use tauri::{AppHandle, Manager};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// How many traces are kept in memory
+const MAX_RECENT_TRACES: usize = 20;
+/// How many traces are kept on disk per conversation
+const MAX_PERSISTED_TRACES: usize = 50;
+const TRACE_DIRNAME: &str = "tool_traces";
+const PAPERS_DIRNAME: &str = "conversation_papers";
+/// How many papers are kept on disk per conversation
+const MAX_CONVERSATION_PAPERS: usize = 500;
+
+/// One call to the decider model, for the initial plan or a follow-up round
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeciderTrace {
+    pub model: String,
+    pub prompt: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_response: Option<String>,
+    #[serde(default)]
+    pub decisions: Vec<ToolDecision>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<String>,
+    /// Request or parse error; no tools are run from a failed decision
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub duration_ms: u64,
+    #[serde(skip, default = "Instant::now")]
+    started: Instant,
+}
+
+impl DeciderTrace {
+    pub fn new(model: &str, prompt: &str) -> Self {
+        DeciderTrace {
+            model: model.to_string(),
+            prompt: prompt.to_string(),
+            raw_response: None,
+            decisions: Vec::new(),
+            reasoning: None,
+            error: None,
+            duration_ms: 0,
+            started: Instant::now(),
+        }
+    }
+
+    pub fn responded(&mut self, raw_response: &str) {
+        self.raw_response = Some(raw_response.to_string());
+        self.duration_ms = self.started.elapsed().as_millis() as u64;
+    }
+
+    pub fn decided(&mut self, response: &MultiToolDecisionResponse) {
+        self.decisions = response.tools.clone();
+        self.reasoning = Some(response.reasoning.clone());
+    }
+
+    pub fn failed(&mut self, error: &str) {
+        self.error = Some(error.to_string());
+        if self.raw_response.is_none() {
+            self.duration_ms = self.started.elapsed().as_millis() as u64;
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ToolCallTrace {
+    pub tool_type: ToolType,
+    pub query: String,
+    pub reasoning: String,
+    pub priority: u8,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<ToolType>,
+    pub status: String, // "completed", "failed" or "skipped"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub duration_ms: u64,
+    pub cache_hit: bool,
+    pub context_chars: usize,
+    pub source_count: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub papers: Vec<PaperSummary>, // Papers the call found, for bibliography export
+}
+
+impl ToolCallTrace {
+    pub fn skipped(decision: &ToolDecision, reason: &str) -> Self {
+        ToolCallTrace {
+            tool_type: decision.tool_type.clone(),
+            query: decision.query.clone(),
+            reasoning: decision.reasoning.clone(),
+            priority: decision.priority,
+            depends_on: decision.depends_on.clone(),
+            status: "skipped".to_string(),
+            error: Some(reason.to_string()),
+            duration_ms: 0,
+            cache_hit: false,
+            context_chars: 0,
+            source_count: 0,
+            papers: Vec::new(),
+        }
+    }
+
+    pub fn from_run(run: &ToolRun) -> Self {
+        ToolCallTrace {
+            tool_type: run.decision.tool_type.clone(),
+            query: run.decision.query.clone(),
+            reasoning: run.decision.reasoning.clone(),
+            priority: run.decision.priority,
+            depends_on: run.decision.depends_on.clone(),
+            status: if run.result.success {
+                "completed".to_string()
+            } else {
+                "failed".to_string()
+            },
+            error: run.result.error.clone(),
+            duration_ms: run.elapsed.as_millis() as u64,
+            cache_hit: run.cache_hit,
+            context_chars: run.context.as_ref().map_or(0, |c| c.len()),
+            source_count: run.sources.len(),
+            papers: run
+                .completed_payload
+                .as_ref()
+                .map(papers_in_payload)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// One round of tool calls and the follow-up decision made after it
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct IterationTrace {
+    pub number: usize,
+    pub tool_calls: Vec<ToolCallTrace>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub follow_up: Option<DeciderTrace>,
+    /// Follow-up calls after each tool refined them (e.g. resolved tickers)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub expanded_follow_ups: Vec<ToolDecision>,
+    pub context_chars: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ToolTrace {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conversation_id: Option<String>,
+    pub started_at: String, // RFC 3339, UTC
+    pub user_query: String,
+    /// `None` when a prompt template chose the tools
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decider: Option<DeciderTrace>,
+    #[serde(default)]
+    pub iterations: Vec<IterationTrace>,
+    pub final_context_chars: usize,
+    pub duration_ms: u64,
+    #[serde(skip, default = "Instant::now")]
+    started: Instant,
+}
+
+impl ToolTrace {
+    pub fn new(user_query: &str, conversation_id: Option<String>) -> Self {
+        ToolTrace {
+            id: Uuid::new_v4().to_string(),
+            conversation_id,
+            started_at: OffsetDateTime::now_utc()
+                .format(&Rfc3339)
+                .unwrap_or_default(),
+            user_query: user_query.to_string(),
+            decider: None,
+            iterations: Vec::new(),
+            final_context_chars: 0,
+            duration_ms: 0,
+            started: Instant::now(),
+        }
+    }
+
+    /// The trace of the current round, starting a new one if `number` is new
+    pub fn iteration(&mut self, number: usize) -> &mut IterationTrace {
+        if self.iterations.last().map(|i| i.number) != Some(number) {
+            self.iterations.push(IterationTrace {
+                number,
+                ..IterationTrace::default()
+            });
+        }
+        self.iterations
+            .last_mut()
+            .expect("iteration was just pushed")
+    }
+
+    pub fn finish(&mut self, final_context_chars: usize) {
+        self.final_context_chars = final_context_chars;
+        self.duration_ms = self.started.elapsed().as_millis() as u64;
+    }
+}
+
+fn recent_traces() -> &'static Mutex<VecDeque<ToolTrace>> {
+    static RECENT: OnceLock<Mutex<VecDeque<ToolTrace>>> = OnceLock::new();
+    RECENT.get_or_init(|| Mutex::new(VecDeque::new()))
+}
+
+/// Keep a finished trace in memory, dropping the oldest beyond the limit
+pub fn remember(trace: ToolTrace) {
+    if let Ok(mut recent) = recent_traces().lock() {
+        recent.push_back(trace);
+        while recent.len() > MAX_RECENT_TRACES {
+            recent.pop_front();
+        }
+    }
+}
+
+/// A remembered trace by id, or the most recent one
+pub fn find(trace_id: Option<&str>) -> Option<ToolTrace> {
+    let recent = recent_traces().lock().ok()?;
+    match trace_id {
+        Some(id) => recent.iter().rev().find(|t| t.id == id).cloned(),
+        None => recent.back().cloned(),
+    }
+}
+
+/// File name for a conversation's traces, keeping only filename-safe characters
+fn trace_filename(conversation_id: &str) -> String {
+    let safe: String = conversation_id
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .collect();
+    format!("{}.json", if safe.is_empty() { "default" } else { &safe })
+}
+
+fn conversation_path(
+    app_handle: &AppHandle,
+    dirname: &str,
+    conversation_id: &str,
+) -> Result<PathBuf, String> {
+    match app_handle.path().app_data_dir() {
+        Ok(dir) => Ok(dir.join(dirname).join(trace_filename(conversation_id))),
+        Err(e) => Err(format!("Failed to get app data directory: {}", e)),
+    }
+}
+
+fn trace_path(app_handle: &AppHandle, conversation_id: &str) -> Result<PathBuf, String> {
+    conversation_path(app_handle, TRACE_DIRNAME, conversation_id)
+}
+
+fn load_from(path: &Path) -> Result<Vec<ToolTrace>, String> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content =
+        fs::read_to_string(path).map_err(|e| format!("Failed to read tool traces: {}", e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Failed to parse tool traces: {}", e))
+}
+
+fn append_to(path: &Path, trace: &ToolTrace) -> Result<(), String> {
+    let mut traces = load_from(path)?;
+    traces.push(trace.clone());
+    if traces.len() > MAX_PERSISTED_TRACES {
+        traces.drain(..traces.len() - MAX_PERSISTED_TRACES);
+    }
+    write_json(path, &traces, "tool traces")
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T, what: &str) -> Result<(), String> {
+    if let Some(parent_dir) = path.parent() {
+        if !parent_dir.exists() {
+            fs::create_dir_all(parent_dir)
+                .map_err(|e| format!("Failed to create {} directory: {}", what, e))?;
+        }
+    }
+    let json = serde_json::to_string_pretty(value)
+        .map_err(|e| format!("Failed to serialize {}: {}", what, e))?;
+    fs::write(path, json).map_err(|e| format!("Failed to write {}: {}", what, e))
+}
+
+/// Store a trace with its conversation; traces without a conversation are not persisted
+pub fn persist(app_handle: &AppHandle, trace: &ToolTrace) -> Result<(), String> {
+    let conversation_id = match trace.conversation_id.as_deref() {
+        Some(id) => id,
+        None => return Ok(()),
+    };
+    append_to(&trace_path(app_handle, conversation_id)?, trace)
+}
+
+/// All persisted traces of a conversation, oldest first
+pub fn load_conversation_traces(
+    app_handle: &AppHandle,
+    conversation_id: &str,
+) -> Result<Vec<ToolTrace>, String> {
+    load_from(&trace_path(app_handle, conversation_id)?)
+}
+
+fn load_papers_from(path: &Path) -> Result<Vec<PaperSummary>, String> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read conversation papers: {}", e))?;
+    serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse conversation papers: {}", e))
+}
+
+/// Add the papers not already in `papers`, by landing page or title
+fn add_new_papers(papers: &mut Vec<PaperSummary>, found: impl IntoIterator<Item = PaperSummary>) {
+    for paper in found {
+        let known = papers.iter().any(|p| {
+            if paper.id.is_empty() {
+                p.title == paper.title
+            } else {
+                p.id == paper.id
+            }
+        });
+        if !known {
+            papers.push(paper);
+        }
+    }
+}
+
+fn add_papers_to(path: &Path, found: Vec<PaperSummary>) -> Result<(), String> {
+    let mut papers = load_papers_from(path)?;
+    add_new_papers(&mut papers, found);
+    if papers.len() > MAX_CONVERSATION_PAPERS {
+        papers.drain(..papers.len() - MAX_CONVERSATION_PAPERS);
+    }
+    write_json(path, &papers, "conversation papers")
+}
+
+/// Store the papers a trace found with its conversation, whether or not traces
+/// are persisted
+pub fn persist_papers(app_handle: &AppHandle, trace: &ToolTrace) -> Result<(), String> {
+    let conversation_id = match trace.conversation_id.as_deref() {
+        Some(id) => id,
+        None => return Ok(()),
+    };
+    let found = papers_of(std::slice::from_ref(trace));
+    if found.is_empty() {
+        return Ok(());
+    }
+    add_papers_to(
+        &conversation_path(app_handle, PAPERS_DIRNAME, conversation_id)?,
+        found,
+    )
+}
+
+/// Papers found in a conversation, oldest first: the stored ones, then any only
+/// in its persisted or recent traces (e.g. from before papers were stored)
+pub fn conversation_papers(
+    app_handle: &AppHandle,
+    conversation_id: &str,
+) -> Result<Vec<PaperSummary>, String> {
+    let mut papers = load_papers_from(&conversation_path(
+        app_handle,
+        PAPERS_DIRNAME,
+        conversation_id,
+    )?)?;
+    let mut traces = load_conversation_traces(app_handle, conversation_id)?;
+    if let Ok(recent) = recent_traces().lock() {
+        for trace in recent
+            .iter()
+            .filter(|t| t.conversation_id.as_deref() == Some(conversation_id))
+        {
+            if !traces.iter().any(|t| t.id == trace.id) {
+                traces.push(trace.clone());
+            }
+        }
+    }
+    add_new_papers(&mut papers, papers_of(&traces));
+    Ok(papers)
+}
+
+fn papers_of(traces: &[ToolTrace]) -> Vec<PaperSummary> {
+    traces
+        .iter()
+        .flat_map(|trace| &trace.iterations)
+        .flat_map(|iteration| &iteration.tool_calls)
+        .flat_map(|call| call.papers.iter().cloned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_iterations_are_grouped_by_number() {
+        let mut trace = ToolTrace::new("weather in Tokyo", None);
+        trace.iteration(1).context_chars = 10;
+        trace.iteration(1).context_chars += 5;
+        trace.iteration(2).context_chars = 7;
+        trace.finish(22);
+
+        let numbers: Vec<usize> = trace.iterations.iter().map(|i| i.number).collect();
+        assert_eq!(numbers, vec![1, 2]);
+        assert_eq!(trace.iterations[0].context_chars, 15);
+        assert_eq!(trace.final_context_chars, 22);
+    }
+
+    #[test]
+    fn test_persisted_traces_round_trip() {
+        let path = std::env::temp_dir()
+            .join(format!("shard-trace-test-{}", Uuid::new_v4()))
+            .join(trace_filename("conv/../42"));
+        assert!(path.ends_with("conv42.json"));
+
+        let trace = ToolTrace::new("GDP of Japan", Some("conv-42".to_string()));
+        append_to(&path, &trace).unwrap();
+        append_to(&path, &ToolTrace::new("and Korea?", None)).unwrap();
+
+        let loaded = load_from(&path).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[0].id, trace.id);
+        assert_eq!(loaded[1].user_query, "and Korea?");
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_stored_papers_are_deduplicated() {
+        let path = std::env::temp_dir()
+            .join(format!("shard-papers-test-{}", Uuid::new_v4()))
+            .join(trace_filename("conv-42"));
+        let paper = |id: &str, title: &str| PaperSummary {
+            id: id.to_string(),
+            title: title.to_string(),
+            ..PaperSummary::default()
+        };
+        add_papers_to(
+            &path,
+            vec![paper("https://arxiv.org/abs/1", "A"), paper("", "B")],
+        )
+        .unwrap();
+        add_papers_to(
+            &path,
+            vec![
+                paper("https://arxiv.org/abs/1", "A (v2)"),
+                paper("", "B"),
+                paper("https://doi.org/10.1/c", "C"),
+            ],
+        )
+        .unwrap();
+
+        let titles: Vec<String> = load_papers_from(&path)
+            .unwrap()
+            .into_iter()
+            .map(|p| p.title)
+            .collect();
+        assert_eq!(titles, vec!["A", "B", "C"]);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
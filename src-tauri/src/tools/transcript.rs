@@ -0,0 +1,717 @@
+//! Video transcript summarization tool
+//!
+//! Reads the caption track of a YouTube video (or a local `.vtt`/`.srt` file
+//! whose path is in the user's message or attached to the conversation),
+//! splits it into timestamped chunks and summarizes it map-reduce style: each
+//! chunk is summarized on its own, then the chunk summaries are merged. Any
+//! `[m:ss]` timestamps in the result link back to that moment of the video.
+
+use super::citations::ToolSource;
+use super::fetch_url::extract_urls;
+use super::readability::{decode_entities, strip_tags, truncate_chars};
+use super::{to_payload, Tool, ToolContext, ToolOutput};
+use crate::mcp_simple::{ToolExample, ToolGuidance, ToolParameter};
+use crate::{call_gemini_api_non_streaming, ChatMessage, ToolType};
+use async_trait::async_trait;
+use futures_util::stream::{self, StreamExt};
+use regex::Regex;
+use reqwest::Url;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// Target size of one transcript chunk, in characters
+const CHUNK_CHARS: usize = 6000;
+/// Longer transcripts get larger chunks so one video needs at most this many map calls
+const MAX_CHUNKS: usize = 12;
+/// Chunk summaries requested at the same time
+const MAX_PARALLEL_SUMMARIES: usize = 3;
+/// Transcript text used as context when no summary can be made
+const MAX_RAW_TRANSCRIPT_CHARS: usize = 12000;
+
+// --- Video Transcript Event Payloads ---
+#[derive(Serialize, Clone, Debug)]
+struct VideoTranscriptStartedPayload {
+    source: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+struct VideoTranscriptCompletedPayload {
+    source: String,
+    success: bool,
+    title: Option<String>,
+    video_url: Option<String>,
+    duration_seconds: Option<u64>,
+    chunks: Option<usize>,
+    summary: Option<String>,
+    error: Option<String>,
+}
+
+/// One caption cue
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cue {
+    pub start: f64, // Seconds from the start of the video
+    pub end: f64,
+    pub text: String,
+}
+
+/// Consecutive cues summarized together
+#[derive(Debug, Clone, PartialEq)]
+pub struct TranscriptChunk {
+    pub start: f64,
+    pub end: f64,
+    /// One "[m:ss] text" line per cue
+    pub text: String,
+}
+
+/// Parse `HH:MM:SS.mmm`, `MM:SS.mmm` or the SRT form `HH:MM:SS,mmm` into seconds
+fn parse_timestamp(timestamp: &str) -> Option<f64> {
+    let normalized = timestamp.trim().replace(',', ".");
+    let parts: Vec<&str> = normalized.split(':').collect();
+    let (hours, minutes, seconds) = match parts.as_slice() {
+        [h, m, s] => (
+            h.parse::<f64>().ok()?,
+            m.parse::<f64>().ok()?,
+            s.parse::<f64>().ok()?,
+        ),
+        [m, s] => (0.0, m.parse::<f64>().ok()?, s.parse::<f64>().ok()?),
+        _ => return None,
+    };
+    Some(hours * 3600.0 + minutes * 60.0 + seconds)
+}
+
+/// Parse WebVTT or SRT subtitles into cues
+///
+/// Inline markup (`<c>`, `<i>`, word timings) is removed, and the repeated text
+/// of rolling auto-generated captions is kept only once.
+pub fn parse_subtitles(content: &str) -> Vec<Cue> {
+    let normalized = content.replace("\r\n", "\n").replace('\r', "\n");
+    let mut cues: Vec<Cue> = Vec::new();
+    for block in normalized.split("\n\n") {
+        let mut lines = block.lines().skip_while(|line| !line.contains("-->"));
+        let timing = match lines.next() {
+            Some(timing) => timing,
+            None => continue, // Header, NOTE or STYLE block
+        };
+        let (start, rest) = match timing.split_once("-->") {
+            Some(parts) => parts,
+            None => continue,
+        };
+        let end = rest.split_whitespace().next().unwrap_or_default();
+        let (start, end) = match (parse_timestamp(start), parse_timestamp(end)) {
+            (Some(start), Some(end)) => (start, end),
+            _ => continue,
+        };
+
+        let raw_text = lines.collect::<Vec<_>>().join(" ");
+        let mut text = decode_entities(&strip_tags(&raw_text))
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        if let Some(previous) = cues.last() {
+            if text == previous.text {
+                continue;
+            }
+            if let Some(new_text) = text.strip_prefix(&previous.text) {
+                text = new_text.trim().to_string();
+            }
+        }
+        if !text.is_empty() {
+            cues.push(Cue { start, end, text });
+        }
+    }
+    cues
+}
+
+/// `m:ss`, or `h:mm:ss` for times past the first hour
+pub fn format_timestamp(seconds: f64) -> String {
+    let total = seconds.max(0.0) as u64;
+    let (hours, minutes, secs) = (total / 3600, (total % 3600) / 60, total % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, secs)
+    } else {
+        format!("{}:{:02}", minutes, secs)
+    }
+}
+
+/// Group cues into chunks of roughly `max_chars` characters
+pub fn chunk_cues(cues: &[Cue], max_chars: usize) -> Vec<TranscriptChunk> {
+    let mut chunks: Vec<TranscriptChunk> = Vec::new();
+    let mut current: Option<TranscriptChunk> = None;
+    for cue in cues {
+        let line = format!("[{}] {}\n", format_timestamp(cue.start), cue.text);
+        if let Some(chunk) = current.as_mut() {
+            if chunk.text.len() + line.len() <= max_chars {
+                chunk.text.push_str(&line);
+                chunk.end = cue.end;
+                continue;
+            }
+        }
+        if let Some(full) = current.take() {
+            chunks.push(full);
+        }
+        current = Some(TranscriptChunk {
+            start: cue.start,
+            end: cue.end,
+            text: line,
+        });
+    }
+    chunks.extend(current);
+    chunks
+}
+
+/// Turn `[m:ss]` markers into links to that moment of the video
+pub fn link_timestamps(text: &str, video_url: &str) -> String {
+    static TIMESTAMP: OnceLock<Regex> = OnceLock::new();
+    let timestamp = TIMESTAMP.get_or_init(|| {
+        Regex::new(r"\[(\d{1,2}(?::\d{2}){1,2})\](\()?").expect("timestamp regex is valid")
+    });
+    timestamp
+        .replace_all(text, |captures: &regex::Captures| {
+            // Already a markdown link
+            if captures.get(2).is_some() {
+                return captures[0].to_string();
+            }
+            match parse_timestamp(&captures[1]) {
+                Some(seconds) => format!("[{}]({}&t={}s)", &captures[1], video_url, seconds as u64),
+                None => captures[0].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+/// The 11-character video id of a YouTube watch, short, embed or youtu.be link
+pub fn youtube_video_id(url: &str) -> Option<String> {
+    let parsed = Url::parse(url.trim()).ok()?;
+    let host = parsed
+        .host_str()?
+        .trim_start_matches("www.")
+        .trim_start_matches("m.");
+    let candidate = match host {
+        "youtu.be" => parsed.path_segments()?.next().map(str::to_string),
+        "youtube.com" | "music.youtube.com" => {
+            let mut segments = parsed.path_segments()?;
+            match segments.next() {
+                Some("watch") => parsed
+                    .query_pairs()
+                    .find(|(key, _)| key == "v")
+                    .map(|(_, value)| value.into_owned()),
+                Some("shorts") | Some("embed") | Some("live") => {
+                    segments.next().map(str::to_string)
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }?;
+    let valid = candidate.len() == 11
+        && candidate
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    valid.then_some(candidate)
+}
+
+async fn get_text(client: &reqwest::Client, url: &str) -> Result<String, String> {
+    let response = client
+        .get(url)
+        .header(
+            "User-Agent",
+            "Mozilla/5.0 (compatible; Shard/1.0; +https://github.com/shard-app/shard)",
+        )
+        .header("Accept-Language", "en-US,en;q=0.8")
+        .send()
+        .await
+        .map_err(|e| format!("YouTube network error: {}", e))?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("YouTube returned HTTP {}", status));
+    }
+    response
+        .text()
+        .await
+        .map_err(|e| format!("YouTube: Failed to read response text: {}", e))
+}
+
+/// Download the caption track of a video as WebVTT, preferring English
+///
+/// Returns the video title (if found) and the subtitles.
+async fn fetch_youtube_captions(
+    client: &reqwest::Client,
+    video_id: &str,
+) -> Result<(Option<String>, String), String> {
+    static TITLE: OnceLock<Regex> = OnceLock::new();
+    static BASE_URL: OnceLock<Regex> = OnceLock::new();
+
+    let watch_page = get_text(
+        client,
+        &format!("https://www.youtube.com/watch?v={}", video_id),
+    )
+    .await?;
+    let title = TITLE
+        .get_or_init(|| Regex::new(r"(?s)<title>(.*?)</title>").expect("title regex is valid"))
+        .captures(&watch_page)
+        .map(|captures| decode_entities(captures[1].trim_end_matches(" - YouTube").trim()))
+        .filter(|title| !title.is_empty());
+
+    let track_urls: Vec<String> = BASE_URL
+        .get_or_init(|| {
+            Regex::new(r#""baseUrl":"(https://www\.youtube\.com/api/timedtext[^"]+)""#)
+                .expect("caption track regex is valid")
+        })
+        .captures_iter(&watch_page)
+        .map(|captures| captures[1].replace("\\u0026", "&").replace("\\/", "/"))
+        .collect();
+    let track_url = track_urls
+        .iter()
+        .find(|url| url.contains("lang=en"))
+        .or_else(|| track_urls.first())
+        .map(|url| format!("{}&fmt=vtt", url))
+        .unwrap_or_else(|| {
+            format!(
+                "https://www.youtube.com/api/timedtext?v={}&lang=en&fmt=vtt",
+                video_id
+            )
+        });
+    log::info!(
+        "Fetching captions for video {} from {}",
+        video_id,
+        track_url
+    );
+
+    let captions = get_text(client, &track_url).await?;
+    if captions.trim().is_empty() {
+        return Err(format!("No captions available for video {}", video_id));
+    }
+    Ok((title, captions))
+}
+
+/// The local file a query names, if the user typed that path or attached the
+/// file; paths chosen from fetched content or by the model alone are refused
+fn permitted_local_path<'a>(
+    query: &'a str,
+    user_query: &str,
+    attached_paths: &[String],
+) -> Result<&'a str, String> {
+    let path = query.trim().trim_start_matches("file://");
+    if !path.is_empty()
+        && (user_query.contains(path) || attached_paths.iter().any(|attached| attached == path))
+    {
+        Ok(path)
+    } else {
+        Err(format!(
+            "Local subtitle files are only read when their path is in your message or attached: {}",
+            path
+        ))
+    }
+}
+
+/// Read a local subtitle file, accepting only `.vtt` and `.srt`
+fn read_subtitle_file(path: &str) -> Result<String, String> {
+    let path = Path::new(path);
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+    if !matches!(extension.as_deref(), Some("vtt") | Some("srt")) {
+        return Err(format!(
+            "Not a YouTube link or a .vtt/.srt subtitle file: {}",
+            path.display()
+        ));
+    }
+    fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+}
+
+async fn summarize_chunk(
+    ctx: &ToolContext<'_>,
+    title: &str,
+    chunk: &TranscriptChunk,
+    index: usize,
+    total: usize,
+) -> Result<String, String> {
+    let prompt = format!(
+        "You are summarizing part {} of {} of the transcript of \"{}\" ({} to {}).\n\
+        Write 3-6 concise bullet points covering the main points of this part.\n\
+        Start every bullet with the timestamp where the point is made, in square brackets exactly as in the transcript, e.g. [12:34].\n\
+        Only use information from the transcript.\n\n\
+        Transcript:\n{}",
+        index + 1,
+        total,
+        title,
+        format_timestamp(chunk.start),
+        format_timestamp(chunk.end),
+        chunk.text
+    );
+    call_gemini_api_non_streaming(
+        ctx.client,
+        vec![ChatMessage {
+            role: "user".to_string(),
+            content: prompt,
+            image_base64_data: None,
+            image_mime_type: None,
+            image_file_api_uri: None,
+            document_ids: None,
+        }],
+        ctx.gemini_api_key,
+        ctx.model_name.to_string(),
+    )
+    .await
+}
+
+/// Summarize each chunk, then merge the partial summaries into one
+async fn map_reduce_summary(
+    ctx: &ToolContext<'_>,
+    title: &str,
+    chunks: &[TranscriptChunk],
+) -> Result<String, String> {
+    let total = chunks.len();
+    let requests: Vec<_> = chunks
+        .iter()
+        .enumerate()
+        .map(|(index, chunk)| summarize_chunk(ctx, title, chunk, index, total))
+        .collect();
+    let partials: Vec<Result<String, String>> = stream::iter(requests)
+        .buffered(MAX_PARALLEL_SUMMARIES)
+        .collect()
+        .await;
+
+    // A summary with parts missing would be cached as if it were complete
+    let mut summaries: Vec<String> = Vec::new();
+    for (chunk, partial) in chunks.iter().zip(partials) {
+        let summary = partial.map_err(|e| {
+            format!(
+                "Failed to summarize the part starting at {}: {}",
+                format_timestamp(chunk.start),
+                e
+            )
+        })?;
+        summaries.push(format!(
+            "Part {} to {}:\n{}",
+            format_timestamp(chunk.start),
+            format_timestamp(chunk.end),
+            summary.trim()
+        ));
+    }
+    if summaries.len() == 1 {
+        return Ok(summaries.remove(0));
+    }
+
+    let prompt = format!(
+        "Below are summaries of consecutive parts of the video \"{}\".\n\
+        Merge them into one summary: a 2-3 sentence overview, then a \"Key moments\" list of the most important points in order.\n\
+        Keep the [m:ss] timestamps exactly as given at the start of each key moment and do not invent new ones.\n\n\
+        {}",
+        title,
+        summaries.join("\n\n")
+    );
+    call_gemini_api_non_streaming(
+        ctx.client,
+        vec![ChatMessage {
+            role: "user".to_string(),
+            content: prompt,
+            image_base64_data: None,
+            image_mime_type: None,
+            image_file_api_uri: None,
+            document_ids: None,
+        }],
+        ctx.gemini_api_key,
+        ctx.model_name.to_string(),
+    )
+    .await
+}
+
+pub struct VideoTranscriptTool;
+
+#[async_trait]
+impl Tool for VideoTranscriptTool {
+    fn tool_type(&self) -> ToolType {
+        ToolType::VideoTranscript
+    }
+
+    fn description(&self) -> &'static str {
+        "Summarize a YouTube video from its captions (query: the video URL, or a local .vtt/.srt file path), with timestamp links"
+    }
+
+    fn follow_up_hint(&self) -> &'static str {
+        "Summarize a YouTube video by its URL"
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "YouTube video URL, or path to a local .vtt or .srt subtitle file"
+                }
+            },
+            "required": ["query"]
+        })
+    }
+
+    fn guidance(&self) -> ToolGuidance {
+        ToolGuidance {
+            name: "Video Transcript".to_string(),
+            description: "Summarize a video from its caption track, with links to key moments".to_string(),
+            usage_pattern: "Use when the user shares a YouTube link or subtitle file and asks what the video covers".to_string(),
+            parameters: vec![ToolParameter {
+                name: "query".to_string(),
+                param_type: "string".to_string(),
+                description: "YouTube video URL, or path to a local .vtt or .srt subtitle file".to_string(),
+                required: true,
+                default_value: None,
+            }],
+            examples: vec![ToolExample {
+                scenario: "User asks to summarize a YouTube talk".to_string(),
+                reasoning: "The talk's content is only available through its captions".to_string(),
+                sequence: vec![
+                    "VIDEO_TRANSCRIPT with the exact video URL".to_string(),
+                    "Answer with the summary, keeping the timestamp links".to_string(),
+                ],
+            }],
+            reasoning_hints: vec![
+                "YouTube links in the user's message are summarized automatically".to_string(),
+                "Only works for videos with captions (including auto-generated ones)".to_string(),
+                "Keep the [m:ss] timestamp links in the answer so users can jump to the moment".to_string(),
+            ],
+        }
+    }
+
+    fn capability_key(&self) -> &'static str {
+        "video_transcript"
+    }
+
+    fn capabilities(&self) -> Value {
+        json!({
+            "description": "Video summarization from caption tracks",
+            "best_for": ["summarizing YouTube videos", "finding key moments", "lecture and talk notes"],
+            "strengths": ["timestamp links", "handles long videos in parts"],
+            "limitations": ["requires captions", "no visual content"]
+        })
+    }
+
+    fn started_event(&self) -> &'static str {
+        "VIDEO_TRANSCRIPT_STARTED"
+    }
+
+    fn completed_event(&self) -> &'static str {
+        "VIDEO_TRANSCRIPT_COMPLETED"
+    }
+
+    fn started_payload(&self, query: &str) -> Value {
+        to_payload(VideoTranscriptStartedPayload {
+            source: query.trim().to_string(),
+        })
+    }
+
+    fn failed_payload(&self, query: &str, error: &str) -> Value {
+        to_payload(VideoTranscriptCompletedPayload {
+            source: query.trim().to_string(),
+            success: false,
+            title: None,
+            video_url: None,
+            duration_seconds: None,
+            chunks: None,
+            summary: None,
+            error: Some(error.to_string()),
+        })
+    }
+
+    /// Captions of a published video rarely change
+    fn cache_ttl(&self) -> Option<Duration> {
+        Some(Duration::from_secs(7 * 24 * 60 * 60))
+    }
+
+    fn cache_key(&self, ctx: &ToolContext<'_>, query: &str) -> Option<String> {
+        // Local files are read afresh, and only after the path check in `execute`.
+        // Without a Gemini key the result is a raw excerpt, which isn't kept either.
+        if ctx.gemini_api_key.is_empty() {
+            return None;
+        }
+        extract_urls(query)
+            .first()
+            .map(|_| format!("{} [{}]", query, ctx.model_name))
+    }
+
+    async fn execute(&self, ctx: &ToolContext<'_>, query: &str) -> Result<ToolOutput, String> {
+        let link = extract_urls(query).into_iter().next();
+        let video_id = link.as_deref().and_then(youtube_video_id);
+        let (title, video_url, subtitles) = match (&video_id, &link) {
+            (Some(video_id), _) => {
+                let (title, captions) = fetch_youtube_captions(ctx.client, video_id).await?;
+                let video_url = format!("https://www.youtube.com/watch?v={}", video_id);
+                (
+                    title.unwrap_or_else(|| format!("YouTube video {}", video_id)),
+                    Some(video_url),
+                    captions,
+                )
+            }
+            (None, Some(url)) => return Err(format!("Not a YouTube video link: {}", url)),
+            (None, None) => {
+                let path = permitted_local_path(query, ctx.user_query, ctx.attached_paths)?;
+                let subtitles = read_subtitle_file(path)?;
+                let title = Path::new(path)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| path.to_string());
+                (title, None, subtitles)
+            }
+        };
+
+        let cues = parse_subtitles(&subtitles);
+        if cues.is_empty() {
+            return Err(format!("The captions of '{}' contain no text", title));
+        }
+        let duration = cues.last().map_or(0.0, |cue| cue.end);
+        let transcript_chars: usize = cues.iter().map(|cue| cue.text.len() + 10).sum();
+        let chunk_chars = CHUNK_CHARS.max(transcript_chars / MAX_CHUNKS + 1);
+        let chunks = chunk_cues(&cues, chunk_chars);
+        log::info!(
+            "Transcript of '{}': {} cues, {} long, {} chunk(s)",
+            title,
+            cues.len(),
+            format_timestamp(duration),
+            chunks.len()
+        );
+
+        let summary = if ctx.gemini_api_key.is_empty() {
+            None
+        } else {
+            // A failed summary is an error rather than an excerpt, so it isn't cached
+            let summary = map_reduce_summary(ctx, &title, &chunks)
+                .await
+                .map_err(|e| format!("Failed to summarize the transcript of '{}': {}", title, e))?;
+            Some(summary)
+        };
+        let body = match &summary {
+            Some(summary) => summary.trim().to_string(),
+            None => {
+                let transcript: String = chunks.iter().map(|c| c.text.as_str()).collect();
+                truncate_chars(&transcript, MAX_RAW_TRANSCRIPT_CHARS)
+            }
+        };
+        let body = match &video_url {
+            Some(video_url) => link_timestamps(&body, video_url),
+            None => body,
+        };
+
+        let source_url = video_url
+            .clone()
+            .unwrap_or_else(|| format!("file://{}", query.trim().trim_start_matches("file://")));
+        let context_text = format!(
+            "Video Transcript {} of '{}' ({}, {} long):\n\n{}",
+            if summary.is_some() {
+                "Summary"
+            } else {
+                "Excerpt"
+            },
+            title,
+            source_url,
+            format_timestamp(duration),
+            body
+        );
+
+        Ok(ToolOutput {
+            content: context_text.clone(),
+            context: Some(context_text),
+            sources: vec![ToolSource::new(&title, &source_url, Some(&body))],
+            completed_payload: to_payload(VideoTranscriptCompletedPayload {
+                source: query.trim().to_string(),
+                success: true,
+                title: Some(title.clone()),
+                video_url,
+                duration_seconds: Some(duration as u64),
+                chunks: Some(chunks.len()),
+                summary: Some(body),
+                error: None,
+            }),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_VTT: &str = include_str!("../../tests/fixtures/sample.vtt");
+    const SAMPLE_SRT: &str = include_str!("../../tests/fixtures/sample.srt");
+
+    #[test]
+    fn test_parse_vtt_and_srt() {
+        let cues = parse_subtitles(SAMPLE_VTT);
+        let texts: Vec<&str> = cues.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            vec![
+                "Welcome to the Rust workshop.",
+                "Today we build a small command line tool.",
+                "First, ownership & borrowing.",
+                "Thanks for watching!",
+            ]
+        );
+        assert_eq!(cues[2].start, 62.25);
+        assert_eq!(cues[3].start, 3600.0);
+
+        let cues = parse_subtitles(SAMPLE_SRT);
+        assert_eq!(cues.len(), 3);
+        assert_eq!(cues[0].text, "Narrator: The city wakes up early.");
+        assert_eq!(cues[1].text, "Markets open at dawn and close by noon.");
+        assert_eq!((cues[2].start, cues[2].end), (130.0, 132.5));
+    }
+
+    #[test]
+    fn test_chunks_keep_timestamps() {
+        let cues = parse_subtitles(SAMPLE_SRT);
+        let chunks = chunk_cues(&cues, 90);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(
+            chunks[0].text,
+            "[0:01] Narrator: The city wakes up early.\n[0:03] Markets open at dawn and close by noon.\n"
+        );
+        assert_eq!((chunks[0].start, chunks[0].end), (1.0, 7.0));
+        assert_eq!(chunks[1].start, 130.0);
+        assert_eq!(format_timestamp(3725.0), "1:02:05");
+    }
+
+    #[test]
+    fn test_youtube_ids_and_timestamp_links() {
+        for url in [
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ&list=PL1",
+            "https://youtu.be/dQw4w9WgXcQ?t=42",
+            "https://m.youtube.com/shorts/dQw4w9WgXcQ",
+        ] {
+            assert_eq!(youtube_video_id(url).as_deref(), Some("dQw4w9WgXcQ"));
+        }
+        assert_eq!(
+            youtube_video_id("https://example.com/watch?v=dQw4w9WgXcQ"),
+            None
+        );
+
+        let linked = link_timestamps(
+            "- [1:05] Ownership\n- [0:10](https://already.linked) kept",
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+        );
+        assert_eq!(
+            linked,
+            "- [1:05](https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=65s) Ownership\n- [0:10](https://already.linked) kept"
+        );
+    }
+
+    #[test]
+    fn test_local_paths_must_come_from_the_user() {
+        let attached = vec!["/home/me/attached.srt".to_string()];
+        let message = "Summarize /home/me/talk.vtt please";
+        assert_eq!(
+            permitted_local_path(" file:///home/me/talk.vtt", message, &attached),
+            Ok("/home/me/talk.vtt")
+        );
+        assert_eq!(
+            permitted_local_path("/home/me/attached.srt", message, &attached),
+            Ok("/home/me/attached.srt")
+        );
+        assert!(permitted_local_path("/etc/secrets.srt", message, &attached).is_err());
+        assert!(permitted_local_path("", message, &attached).is_err());
+    }
+}
@@ -0,0 +1,1550 @@
+//! Weather lookup tool
+//!
+//! Extracts the location from the query, geocodes it and reads the current
+//! conditions from Open-Meteo. Saved places ("home", "office") skip geocoding,
+//! and a query naming no place uses the default one. When several places share
+//! a name, the region and country in the query narrow them down, then the most
+//! populous wins unless the model can tell from the message which is meant; the
+//! others are listed so the user can pick. Weather comes with a daily forecast
+//! (today only, or up to 16 days when the query asks for one), an hourly
+//! breakdown ("hourly", "tonight", "next 12 hours") and air quality when asked
+//! about. Units follow the metric/imperial preference in settings unless the
+//! query names one. The structured forecast is sent in
+//! WEATHER_LOOKUP_COMPLETED.
+
+use super::citations::ToolSource;
+use super::{to_payload, Tool, ToolContext, ToolOutput};
+use crate::mcp_simple::{ToolExample, ToolGuidance, ToolParameter};
+use crate::{call_gemini_api_non_streaming, ChatMessage, ToolType};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// Longest daily forecast Open-Meteo serves
+const MAX_FORECAST_DAYS: u8 = 16;
+/// Days forecast when a query just asks for "the forecast"
+const DEFAULT_FORECAST_DAYS: u8 = 7;
+/// Longest hourly breakdown given
+const MAX_FORECAST_HOURS: u8 = 48;
+/// Hours given for "hourly", "tonight" and similar
+const DEFAULT_FORECAST_HOURS: u8 = 24;
+/// Places asked of the geocoder for each name
+const GEOCODING_CANDIDATES: usize = 10;
+/// Same-named places offered to the model, and listed as alternatives
+const MAX_ALTERNATIVES: usize = 5;
+/// How many times bigger than its namesakes a place must be to be chosen outright
+const CLEAR_POPULATION_RATIO: u64 = 10;
+
+// --- ADDED: Weather Lookup Event Payloads ---
+#[derive(Serialize, Clone, Debug)]
+struct WeatherLookupStartedPayload {
+    location: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+struct WeatherLookupCompletedPayload {
+    location: String,
+    success: bool,
+    temperature: Option<f32>,
+    unit: Option<String>,
+    description: Option<String>,
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    forecast: Option<WeatherForecast>,
+}
+
+// --- ADDED: Open-Meteo Geocoding API Structures ---
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct GeocodingResult {
+    id: Option<f64>,
+    name: Option<String>,
+    latitude: Option<f32>,
+    longitude: Option<f32>,
+    country: Option<String>,
+    country_code: Option<String>,
+    admin1: Option<String>,
+    admin2: Option<String>,
+    population: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct GeocodingResponse {
+    results: Option<Vec<GeocodingResult>>,
+    generationtime_ms: Option<f32>,
+}
+
+// --- ADDED: Open-Meteo Weather API Structures ---
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct WeatherCurrentUnits {
+    temperature_2m: Option<String>,
+    wind_speed_10m: Option<String>,
+    precipitation: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct WeatherCurrentData {
+    time: Option<String>,
+    interval: Option<i32>,
+    temperature_2m: Option<f32>,
+    apparent_temperature: Option<f32>,
+    relative_humidity_2m: Option<f32>,
+    precipitation: Option<f32>,
+    weather_code: Option<u8>,
+    wind_speed_10m: Option<f32>,
+    wind_direction_10m: Option<f32>,
+    uv_index: Option<f32>,
+}
+
+/// Daily forecast columns, one value per day
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+struct WeatherDailyData {
+    time: Vec<String>,
+    weather_code: Vec<Option<u8>>,
+    temperature_2m_max: Vec<Option<f32>>,
+    temperature_2m_min: Vec<Option<f32>>,
+    precipitation_sum: Vec<Option<f32>>,
+    precipitation_probability_max: Vec<Option<f32>>,
+    wind_speed_10m_max: Vec<Option<f32>>,
+    uv_index_max: Vec<Option<f32>>,
+    sunrise: Vec<Option<String>>,
+    sunset: Vec<Option<String>>,
+}
+
+/// Hourly forecast columns, one value per hour
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+struct WeatherHourlyData {
+    time: Vec<String>,
+    temperature_2m: Vec<Option<f32>>,
+    precipitation_probability: Vec<Option<f32>>,
+    precipitation: Vec<Option<f32>>,
+    weather_code: Vec<Option<u8>>,
+    wind_speed_10m: Vec<Option<f32>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct WeatherResponse {
+    latitude: Option<f32>,
+    longitude: Option<f32>,
+    generationtime_ms: Option<f32>,
+    utc_offset_seconds: Option<i32>,
+    timezone: Option<String>,
+    timezone_abbreviation: Option<String>,
+    elevation: Option<f32>,
+    current_units: Option<WeatherCurrentUnits>,
+    current: Option<WeatherCurrentData>,
+    daily: Option<WeatherDailyData>,
+    hourly: Option<WeatherHourlyData>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct AirQualityCurrentData {
+    us_aqi: Option<f32>,
+    european_aqi: Option<f32>,
+    pm2_5: Option<f32>,
+    pm10: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct AirQualityResponse {
+    current: Option<AirQualityCurrentData>,
+}
+
+/// Units weather is reported in
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WeatherUnits {
+    #[default]
+    Metric,
+    Imperial,
+}
+
+impl WeatherUnits {
+    /// Open-Meteo's temperature, wind speed and precipitation unit parameters
+    fn api_params(self) -> [(&'static str, &'static str); 3] {
+        match self {
+            WeatherUnits::Metric => [
+                ("temperature_unit", "celsius"),
+                ("wind_speed_unit", "kmh"),
+                ("precipitation_unit", "mm"),
+            ],
+            WeatherUnits::Imperial => [
+                ("temperature_unit", "fahrenheit"),
+                ("wind_speed_unit", "mph"),
+                ("precipitation_unit", "inch"),
+            ],
+        }
+    }
+}
+
+/// A place the geocoder found for a name
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LocationCandidate {
+    pub name: String,
+    pub admin1: Option<String>, // State or region
+    pub admin2: Option<String>, // County or district
+    pub country: Option<String>,
+    pub country_code: Option<String>,
+    pub latitude: f32,
+    pub longitude: f32,
+    pub population: Option<u64>,
+}
+
+impl LocationCandidate {
+    /// Name with region and country, e.g. "Paris, Texas, United States"
+    pub fn label(&self) -> String {
+        std::iter::once(self.name.as_str())
+            .chain(self.admin1.as_deref())
+            .chain(self.country.as_deref())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Whether a qualifier from the query ("Texas", "France", "FR") fits this place
+    fn matches(&self, qualifier: &str) -> bool {
+        let qualifier = qualifier.to_lowercase();
+        [
+            &self.admin1,
+            &self.admin2,
+            &self.country,
+            &self.country_code,
+        ]
+        .into_iter()
+        .flatten()
+        .any(|field| field.to_lowercase() == qualifier)
+    }
+}
+
+/// A named place ("home", "office") whose weather is looked up without geocoding
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SavedPlace {
+    pub name: String,     // What queries call it; matched case-insensitively
+    pub location: String, // Shown in answers, e.g. "Paris, Île-de-France, France"
+    pub latitude: f32,
+    pub longitude: f32,
+}
+
+/// Weather settings stored in `AppConfig`; `None` fields use the defaults
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct WeatherSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub units: Option<WeatherUnits>, // Metric by default; "in fahrenheit" in a query overrides it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub places: Option<Vec<SavedPlace>>, // Named places such as "home" and "office"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_place: Option<String>, // Name of the place used when a query names none
+}
+
+impl WeatherSettings {
+    /// Reject unnamed, duplicate or out-of-range places and a default that isn't saved
+    pub fn validate(&self) -> Result<(), String> {
+        let mut names: Vec<String> = Vec::new();
+        for place in self.places.iter().flatten() {
+            let name = place.name.trim().to_lowercase();
+            if name.is_empty() {
+                return Err("Every saved place needs a name".to_string());
+            }
+            if names.contains(&name) {
+                return Err(format!(
+                    "More than one saved place is called '{}'",
+                    place.name
+                ));
+            }
+            if !(-90.0..=90.0).contains(&place.latitude)
+                || !(-180.0..=180.0).contains(&place.longitude)
+            {
+                return Err(format!(
+                    "Saved place '{}' has invalid coordinates",
+                    place.name
+                ));
+            }
+            names.push(name);
+        }
+        if let Some(default_place) = &self.default_place {
+            if self.place(default_place).is_none() {
+                return Err(format!(
+                    "The default place '{}' is not a saved place",
+                    default_place
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// The saved place a location refers to: "home", "my office", "at home"
+    fn place(&self, location: &str) -> Option<&SavedPlace> {
+        let mut location = location
+            .trim()
+            .trim_matches(|c: char| c.is_ascii_punctuation())
+            .to_lowercase();
+        while let Some(rest) = ["at ", "near ", "in ", "around ", "my ", "the "]
+            .iter()
+            .find_map(|prefix| location.strip_prefix(prefix))
+        {
+            location = rest.trim_start().to_string();
+        }
+        self.places
+            .iter()
+            .flatten()
+            .find(|place| place.name.trim().to_lowercase() == location)
+    }
+
+    fn default_location(&self) -> Option<&SavedPlace> {
+        self.default_place
+            .as_deref()
+            .and_then(|name| self.place(name))
+    }
+
+    fn place_names(&self) -> Vec<String> {
+        self.places
+            .iter()
+            .flatten()
+            .map(|place| place.name.trim().to_string())
+            .collect()
+    }
+}
+
+/// The place a lookup reads the weather for
+#[derive(Debug, Clone, PartialEq)]
+struct ResolvedLocation {
+    name: String,
+    latitude: f32,
+    longitude: f32,
+    /// Other places with the same name, so the user can say which they meant
+    alternatives: Vec<String>,
+}
+
+impl From<&SavedPlace> for ResolvedLocation {
+    fn from(place: &SavedPlace) -> Self {
+        ResolvedLocation {
+            name: place.location.clone(),
+            latitude: place.latitude,
+            longitude: place.longitude,
+            alternatives: Vec::new(),
+        }
+    }
+}
+
+/// What a weather query asks for beyond the current conditions
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ForecastRequest {
+    /// Days of daily forecast, today included
+    days: u8,
+    /// Hours of hourly forecast from now; 0 for none
+    hours: u8,
+    air_quality: bool,
+    /// Units asked for in the query itself
+    units: Option<WeatherUnits>,
+}
+
+// --- Structured forecast sent in WEATHER_LOOKUP_COMPLETED ---
+#[derive(Serialize, Clone, Debug, PartialEq)]
+struct ForecastUnits {
+    temperature: String,
+    wind_speed: String,
+    precipitation: String,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+struct CurrentConditions {
+    time: Option<String>,
+    temperature: Option<f32>,
+    apparent_temperature: Option<f32>,
+    relative_humidity: Option<f32>,
+    precipitation: Option<f32>,
+    wind_speed: Option<f32>,
+    wind_direction: Option<f32>,
+    uv_index: Option<f32>,
+    weather_code: Option<u8>,
+    description: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+struct DailyForecast {
+    date: String,
+    weather_code: Option<u8>,
+    description: Option<String>,
+    temperature_max: Option<f32>,
+    temperature_min: Option<f32>,
+    precipitation_sum: Option<f32>,
+    precipitation_probability: Option<f32>,
+    wind_speed_max: Option<f32>,
+    uv_index_max: Option<f32>,
+    sunrise: Option<String>,
+    sunset: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+struct HourlyForecast {
+    time: String,
+    temperature: Option<f32>,
+    precipitation_probability: Option<f32>,
+    precipitation: Option<f32>,
+    weather_code: Option<u8>,
+    description: Option<String>,
+    wind_speed: Option<f32>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+struct AirQuality {
+    us_aqi: Option<f32>,
+    european_aqi: Option<f32>,
+    pm2_5: Option<f32>,
+    pm10: Option<f32>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+struct WeatherForecast {
+    location: String,
+    latitude: f32,
+    longitude: f32,
+    timezone: Option<String>,
+    units: ForecastUnits,
+    current: Option<CurrentConditions>,
+    daily: Vec<DailyForecast>,
+    hourly: Vec<HourlyForecast>,
+    air_quality: Option<AirQuality>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    alternatives: Vec<String>, // Same-named places that were not chosen
+}
+
+/// Splits "Paris, Texas, United States" into the name to search for and the
+/// region and country that narrow it down
+fn split_location(location: &str) -> (String, Vec<String>) {
+    let mut parts = location
+        .split(',')
+        .map(str::trim)
+        .filter(|part| !part.is_empty());
+    let name = parts.next().unwrap_or_default().to_string();
+    (name, parts.map(str::to_string).collect())
+}
+
+/// Orders the candidates that fit a name and its qualifiers, most populous
+/// first, and tells whether the first is clearly the place meant
+fn rank_candidates(
+    candidates: &[LocationCandidate],
+    name: &str,
+    qualifiers: &[String],
+) -> (Vec<usize>, bool) {
+    let name = name.to_lowercase();
+    let mut ranked: Vec<usize> = (0..candidates.len()).collect();
+    // Exact names first; the geocoder also returns places that merely start with it
+    let exact: Vec<usize> = ranked
+        .iter()
+        .copied()
+        .filter(|&i| candidates[i].name.to_lowercase() == name)
+        .collect();
+    if !exact.is_empty() {
+        ranked = exact;
+    }
+    // Qualifiers the geocoder doesn't know ("CA" for California) are ignored
+    let qualified: Vec<usize> = ranked
+        .iter()
+        .copied()
+        .filter(|&i| qualifiers.iter().all(|q| candidates[i].matches(q)))
+        .collect();
+    if !qualified.is_empty() {
+        ranked = qualified;
+    }
+    let population = |i: usize| candidates[i].population.unwrap_or(0);
+    ranked.sort_by_key(|&i| std::cmp::Reverse(population(i)));
+    let clear = match ranked.as_slice() {
+        [_] => true,
+        [first, second, ..] => {
+            population(*first) > 0
+                && population(*first) >= population(*second).saturating_mul(CLEAR_POPULATION_RATIO)
+        }
+        [] => false,
+    };
+    (ranked, clear)
+}
+
+/// Places matching a name, from the Open-Meteo geocoder
+async fn geocode_location(
+    client: &reqwest::Client,
+    location_name: &str,
+) -> Result<Vec<LocationCandidate>, String> {
+    let base_url = "https://geocoding-api.open-meteo.com/v1/search";
+    let params = [
+        ("name", location_name.to_string()),
+        ("count", GEOCODING_CANDIDATES.to_string()),
+        ("language", "en".to_string()),
+        ("format", "json".to_string()),
+    ];
+    let geo_response: GeocodingResponse =
+        fetch_open_meteo(client, base_url, &params, "Geocoding").await?;
+    log::info!("Geocoding: Parsed JSON: {:#?}", geo_response);
+    Ok(geo_response
+        .results
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|result| {
+            Some(LocationCandidate {
+                name: result.name?,
+                admin1: result.admin1,
+                admin2: result.admin2,
+                country: result.country,
+                country_code: result.country_code,
+                latitude: result.latitude?,
+                longitude: result.longitude?,
+                population: result.population,
+            })
+        })
+        .collect())
+}
+
+/// Places a location ("Paris", "Paris, Texas") may refer to, the likeliest
+/// first, and whether the first is clearly the one meant
+pub async fn find_locations(
+    client: &reqwest::Client,
+    location: &str,
+) -> Result<(Vec<LocationCandidate>, bool), String> {
+    let (name, qualifiers) = split_location(location);
+    if name.is_empty() {
+        return Ok((Vec::new(), false));
+    }
+    let candidates = geocode_location(client, &name).await?;
+    let (ranked, clear) = rank_candidates(&candidates, &name, &qualifiers);
+    Ok((
+        ranked.into_iter().map(|i| candidates[i].clone()).collect(),
+        clear,
+    ))
+}
+
+async fn extract_location_for_geocoding(
+    client: &reqwest::Client,
+    user_query: &str, // The full user query, e.g., "what is the weather in Paris, France?"
+    gemini_api_key: &str, // API key as a slice
+    model_name: String, // Model name for Gemini
+    place_names: &[String], // The user's saved places, e.g. "home"
+) -> Result<Option<String>, String> {
+    // Returns the extracted location, None if the query names no place, or an error
+    let saved_places = if place_names.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "The user has saved these places: {}. If the query refers to one of them (\"at home\", \"near my office\"), output its name exactly.\n",
+            place_names.join(", ")
+        )
+    };
+    let extractor_prompt = format!(
+        "{}{}{}{}{}{}{}{}{}{}{}{}{}",
+        "You are an expert at identifying the geographical location mentioned in a user\'s query about weather.\n",
+        "Given the user query, extract only the location (city, state, country, etc.). Do not include phrases like \"weather in\", \"what is the temperature in\", etc.\n",
+        "Write the state or region and the country out in full when the query gives or clearly implies them.\n",
+        saved_places,
+        "If the query names no location at all, output NONE.\n",
+        "For example:\n",
+        "- User Query: \"weather in San Francisco, CA\" -> Location: \"San Francisco, California, United States\"\n",
+        "- User Query: \"what is the temperature in London today?\" -> Location: \"London\"\n",
+        "- User Query: \"Is it raining in Tokyo, Japan? Show me the forecast.\" -> Location: \"Tokyo, Japan\"\n",
+        "- User Query: \"will it rain tomorrow?\" -> Location: \"NONE\"\n",
+        "Output only the location itself.\n\n",
+        format!("User Query: '{}'\n", user_query),
+        "Location:"
+    );
+
+    let extractor_messages = vec![ChatMessage {
+        role: "user".to_string(),
+        content: extractor_prompt,
+        image_base64_data: None,
+        image_mime_type: None,
+        image_file_api_uri: None,
+        document_ids: None,
+    }];
+
+    log::info!(
+        "Requesting location extraction for geocoding from query: '{}'",
+        user_query
+    );
+
+    match call_gemini_api_non_streaming(client, extractor_messages, gemini_api_key, model_name)
+        .await
+    {
+        Ok(extracted_location_raw) => {
+            let extracted_location = extracted_location_raw.trim().trim_matches('"').to_string();
+            log::info!(
+                "Extracted location for geocoding: '{}' from original query: '{}'",
+                extracted_location,
+                user_query
+            );
+            if extracted_location.is_empty() {
+                log::warn!("Location extractor for geocoding returned empty. Falling back to original query (trimmed).");
+                Ok(Some(user_query.trim().to_string())) // Fallback, though less ideal
+            } else if extracted_location.eq_ignore_ascii_case("none") {
+                Ok(None)
+            } else {
+                Ok(Some(extracted_location))
+            }
+        }
+        Err(e) => {
+            log::error!("Error calling location extractor for geocoding (query: '{}'): {}. Falling back to original query (trimmed).", user_query, e);
+            Ok(Some(user_query.trim().to_string())) // Fallback on error
+        }
+    }
+}
+
+/// Asks the model which of several same-named places the user means; the
+/// first (most populous) when nothing in the message points to one
+async fn choose_location(
+    client: &reqwest::Client,
+    user_query: &str,
+    candidates: &[LocationCandidate],
+    gemini_api_key: &str,
+    model_name: String,
+) -> usize {
+    let options: Vec<String> = candidates
+        .iter()
+        .enumerate()
+        .map(|(i, candidate)| match candidate.population {
+            Some(population) => format!(
+                "{}. {} (population {})",
+                i + 1,
+                candidate.label(),
+                population
+            ),
+            None => format!("{}. {}", i + 1, candidate.label()),
+        })
+        .collect();
+    let prompt = format!(
+        "A user asked about the weather: '{}'\n\nThe place they named matches several places:\n{}\n\nReply with only the number of the place the user most likely means. If nothing in their message points to one, reply 1.",
+        user_query,
+        options.join("\n")
+    );
+    let messages = vec![ChatMessage {
+        role: "user".to_string(),
+        content: prompt,
+        image_base64_data: None,
+        image_mime_type: None,
+        image_file_api_uri: None,
+        document_ids: None,
+    }];
+    match call_gemini_api_non_streaming(client, messages, gemini_api_key, model_name).await {
+        Ok(reply) => reply
+            .split(|c: char| !c.is_ascii_digit())
+            .find_map(|number| number.parse::<usize>().ok())
+            .filter(|number| (1..=candidates.len()).contains(number))
+            .map_or(0, |number| number - 1),
+        Err(e) => {
+            log::warn!("Weather: Could not ask which place was meant: {}", e);
+            0
+        }
+    }
+}
+
+/// The place a query is about: a saved place, the default place when it names
+/// none, or the geocoded place it most likely means
+async fn resolve_location(
+    client: &reqwest::Client,
+    query: &str,
+    gemini_api_key: &str,
+    model_name: String,
+    settings: &WeatherSettings,
+) -> Result<Option<ResolvedLocation>, String> {
+    if let Some(place) = settings.place(query) {
+        log::info!("Weather: '{}' is the saved place '{}'", query, place.name);
+        return Ok(Some(place.into()));
+    }
+
+    // 1. Extract location using the LLM extractor
+    let extracted = extract_location_for_geocoding(
+        client,
+        query,
+        gemini_api_key,
+        model_name.clone(),
+        &settings.place_names(),
+    )
+    .await
+    .map_err(|e| {
+        log::error!("Weather: Location extraction step failed for query '{}': {}. No geocoding will be attempted.", query, e);
+        format!("Location extraction failed: {}", e)
+    })?;
+    let location_to_geocode = match extracted {
+        Some(location) => location,
+        None => {
+            return match settings.default_location() {
+                Some(place) => {
+                    log::info!("Weather: No place in '{}', using the default place '{}'", query, place.name);
+                    Ok(Some(place.into()))
+                }
+                None => Err("The query names no location and no default place is saved in the weather settings".to_string()),
+            };
+        }
+    };
+    if let Some(place) = settings.place(&location_to_geocode) {
+        return Ok(Some(place.into()));
+    }
+
+    // 2. Geocode the extracted location, asking the model when several places fit
+    let (candidates, clear) = find_locations(client, &location_to_geocode)
+        .await
+        .map_err(|e| {
+            log::error!(
+                "Weather: Geocoding step failed for '{}': {}",
+                location_to_geocode,
+                e
+            );
+            e
+        })?;
+    if candidates.is_empty() {
+        log::warn!("Weather: Geocoding failed for '{}'.", location_to_geocode);
+        return Ok(None);
+    }
+    let shortlist = &candidates[..candidates.len().min(MAX_ALTERNATIVES)];
+    let chosen = if clear {
+        0
+    } else {
+        choose_location(client, query, shortlist, gemini_api_key, model_name).await
+    };
+    let place = &shortlist[chosen];
+    log::info!(
+        "Geocoded extracted location '{}' to ({}, {}), name: {} ({} candidate(s))",
+        location_to_geocode,
+        place.latitude,
+        place.longitude,
+        place.label(),
+        candidates.len()
+    );
+    Ok(Some(ResolvedLocation {
+        name: place.label(),
+        latitude: place.latitude,
+        longitude: place.longitude,
+        alternatives: shortlist
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != chosen)
+            .map(|(_, candidate)| candidate.label())
+            .collect(),
+    }))
+}
+
+/// Description of a WMO weather interpretation code
+fn weather_description(code: u8) -> &'static str {
+    match code {
+        0 => "Clear sky",
+        1 => "Mainly clear",
+        2 => "Partly cloudy",
+        3 => "Overcast",
+        45 => "Fog",
+        48 => "Depositing rime fog",
+        51 => "Light drizzle",
+        53 => "Moderate drizzle",
+        55 => "Dense drizzle",
+        56 | 57 => "Freezing drizzle",
+        61 => "Slight rain",
+        63 => "Moderate rain",
+        65 => "Heavy rain",
+        66 | 67 => "Freezing rain",
+        71 => "Slight snowfall",
+        73 => "Moderate snowfall",
+        75 => "Heavy snowfall",
+        77 => "Snow grains",
+        80 => "Slight rain showers",
+        81 => "Moderate rain showers",
+        82 => "Violent rain showers",
+        85 | 86 => "Snow showers",
+        95 => "Thunderstorm",
+        96 | 99 => "Thunderstorm with hail",
+        _ => "Unknown conditions",
+    }
+}
+
+/// Eight-point compass direction the wind blows from
+fn compass_direction(degrees: f32) -> &'static str {
+    const DIRECTIONS: [&str; 8] = ["N", "NE", "E", "SE", "S", "SW", "W", "NW"];
+    DIRECTIONS[((degrees.rem_euclid(360.0) + 22.5) / 45.0) as usize % 8]
+}
+
+impl ForecastRequest {
+    /// Reads the period, air quality and units a query asks about, e.g. "will it
+    /// rain in Oslo this weekend", "hourly forecast for Lyon in fahrenheit"
+    fn parse(text: &str) -> ForecastRequest {
+        let lower = text.to_lowercase();
+        let words: Vec<&str> = lower
+            .split(|c: char| !c.is_alphanumeric() && c != '.')
+            .filter(|w| !w.is_empty())
+            .collect();
+        let has = |word: &str| words.contains(&word);
+        let mut request = ForecastRequest {
+            days: 1,
+            hours: 0,
+            air_quality: ["air", "aqi", "pollution", "smog", "pm2.5", "pm10"]
+                .iter()
+                .any(|word| has(word)),
+            units: None,
+        };
+
+        if has("forecast") || has("week") || has("weekend") {
+            request.days = DEFAULT_FORECAST_DAYS;
+        } else if has("tomorrow") {
+            request.days = 2;
+        }
+        if has("hourly")
+            || has("tonight")
+            || has("afternoon")
+            || has("evening")
+            || lower.contains("hour by hour")
+        {
+            request.hours = DEFAULT_FORECAST_HOURS;
+        }
+        for pair in words.windows(2) {
+            // "next 10 days", "5-day forecast", "next 12 hours"
+            if let Ok(count) = pair[0].parse::<u8>() {
+                match pair[1] {
+                    "day" | "days" => request.days = count.clamp(1, MAX_FORECAST_DAYS),
+                    "hour" | "hours" => request.hours = count.clamp(1, MAX_FORECAST_HOURS),
+                    _ => {}
+                }
+            }
+        }
+
+        if has("fahrenheit") || has("imperial") || lower.contains("°f") {
+            request.units = Some(WeatherUnits::Imperial);
+        } else if has("celsius") || has("metric") || lower.contains("°c") {
+            request.units = Some(WeatherUnits::Metric);
+        }
+        request
+    }
+
+    /// Reads the planner's query, falling back to the user's message for a
+    /// period, units or air quality the query doesn't name
+    fn for_lookup(query: &str, user_query: &str) -> ForecastRequest {
+        let request = ForecastRequest::parse(query);
+        let message = ForecastRequest::parse(user_query);
+        let names_period = request.days > 1 || request.hours > 0;
+        ForecastRequest {
+            days: if names_period {
+                request.days
+            } else {
+                message.days
+            },
+            hours: if names_period {
+                request.hours
+            } else {
+                message.hours
+            },
+            air_quality: request.air_quality || message.air_quality,
+            units: request.units.or(message.units),
+        }
+    }
+}
+
+/// The value at `index` of an Open-Meteo column, if present
+fn column<T: Clone>(values: &[Option<T>], index: usize) -> Option<T> {
+    values.get(index).cloned().flatten()
+}
+
+fn build_forecast(
+    location: ResolvedLocation,
+    weather: WeatherResponse,
+    air_quality: Option<AirQualityResponse>,
+    units: WeatherUnits,
+) -> WeatherForecast {
+    let (temperature_unit, wind_unit, precipitation_unit) = match units {
+        WeatherUnits::Metric => ("°C", "km/h", "mm"),
+        WeatherUnits::Imperial => ("°F", "mph", "inch"),
+    };
+    let api_units = weather.current_units;
+    let unit = |field: Option<String>, default: &str| field.unwrap_or_else(|| default.to_string());
+    let daily = weather.daily.unwrap_or_default();
+    let hourly = weather.hourly.unwrap_or_default();
+    WeatherForecast {
+        location: location.name,
+        latitude: location.latitude,
+        longitude: location.longitude,
+        timezone: weather.timezone,
+        units: ForecastUnits {
+            temperature: unit(
+                api_units.as_ref().and_then(|u| u.temperature_2m.clone()),
+                temperature_unit,
+            ),
+            wind_speed: unit(
+                api_units.as_ref().and_then(|u| u.wind_speed_10m.clone()),
+                wind_unit,
+            ),
+            precipitation: unit(api_units.and_then(|u| u.precipitation), precipitation_unit),
+        },
+        current: weather.current.map(|current| CurrentConditions {
+            time: current.time,
+            temperature: current.temperature_2m,
+            apparent_temperature: current.apparent_temperature,
+            relative_humidity: current.relative_humidity_2m,
+            precipitation: current.precipitation,
+            wind_speed: current.wind_speed_10m,
+            wind_direction: current.wind_direction_10m,
+            uv_index: current.uv_index,
+            weather_code: current.weather_code,
+            description: current
+                .weather_code
+                .map(|code| weather_description(code).to_string()),
+        }),
+        daily: daily
+            .time
+            .iter()
+            .enumerate()
+            .map(|(i, date)| DailyForecast {
+                date: date.clone(),
+                weather_code: column(&daily.weather_code, i),
+                description: column(&daily.weather_code, i)
+                    .map(|code| weather_description(code).to_string()),
+                temperature_max: column(&daily.temperature_2m_max, i),
+                temperature_min: column(&daily.temperature_2m_min, i),
+                precipitation_sum: column(&daily.precipitation_sum, i),
+                precipitation_probability: column(&daily.precipitation_probability_max, i),
+                wind_speed_max: column(&daily.wind_speed_10m_max, i),
+                uv_index_max: column(&daily.uv_index_max, i),
+                sunrise: column(&daily.sunrise, i),
+                sunset: column(&daily.sunset, i),
+            })
+            .collect(),
+        hourly: hourly
+            .time
+            .iter()
+            .enumerate()
+            .map(|(i, time)| HourlyForecast {
+                time: time.clone(),
+                temperature: column(&hourly.temperature_2m, i),
+                precipitation_probability: column(&hourly.precipitation_probability, i),
+                precipitation: column(&hourly.precipitation, i),
+                weather_code: column(&hourly.weather_code, i),
+                description: column(&hourly.weather_code, i)
+                    .map(|code| weather_description(code).to_string()),
+                wind_speed: column(&hourly.wind_speed_10m, i),
+            })
+            .collect(),
+        air_quality: air_quality
+            .and_then(|air| air.current)
+            .map(|current| AirQuality {
+                us_aqi: current.us_aqi,
+                european_aqi: current.european_aqi,
+                pm2_5: current.pm2_5,
+                pm10: current.pm10,
+            }),
+        alternatives: location.alternatives,
+    }
+}
+
+/// The forecast as text for the research context
+fn forecast_text(forecast: &WeatherForecast) -> String {
+    let units = &forecast.units;
+    let mut lines = vec![match &forecast.timezone {
+        Some(timezone) => format!("Weather in {} (times in {}):", forecast.location, timezone),
+        None => format!("Weather in {}:", forecast.location),
+    }];
+    if let Some(current) = &forecast.current {
+        let mut parts = Vec::new();
+        if let Some(temperature) = current.temperature {
+            let mut temperature = format!("{}{}", temperature, units.temperature);
+            if let Some(feels_like) = current.apparent_temperature {
+                temperature.push_str(&format!(
+                    " (feels like {}{})",
+                    feels_like, units.temperature
+                ));
+            }
+            parts.push(temperature);
+        }
+        parts.extend(current.description.clone());
+        if let Some(humidity) = current.relative_humidity {
+            parts.push(format!("humidity {}%", humidity));
+        }
+        if let Some(wind_speed) = current.wind_speed {
+            let direction = current
+                .wind_direction
+                .map(|degrees| format!(" from {}", compass_direction(degrees)))
+                .unwrap_or_default();
+            parts.push(format!(
+                "wind {} {}{}",
+                wind_speed, units.wind_speed, direction
+            ));
+        }
+        if let Some(precipitation) = current.precipitation {
+            parts.push(format!(
+                "precipitation {} {}",
+                precipitation, units.precipitation
+            ));
+        }
+        if let Some(uv_index) = current.uv_index {
+            parts.push(format!("UV index {}", uv_index));
+        }
+        lines.push(format!(
+            "Now{}: {}",
+            current
+                .time
+                .as_ref()
+                .map(|time| format!(" ({})", time))
+                .unwrap_or_default(),
+            parts.join(", ")
+        ));
+    }
+    if !forecast.daily.is_empty() {
+        lines.push("Daily forecast:".to_string());
+        for day in &forecast.daily {
+            let mut parts: Vec<String> = day.description.iter().cloned().collect();
+            if let (Some(min), Some(max)) = (day.temperature_min, day.temperature_max) {
+                parts.push(format!("{} to {}{}", min, max, units.temperature));
+            }
+            if let Some(precipitation) = day.precipitation_sum {
+                let chance = day
+                    .precipitation_probability
+                    .map(|p| format!(" ({}% chance)", p))
+                    .unwrap_or_default();
+                parts.push(format!(
+                    "precipitation {} {}{}",
+                    precipitation, units.precipitation, chance
+                ));
+            }
+            if let Some(wind_speed) = day.wind_speed_max {
+                parts.push(format!("wind up to {} {}", wind_speed, units.wind_speed));
+            }
+            if let Some(uv_index) = day.uv_index_max {
+                parts.push(format!("UV index {}", uv_index));
+            }
+            if let (Some(sunrise), Some(sunset)) = (&day.sunrise, &day.sunset) {
+                let time = |t: &str| t.rsplit('T').next().unwrap_or(t).to_string();
+                parts.push(format!(
+                    "sunrise {}, sunset {}",
+                    time(sunrise),
+                    time(sunset)
+                ));
+            }
+            lines.push(format!("- {}: {}", day.date, parts.join(", ")));
+        }
+    }
+    if !forecast.hourly.is_empty() {
+        lines.push("Hourly forecast:".to_string());
+        for hour in &forecast.hourly {
+            let mut parts = Vec::new();
+            if let Some(temperature) = hour.temperature {
+                parts.push(format!("{}{}", temperature, units.temperature));
+            }
+            parts.extend(hour.description.clone());
+            if let Some(probability) = hour.precipitation_probability {
+                let amount = hour
+                    .precipitation
+                    .map(|p| format!(" ({} {})", p, units.precipitation))
+                    .unwrap_or_default();
+                parts.push(format!(
+                    "{}% chance of precipitation{}",
+                    probability, amount
+                ));
+            }
+            if let Some(wind_speed) = hour.wind_speed {
+                parts.push(format!("wind {} {}", wind_speed, units.wind_speed));
+            }
+            lines.push(format!("- {}: {}", hour.time, parts.join(", ")));
+        }
+    }
+    if let Some(air) = &forecast.air_quality {
+        let mut parts = Vec::new();
+        if let Some(aqi) = air.us_aqi {
+            parts.push(format!("US AQI {}", aqi));
+        }
+        if let Some(aqi) = air.european_aqi {
+            parts.push(format!("European AQI {}", aqi));
+        }
+        if let Some(pm2_5) = air.pm2_5 {
+            parts.push(format!("PM2.5 {} µg/m³", pm2_5));
+        }
+        if let Some(pm10) = air.pm10 {
+            parts.push(format!("PM10 {} µg/m³", pm10));
+        }
+        if !parts.is_empty() {
+            lines.push(format!("Air quality: {}", parts.join(", ")));
+        }
+    }
+    if !forecast.alternatives.is_empty() {
+        lines.push(format!(
+            "Other places with this name: {} (name the region or country to choose one)",
+            forecast.alternatives.join("; ")
+        ));
+    }
+    lines.join("\n")
+}
+
+/// GETs an Open-Meteo endpoint and parses its JSON, logging like the other lookups
+async fn fetch_open_meteo<T: serde::de::DeserializeOwned>(
+    client: &reqwest::Client,
+    base_url: &str,
+    params: &[(&str, String)],
+    what: &str,
+) -> Result<T, String> {
+    let request_url = client
+        .get(base_url)
+        .query(params)
+        .build()
+        .map_err(|e| format!("Failed to build {} URL: {}", what, e))?
+        .url()
+        .to_string();
+    log::info!("{} lookup. URL: {}", what, request_url);
+    let response = client
+        .get(base_url)
+        .query(params)
+        .send()
+        .await
+        .map_err(|e| {
+            log::error!("{}: Network error: {}", what, e);
+            format!("{} network error: {}", what, e)
+        })?;
+    let status = response.status();
+    let response_text = response
+        .text()
+        .await
+        .map_err(|e| format!("{}: Failed to read response text: {}", what, e))?;
+    if !status.is_success() {
+        log::error!("{}: API error status {}: {}", what, status, response_text);
+        return Err(format!(
+            "{} API error: {} - {}",
+            what, status, response_text
+        ));
+    }
+    serde_json::from_str::<T>(&response_text).map_err(|e| {
+        log::error!("{}: JSON parse error: {}. Raw: {}", what, e, response_text);
+        format!("{} JSON error: {}. Ensure response is valid JSON.", what, e)
+    })
+}
+
+// --- UPDATED: Weather Lookup Function (uses location extractor) ---
+async fn perform_weather_lookup(
+    client: &reqwest::Client,
+    original_user_query: &str, // This is the full query like "weather in Paris"
+    gemini_api_key_for_extractor: &str, // API key for the extractor LLM call
+    extractor_model_name: String, // Model for the extractor LLM call
+    request: ForecastRequest,
+    units: WeatherUnits,
+    settings: &WeatherSettings,
+) -> Result<Option<WeatherForecast>, String> {
+    // 1. Find the place: saved, default or geocoded
+    let location = match resolve_location(
+        client,
+        original_user_query,
+        gemini_api_key_for_extractor,
+        extractor_model_name,
+        settings,
+    )
+    .await?
+    {
+        Some(location) => location,
+        None => return Ok(None),
+    };
+    let (lat, lon) = (location.latitude, location.longitude);
+
+    // 2. Current conditions, daily and hourly forecast, and air quality if asked for
+    let mut params = vec![
+        ("latitude", lat.to_string()),
+        ("longitude", lon.to_string()),
+        (
+            "current",
+            "temperature_2m,apparent_temperature,relative_humidity_2m,precipitation,weather_code,wind_speed_10m,wind_direction_10m,uv_index".to_string(),
+        ),
+        (
+            "daily",
+            "weather_code,temperature_2m_max,temperature_2m_min,precipitation_sum,precipitation_probability_max,wind_speed_10m_max,uv_index_max,sunrise,sunset".to_string(),
+        ),
+        ("forecast_days", request.days.to_string()),
+        ("timezone", "auto".to_string()),
+    ];
+    params.extend(
+        units
+            .api_params()
+            .map(|(name, value)| (name, value.to_string())),
+    );
+    if request.hours > 0 {
+        params.push((
+            "hourly",
+            "temperature_2m,precipitation_probability,precipitation,weather_code,wind_speed_10m"
+                .to_string(),
+        ));
+        params.push(("forecast_hours", request.hours.to_string()));
+    }
+    let weather: WeatherResponse = fetch_open_meteo(
+        client,
+        "https://api.open-meteo.com/v1/forecast",
+        &params,
+        "Weather",
+    )
+    .await?;
+    log::info!("Weather: Parsed JSON: {:#?}", weather);
+
+    let air_quality = if request.air_quality {
+        let params = [
+            ("latitude", lat.to_string()),
+            ("longitude", lon.to_string()),
+            ("current", "us_aqi,european_aqi,pm2_5,pm10".to_string()),
+        ];
+        // Air quality is an extra; the forecast is still worth returning without it
+        fetch_open_meteo::<AirQualityResponse>(
+            client,
+            "https://air-quality-api.open-meteo.com/v1/air-quality",
+            &params,
+            "Air quality",
+        )
+        .await
+        .map_err(|e| log::warn!("Weather: {}", e))
+        .ok()
+    } else {
+        None
+    };
+
+    let forecast = build_forecast(location, weather, air_quality, units);
+    if forecast.current.is_none() && forecast.daily.is_empty() {
+        log::info!("Weather: No current data for ({}, {}).", lat, lon);
+        return Ok(None);
+    }
+    Ok(Some(forecast))
+}
+
+pub struct WeatherTool;
+
+#[async_trait]
+impl Tool for WeatherTool {
+    fn tool_type(&self) -> ToolType {
+        ToolType::WeatherLookup
+    }
+
+    fn description(&self) -> &'static str {
+        "Current weather, daily forecasts up to 16 days, hourly breakdowns and air quality for specific locations (use city names or zip codes)"
+    }
+
+    fn follow_up_hint(&self) -> &'static str {
+        "Weather and forecasts for specific cities (use city names)"
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "City, address, or geographic location, optionally with the period wanted: 'Paris', 'Paris forecast', 'Oslo next 10 days', 'Lyon hourly', 'Denver tomorrow in fahrenheit', 'Delhi air quality', 'Paris, Texas', 'home tomorrow'"
+                }
+            },
+            "required": ["query"]
+        })
+    }
+
+    fn guidance(&self) -> ToolGuidance {
+        ToolGuidance {
+            name: "Weather Lookup".to_string(),
+            description: "Get current weather, multi-day and hourly forecasts, precipitation, wind, UV and air quality for any location worldwide".to_string(),
+            usage_pattern: "Use when current or forecast weather information is needed for decision making; name the period (tomorrow, weekend, next 5 days, hourly) in the query"
+                .to_string(),
+            parameters: vec![ToolParameter {
+                name: "location".to_string(),
+                param_type: "string".to_string(),
+                description: "City, address, or geographic location, optionally with a period ('forecast', 'next 5 days', 'hourly')".to_string(),
+                required: true,
+                default_value: None,
+            }],
+            examples: vec![ToolExample {
+                scenario: "User planning outdoor activities".to_string(),
+                reasoning: "Current weather affects planning decisions".to_string(),
+                sequence: vec![
+                    "Get weather for the specific location".to_string(),
+                    "Consider forecast implications for activities".to_string(),
+                ],
+            },
+            ToolExample {
+                scenario: "User asks whether it will rain during a weekend trip".to_string(),
+                reasoning: "Needs the daily forecast with precipitation chances".to_string(),
+                sequence: vec![
+                    "Look up 'Barcelona weekend forecast'".to_string(),
+                    "Report the precipitation chance for Saturday and Sunday".to_string(),
+                ],
+            }],
+            reasoning_hints: vec![
+                "Essential for time-sensitive decisions".to_string(),
+                "Consider geographic accuracy of location; add the region or country for names shared by several places".to_string(),
+                "Saved places ('home', 'office') and 'default location' need no city name".to_string(),
+                "Useful for travel and activity planning".to_string(),
+                "Forecasts reach 16 days ahead; hourly breakdowns 48 hours".to_string(),
+            ],
+        }
+    }
+
+    fn capability_key(&self) -> &'static str {
+        "weather_lookup"
+    }
+
+    fn capabilities(&self) -> Value {
+        json!({
+            "description": "Current weather conditions and forecasts worldwide",
+            "best_for": ["travel planning", "outdoor activities", "location-based decisions", "rain and temperature forecasts", "air quality"],
+            "strengths": ["real-time data", "global coverage", "detailed conditions", "daily forecasts up to 16 days", "hourly breakdowns", "metric or imperial units"],
+            "limitations": ["no historical weather", "forecast accuracy drops after a few days"]
+        })
+    }
+
+    fn started_event(&self) -> &'static str {
+        "WEATHER_LOOKUP_STARTED"
+    }
+
+    fn completed_event(&self) -> &'static str {
+        "WEATHER_LOOKUP_COMPLETED"
+    }
+
+    fn started_payload(&self, query: &str) -> Value {
+        to_payload(WeatherLookupStartedPayload {
+            location: query.to_string(),
+        })
+    }
+
+    fn failed_payload(&self, query: &str, error: &str) -> Value {
+        to_payload(WeatherLookupCompletedPayload {
+            location: query.to_string(),
+            success: false,
+            temperature: None,
+            unit: None,
+            description: None,
+            error: Some(error.to_string()),
+            forecast: None,
+        })
+    }
+
+    async fn execute(&self, ctx: &ToolContext<'_>, query: &str) -> Result<ToolOutput, String> {
+        // The planner's query is often just the place; the period is in the user's message
+        let request = ForecastRequest::for_lookup(query, ctx.user_query);
+        let settings = ctx.config.weather.clone().unwrap_or_default();
+        let units = request.units.or(settings.units).unwrap_or_default();
+        match perform_weather_lookup(
+            ctx.client,
+            query,
+            ctx.gemini_api_key,
+            ctx.model_name.to_string(),
+            request,
+            units,
+            &settings,
+        )
+        .await?
+        {
+            Some(forecast) => {
+                let weather_text = forecast_text(&forecast);
+                let current = forecast.current.as_ref();
+                Ok(ToolOutput {
+                    content: weather_text.clone(),
+                    context: Some(format!(
+                        "Weather Information for '{}':\n{}",
+                        query, weather_text
+                    )),
+                    sources: vec![ToolSource::new(
+                        &format!("Open-Meteo forecast for {}", forecast.location),
+                        &format!(
+                            "https://open-meteo.com/en/docs#latitude={}&longitude={}",
+                            forecast.latitude, forecast.longitude
+                        ),
+                        Some(&weather_text),
+                    )],
+                    completed_payload: to_payload(WeatherLookupCompletedPayload {
+                        location: query.to_string(),
+                        success: true,
+                        temperature: current.and_then(|c| c.temperature),
+                        unit: Some(forecast.units.temperature.clone()),
+                        description: current.and_then(|c| c.description.clone()),
+                        error: None,
+                        forecast: Some(forecast),
+                    }),
+                })
+            }
+            None => Err("Location not found".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_forecast_request_parse() {
+        let today = ForecastRequest::parse("Paris");
+        assert_eq!((today.days, today.hours, today.air_quality), (1, 0, false));
+        assert_eq!(today.units, None);
+
+        assert_eq!(ForecastRequest::parse("Paris tomorrow").days, 2);
+        assert_eq!(
+            ForecastRequest::parse("will it rain in Oslo this weekend").days,
+            7
+        );
+        assert_eq!(ForecastRequest::parse("Lyon next 10 days").days, 10);
+        assert_eq!(ForecastRequest::parse("Lyon 30 day forecast").days, 16);
+        assert_eq!(ForecastRequest::parse("Denver hourly").hours, 24);
+        assert_eq!(ForecastRequest::parse("Denver next 72 hours").hours, 48);
+        assert!(ForecastRequest::parse("air quality in Delhi").air_quality);
+
+        assert_eq!(
+            ForecastRequest::parse("Boston tomorrow in fahrenheit").units,
+            Some(WeatherUnits::Imperial)
+        );
+        assert_eq!(
+            ForecastRequest::parse("New York in °C").units,
+            Some(WeatherUnits::Metric)
+        );
+    }
+
+    #[test]
+    fn test_query_period_wins_over_the_message() {
+        let request = ForecastRequest::for_lookup(
+            "Oslo tomorrow",
+            "What's the weather in Oslo tomorrow, and what was it like last week?",
+        );
+        assert_eq!((request.days, request.hours), (2, 0));
+
+        let request =
+            ForecastRequest::for_lookup("Denver", "Denver hourly forecast in fahrenheit please");
+        assert_eq!((request.days, request.hours), (7, 24));
+        assert_eq!(request.units, Some(WeatherUnits::Imperial));
+
+        let request = ForecastRequest::for_lookup("Lyon in celsius", "Lyon weather in fahrenheit");
+        assert_eq!(request.units, Some(WeatherUnits::Metric));
+    }
+
+    #[test]
+    fn test_compass_direction() {
+        assert_eq!(compass_direction(0.0), "N");
+        assert_eq!(compass_direction(350.0), "N");
+        assert_eq!(compass_direction(90.0), "E");
+        assert_eq!(compass_direction(225.0), "SW");
+    }
+
+    #[test]
+    fn test_build_forecast() {
+        let weather: WeatherResponse = serde_json::from_str(
+            r#"{
+                "timezone": "Europe/Paris",
+                "current_units": {"temperature_2m": "°C", "wind_speed_10m": "km/h", "precipitation": "mm"},
+                "current": {"time": "2024-05-01T14:00", "temperature_2m": 18.5, "apparent_temperature": 17.9,
+                            "relative_humidity_2m": 60, "precipitation": 0.0, "weather_code": 2,
+                            "wind_speed_10m": 12.0, "wind_direction_10m": 270, "uv_index": 4.5},
+                "daily": {"time": ["2024-05-01", "2024-05-02"], "weather_code": [2, 61],
+                          "temperature_2m_max": [19.0, 16.2], "temperature_2m_min": [9.1, null],
+                          "precipitation_sum": [0.0, 4.2], "precipitation_probability_max": [10, 80],
+                          "sunrise": ["2024-05-01T06:35", "2024-05-02T06:33"],
+                          "sunset": ["2024-05-01T21:03", "2024-05-02T21:05"]}
+            }"#,
+        )
+        .unwrap();
+        let air: AirQualityResponse =
+            serde_json::from_str(r#"{"current": {"us_aqi": 42, "pm2_5": 8.3}}"#).unwrap();
+        let location = ResolvedLocation {
+            name: "Paris, Île-de-France, France".to_string(),
+            latitude: 48.85,
+            longitude: 2.35,
+            alternatives: vec!["Paris, Texas, United States".to_string()],
+        };
+        let forecast = build_forecast(location, weather, Some(air), WeatherUnits::Metric);
+
+        let current = forecast.current.as_ref().unwrap();
+        assert_eq!(current.temperature, Some(18.5));
+        assert_eq!(current.description.as_deref(), Some("Partly cloudy"));
+        assert_eq!(forecast.daily.len(), 2);
+        assert_eq!(
+            forecast.daily[1].description.as_deref(),
+            Some("Slight rain")
+        );
+        assert_eq!(forecast.daily[1].temperature_min, None);
+        assert_eq!(forecast.daily[1].wind_speed_max, None);
+        assert!(forecast.hourly.is_empty());
+        assert_eq!(forecast.air_quality.as_ref().unwrap().us_aqi, Some(42.0));
+
+        let text = forecast_text(&forecast);
+        assert!(text.contains("Now (2024-05-01T14:00): 18.5°C (feels like 17.9°C), Partly cloudy"));
+        assert!(text.contains("wind 12 km/h from W"));
+        assert!(text.contains(
+            "- 2024-05-02: Slight rain, precipitation 4.2 mm (80% chance), sunrise 06:33, sunset 21:05"
+        ));
+        assert!(text.contains("Air quality: US AQI 42, PM2.5 8.3 µg/m³"));
+        assert!(!text.contains("°°"));
+        assert!(text.contains("Other places with this name: Paris, Texas, United States"));
+    }
+
+    fn candidate(
+        name: &str,
+        admin1: &str,
+        country: &str,
+        population: Option<u64>,
+    ) -> LocationCandidate {
+        LocationCandidate {
+            name: name.to_string(),
+            admin1: Some(admin1.to_string()),
+            admin2: None,
+            country: Some(country.to_string()),
+            country_code: None,
+            latitude: 0.0,
+            longitude: 0.0,
+            population,
+        }
+    }
+
+    #[test]
+    fn test_rank_candidates() {
+        assert_eq!(
+            split_location("Paris, Texas, United States"),
+            (
+                "Paris".to_string(),
+                vec!["Texas".to_string(), "United States".to_string()]
+            )
+        );
+
+        let candidates = vec![
+            candidate("Paris", "Texas", "United States", Some(24_171)),
+            candidate("Paris", "Île-de-France", "France", Some(2_138_551)),
+            candidate("Parisot", "Occitanie", "France", Some(500)),
+            candidate("Paris", "Tennessee", "United States", Some(10_156)),
+        ];
+        // The capital dwarfs its namesakes; prefix matches are dropped
+        assert_eq!(
+            rank_candidates(&candidates, "Paris", &[]),
+            (vec![1, 0, 3], true)
+        );
+        assert_eq!(
+            rank_candidates(&candidates, "paris", &["texas".to_string()]),
+            (vec![0], true)
+        );
+        // Unknown qualifiers fall back to every place with the name
+        assert_eq!(
+            rank_candidates(&candidates, "Paris", &["TX".to_string()]).0,
+            vec![1, 0, 3]
+        );
+        // Two places of similar size are left for the model to choose from
+        assert_eq!(
+            rank_candidates(&candidates, "Paris", &["United States".to_string()]),
+            (vec![0, 3], false)
+        );
+        assert_eq!(
+            rank_candidates(&candidates[..1], "Paris", &[]),
+            (vec![0], true)
+        );
+        assert_eq!(candidates[1].label(), "Paris, Île-de-France, France");
+    }
+
+    #[test]
+    fn test_saved_places() {
+        let place = |name: &str| SavedPlace {
+            name: name.to_string(),
+            location: format!("{} town", name),
+            latitude: 51.5,
+            longitude: -0.1,
+        };
+        let mut settings = WeatherSettings {
+            units: None,
+            places: Some(vec![place("Home"), place("office")]),
+            default_place: Some("home".to_string()),
+        };
+        assert!(settings.validate().is_ok());
+        assert_eq!(settings.place("home").unwrap().name, "Home");
+        assert_eq!(settings.place("at my Office?").unwrap().name, "office");
+        assert!(settings.place("Homestead").is_none());
+        assert_eq!(settings.default_location().unwrap().name, "Home");
+
+        settings.default_place = Some("cabin".to_string());
+        assert!(settings.validate().is_err());
+        settings.default_place = None;
+        settings.places = Some(vec![place("home"), place("HOME")]);
+        assert!(settings.validate().is_err());
+        settings.places = Some(vec![SavedPlace {
+            latitude: 91.0,
+            ..place("home")
+        }]);
+        assert!(settings.validate().is_err());
+    }
+}
@@ -0,0 +1,691 @@
+//! General web search tool
+//!
+//! Searches the open web through a configurable provider (DuckDuckGo HTML,
+//! SearxNG, Brave or an offline stub), then fetches the top result pages and
+//! extracts their readable text for the research context.
+
+use super::citations::ToolSource;
+use super::fetch_url::fetch_document;
+use super::readability::{decode_entities, strip_tags, truncate_chars};
+use super::{to_payload, Tool, ToolContext, ToolOutput};
+use crate::mcp_simple::{ToolExample, ToolGuidance, ToolParameter};
+use crate::ToolType;
+use async_trait::async_trait;
+use futures_util::future::join_all;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+const DEFAULT_PROVIDER: &str = "duckduckgo";
+const DEFAULT_MAX_RESULTS: usize = 5;
+const DEFAULT_PAGES_TO_READ: usize = 3;
+/// Readable text kept per fetched page
+const MAX_PAGE_CHARS: usize = 4000;
+
+/// Provider names accepted in `WebSearchSettings::provider`
+pub const PROVIDERS: &[&str] = &["duckduckgo", "searxng", "brave", "stub"];
+
+/// Web search settings stored in `AppConfig`; `None` fields use the defaults
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct WebSearchSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>, // One of PROVIDERS, "duckduckgo" by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub searxng_url: Option<String>, // Base URL of a SearxNG instance with the JSON format enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub brave_api_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_results: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pages_to_read: Option<usize>, // Top results whose pages are fetched and read
+}
+
+impl WebSearchSettings {
+    /// Identifies the provider and limits that decide the results, for cache keys;
+    /// `None` for the stub provider, whose placeholder results aren't worth keeping
+    pub fn cache_scope(&self) -> Option<String> {
+        let provider = self
+            .provider
+            .as_deref()
+            .unwrap_or(DEFAULT_PROVIDER)
+            .trim()
+            .to_lowercase();
+        let instance = match provider.as_str() {
+            "stub" => return None,
+            "searxng" => self.searxng_url.as_deref().unwrap_or_default().trim(),
+            _ => "",
+        };
+        Some(format!(
+            "{} {} results:{} pages:{}",
+            provider,
+            instance,
+            self.max_results.unwrap_or(DEFAULT_MAX_RESULTS),
+            self.pages_to_read.unwrap_or(DEFAULT_PAGES_TO_READ)
+        ))
+    }
+}
+
+// --- Web Search Event Payloads ---
+#[derive(Serialize, Clone, Debug)]
+struct WebSearchStartedPayload {
+    query: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+struct WebSearchCompletedPayload {
+    query: String,
+    success: bool,
+    summary: Option<String>,
+    source_name: Option<Vec<String>>,
+    source_url: Option<Vec<String>>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    pub title: String,
+    pub url: String,
+    pub snippet: String,
+}
+
+#[async_trait]
+pub trait SearchProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Whether result pages should be fetched and read after searching
+    fn reads_pages(&self) -> bool {
+        true
+    }
+
+    async fn search(
+        &self,
+        client: &reqwest::Client,
+        query: &str,
+        max_results: usize,
+    ) -> Result<Vec<SearchHit>, String>;
+}
+
+/// The provider selected in the settings
+pub fn provider_for(settings: &WebSearchSettings) -> Result<Box<dyn SearchProvider>, String> {
+    let name = settings
+        .provider
+        .as_deref()
+        .unwrap_or(DEFAULT_PROVIDER)
+        .trim()
+        .to_lowercase();
+    match name.as_str() {
+        "duckduckgo" => Ok(Box::new(DuckDuckGoHtmlProvider)),
+        "searxng" => match settings.searxng_url.as_deref().map(str::trim) {
+            Some(url) if !url.is_empty() => Ok(Box::new(SearxngProvider {
+                base_url: url.trim_end_matches('/').to_string(),
+            })),
+            _ => Err("SearxNG web search requires a searxng_url".to_string()),
+        },
+        "brave" => match settings.brave_api_key.as_deref().map(str::trim) {
+            Some(key) if !key.is_empty() => Ok(Box::new(BraveProvider {
+                api_key: key.to_string(),
+            })),
+            _ => Err("Brave web search requires a brave_api_key".to_string()),
+        },
+        "stub" => Ok(Box::new(StubProvider)),
+        other => Err(format!(
+            "Unknown web search provider '{}'. Expected one of: {}",
+            other,
+            PROVIDERS.join(", ")
+        )),
+    }
+}
+
+async fn get_text(request: reqwest::RequestBuilder, provider: &str) -> Result<String, String> {
+    let response = request
+        .header(
+            "User-Agent",
+            "Shard/1.0 (https://github.com/shard-app/shard)",
+        )
+        .send()
+        .await
+        .map_err(|e| format!("{} network error: {}", provider, e))?;
+    let status = response.status();
+    let text = response
+        .text()
+        .await
+        .map_err(|e| format!("{}: Failed to read response text: {}", provider, e))?;
+    if !status.is_success() {
+        log::error!("{}: API error status {}: {}", provider, status, text);
+        return Err(format!("{} API error: {}", provider, status));
+    }
+    Ok(text)
+}
+
+// --- DuckDuckGo (HTML endpoint, no API key) ---
+struct DuckDuckGoHtmlProvider;
+
+#[async_trait]
+impl SearchProvider for DuckDuckGoHtmlProvider {
+    fn name(&self) -> &'static str {
+        "duckduckgo"
+    }
+
+    async fn search(
+        &self,
+        client: &reqwest::Client,
+        query: &str,
+        max_results: usize,
+    ) -> Result<Vec<SearchHit>, String> {
+        let html = get_text(
+            client
+                .get("https://html.duckduckgo.com/html/")
+                .query(&[("q", query)]),
+            "DuckDuckGo",
+        )
+        .await?;
+        let mut hits = parse_duckduckgo_html(&html);
+        hits.truncate(max_results);
+        Ok(hits)
+    }
+}
+
+/// Parse result links and snippets out of a DuckDuckGo HTML results page
+fn parse_duckduckgo_html(html: &str) -> Vec<SearchHit> {
+    static RESULT_LINK: OnceLock<Regex> = OnceLock::new();
+    static SNIPPET: OnceLock<Regex> = OnceLock::new();
+    let result_link = RESULT_LINK.get_or_init(|| {
+        Regex::new(r#"(?is)<a[^>]*class="result__a"[^>]*href="([^"]*)"[^>]*>(.*?)</a>"#)
+            .expect("DuckDuckGo result regex is valid")
+    });
+    let snippet = SNIPPET.get_or_init(|| {
+        Regex::new(r#"(?is)<a[^>]*class="result__snippet"[^>]*>(.*?)</a>"#)
+            .expect("DuckDuckGo snippet regex is valid")
+    });
+
+    let links: Vec<_> = result_link.captures_iter(html).collect();
+    let mut hits = Vec::new();
+    for (i, captures) in links.iter().enumerate() {
+        let start = captures.get(0).map_or(0, |m| m.end());
+        let end = links
+            .get(i + 1)
+            .and_then(|next| next.get(0))
+            .map_or(html.len(), |m| m.start());
+        let url = match resolve_duckduckgo_url(&captures[1]) {
+            Some(url) => url,
+            None => continue, // Ads and internal links
+        };
+        let snippet_text = snippet
+            .captures(&html[start..end])
+            .map(|s| clean_text(&s[1]))
+            .unwrap_or_default();
+        hits.push(SearchHit {
+            title: clean_text(&captures[2]),
+            url,
+            snippet: snippet_text,
+        });
+    }
+    hits
+}
+
+/// Unwrap DuckDuckGo's redirect links (`//duckduckgo.com/l/?uddg=...`)
+fn resolve_duckduckgo_url(href: &str) -> Option<String> {
+    let href = decode_entities(href);
+    if let Some(position) = href.find("uddg=") {
+        let encoded = href[position + 5..].split('&').next().unwrap_or_default();
+        return urlencoding::decode(encoded)
+            .ok()
+            .map(|url| url.into_owned());
+    }
+    if href.contains("duckduckgo.com/") {
+        return None;
+    }
+    if let Some(rest) = href.strip_prefix("//") {
+        return Some(format!("https://{}", rest));
+    }
+    href.starts_with("http").then_some(href)
+}
+
+fn clean_text(html: &str) -> String {
+    decode_entities(&strip_tags(html))
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// --- SearxNG (self-hosted metasearch, JSON API) ---
+struct SearxngProvider {
+    base_url: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct SearxngResponse {
+    #[serde(default)]
+    results: Vec<SearxngResult>,
+}
+
+#[derive(Deserialize, Debug)]
+struct SearxngResult {
+    title: String,
+    url: String,
+    #[serde(default)]
+    content: Option<String>,
+}
+
+#[async_trait]
+impl SearchProvider for SearxngProvider {
+    fn name(&self) -> &'static str {
+        "searxng"
+    }
+
+    async fn search(
+        &self,
+        client: &reqwest::Client,
+        query: &str,
+        max_results: usize,
+    ) -> Result<Vec<SearchHit>, String> {
+        let text = get_text(
+            client
+                .get(format!("{}/search", self.base_url))
+                .query(&[("q", query), ("format", "json")]),
+            "SearxNG",
+        )
+        .await?;
+        let response: SearxngResponse =
+            serde_json::from_str(&text).map_err(|e| format!("SearxNG JSON parse error: {}", e))?;
+        Ok(response
+            .results
+            .into_iter()
+            .take(max_results)
+            .map(|r| SearchHit {
+                title: clean_text(&r.title),
+                url: r.url,
+                snippet: clean_text(&r.content.unwrap_or_default()),
+            })
+            .collect())
+    }
+}
+
+// --- Brave Search API ---
+struct BraveProvider {
+    api_key: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct BraveResponse {
+    web: Option<BraveWebResults>,
+}
+
+#[derive(Deserialize, Debug)]
+struct BraveWebResults {
+    #[serde(default)]
+    results: Vec<BraveResult>,
+}
+
+#[derive(Deserialize, Debug)]
+struct BraveResult {
+    title: String,
+    url: String,
+    #[serde(default)]
+    description: Option<String>,
+}
+
+#[async_trait]
+impl SearchProvider for BraveProvider {
+    fn name(&self) -> &'static str {
+        "brave"
+    }
+
+    async fn search(
+        &self,
+        client: &reqwest::Client,
+        query: &str,
+        max_results: usize,
+    ) -> Result<Vec<SearchHit>, String> {
+        let count = max_results.to_string();
+        let text = get_text(
+            client
+                .get("https://api.search.brave.com/res/v1/web/search")
+                .query(&[("q", query), ("count", count.as_str())])
+                .header("Accept", "application/json")
+                .header("X-Subscription-Token", &self.api_key),
+            "Brave Search",
+        )
+        .await?;
+        let response: BraveResponse = serde_json::from_str(&text)
+            .map_err(|e| format!("Brave Search JSON parse error: {}", e))?;
+        Ok(response
+            .web
+            .map(|web| web.results)
+            .unwrap_or_default()
+            .into_iter()
+            .take(max_results)
+            .map(|r| SearchHit {
+                title: clean_text(&r.title),
+                url: r.url,
+                snippet: clean_text(&r.description.unwrap_or_default()),
+            })
+            .collect())
+    }
+}
+
+// --- Offline stub, for development without network access ---
+struct StubProvider;
+
+#[async_trait]
+impl SearchProvider for StubProvider {
+    fn name(&self) -> &'static str {
+        "stub"
+    }
+
+    fn reads_pages(&self) -> bool {
+        false
+    }
+
+    async fn search(
+        &self,
+        _client: &reqwest::Client,
+        query: &str,
+        _max_results: usize,
+    ) -> Result<Vec<SearchHit>, String> {
+        Ok(vec![SearchHit {
+            title: format!("Stub result for '{}'", query),
+            url: format!(
+                "https://example.com/search?q={}",
+                urlencoding::encode(query)
+            ),
+            snippet:
+                "The stub web search provider returns placeholder results without network access."
+                    .to_string(),
+        }])
+    }
+}
+
+pub struct WebSearchTool;
+
+#[async_trait]
+impl Tool for WebSearchTool {
+    fn tool_type(&self) -> ToolType {
+        ToolType::WebSearch
+    }
+
+    fn description(&self) -> &'static str {
+        "General web search that reads the top result pages; for recent events, niche topics, products, documentation and anything not covered by Wikipedia"
+    }
+
+    fn follow_up_hint(&self) -> &'static str {
+        "Open-web search for recent or niche information (short keyword queries)"
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "Search engine query, a few specific keywords"
+                }
+            },
+            "required": ["query"]
+        })
+    }
+
+    fn guidance(&self) -> ToolGuidance {
+        ToolGuidance {
+            name: "Web Search".to_string(),
+            description: "Search the open web and read the top result pages".to_string(),
+            usage_pattern: "Use for recent events, niche or fast-changing topics, and sources outside Wikipedia. Prefer Wikipedia for stable background knowledge.".to_string(),
+            parameters: vec![ToolParameter {
+                name: "query".to_string(),
+                param_type: "string".to_string(),
+                description: "Search engine query, a few specific keywords".to_string(),
+                required: true,
+                default_value: None,
+            }],
+            examples: vec![
+                ToolExample {
+                    scenario: "User asks what changed in the latest Rust release".to_string(),
+                    reasoning: "Release notes are recent and not covered well by Wikipedia".to_string(),
+                    sequence: vec![
+                        "GOOD: 'Rust 1.80 release notes'".to_string(),
+                        "BAD: 'Rust' - too broad, Wikipedia covers the background".to_string(),
+                    ],
+                },
+                ToolExample {
+                    scenario: "User asks about a news event from this week".to_string(),
+                    reasoning: "Encyclopedias lag behind current events".to_string(),
+                    sequence: vec![
+                        "Search with the event name and a date or month".to_string(),
+                        "Combine with Wikipedia only for background context".to_string(),
+                    ],
+                },
+            ],
+            reasoning_hints: vec![
+                "Use short, specific keyword queries, as you would type into a search engine".to_string(),
+                "Include dates, versions or names to get recent results".to_string(),
+                "Do NOT use web search for stock prices or weather - use the dedicated tools".to_string(),
+                "Pages may be outdated or unreliable; prefer authoritative domains in the answer".to_string(),
+            ],
+        }
+    }
+
+    fn capability_key(&self) -> &'static str {
+        "web_search"
+    }
+
+    fn capabilities(&self) -> Value {
+        json!({
+            "description": "Open-web search with page reading",
+            "best_for": ["recent events", "niche topics", "product and documentation lookups"],
+            "strengths": ["broad coverage", "up-to-date information"],
+            "limitations": ["source quality varies", "pages may block automated access"]
+        })
+    }
+
+    fn started_event(&self) -> &'static str {
+        "WEB_SEARCH_STARTED"
+    }
+
+    fn completed_event(&self) -> &'static str {
+        "WEB_SEARCH_COMPLETED"
+    }
+
+    fn started_payload(&self, query: &str) -> Value {
+        to_payload(WebSearchStartedPayload {
+            query: query.to_string(),
+        })
+    }
+
+    fn failed_payload(&self, query: &str, error: &str) -> Value {
+        to_payload(WebSearchCompletedPayload {
+            query: query.to_string(),
+            success: false,
+            summary: None,
+            source_name: None,
+            source_url: None,
+            error: Some(error.to_string()),
+        })
+    }
+
+    /// Search results move quickly, but not within the hour
+    fn cache_ttl(&self) -> Option<Duration> {
+        Some(Duration::from_secs(60 * 60))
+    }
+
+    /// Results depend on the provider and on the pages the fetch settings let it read
+    fn cache_key(&self, ctx: &ToolContext<'_>, query: &str) -> Option<String> {
+        let scope = ctx
+            .config
+            .web_search
+            .clone()
+            .unwrap_or_default()
+            .cache_scope()?;
+        let fetch_settings = ctx.config.url_fetch.clone().unwrap_or_default();
+        Some(format!(
+            "{} [{}] [{}]",
+            query,
+            scope,
+            fetch_settings.cache_scope()
+        ))
+    }
+
+    async fn execute(&self, ctx: &ToolContext<'_>, query: &str) -> Result<ToolOutput, String> {
+        let settings = ctx.config.web_search.clone().unwrap_or_default();
+        let provider = provider_for(&settings)?;
+        let max_results = settings.max_results.unwrap_or(DEFAULT_MAX_RESULTS).max(1);
+        log::info!(
+            "Performing web search via {} for '{}' (up to {} results)",
+            provider.name(),
+            query,
+            max_results
+        );
+        let hits = provider.search(ctx.client, query, max_results).await?;
+
+        if hits.is_empty() {
+            log::info!("Web search for '{}' returned no results.", query);
+            let summary = "No web results found.".to_string();
+            return Ok(ToolOutput {
+                content: summary.clone(),
+                context: None,
+                sources: Vec::new(),
+                completed_payload: to_payload(WebSearchCompletedPayload {
+                    query: query.to_string(),
+                    success: true,
+                    summary: Some(summary),
+                    source_name: None,
+                    source_url: None,
+                    error: None,
+                }),
+            });
+        }
+
+        // Read the top pages concurrently; a page that fails falls back to its snippet
+        let pages_to_read = if provider.reads_pages() {
+            settings.pages_to_read.unwrap_or(DEFAULT_PAGES_TO_READ)
+        } else {
+            0
+        };
+        let fetch_settings = ctx.config.url_fetch.clone().unwrap_or_default();
+        let pages = join_all(
+            hits.iter()
+                .take(pages_to_read)
+                .map(|hit| fetch_document(&hit.url, &fetch_settings)),
+        )
+        .await;
+
+        let mut combined = String::new();
+        for (i, hit) in hits.iter().enumerate() {
+            combined.push_str(&format!("Title: {}\nURL: {}\n", hit.title, hit.url));
+            match pages.get(i) {
+                Some(Ok(page)) if !page.text.is_empty() => {
+                    combined.push_str(&format!(
+                        "Content: {}\n\n",
+                        truncate_chars(&page.text, MAX_PAGE_CHARS)
+                    ));
+                }
+                Some(Err(e)) => {
+                    log::warn!("Web search: {}", e);
+                    combined.push_str(&format!("Snippet: {}\n\n", hit.snippet));
+                }
+                _ => combined.push_str(&format!("Snippet: {}\n\n", hit.snippet)),
+            }
+        }
+        log::info!(
+            "Web search for '{}' found {} results, read {} pages",
+            query,
+            hits.len(),
+            pages.iter().filter(|p| p.is_ok()).count()
+        );
+
+        let context_text = format!(
+            "Web Search Results for '{}':\n\n{}",
+            query,
+            combined.trim_end()
+        );
+        let summary = hits
+            .iter()
+            .map(|hit| format!("{}: {}", hit.title, hit.snippet))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        Ok(ToolOutput {
+            content: context_text.clone(),
+            context: Some(context_text),
+            sources: hits
+                .iter()
+                .map(|hit| ToolSource::new(&hit.title, &hit.url, Some(&hit.snippet)))
+                .collect(),
+            completed_payload: to_payload(WebSearchCompletedPayload {
+                query: query.to_string(),
+                success: true,
+                summary: Some(summary),
+                source_name: Some(hits.iter().map(|hit| hit.title.clone()).collect()),
+                source_url: Some(hits.iter().map(|hit| hit.url.clone()).collect()),
+                error: None,
+            }),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duckduckgo_html() {
+        let html = r#"
+            <div class="result results_links results_links_deep web-result">
+              <h2 class="result__title">
+                <a rel="nofollow" class="result__a" href="//duckduckgo.com/l/?uddg=https%3A%2F%2Fwww.rust-lang.org%2F&amp;rut=abc">Rust <b>Programming</b> Language</a>
+              </h2>
+              <a class="result__snippet" href="//duckduckgo.com/l/?uddg=https%3A%2F%2Fwww.rust-lang.org%2F">A language empowering everyone &amp; more.</a>
+            </div>
+            <div class="result results_links">
+              <a rel="nofollow" class="result__a" href="https://duckduckgo.com/y.js?ad_provider=x">Sponsored</a>
+            </div>
+            <div class="result results_links">
+              <a rel="nofollow" class="result__a" href="https://doc.rust-lang.org/book/">The Rust Book</a>
+            </div>"#;
+        let hits = parse_duckduckgo_html(html);
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].title, "Rust Programming Language");
+        assert_eq!(hits[0].url, "https://www.rust-lang.org/");
+        assert_eq!(hits[0].snippet, "A language empowering everyone & more.");
+        assert_eq!(hits[1].url, "https://doc.rust-lang.org/book/");
+        assert_eq!(hits[1].snippet, "");
+    }
+
+    #[test]
+    fn test_provider_selection() {
+        let default_provider = provider_for(&WebSearchSettings::default()).unwrap();
+        assert_eq!(default_provider.name(), "duckduckgo");
+
+        let searxng = WebSearchSettings {
+            provider: Some("SearxNG".to_string()),
+            ..WebSearchSettings::default()
+        };
+        assert!(provider_for(&searxng).is_err());
+        let searxng = WebSearchSettings {
+            searxng_url: Some("http://localhost:8888/".to_string()),
+            ..searxng
+        };
+        assert_eq!(provider_for(&searxng).unwrap().name(), "searxng");
+
+        let unknown = WebSearchSettings {
+            provider: Some("altavista".to_string()),
+            ..WebSearchSettings::default()
+        };
+        assert!(provider_for(&unknown).is_err());
+
+        // Each provider caches its own results; the stub's aren't cached
+        let stub = WebSearchSettings {
+            provider: Some("stub".to_string()),
+            ..WebSearchSettings::default()
+        };
+        assert_eq!(stub.cache_scope(), None);
+        let default_scope = WebSearchSettings::default().cache_scope().unwrap();
+        assert_ne!(searxng.cache_scope().unwrap(), default_scope);
+        let more_results = WebSearchSettings {
+            max_results: Some(10),
+            ..WebSearchSettings::default()
+        };
+        assert_ne!(more_results.cache_scope().unwrap(), default_scope);
+    }
+}
@@ -0,0 +1,1452 @@
+//! Wikipedia research tool
+//!
+//! Reads articles through the MediaWiki API and follows promising leads page
+//! by page, with a helper LLM choosing the next search term. Each article is
+//! split into its sections and only the intro and the sections most relevant
+//! to the query are read, each cited with its section anchor. Wikipedia's
+//! language follows the user's message (or a setting), missing articles are
+//! found through the fallback language's langlinks, and any other MediaWiki
+//! site (an internal wiki, Wiktionary) can be searched first.
+
+use super::citations::ToolSource;
+use super::readability::truncate_chars;
+use super::{to_payload, Tool, ToolContext, ToolOutput};
+use crate::documents::score_texts;
+use crate::mcp_simple::{ToolExample, ToolGuidance, ToolParameter};
+use crate::{call_gemini_api_non_streaming, ChatMessage, ToolType};
+use async_trait::async_trait;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// Maximum number of pages read by one iterative research run
+const MAX_RESEARCH_PAGES: usize = 4;
+/// Characters of an article read per page: the intro plus the best-matching sections
+const MAX_ARTICLE_CHARS: usize = 6000;
+/// The intro is always read, up to this length
+const MAX_INTRO_CHARS: usize = 2000;
+
+// --- Web Search Event Payloads ---
+#[derive(Serialize, Clone, Debug)]
+struct ArticleLookupStartedPayload {
+    query: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+struct ArticleLookupCompletedPayload {
+    query: String,
+    success: bool,
+    summary: Option<String>,
+    source_name: Option<Vec<String>>,
+    source_url: Option<Vec<String>>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IterativeSearchResult {
+    pub title: String,
+    pub summary: String,
+    pub url: String,
+    pub path_taken: Vec<String>,
+    /// Sections of the article that were read, in article order
+    #[serde(default)]
+    pub sections: Vec<SectionSource>,
+}
+
+/// A section read from an article, linked by its anchor
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SectionSource {
+    /// `None` for the intro
+    pub heading: Option<String>,
+    pub url: String,
+    pub text: String,
+}
+
+/// One section of an article's plain-text extract
+#[derive(Debug, Clone, PartialEq)]
+pub struct WikiSection {
+    /// `None` for the intro before the first heading
+    pub heading: Option<String>,
+    /// Heading level: 2 for `== H ==`, 3 for `=== H ===`, ...
+    pub level: usize,
+    pub text: String,
+}
+
+/// Split a plain-text extract with `== Heading ==` markers into sections
+pub fn split_sections(extract: &str) -> Vec<WikiSection> {
+    static HEADING: OnceLock<Regex> = OnceLock::new();
+    let heading = HEADING.get_or_init(|| {
+        Regex::new(r"^(={2,6})\s*(.+?)\s*={2,6}$").expect("section heading regex is valid")
+    });
+
+    let mut sections = vec![WikiSection {
+        heading: None,
+        level: 1,
+        text: String::new(),
+    }];
+    for line in extract.lines() {
+        if let Some(captures) = heading.captures(line.trim()) {
+            sections.push(WikiSection {
+                heading: Some(captures[2].to_string()),
+                level: captures[1].len(),
+                text: String::new(),
+            });
+        } else if let Some(section) = sections.last_mut() {
+            section.text.push_str(line);
+            section.text.push('\n');
+        }
+    }
+    for section in sections.iter_mut() {
+        section.text = section.text.trim().to_string();
+    }
+    // Headings directly followed by a subheading have no text of their own
+    sections.retain(|section| !section.text.is_empty());
+    sections
+}
+
+/// The intro plus the sections that best match `query`, in article order, within `max_chars`
+pub fn select_sections(
+    sections: &[WikiSection],
+    query: &str,
+    max_chars: usize,
+) -> Vec<WikiSection> {
+    let mut selected: Vec<(usize, WikiSection)> = Vec::new();
+    let mut used = 0;
+    if let Some(intro) = sections.first().filter(|s| s.heading.is_none()) {
+        let text = truncate_chars(&intro.text, MAX_INTRO_CHARS.min(max_chars));
+        used += text.chars().count();
+        selected.push((
+            0,
+            WikiSection {
+                text,
+                ..intro.clone()
+            },
+        ));
+    }
+
+    let texts: Vec<String> = sections
+        .iter()
+        .map(|s| format!("{}\n{}", s.heading.as_deref().unwrap_or_default(), s.text))
+        .collect();
+    let scores = score_texts(&texts.iter().map(String::as_str).collect::<Vec<_>>(), query);
+    let mut order: Vec<usize> = (0..sections.len())
+        .filter(|&i| sections[i].heading.is_some() && scores[i] > 0.0)
+        .collect();
+    order.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+    for i in order {
+        let remaining = max_chars.saturating_sub(used);
+        if remaining < 200 {
+            break;
+        }
+        // A long but relevant section is shortened rather than skipped
+        let text = truncate_chars(&sections[i].text, remaining);
+        used += text.chars().count();
+        selected.push((
+            i,
+            WikiSection {
+                text,
+                ..sections[i].clone()
+            },
+        ));
+    }
+    selected.sort_by_key(|(i, _)| *i);
+    selected.into_iter().map(|(_, section)| section).collect()
+}
+
+/// Article URL pointing at a section's anchor
+fn section_url(page_url: &str, heading: Option<&str>) -> String {
+    match heading {
+        Some(heading) => format!(
+            "{}#{}",
+            page_url,
+            urlencoding::encode(&heading.replace(' ', "_"))
+        ),
+        None => page_url.to_string(),
+    }
+}
+
+/// Sections as text for the model, keeping their headings
+fn render_sections(sections: &[WikiSection]) -> String {
+    sections
+        .iter()
+        .map(|section| match &section.heading {
+            Some(heading) => {
+                let marker = "=".repeat(section.level);
+                format!("{} {} {}\n{}", marker, heading, marker, section.text)
+            }
+            None => section.text.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "decision_type")]
+enum AnalysisLLMDecision {
+    #[serde(rename = "FOUND_ANSWER")]
+    FoundAnswer { summary: String, title: String },
+    #[serde(rename = "NEXT_TERM")]
+    NextTerm { term: String, reason: String },
+    #[serde(rename = "STOP")]
+    Stop { reason: String },
+}
+
+// --- ADDED: Wikipedia API Structures ---
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct WikipediaLangLink {
+    lang: String,
+    title: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct WikipediaQueryPage {
+    pageid: Option<i64>,
+    title: Option<String>,
+    extract: Option<String>,
+    missing: Option<bool>,
+    #[serde(default)]
+    langlinks: Vec<WikipediaLangLink>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct WikipediaQuery {
+    #[serde(default)]
+    pages: Vec<WikipediaQueryPage>, // Changed from HashMap<String, WikipediaQueryPage>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct WikipediaResponse {
+    batchcomplete: Option<bool>, // Changed from Option<String> to Option<bool>
+    query: Option<WikipediaQuery>,
+}
+
+/// A MediaWiki site articles can be read from (an internal wiki, Wiktionary, ...)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WikiEndpoint {
+    pub name: String,    // Shown with results, e.g. "Team Wiki"
+    pub api_url: String, // e.g. "https://wiki.example.com/w/api.php"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub article_url: Option<String>, // Page URL with $1 for the title; derived from api_url if unset
+}
+
+impl WikiEndpoint {
+    pub fn wikipedia(language: &str) -> Self {
+        WikiEndpoint {
+            name: format!("Wikipedia ({})", language),
+            api_url: format!("https://{}.wikipedia.org/w/api.php", language),
+            article_url: Some(format!("https://{}.wikipedia.org/wiki/$1", language)),
+        }
+    }
+
+    /// Link to an article, following MediaWiki's `/w/api.php` → `/wiki/$1` layout by default
+    pub fn page_url(&self, title: &str) -> String {
+        let pattern = match &self.article_url {
+            Some(pattern) => pattern.clone(),
+            None => match self.api_url.strip_suffix("/w/api.php") {
+                Some(base) => format!("{}/wiki/$1", base),
+                None => self.api_url.replace("api.php", "index.php?title=$1"),
+            },
+        };
+        pattern.replace("$1", &urlencoding::encode(&title.replace(' ', "_")))
+    }
+}
+
+/// Wiki settings stored in `AppConfig`; `None` fields use the defaults
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct WikiSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>, // Wikipedia language code, or "auto" (default) to follow the user's message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fallback_language: Option<String>, // Searched when an article is missing, "en" by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoints: Option<Vec<WikiEndpoint>>, // Extra MediaWiki sites, searched before Wikipedia
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include_wikipedia: Option<bool>, // On by default; off to search only the extra endpoints
+}
+
+impl WikiSettings {
+    /// Reject language codes and endpoints that could never be queried
+    pub fn validate(&self) -> Result<(), String> {
+        let is_language_code = |code: &str| {
+            !code.is_empty() && code.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        };
+        if let Some(language) = self.language.as_deref().map(str::trim) {
+            if language != "auto" && !is_language_code(language) {
+                return Err(format!("Invalid Wikipedia language code: '{}'", language));
+            }
+        }
+        if let Some(language) = self.fallback_language.as_deref().map(str::trim) {
+            if !language.is_empty() && !is_language_code(language) {
+                return Err(format!("Invalid fallback language code: '{}'", language));
+            }
+        }
+        for endpoint in self.endpoints.iter().flatten() {
+            if endpoint.name.trim().is_empty() {
+                return Err("Every wiki endpoint needs a name".to_string());
+            }
+            match reqwest::Url::parse(&endpoint.api_url) {
+                Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {}
+                _ => {
+                    return Err(format!(
+                        "Wiki endpoint '{}' needs an http(s) API URL",
+                        endpoint.name
+                    ))
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The wikis one research run reads from
+#[derive(Debug, Clone, PartialEq)]
+pub struct WikiSources {
+    /// Searched in order; the first site with the article wins
+    pub sites: Vec<WikiEndpoint>,
+    /// Wikipedia language of `sites`, if Wikipedia is included
+    pub language: Option<String>,
+    /// Wikipedia language searched when no site has the article
+    pub fallback_language: Option<String>,
+}
+
+impl WikiSources {
+    /// Resolve the settings for a message, detecting its language when set to "auto"
+    pub fn resolve(settings: &WikiSettings, user_query: &str) -> Self {
+        let mut sites = settings.endpoints.clone().unwrap_or_default();
+        if !settings.include_wikipedia.unwrap_or(true) {
+            return WikiSources {
+                sites,
+                language: None,
+                fallback_language: None,
+            };
+        }
+        let language = match settings.language.as_deref().map(str::trim) {
+            Some(code) if !code.is_empty() && code != "auto" => code.to_lowercase(),
+            _ => detect_language(user_query).unwrap_or("en").to_string(),
+        };
+        let fallback_language = settings
+            .fallback_language
+            .as_deref()
+            .map(str::trim)
+            .unwrap_or("en")
+            .to_lowercase();
+        sites.push(WikiEndpoint::wikipedia(&language));
+        WikiSources {
+            sites,
+            fallback_language: (!fallback_language.is_empty() && fallback_language != language)
+                .then_some(fallback_language),
+            language: Some(language),
+        }
+    }
+
+    /// Identifies the sites and languages searched, for cache keys
+    pub fn cache_scope(&self) -> String {
+        let mut scope: Vec<&str> = self
+            .sites
+            .iter()
+            .map(|site| site.api_url.as_str())
+            .collect();
+        scope.extend(self.fallback_language.as_deref());
+        scope.join(" ")
+    }
+}
+
+/// Frequent short words that identify a Latin-script language
+const LANGUAGE_HINTS: &[(&str, &[&str])] = &[
+    (
+        "en",
+        &[
+            "the", "and", "is", "are", "was", "what", "who", "how", "of", "does", "with",
+        ],
+    ),
+    (
+        "de",
+        &[
+            "der", "die", "das", "und", "ist", "wie", "was", "wer", "nicht", "ein", "eine", "mit",
+        ],
+    ),
+    (
+        "fr",
+        &[
+            "le", "la", "les", "et", "est", "une", "des", "qui", "quoi", "comment", "du", "pour",
+        ],
+    ),
+    (
+        "es",
+        &[
+            "el", "los", "las", "y", "es", "qué", "cómo", "quién", "una", "del", "por", "cuál",
+        ],
+    ),
+    (
+        "it",
+        &[
+            "il", "gli", "è", "che", "chi", "come", "della", "per", "sono", "cosa", "di",
+        ],
+    ),
+    (
+        "pt",
+        &[
+            "o", "os", "é", "que", "quem", "como", "um", "uma", "da", "não", "para", "qual",
+        ],
+    ),
+    (
+        "nl",
+        &[
+            "het", "een", "en", "wat", "wie", "hoe", "van", "niet", "voor", "zijn", "de",
+        ],
+    ),
+];
+
+/// Best guess of the language of a message, as a Wikipedia language code
+///
+/// Non-Latin scripts decide directly; Latin-script text needs at least two
+/// hint words and a clear winner, otherwise `None`.
+pub fn detect_language(text: &str) -> Option<&'static str> {
+    let mut letters = 0;
+    let mut scripts: Vec<(&'static str, usize)> = Vec::new();
+    for c in text.chars().filter(|c| c.is_alphabetic()) {
+        letters += 1;
+        let script = match c as u32 {
+            0x3040..=0x30FF => "ja",
+            0xAC00..=0xD7AF | 0x1100..=0x11FF => "ko",
+            0x4E00..=0x9FFF => "zh",
+            0x0400..=0x04FF => "ru",
+            0x0370..=0x03FF => "el",
+            0x0590..=0x05FF => "he",
+            0x0600..=0x06FF => "ar",
+            0x0900..=0x097F => "hi",
+            0x0E00..=0x0E7F => "th",
+            _ => continue,
+        };
+        match scripts.iter_mut().find(|(name, _)| *name == script) {
+            Some((_, count)) => *count += 1,
+            None => scripts.push((script, 1)),
+        }
+    }
+    let non_latin: usize = scripts.iter().map(|(_, count)| count).sum();
+    if letters > 0 && non_latin * 10 >= letters * 3 {
+        // Kanji are shared with Chinese, so any kana means Japanese
+        if scripts.iter().any(|(name, _)| *name == "ja") {
+            return Some("ja");
+        }
+        return scripts
+            .iter()
+            .max_by_key(|(_, count)| *count)
+            .map(|(name, _)| *name);
+    }
+
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let mut scores: Vec<(&'static str, usize)> = LANGUAGE_HINTS
+        .iter()
+        .map(|(language, hints)| {
+            let score = words.iter().filter(|w| hints.contains(&w.as_str())).count();
+            (*language, score)
+        })
+        .collect();
+    scores.sort_by(|a, b| b.1.cmp(&a.1));
+    match scores.as_slice() {
+        [(language, best), (_, second), ..] if *best >= 2 && best > second => Some(language),
+        _ => None,
+    }
+}
+
+/// An article found on one of the wikis
+#[derive(Debug, Clone, PartialEq)]
+struct WikiPage {
+    title: String,
+    extract: String,
+    url: String,
+    /// Title of the same article in the requested language, from langlinks
+    langlink: Option<String>,
+}
+
+/// Read an article from one wiki
+///
+/// Looks the term up as a title first and falls back to the wiki's full-text
+/// search. With `langlink_language` set, also returns the article's title in
+/// that language.
+async fn fetch_wiki_page(
+    client: &reqwest::Client,
+    site: &WikiEndpoint,
+    search_term: &str,
+    langlink_language: Option<&str>,
+) -> Result<Option<WikiPage>, String> {
+    let mut page = query_wiki(client, site, search_term, false, langlink_language).await?;
+    if page.as_ref().map_or(true, |p| p.missing == Some(true)) {
+        log::info!(
+            "{}: No page titled '{}', trying search.",
+            site.name,
+            search_term
+        );
+        page = query_wiki(client, site, search_term, true, langlink_language).await?;
+    }
+    let page = match page {
+        Some(page) if page.missing != Some(true) => page,
+        _ => return Ok(None),
+    };
+    let extract = page.extract.as_deref().unwrap_or_default().trim();
+    if extract.is_empty() {
+        log::info!("{}: No suitable extract for '{}'.", site.name, search_term);
+        return Ok(None);
+    }
+    let title = page
+        .title
+        .clone()
+        .unwrap_or_else(|| search_term.to_string());
+    log::info!("{}: Found extract for title '{}'", site.name, title);
+    Ok(Some(WikiPage {
+        url: site.page_url(&title),
+        extract: extract.to_string(),
+        langlink: langlink_language.and_then(|language| {
+            page.langlinks
+                .iter()
+                .find(|link| link.lang == language)
+                .map(|link| link.title.clone())
+        }),
+        title,
+    }))
+}
+
+async fn query_wiki(
+    client: &reqwest::Client,
+    site: &WikiEndpoint,
+    search_term: &str,
+    use_search: bool,
+    langlink_language: Option<&str>,
+) -> Result<Option<WikipediaQueryPage>, String> {
+    let mut params: Vec<(&str, &str)> = vec![
+        ("action", "query"),
+        ("format", "json"),
+        ("explaintext", "true"),
+        ("exsectionformat", "wiki"),
+        ("redirects", "1"),
+        ("formatversion", "2"),
+    ];
+    if use_search {
+        params.extend([
+            ("generator", "search"),
+            ("gsrsearch", search_term),
+            ("gsrlimit", "1"),
+        ]);
+    } else {
+        params.push(("titles", search_term));
+    }
+    match langlink_language {
+        Some(language) => params.extend([("prop", "extracts|langlinks"), ("lllang", language)]),
+        None => params.push(("prop", "extracts")),
+    }
+
+    log::info!(
+        "Performing wiki lookup on {} for '{}' (search: {})",
+        site.api_url,
+        search_term,
+        use_search
+    );
+    let response = client
+        .get(&site.api_url)
+        .query(&params)
+        .header(
+            "User-Agent",
+            "Shard/1.0 (https://github.com/shard-app/shard)",
+        )
+        .send()
+        .await
+        .map_err(|e| format!("{} network error: {}", site.name, e))?;
+    let status = response.status();
+    let response_text = response
+        .text()
+        .await
+        .map_err(|e| format!("{}: Failed to read response text: {}", site.name, e))?;
+    if !status.is_success() {
+        log::error!(
+            "{}: API error status {}: {}",
+            site.name,
+            status,
+            response_text
+        );
+        return Err(format!(
+            "{} API error: {} - {}",
+            site.name, status, response_text
+        ));
+    }
+    let wiki_response = serde_json::from_str::<WikipediaResponse>(&response_text).map_err(|e| {
+        log::error!(
+            "{}: Failed to parse JSON: {}. Raw: {}",
+            site.name,
+            e,
+            response_text
+        );
+        format!("{} JSON parse error: {}", site.name, e)
+    })?;
+    Ok(wiki_response
+        .query
+        .and_then(|query| query.pages.into_iter().next()))
+}
+
+// --- ADDED: Wikipedia Lookup Function ---
+/// Find an article on the configured wikis
+///
+/// Sites are tried in order. If none has the article, Wikipedia in the
+/// fallback language is searched and its langlinks lead back to the article
+/// in the message's language; without one, the fallback article is used.
+async fn perform_wikipedia_lookup(
+    client: &reqwest::Client,
+    wikis: &WikiSources,
+    search_term: &str,
+) -> Result<Option<WikiPage>, String> {
+    let mut last_error: Option<String> = None;
+    let mut any_succeeded = false;
+    for site in &wikis.sites {
+        match fetch_wiki_page(client, site, search_term, None).await {
+            Ok(Some(page)) => return Ok(Some(page)),
+            Ok(None) => any_succeeded = true,
+            Err(e) => {
+                log::warn!("Wiki lookup on {} failed: {}", site.name, e);
+                last_error = Some(e);
+            }
+        }
+    }
+
+    if let Some(fallback_language) = &wikis.fallback_language {
+        let fallback_site = WikiEndpoint::wikipedia(fallback_language);
+        match fetch_wiki_page(
+            client,
+            &fallback_site,
+            search_term,
+            wikis.language.as_deref(),
+        )
+        .await
+        {
+            Ok(Some(page)) => {
+                if let (Some(linked_title), Some(language)) = (&page.langlink, &wikis.language) {
+                    let site = WikiEndpoint::wikipedia(language);
+                    log::info!(
+                        "Following langlink from '{}' to {}: '{}'",
+                        page.title,
+                        site.name,
+                        linked_title
+                    );
+                    if let Ok(Some(linked)) =
+                        fetch_wiki_page(client, &site, linked_title, None).await
+                    {
+                        return Ok(Some(linked));
+                    }
+                }
+                return Ok(Some(page));
+            }
+            Ok(None) => any_succeeded = true,
+            Err(e) => {
+                log::warn!("Wiki lookup on {} failed: {}", fallback_site.name, e);
+                last_error = Some(e);
+            }
+        }
+    }
+
+    match last_error {
+        Some(e) if !any_succeeded => Err(e),
+        _ => {
+            log::info!("Wiki: No article found for '{}'.", search_term);
+            Ok(None)
+        }
+    }
+}
+
+// --- ADDED: Wikipedia Search Term Extractor Function ---
+async fn extract_wikipedia_search_term(
+    client: &reqwest::Client,
+    user_query: &str,
+    gemini_api_key_string: String,
+    model_name: String,
+) -> Result<Vec<String>, String> {
+    let extractor_prompt = format!(
+        "You are an expert at identifying core subjects or named entities in a user's query that are suitable for Wikipedia searches.\n\
+        Given the user query, extract a list of concise and accurate search terms for Wikipedia.\n\
+        If the query is simple, provide a single search term in the list.\n\
+        If the query is complex or multifaceted, break it down into multiple relevant search terms.\n\
+        Focus on the main topics, persons, places, or concepts.\n\
+        Do not include conversational phrases like 'tell me about', 'what is', 'who was'.\n\
+        Write the search terms in the same language as the user query.\n\
+        Output the search terms as a JSON array of strings. For example: [\"Term 1\", \"Term 2\"]. If only one term, output as [\"Term\"].\n\n\
+        Examples:\n\
+        - User Query: \"Tell me more about the history of the Eiffel Tower in Paris.\"\n\
+          Search Terms (JSON): [\"Eiffel Tower\"]\n\
+        - User Query: \"Who was the first president of the United States?\"\n\
+          Search Terms (JSON): [\"George Washington\"]\n\
+        - User Query: \"What are the symptoms of influenza?\"\n\
+          Search Terms (JSON): [\"Influenza\"]\n\
+        - User Query: \"Explain quantum entanglement for me.\"\n\
+          Search Terms (JSON): [\"Quantum entanglement\"]\n\
+        - User Query: \"Compare the economies of Germany and France.\"\n\
+          Search Terms (JSON): [\"Economy of Germany\", \"Economy of France\"]\n\
+        - User Query: \"Compare Federer and Nadal.\"\n\
+          Search Terms (JSON): [\"Roger Federer\", \"Rafael Nadal\"]\n\
+        - User Query: \"Impact of Renaissance art on Baroque architecture.\"\n\
+          Search Terms (JSON): [\"Renaissance art\", \"Baroque architecture\"]\n\
+        User Query: '{}'\n\
+        Search Terms (JSON):",
+        user_query
+    );
+
+    let extractor_messages = vec![ChatMessage {
+        role: "user".to_string(),
+        content: extractor_prompt,
+        image_base64_data: None,
+        image_mime_type: None,
+        image_file_api_uri: None,
+        document_ids: None,
+    }];
+
+    log::info!(
+        "Requesting Wikipedia search term extraction for query: '{}'",
+        user_query
+    );
+
+    match call_gemini_api_non_streaming(
+        client,
+        extractor_messages,
+        &gemini_api_key_string,
+        model_name,
+    )
+    .await
+    {
+        Ok(response_str) => match serde_json::from_str::<Vec<String>>(&response_str) {
+            Ok(terms) => {
+                if terms.is_empty() {
+                    log::warn!("Wikipedia search term extractor returned an empty list for query: '{}'. Falling back to original query.", user_query);
+                    Ok(vec![user_query.to_string()])
+                } else {
+                    log::info!(
+                        "Extracted Wikipedia search terms: {:?} for original query: '{}'",
+                        terms,
+                        user_query
+                    );
+                    Ok(terms)
+                }
+            }
+            Err(e) => {
+                log::error!("Failed to parse Wikipedia search terms from LLM response for query '{}'. Error: {}. Response: \"{}\". Falling back to original query.", user_query, e, response_str);
+                Ok(vec![user_query.to_string()])
+            }
+        },
+        Err(e) => {
+            log::error!("Error calling Wikipedia search term extractor for query '{}': {}. Falling back to original query.", user_query, e);
+            Ok(vec![user_query.to_string()])
+        }
+    }
+}
+
+async fn analyze_wikipedia_page_for_iteration(
+    client: &reqwest::Client,
+    original_user_query: &str,
+    searched_term: &str,
+    page_title: &str,
+    page_content: &str,
+    visited_page_titles: &[String],
+    gemini_api_key: &str,
+    model_name: &str,
+) -> Result<AnalysisLLMDecision, String> {
+    const MAX_CONTENT_CHARS: usize = 100000;
+    let truncated_content = if page_content.chars().count() > MAX_CONTENT_CHARS {
+        page_content
+            .chars()
+            .take(MAX_CONTENT_CHARS)
+            .collect::<String>()
+            + "\n[Content truncated]"
+    } else {
+        page_content.to_string()
+    };
+
+    let visited_titles_str = visited_page_titles.join(", ");
+
+    let prompt = format!(
+        "You are an AI assistant helping a user research a topic using Wikipedia. Your goal is to navigate Wikipedia pages iteratively to find the answer or relevant information for the user's original query.\n\n\
+        Original User Query: \"{}\"\n\n\
+        You have just read the Wikipedia page titled: \"{}\" (found by searching for \"{}\").\n\
+        Here is the (potentially truncated) content of this page:\n---\n{}\n---\n\n\
+        You have already visited or processed the following Wikipedia page titles in this research chain: [{}]. Do not suggest revisiting these.\n\n\
+        Based on the original user query and the content of the current page, decide the next step:\n\
+        1. If the current page's content directly and substantially answers the user's original query, or provides key information directly relevant to it: \
+           Respond with a JSON object: {{\"decision_type\": \"FOUND_ANSWER\", \"summary\": \"<brief summary of the answer/info found on this page>\", \"title\": \"<current page title>\"}}\n\
+        2. If the current page provides clues or mentions a more specific entity (person, place, event, concept, document, case name, etc.) that seems like a promising next step for a Wikipedia search to get closer to answering the original query: \
+           Respond with a JSON object: {{\"decision_type\": \"NEXT_TERM\", \"term\": \"<concise Wikipedia search term for the next step>\", \"reason\": \"<briefly explain why this term is a good next step>\"}}. The term should be a precise Wikipedia article title if possible. Ensure the term is not in the list of already visited pages.\n\
+        3. If the current page is not relevant, or doesn't offer a clear next step towards answering the query, or if you think the research path is a dead end: \
+           Respond with a JSON object: {{\"decision_type\": \"STOP\", \"reason\": \"<briefly explain why you are stopping this path>\"}}\n\n\
+        Focus on finding the most direct path to the answer. Be specific with \"NEXT_TERM\" suggestions. Ensure the JSON is valid.",
+        original_user_query, page_title, searched_term, truncated_content, visited_titles_str
+    );
+
+    let messages = vec![ChatMessage {
+        role: "user".to_string(),
+        content: prompt,
+        image_base64_data: None,
+        image_mime_type: None,
+        image_file_api_uri: None,
+        document_ids: None,
+    }];
+
+    log::info!(
+        "Requesting Wikipedia content analysis for page: '{}', original query: '{}'",
+        page_title,
+        original_user_query
+    );
+
+    match call_gemini_api_non_streaming(client, messages, gemini_api_key, model_name.to_string())
+        .await
+    {
+        Ok(response_str) => {
+            log::debug!(
+                "Raw analysis response for page '{}': {}",
+                page_title,
+                response_str
+            );
+            let cleaned_response = response_str
+                .trim()
+                .trim_start_matches("```json")
+                .trim_start_matches("```")
+                .trim_end_matches("```")
+                .trim();
+            match serde_json::from_str::<AnalysisLLMDecision>(cleaned_response) {
+                Ok(decision) => Ok(decision),
+                Err(e) => {
+                    log::error!("Failed to parse analysis LLM response for page '{}'. Error: {}. Response: '{}', Cleaned: '{}'", page_title, e, response_str, cleaned_response);
+                    Err(format!(
+                        "Failed to parse analysis response: {}. Raw: {}",
+                        e, response_str
+                    ))
+                }
+            }
+        }
+        Err(e) => {
+            log::error!(
+                "Error calling analysis LLM for page '{}': {}",
+                page_title,
+                e
+            );
+            Err(format!("LLM call failed for analysis: {}", e))
+        }
+    }
+}
+
+pub async fn perform_iterative_wikipedia_research(
+    client: &reqwest::Client,
+    initial_user_query: &str,
+    wikis: &WikiSources,
+    gemini_api_key: &str,
+    model_name: &str,
+    max_iterations: usize,
+) -> Result<Vec<IterativeSearchResult>, String> {
+    use std::collections::{HashSet, VecDeque};
+
+    let mut all_found_info: Vec<IterativeSearchResult> = Vec::new();
+    let mut visited_page_titles: HashSet<String> = HashSet::new();
+    let mut search_queue: VecDeque<(String, Vec<String>)> = VecDeque::new();
+
+    log::info!(
+        "Starting iterative Wikipedia research for query: '{}'",
+        initial_user_query
+    );
+
+    let initial_terms = match extract_wikipedia_search_term(
+        client,
+        initial_user_query,
+        gemini_api_key.to_string(),
+        model_name.to_string(),
+    )
+    .await
+    {
+        Ok(terms) => terms,
+        Err(e) => {
+            log::error!(
+                "Failed initial term extraction for query '{}': {}",
+                initial_user_query,
+                e
+            );
+            // Fallback to using the original query if extraction fails
+            vec![initial_user_query.to_string()]
+        }
+    };
+
+    for term in initial_terms {
+        if !term.trim().is_empty() {
+            search_queue.push_back((term.clone(), vec![term]));
+        }
+    }
+
+    if search_queue.is_empty() && !initial_user_query.trim().is_empty() {
+        log::warn!("Initial term extraction yielded empty results for query: '{}'. Falling back to original query.", initial_user_query);
+        search_queue.push_back((
+            initial_user_query.to_string(),
+            vec![initial_user_query.to_string()],
+        ));
+    }
+
+    let mut current_iteration = 0;
+
+    while let Some((current_term, current_path)) = search_queue.pop_front() {
+        if current_iteration >= max_iterations {
+            log::warn!(
+                "Max iterations ({}) reached for query: {}",
+                max_iterations,
+                initial_user_query
+            );
+            break;
+        }
+        // Check based on the term we intend to search. Actual page titles are checked after lookup.
+        if visited_page_titles.contains(&current_term) && current_path.len() > 1 {
+            // Allow initial terms to be re-processed if they lead to different actual titles
+            log::debug!(
+                "Skipping already processed search term in path: {}",
+                current_term
+            );
+            continue;
+        }
+
+        current_iteration += 1;
+        log::info!(
+            "Iterative search (iter {}/{}, path depth {}): Looking up '{}'. Path: {:?}",
+            current_iteration,
+            max_iterations,
+            current_path.len(),
+            current_term,
+            current_path
+        );
+
+        match perform_wikipedia_lookup(client, wikis, &current_term).await {
+            Ok(pages) => {
+                if let Some(page) = pages.filter(|page| !page.extract.is_empty()) {
+                    let WikiPage {
+                        title,
+                        extract,
+                        url,
+                        ..
+                    } = page;
+                    if visited_page_titles.contains(&title) {
+                        log::debug!("Skipping already visited Wikipedia page title: {}", title);
+                        continue;
+                    }
+
+                    // Only the intro and the sections relevant to this search are read
+                    let sections = select_sections(
+                        &split_sections(&extract),
+                        &format!("{} {}", initial_user_query, current_term),
+                        MAX_ARTICLE_CHARS,
+                    );
+                    let content = render_sections(&sections);
+                    log::info!(
+                        "Adding page to results: '{}' ({} sections read)",
+                        title,
+                        sections.len()
+                    );
+                    all_found_info.push(IterativeSearchResult {
+                        title: title.clone(),
+                        summary: content.clone(),
+                        sections: sections
+                            .into_iter()
+                            .map(|section| SectionSource {
+                                url: section_url(&url, section.heading.as_deref()),
+                                heading: section.heading,
+                                text: section.text,
+                            })
+                            .collect(),
+                        url: url.clone(),
+                        path_taken: current_path.clone(),
+                    });
+
+                    visited_page_titles.insert(title.clone());
+
+                    // Only analyze if we haven't hit max_iterations for the *next* step
+                    if current_iteration < max_iterations {
+                        let visited_titles_vec: Vec<String> =
+                            visited_page_titles.iter().cloned().collect();
+                        match analyze_wikipedia_page_for_iteration(
+                            client,
+                            initial_user_query,
+                            &current_term,
+                            &title,
+                            &content,
+                            &visited_titles_vec,
+                            gemini_api_key,
+                            model_name,
+                        )
+                        .await
+                        {
+                            Ok(decision) => match decision {
+                                AnalysisLLMDecision::FoundAnswer {
+                                    summary: llm_summary,
+                                    title: found_title,
+                                } => {
+                                    log::info!(
+                                        "LLM indicated page '{}' (summary: '{}') as directly answering query '{}'. Information already captured.",
+                                        found_title,
+                                        llm_summary,
+                                        initial_user_query
+                                    );
+                                    // Optionally, one could update the summary in all_found_info if llm_summary is preferred,
+                                    // or simply stop this particular search path by not queueing further terms from it.
+                                }
+                                AnalysisLLMDecision::NextTerm {
+                                    term: next_term,
+                                    reason,
+                                } => {
+                                    log::info!(
+                                        "Next term for '{}' is '{}'. Reason: {}",
+                                        initial_user_query,
+                                        next_term,
+                                        reason
+                                    );
+                                    // Check conditions for adding to queue
+                                    if !visited_page_titles.contains(&next_term)
+                                        && !search_queue.iter().any(|(t, _)| t == &next_term)
+                                        && current_path.len() < max_iterations
+                                    // Path depth check
+                                    {
+                                        let mut next_path = current_path.clone();
+                                        next_path.push(next_term.clone());
+                                        search_queue.push_back((next_term, next_path));
+                                    } else {
+                                        log::debug!("Skipping next term suggestion '{}': already visited, in queue, or path too deep.", next_term);
+                                    }
+                                }
+                                AnalysisLLMDecision::Stop { reason } => {
+                                    log::info!(
+                                        "Stopping search on path {:?} for query '{}'. Reason: {}",
+                                        current_path,
+                                        initial_user_query,
+                                        reason
+                                    );
+                                }
+                            },
+                            Err(e) => {
+                                log::error!("Error analyzing Wikipedia content for term '{}', page title '{}': {}", current_term, title, e);
+                            }
+                        }
+                    } else {
+                        log::info!("Max iterations reached after processing page '{}'. Not analyzing for next steps.", title);
+                    }
+                } else {
+                    log::warn!(
+                                            "No usable content found for Wikipedia term '{}' after processing API results.",
+                                            current_term
+                                        );
+                    visited_page_titles.insert(current_term.clone()); // Mark term as processed to avoid retrying if it yields nothing
+                }
+            }
+            Err(e) => {
+                log::error!(
+                    "Error performing Wikipedia lookup for term '{}': {}",
+                    current_term,
+                    e
+                );
+                visited_page_titles.insert(current_term.clone());
+            }
+        }
+    }
+    log::info!(
+        "Finished iterative Wikipedia research for query: '{}'. Found {} results.",
+        initial_user_query,
+        all_found_info.len()
+    );
+    Ok(all_found_info)
+}
+
+/// One citation per section read, falling back to the whole article
+fn result_sources(result: &IterativeSearchResult) -> Vec<ToolSource> {
+    if result.sections.is_empty() {
+        return vec![ToolSource::new(
+            &result.title,
+            &result.url,
+            Some(&result.summary),
+        )];
+    }
+    result
+        .sections
+        .iter()
+        .map(|section| {
+            let title = match &section.heading {
+                Some(heading) => format!("{} § {}", result.title, heading),
+                None => result.title.clone(),
+            };
+            ToolSource::new(&title, &section.url, Some(&section.text))
+        })
+        .collect()
+}
+
+pub struct WikipediaTool;
+
+#[async_trait]
+impl Tool for WikipediaTool {
+    fn tool_type(&self) -> ToolType {
+        ToolType::WikipediaLookup
+    }
+
+    fn description(&self) -> &'static str {
+        "Iterative Wikipedia research for factual information, background context, and general knowledge"
+    }
+
+    fn follow_up_hint(&self) -> &'static str {
+        "Use GENERIC terms only (e.g., \"artificial intelligence\", not \"AI companies\")"
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "Generic, foundational topic (NOT specific subtopics)"
+                }
+            },
+            "required": ["query"]
+        })
+    }
+
+    fn guidance(&self) -> ToolGuidance {
+        ToolGuidance {
+            name: "Wikipedia Research".to_string(),
+            description: "Search and gather comprehensive information from Wikipedia using GENERIC, foundational terms".to_string(),
+            usage_pattern: "Use broad, foundational topics rather than specific subtopics. Let iterative research extract details.".to_string(),
+            parameters: vec![
+                ToolParameter {
+                    name: "query".to_string(),
+                    param_type: "string".to_string(),
+                    description: "Generic, foundational topic (NOT specific subtopics)".to_string(),
+                    required: true,
+                    default_value: None,
+                },
+                ToolParameter {
+                    name: "max_iterations".to_string(),
+                    param_type: "number".to_string(),
+                    description: "Maximum research depth (1-4)".to_string(),
+                    required: false,
+                    default_value: Some("3".to_string()),
+                }
+            ],
+            examples: vec![
+                ToolExample {
+                    scenario: "User asks about quantum computing companies and stock prices".to_string(),
+                    reasoning: "Start with foundational 'quantum computing' topic, not 'quantum computing companies'".to_string(),
+                    sequence: vec![
+                        "GOOD: Search for 'quantum computing' - broad, authoritative coverage".to_string(),
+                        "BAD: Search for 'quantum computing companies' - too specific, limited scope".to_string(),
+                        "Extract company details from broad article for follow-up financial queries".to_string(),
+                    ],
+                },
+                ToolExample {
+                    scenario: "User asks about AI developments".to_string(),
+                    reasoning: "Use generic 'artificial intelligence' rather than specific subtopics".to_string(),
+                    sequence: vec![
+                        "GOOD: 'artificial intelligence', 'machine learning'".to_string(),
+                        "BAD: 'AI stocks', 'machine learning companies', 'AI startups'".to_string(),
+                    ],
+                },
+            ],
+            reasoning_hints: vec![
+                "ALWAYS use generic, foundational terms for Wikipedia queries".to_string(),
+                "AVOID specific subtopics like 'companies', 'stocks', 'manufacturers'".to_string(),
+                "Let the iterative system extract specific details from broad articles".to_string(),
+                "Good examples: 'quantum computing', 'renewable energy', 'artificial intelligence'".to_string(),
+                "Bad examples: 'quantum computing companies', 'solar stocks', 'AI startups'".to_string(),
+                "Broad articles contain more comprehensive, authoritative information".to_string(),
+            ],
+        }
+    }
+
+    fn capability_key(&self) -> &'static str {
+        "wikipedia_research"
+    }
+
+    fn capabilities(&self) -> Value {
+        json!({
+            "description": "Comprehensive encyclopedia research with iterative search",
+            "best_for": ["general knowledge", "background information", "concept explanations"],
+            "strengths": ["authoritative sources", "comprehensive coverage", "reliable information"],
+            "limitations": ["may not have very recent information", "general rather than specialized"]
+        })
+    }
+
+    fn started_event(&self) -> &'static str {
+        "ARTICLE_LOOKUP_STARTED"
+    }
+
+    fn completed_event(&self) -> &'static str {
+        "ARTICLE_LOOKUP_COMPLETED"
+    }
+
+    fn started_payload(&self, query: &str) -> Value {
+        to_payload(ArticleLookupStartedPayload {
+            query: query.to_string(),
+        })
+    }
+
+    fn failed_payload(&self, query: &str, error: &str) -> Value {
+        to_payload(ArticleLookupCompletedPayload {
+            query: query.to_string(),
+            success: false,
+            summary: None,
+            source_name: None,
+            source_url: None,
+            error: Some(error.to_string()),
+        })
+    }
+
+    /// Articles change slowly
+    fn cache_ttl(&self) -> Option<Duration> {
+        Some(Duration::from_secs(3 * 24 * 60 * 60))
+    }
+
+    /// The same query reads different wikis depending on the settings and the
+    /// language of the message
+    fn cache_key(&self, ctx: &ToolContext<'_>, query: &str) -> Option<String> {
+        let wikis =
+            WikiSources::resolve(&ctx.config.wiki.clone().unwrap_or_default(), ctx.user_query);
+        Some(format!("{} [{}]", query, wikis.cache_scope()))
+    }
+
+    async fn execute(&self, ctx: &ToolContext<'_>, query: &str) -> Result<ToolOutput, String> {
+        let wikis =
+            WikiSources::resolve(&ctx.config.wiki.clone().unwrap_or_default(), ctx.user_query);
+        let results = perform_iterative_wikipedia_research(
+            ctx.client,
+            query,
+            &wikis,
+            ctx.gemini_api_key,
+            ctx.model_name,
+            MAX_RESEARCH_PAGES,
+        )
+        .await?;
+
+        if results.is_empty() {
+            log::info!(
+                "Wikipedia lookup for '{}' completed, but no information found.",
+                query
+            );
+            let summary = "No specific information found after iterative search.".to_string();
+            return Ok(ToolOutput {
+                content: summary.clone(),
+                context: None,
+                sources: Vec::new(),
+                completed_payload: to_payload(ArticleLookupCompletedPayload {
+                    query: query.to_string(),
+                    success: true,
+                    summary: Some(summary),
+                    source_name: None,
+                    source_url: None,
+                    error: None,
+                }),
+            });
+        }
+
+        log::info!(
+            "Wikipedia lookup successful for '{}'. Found {} results.",
+            query,
+            results.len()
+        );
+        let mut combined_summary = String::new();
+        for res in results.iter() {
+            combined_summary.push_str(&format!(
+                "Title: {}\nSummary: {}\n\n",
+                res.title, res.summary,
+            ));
+        }
+        let context_text = format!(
+            "Wikipedia Research Results for '{}':\n\n{}",
+            query,
+            combined_summary.trim_end()
+        );
+
+        Ok(ToolOutput {
+            content: context_text.clone(),
+            context: Some(context_text),
+            sources: results.iter().flat_map(result_sources).collect(),
+            completed_payload: to_payload(ArticleLookupCompletedPayload {
+                query: query.to_string(),
+                success: true,
+                summary: Some(combined_summary.trim_end().to_string()),
+                source_name: Some(results.iter().map(|r| r.title.clone()).collect()),
+                source_url: Some(results.iter().map(|r| r.url.clone()).collect()),
+                error: None,
+            }),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_language() {
+        assert_eq!(
+            detect_language("Wie hoch ist der Eiffelturm und wer hat ihn gebaut?"),
+            Some("de")
+        );
+        assert_eq!(
+            detect_language("Qui a construit la tour Eiffel et pour quoi ?"),
+            Some("fr")
+        );
+        assert_eq!(
+            detect_language("What is the history of the Eiffel Tower?"),
+            Some("en")
+        );
+        assert_eq!(detect_language("東京タワーの高さは？"), Some("ja"));
+        assert_eq!(detect_language("Какая высота Эйфелевой башни?"), Some("ru"));
+        assert_eq!(detect_language("Eiffel Tower"), None);
+    }
+
+    #[test]
+    fn test_sources_follow_language_and_endpoints() {
+        let settings = WikiSettings {
+            endpoints: Some(vec![WikiEndpoint {
+                name: "Team Wiki".to_string(),
+                api_url: "https://wiki.example.com/w/api.php".to_string(),
+                article_url: None,
+            }]),
+            ..WikiSettings::default()
+        };
+        let wikis = WikiSources::resolve(
+            &settings,
+            "Wie funktioniert die Photosynthese und was ist das?",
+        );
+        assert_eq!(wikis.sites.len(), 2);
+        assert_eq!(wikis.sites[1].api_url, "https://de.wikipedia.org/w/api.php");
+        assert_eq!(wikis.language.as_deref(), Some("de"));
+        assert_eq!(wikis.fallback_language.as_deref(), Some("en"));
+
+        let english = WikiSources::resolve(&WikiSettings::default(), "Photosynthesis");
+        assert_eq!(english.language.as_deref(), Some("en"));
+        assert_eq!(english.fallback_language, None);
+        assert_eq!(english.cache_scope(), "https://en.wikipedia.org/w/api.php");
+        assert_ne!(english.cache_scope(), wikis.cache_scope());
+
+        let fixed = WikiSettings {
+            language: Some("ja".to_string()),
+            include_wikipedia: Some(true),
+            ..WikiSettings::default()
+        };
+        assert_eq!(
+            WikiSources::resolve(&fixed, "Photosynthesis")
+                .language
+                .as_deref(),
+            Some("ja")
+        );
+        assert!(WikiSettings {
+            language: Some("en/../x".to_string()),
+            ..WikiSettings::default()
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn test_page_urls() {
+        assert_eq!(
+            WikiEndpoint::wikipedia("de").page_url("Eiffelturm"),
+            "https://de.wikipedia.org/wiki/Eiffelturm"
+        );
+        let team_wiki = WikiEndpoint {
+            name: "Team Wiki".to_string(),
+            api_url: "https://wiki.example.com/w/api.php".to_string(),
+            article_url: None,
+        };
+        assert_eq!(
+            team_wiki.page_url("Release process"),
+            "https://wiki.example.com/wiki/Release_process"
+        );
+        let plain = WikiEndpoint {
+            api_url: "https://intranet.example.com/mediawiki/api.php".to_string(),
+            ..team_wiki
+        };
+        assert_eq!(
+            plain.page_url("On call"),
+            "https://intranet.example.com/mediawiki/index.php?title=On_call"
+        );
+    }
+
+    const ARTICLE: &str = "The Eiffel Tower is a wrought-iron lattice tower in Paris.\n\n\
+== History ==\n\
+=== Origin ===\n\
+The design was drawn up by Maurice Koechlin and Emile Nouguier for the 1889 fair.\n\n\
+== Design ==\n\
+The tower is 330 metres tall and was the tallest structure for 41 years.\n\n\
+== In popular culture ==\n\
+The tower appears in many films.\n";
+
+    #[test]
+    fn test_split_sections() {
+        let sections = split_sections(ARTICLE);
+        let headings: Vec<Option<&str>> = sections.iter().map(|s| s.heading.as_deref()).collect();
+        // "History" only holds a subsection, so it has no text of its own
+        assert_eq!(
+            headings,
+            vec![
+                None,
+                Some("Origin"),
+                Some("Design"),
+                Some("In popular culture")
+            ]
+        );
+        assert_eq!(sections[1].level, 3);
+        assert_eq!(
+            sections[2].text,
+            "The tower is 330 metres tall and was the tallest structure for 41 years."
+        );
+    }
+
+    #[test]
+    fn test_select_sections_keeps_intro_and_relevant_sections() {
+        let sections = split_sections(ARTICLE);
+        let selected = select_sections(&sections, "how tall is the tower in metres", 6000);
+        let headings: Vec<Option<&str>> = selected.iter().map(|s| s.heading.as_deref()).collect();
+        assert_eq!(headings[0], None);
+        assert!(headings.contains(&Some("Design")));
+        assert!(!headings.contains(&Some("Origin")));
+
+        // With room for only one section, the best match wins
+        let budget = sections[0].text.chars().count() + 250;
+        let selected = select_sections(&sections, "Koechlin design 1889", budget);
+        assert_eq!(selected.len(), 2);
+        assert_eq!(selected[1].heading.as_deref(), Some("Origin"));
+    }
+
+    #[test]
+    fn test_section_sources() {
+        assert_eq!(
+            section_url(
+                "https://en.wikipedia.org/wiki/Eiffel_Tower",
+                Some("In popular culture")
+            ),
+            "https://en.wikipedia.org/wiki/Eiffel_Tower#In_popular_culture"
+        );
+        let result = IterativeSearchResult {
+            title: "Eiffel Tower".to_string(),
+            summary: String::new(),
+            url: "https://en.wikipedia.org/wiki/Eiffel_Tower".to_string(),
+            path_taken: vec!["Eiffel Tower".to_string()],
+            sections: vec![
+                SectionSource {
+                    heading: None,
+                    url: "https://en.wikipedia.org/wiki/Eiffel_Tower".to_string(),
+                    text: "A tower.".to_string(),
+                },
+                SectionSource {
+                    heading: Some("Design".to_string()),
+                    url: "https://en.wikipedia.org/wiki/Eiffel_Tower#Design".to_string(),
+                    text: "330 metres.".to_string(),
+                },
+            ],
+        };
+        let sources = result_sources(&result);
+        assert_eq!(sources[0].title, "Eiffel Tower");
+        assert_eq!(sources[1].title, "Eiffel Tower § Design");
+        assert_eq!(
+            sources[1].url,
+            "https://en.wikipedia.org/wiki/Eiffel_Tower#Design"
+        );
+    }
+}